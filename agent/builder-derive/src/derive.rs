@@ -120,6 +120,14 @@ pub(crate) fn build_struct(ast: &syn::DeriveInput) -> TokenStream {
                     capabilities: Vec<String>,
                     #[serde(skip)]
                     privileged: Option<bool>,
+                    #[serde(skip)]
+                    ttl_after_finished: Option<String>,
+                    #[serde(skip)]
+                    priority: Option<i32>,
+                    #[serde(skip)]
+                    node_selector: std::collections::BTreeMap<String, String>,
+                    #[serde(skip)]
+                    service_account: Option<String>,
                 }
 
                 impl #build_ident{
@@ -212,6 +220,18 @@ pub(crate) fn build_struct(ast: &syn::DeriveInput) -> TokenStream {
                         self
                     }
 
+                    pub fn ttl_after_finished<S1>(&mut self, ttl_after_finished: S1) -> &mut Self
+                    where
+                    S1: Into<String> {
+                        self.ttl_after_finished = Some(ttl_after_finished.into());
+                        self
+                    }
+
+                    pub fn set_ttl_after_finished(&mut self, ttl_after_finished: Option<String>) -> &mut Self {
+                        self.ttl_after_finished = ttl_after_finished;
+                        self
+                    }
+
                     pub fn keep_running(&mut self, keep_running: bool) -> &mut Self {
                         self.keep_running = Some(keep_running);
                         self
@@ -234,6 +254,41 @@ pub(crate) fn build_struct(ast: &syn::DeriveInput) -> TokenStream {
                         self
                     }
 
+                    pub fn node_selector<S1, S2>(&mut self, key: S1, value: S2) -> &mut Self
+                    where
+                    S1: Into<String>,
+                    S2: Into<String>, {
+                        self.node_selector.insert(key.into(), value.into());
+                        self
+                    }
+
+                    pub fn set_node_selector(&mut self, node_selector: Option<std::collections::BTreeMap<String, String>>) -> &mut Self {
+                        self.node_selector = node_selector.unwrap_or_default();
+                        self
+                    }
+
+                    pub fn service_account<S1>(&mut self, service_account: S1) -> &mut Self
+                    where
+                    S1: Into<String> {
+                        self.service_account = Some(service_account.into());
+                        self
+                    }
+
+                    pub fn set_service_account(&mut self, service_account: Option<String>) -> &mut Self {
+                        self.service_account = service_account;
+                        self
+                    }
+
+                    pub fn priority(&mut self, priority: i32) -> &mut Self {
+                        self.priority = Some(priority);
+                        self
+                    }
+
+                    pub fn set_priority(&mut self, priority: Option<i32>) -> &mut Self {
+                        self.priority = priority;
+                        self
+                    }
+
                     pub fn privileged(&mut self, privileged: bool) -> &mut Self {
                         self.privileged = Some(privileged);
                         self
@@ -267,8 +322,16 @@ pub(crate) fn build_struct(ast: &syn::DeriveInput) -> TokenStream {
                                     secrets: Some(self.secrets.clone()),
                                     capabilities: Some(self.capabilities.clone()),
                                     privileged: self.privileged,
-                                    timeout: None
+                                    timeout: None,
+                                    node_selector: (!self.node_selector.is_empty()).then(|| self.node_selector.clone()),
+                                    service_account: self.service_account.clone(),
+                                    ..Default::default()
                                 },
+                                leases: Default::default(),
+                                ttl_after_finished: self.ttl_after_finished.clone(),
+                                priority: self.priority,
+                                cancel: false,
+                                suspend: false,
                             },
                         ))
                     }
@@ -302,6 +365,16 @@ pub(crate) fn build_struct(ast: &syn::DeriveInput) -> TokenStream {
                     destruction_policy: Option<testsys_model::DestructionPolicy>,
                     #[serde(skip)]
                     privileged: Option<bool>,
+                    #[serde(skip)]
+                    max_lifetime: Option<String>,
+                    #[serde(skip)]
+                    retry_policy: Option<testsys_model::RetryPolicy>,
+                    #[serde(skip)]
+                    priority: Option<i32>,
+                    #[serde(skip)]
+                    node_selector: std::collections::BTreeMap<String, String>,
+                    #[serde(skip)]
+                    service_account: Option<String>,
                 }
 
                 impl #build_ident{
@@ -416,6 +489,63 @@ pub(crate) fn build_struct(ast: &syn::DeriveInput) -> TokenStream {
                         self
                     }
 
+                    pub fn max_lifetime<S1>(&mut self, max_lifetime: S1) -> &mut Self
+                    where
+                    S1: Into<String> {
+                        self.max_lifetime = Some(max_lifetime.into());
+                        self
+                    }
+
+                    pub fn set_max_lifetime(&mut self, max_lifetime: Option<String>) -> &mut Self {
+                        self.max_lifetime = max_lifetime;
+                        self
+                    }
+
+                    pub fn retry_policy(&mut self, retry_policy: testsys_model::RetryPolicy) -> &mut Self {
+                        self.retry_policy = Some(retry_policy);
+                        self
+                    }
+
+                    pub fn set_retry_policy(&mut self, retry_policy: Option<testsys_model::RetryPolicy>) -> &mut Self {
+                        self.retry_policy = retry_policy;
+                        self
+                    }
+
+                    pub fn node_selector<S1, S2>(&mut self, key: S1, value: S2) -> &mut Self
+                    where
+                    S1: Into<String>,
+                    S2: Into<String>, {
+                        self.node_selector.insert(key.into(), value.into());
+                        self
+                    }
+
+                    pub fn set_node_selector(&mut self, node_selector: Option<std::collections::BTreeMap<String, String>>) -> &mut Self {
+                        self.node_selector = node_selector.unwrap_or_default();
+                        self
+                    }
+
+                    pub fn service_account<S1>(&mut self, service_account: S1) -> &mut Self
+                    where
+                    S1: Into<String> {
+                        self.service_account = Some(service_account.into());
+                        self
+                    }
+
+                    pub fn set_service_account(&mut self, service_account: Option<String>) -> &mut Self {
+                        self.service_account = service_account;
+                        self
+                    }
+
+                    pub fn priority(&mut self, priority: i32) -> &mut Self {
+                        self.priority = Some(priority);
+                        self
+                    }
+
+                    pub fn set_priority(&mut self, priority: Option<i32>) -> &mut Self {
+                        self.priority = priority;
+                        self
+                    }
+
                     pub fn privileged(&mut self, privileged: bool) -> &mut Self {
                         self.privileged = Some(privileged);
                         self
@@ -449,8 +579,15 @@ pub(crate) fn build_struct(ast: &syn::DeriveInput) -> TokenStream {
                                 capabilities: Some(self.capabilities.clone()),
                                 timeout: None,
                                 privileged: self.privileged,
+                                node_selector: (!self.node_selector.is_empty()).then(|| self.node_selector.clone()),
+                                service_account: self.service_account.clone(),
+                                ..Default::default()
                             },
-                            destruction_policy: self.destruction_policy.as_ref().cloned().unwrap_or_default()
+                            destruction_policy: self.destruction_policy.as_ref().cloned().unwrap_or_default(),
+                            max_lifetime: self.max_lifetime.clone(),
+                            retry_policy: self.retry_policy.clone(),
+                            priority: self.priority,
+                            suspend: false,
                         },
                         ))
                     }
@@ -10,9 +10,12 @@
 //! ```
 //! use configuration_derive::Configuration;
 //! use builder_derive::Builder;
+//! use schemars::JsonSchema;
 //! use serde::{Deserialize, Serialize};
 //!
-//! #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+//! #[derive(
+//!     Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+//! )]
 //! #[crd("Test")]
 //! struct Config{
 //!     field: Option<String>
@@ -1,15 +1,21 @@
 use builder_derive::Builder;
 use configuration_derive::Configuration;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use testsys_model::Configuration as _;
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+)]
 #[crd("Test")]
 struct SampleTest {
     bar: i32,
     biz: Option<String>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+)]
 #[crd("Resource")]
 struct SampleResource {
     bar: i32,
@@ -116,3 +122,13 @@ fn resource_derive_1() {
     );
     assert_eq!(resource.metadata.name.unwrap(), "resource".to_string());
 }
+
+#[test]
+fn test_derive_schema() {
+    let schema = serde_json::to_value(SampleTest::schema().unwrap()).unwrap();
+    assert_eq!(schema["required"], serde_json::json!(["bar"]));
+    assert_eq!(
+        schema["properties"]["biz"]["type"],
+        serde_json::json!(["string", "null"])
+    );
+}
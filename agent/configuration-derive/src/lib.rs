@@ -1,4 +1,7 @@
 //! Automatically implement `Configuration` for an agent's config
+//!
+//! The type must also derive `schemars::JsonSchema`, which is used to provide the JSON Schema of
+//! the configuration through `Configuration::schema`.
 
 use proc_macro::{self, TokenStream};
 
@@ -11,7 +14,11 @@ pub fn derive_configuration(input: TokenStream) -> TokenStream {
     let ident = ast.ident;
 
     quote! {
-       impl testsys_model::Configuration for #ident{}
+       impl testsys_model::Configuration for #ident {
+           fn schema() -> Option<::schemars::schema::RootSchema> {
+               Some(::schemars::schema_for!(#ident))
+           }
+       }
     }
     .into()
 }
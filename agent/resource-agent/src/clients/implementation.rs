@@ -34,7 +34,7 @@ impl From<Resources> for ErrorResources {
 #[async_trait::async_trait]
 impl InfoClient for DefaultInfoClient {
     async fn new(data: BootstrapData) -> ClientResult<Self> {
-        let client = ResourceClient::new()
+        let client = ResourceClient::new_default_namespaced()
             .await
             .map_err(|e| ClientError::InitializationFailed(Some(Box::new(e))))?;
         Ok(Self { data, client })
@@ -71,7 +71,7 @@ impl AgentClient for DefaultAgentClient {
     async fn new(data: BootstrapData) -> ClientResult<Self> {
         Ok(Self {
             data,
-            resource_client: ResourceClient::new()
+            resource_client: ResourceClient::new_default_namespaced()
                 .await
                 .map_err(|e| ClientError::InitializationFailed(Some(Box::new(e))))?,
         })
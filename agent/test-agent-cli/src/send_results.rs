@@ -36,6 +36,7 @@ impl SendResults {
             num_failed: self.failed,
             num_skipped: self.skipped,
             other_info: self.other_info.clone(),
+            ..Default::default()
         };
         k8s_client
             .send_test_results(test_results)
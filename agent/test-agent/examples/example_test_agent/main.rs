@@ -79,6 +79,7 @@ where
             num_failed: 0,
             num_skipped: 0,
             other_info: Some("Running Test".to_string()),
+            ..Default::default()
         };
 
         info_client
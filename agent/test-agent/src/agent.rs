@@ -1,14 +1,19 @@
 use crate::error::{self, AgentError, Error, Result};
 use crate::{BootstrapData, Client, InfoClient, Runner};
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use snafu::ResultExt;
 use std::fs::File;
+use std::future::Future;
 use std::path::PathBuf;
 use std::time::Duration;
 use tar::Builder;
-use testsys_model::{Outcome, TestResults};
+use testsys_model::{Outcome, TestAttempt, TestResults};
+use tokio::signal::unix::{signal, Signal, SignalKind};
 use tokio::time::sleep;
 
+/// How often the agent checks whether the test has been cancelled while the test is running.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
 /// The `TestAgent` is the main entrypoint for the program running in a TestPod. It starts a test
 /// run, regularly checks the health of the test run, observes cancellation of a test run, and sends
 /// the results of a test run.
@@ -59,9 +64,17 @@ where
     }
 
     /// Run the `TestAgent`. This function returns once the test has completed and `keep_running`
-    /// is `false`.
+    /// is `false`, or once the agent receives a SIGTERM. A test that is cancelled, with the `cancel`
+    /// field of the test or a SIGTERM, is terminated and finishes with a `Cancelled` outcome.
     pub async fn run(&mut self) -> Result<(), C::E, R::E> {
-        let result = self.run_inner().await;
+        let mut sigterm = match signal(SignalKind::terminate()) {
+            Ok(sigterm) => Some(sigterm),
+            Err(e) => {
+                error!("Unable to listen for SIGTERM: {}", e);
+                None
+            }
+        };
+        let result = self.run_inner(&mut sigterm).await;
         let tar_result = self.tar_results().await;
 
         match &result {
@@ -75,7 +88,10 @@ where
 
         if self.keep_running().await {
             info!("'keep_running' is true.");
-            self.loop_while_keep_running_is_true().await
+            tokio::select! {
+                _ = self.loop_while_keep_running_is_true() => {}
+                _ = sigterm_received(&mut sigterm) => info!("Received SIGTERM, exiting."),
+            }
         }
 
         // We want the running error first if there was one.
@@ -85,29 +101,31 @@ where
         }
     }
 
-    /// Run the `TestAgent`. This function returns once the test has completed.
-    async fn run_inner(&mut self) -> Result<(), C::E, R::E> {
+    /// Run the `TestAgent`. This function returns once the test has completed or been cancelled.
+    async fn run_inner(&mut self, sigterm: &mut Option<Signal>) -> Result<(), C::E, R::E> {
         debug!("running test");
         self.client
             .send_test_starting()
             .await
             .map_err(error::Error::Client)?;
 
+        let mut attempt = TestAttempt::start(1);
         self.client
             .send_test_update(TestResults {
                 outcome: Outcome::InProgress,
                 other_info: Some("Starting Test".to_string()),
+                attempt: Some(attempt.clone()),
                 ..Default::default()
             })
             .await
             .map_err(error::Error::Client)?;
 
-        let mut test_results = match self
-            .runner
-            .run(&self.info_client)
-            .await
-            .map_err(error::Error::Runner)
-        {
+        let run = self.runner.run(&self.info_client);
+        let result = match unless_cancelled(&self.client, sigterm, run).await {
+            Ok(result) => result,
+            Err(reason) => return self.cancel(attempt, reason).await,
+        };
+        let mut test_results = match result.map_err(error::Error::Runner) {
             Ok(ok) => ok,
             Err(e) => {
                 self.send_error_best_effort(&e).await;
@@ -115,6 +133,8 @@ where
                 return Err(e);
             }
         };
+        attempt.finish();
+        test_results.attempt = Some(attempt);
 
         // If we are unable to get the number of retries it is safer to assume it is zero
         // then to error.
@@ -136,21 +156,23 @@ where
                 self.send_error_best_effort(&e).await;
             }
 
+            let mut attempt = TestAttempt::start(retry_count + 2);
             self.client
                 .send_test_update(TestResults {
                     outcome: Outcome::InProgress,
                     other_info: Some("Rerunning Test".to_string()),
+                    attempt: Some(attempt.clone()),
                     ..Default::default()
                 })
                 .await
                 .map_err(error::Error::Client)?;
 
-            test_results = match self
-                .runner
-                .rerun_failed(&test_results, &self.info_client)
-                .await
-                .map_err(error::Error::Runner)
-            {
+            let rerun = self.runner.rerun_failed(&test_results, &self.info_client);
+            let result = match unless_cancelled(&self.client, sigterm, rerun).await {
+                Ok(result) => result,
+                Err(reason) => return self.cancel(attempt, reason).await,
+            };
+            test_results = match result.map_err(error::Error::Runner) {
                 Ok(ok) => ok,
                 Err(e) => {
                     self.send_error_best_effort(&e).await;
@@ -158,6 +180,8 @@ where
                     return Err(e);
                 }
             };
+            attempt.finish();
+            test_results.attempt = Some(attempt);
             retry_count += 1;
         }
 
@@ -193,6 +217,37 @@ where
         Ok(())
     }
 
+    /// Finishes a test that was cancelled for `reason` during `attempt`. The `Runner` is terminated
+    /// and a `Cancelled` outcome is reported. The results directory is archived afterwards, like
+    /// for any other test, so the partial results are kept.
+    async fn cancel(&mut self, mut attempt: TestAttempt, reason: String) -> Result<(), C::E, R::E> {
+        info!("{}, terminating the test runner.", reason);
+        if let Err(e) = self.runner.terminate().await.map_err(error::Error::Runner) {
+            error!("unable to terminate test runner: {}", e);
+            self.send_error_best_effort(&e).await;
+            return Err(e);
+        }
+        attempt.finish();
+        if let Err(e) = self
+            .client
+            .send_test_results(TestResults {
+                outcome: Outcome::Cancelled,
+                other_info: Some(reason),
+                attempt: Some(attempt),
+                ..Default::default()
+            })
+            .await
+            .map_err(error::Error::Client)
+        {
+            self.send_error_best_effort(&e).await;
+            return Err(e);
+        }
+        self.client
+            .send_test_completed()
+            .await
+            .map_err(error::Error::Client)
+    }
+
     /// Returns `true` if the error was successfully sent, `false` if the error could not be sent.
     async fn send_error_best_effort(&mut self, e: &Error<C::E, R::E>) {
         if let Err(send_error) = self.client.send_error(e).await {
@@ -262,3 +317,49 @@ where
         }
     }
 }
+
+/// Runs `future` unless the test is cancelled first, in which case the reason for the cancellation
+/// is returned.
+async fn unless_cancelled<C, F>(
+    client: &C,
+    sigterm: &mut Option<Signal>,
+    future: F,
+) -> std::result::Result<F::Output, String>
+where
+    C: Client,
+    F: Future,
+{
+    tokio::select! {
+        output = future => Ok(output),
+        reason = cancellation(client, sigterm) => Err(reason),
+    }
+}
+
+/// Returns the reason once the test has been cancelled with the `cancel` field of the test or a
+/// SIGTERM.
+async fn cancellation<C>(client: &C, sigterm: &mut Option<Signal>) -> String
+where
+    C: Client,
+{
+    loop {
+        match client.cancel_requested().await {
+            Ok(true) => return "The test was cancelled".to_string(),
+            Ok(false) => {}
+            Err(e) => warn!("Unable to check whether the test was cancelled: {}", e),
+        }
+        tokio::select! {
+            _ = sigterm_received(sigterm) => return "The test agent received SIGTERM".to_string(),
+            _ = sleep(CANCEL_POLL_INTERVAL) => {}
+        }
+    }
+}
+
+/// Waits for a SIGTERM, or forever if the agent is unable to listen for one.
+async fn sigterm_received(sigterm: &mut Option<Signal>) {
+    match sigterm {
+        Some(sigterm) => {
+            sigterm.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
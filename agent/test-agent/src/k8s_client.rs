@@ -50,7 +50,9 @@ impl Client for DefaultClient {
 
     async fn new(bootstrap_data: BootstrapData) -> Result<Self, Self::E> {
         Ok(Self {
-            client: TestClient::new().await.context(K8sSnafu)?,
+            client: TestClient::new_default_namespaced()
+                .await
+                .context(K8sSnafu)?,
             name: bootstrap_data.test_name,
             results_dir: TempDir::new().context(TempDirCreateSnafu)?,
         })
@@ -61,6 +63,11 @@ impl Client for DefaultClient {
         Ok(test_data.spec.agent.keep_running)
     }
 
+    async fn cancel_requested(&self) -> Result<bool, Self::E> {
+        let test_data = self.client.get(&self.name).await.context(K8sSnafu)?;
+        Ok(test_data.spec.cancel)
+    }
+
     async fn retries(&self) -> Result<u32, Self::E> {
         let test_data = self.client.get(&self.name).await.context(K8sSnafu)?;
         Ok(test_data.spec.retries.unwrap_or_default())
@@ -72,13 +79,14 @@ impl Client for DefaultClient {
     {
         let test_data = self.client.get(&self.name).await.context(K8sSnafu)?;
 
+        let leased_resources = test_data.leased_resources();
         let raw_config = test_data.spec.agent.configuration.unwrap_or_default();
 
-        let resource_client = ResourceClient::new()
+        let resource_client = ResourceClient::new_default_namespaced()
             .await
             .context(ResourceClientCreateSnafu)?;
         let resolved_config = resource_client
-            .resolve_templated_config(raw_config)
+            .resolve_templated_config_with_leases(raw_config, &leased_resources)
             .await
             .context(ResolveConfigSnafu)?;
 
@@ -136,6 +144,17 @@ impl Client for DefaultClient {
         Ok(())
     }
 
+    async fn send_outputs<O>(&self, outputs: O) -> Result<(), Self::E>
+    where
+        O: Configuration,
+    {
+        self.client
+            .send_outputs(&self.name, outputs)
+            .await
+            .context(K8sSnafu)?;
+        Ok(())
+    }
+
     async fn results_directory(&self) -> Result<PathBuf, Self::E> {
         Ok(self.results_dir.path().to_path_buf())
     }
@@ -149,7 +168,7 @@ impl Client for DefaultClient {
 impl InfoClient for DefaultInfoClient {
     async fn new(d: BootstrapData) -> InfoClientResult<Self> {
         Ok(Self {
-            client: TestClient::new()
+            client: TestClient::new_default_namespaced()
                 .await
                 .map_err(|e| InfoClientError::InitializationFailed(Some(e.into())))?,
             data: d,
@@ -163,4 +182,15 @@ impl InfoClient for DefaultInfoClient {
             .map_err(|e| InfoClientError::RequestFailed(Some(e.into())))?;
         Ok(())
     }
+
+    async fn send_outputs<O>(&self, outputs: O) -> InfoClientResult<()>
+    where
+        O: Configuration,
+    {
+        self.client
+            .send_outputs(&self.data.test_name, outputs)
+            .await
+            .map_err(|e| InfoClientError::RequestFailed(Some(e.into())))?;
+        Ok(())
+    }
 }
@@ -41,7 +41,9 @@ pub struct Spec<C: Configuration> {
 /// The [`TestAgent`] will call your implementation of the `Runner` trait as follows:
 /// - `new` will be called to instantiate the object.
 /// - `run` will be called to run the test(s).
-/// - `terminate` will be called before the program exits.
+/// - `terminate` will be called before the program exits, including when the test is cancelled
+///   while `run` or `rerun_failed` is in progress. In that case, the in-progress call is dropped
+///   and the files in the results directory are kept as the partial results of the test.
 ///
 /// You will also define a [`Configuration`] type to define data that your test needs when it
 /// starts. This requires serialization and other common traits, but otherwise can be whatever
@@ -76,6 +78,7 @@ pub trait Runner<I: InfoClient>: Sized + Send {
             num_passed: 0,
             num_skipped: 0,
             other_info: Some("rerun_failed not defined".to_string()),
+            ..Default::default()
         })
     }
 
@@ -118,6 +121,9 @@ pub trait Client: Sized {
     /// Determine if the pod should keep running after it has finished or encountered and error.
     async fn keep_running(&self) -> Result<bool, Self::E>;
 
+    /// Determine if the user has asked for the test to be cancelled.
+    async fn cancel_requested(&self) -> Result<bool, Self::E>;
+
     /// Determine the number of retries the agent is expected to perform for failed tests.
     async fn retries(&self) -> Result<u32, Self::E>;
 
@@ -139,6 +145,16 @@ pub trait Client: Sized {
 
     /// Set the task state as `Completed` indicating that no more retries or testing will occur.
     async fn send_test_completed(&self) -> Result<(), Self::E>;
+
+    /// Publish values for other tests and resources to use in their configuration with
+    /// `${test:<test name>.<field name>}`. The values can be used once the test has completed.
+    /// The default implementation does not publish anything.
+    async fn send_outputs<O>(&self, _outputs: O) -> Result<(), Self::E>
+    where
+        O: Configuration,
+    {
+        Ok(())
+    }
 }
 
 /// Provides the default [`Client`] implementation.
@@ -152,6 +168,16 @@ pub struct DefaultClient {
 pub trait InfoClient: Sized + Send + Sync {
     async fn new(d: BootstrapData) -> InfoClientResult<Self>;
     async fn send_test_update(&self, results: TestResults) -> InfoClientResult<()>;
+
+    /// Publish values for other tests and resources to use in their configuration with
+    /// `${test:<test name>.<field name>}`. The values can be used once the test has completed.
+    /// The default implementation does not publish anything.
+    async fn send_outputs<O>(&self, _outputs: O) -> InfoClientResult<()>
+    where
+        O: Configuration,
+    {
+        Ok(())
+    }
 }
 
 pub struct DefaultInfoClient {
@@ -9,6 +9,7 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Display};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tempfile::{tempdir, TempDir};
 use test_agent::error::InfoClientResult;
 use test_agent::{BootstrapData, Client, InfoClient, Runner};
@@ -70,17 +71,24 @@ where
 struct MockClient {
     results_dir: TempDir,
     results_file: TempDir,
+    /// Whether the test has been cancelled, which we use to mock the cancellation of a test named
+    /// `cancelled-test`.
+    cancel: bool,
 }
 
+/// Set when `MockClient` is sent the results of a cancelled test.
+static CANCELLED_RESULTS_SENT: AtomicBool = AtomicBool::new(false);
+
 #[async_trait]
 impl Client for MockClient {
     /// We use a `String` as the error type for convenience.
     type E = String;
 
-    async fn new(_: BootstrapData) -> Result<Self, Self::E> {
+    async fn new(bootstrap_data: BootstrapData) -> Result<Self, Self::E> {
         Ok(Self {
             results_dir: tempdir().unwrap(),
             results_file: tempdir().unwrap(),
+            cancel: bootstrap_data.test_name == "cancelled-test",
         })
     }
 
@@ -109,6 +117,9 @@ impl Client for MockClient {
 
     async fn send_test_results(&self, results: TestResults) -> Result<(), Self::E> {
         println!("MockClient::send_test_results: {:?}", results);
+        if results.outcome == Outcome::Cancelled {
+            CANCELLED_RESULTS_SENT.store(true, Ordering::SeqCst);
+        }
         Ok(())
     }
 
@@ -132,6 +143,10 @@ impl Client for MockClient {
         Ok(self.results_file.path().join("result.tar.gz"))
     }
 
+    async fn cancel_requested(&self) -> Result<bool, Self::E> {
+        Ok(self.cancel)
+    }
+
     async fn retries(&self) -> Result<u32, Self::E> {
         Ok(0)
     }
@@ -140,6 +155,14 @@ impl Client for MockClient {
         println!("MockClient::send_test_completed");
         Ok(())
     }
+
+    async fn send_outputs<O>(&self, outputs: O) -> Result<(), Self::E>
+    where
+        O: Configuration,
+    {
+        println!("MockClient::send_outputs: {:?}", outputs);
+        Ok(())
+    }
 }
 
 struct MyInfoClient {}
@@ -171,3 +194,18 @@ async fn mock_test() -> std::io::Result<()> {
     assert!(std::path::Path::new(&agent_main.results_file().await.unwrap()).is_file());
     Ok(())
 }
+
+/// A cancelled test is terminated, reports a `Cancelled` outcome and still archives its results.
+#[tokio::test]
+async fn mock_cancelled_test() -> std::io::Result<()> {
+    let mut agent_main =
+        test_agent::TestAgent::<MockClient, MyRunner, MyInfoClient>::new(BootstrapData {
+            test_name: String::from("cancelled-test"),
+        })
+        .await
+        .unwrap();
+    agent_main.run().await.unwrap();
+    assert!(CANCELLED_RESULTS_SENT.load(Ordering::SeqCst));
+    assert!(std::path::Path::new(&agent_main.results_file().await.unwrap()).is_file());
+    Ok(())
+}
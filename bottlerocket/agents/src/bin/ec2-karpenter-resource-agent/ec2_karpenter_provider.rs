@@ -8,6 +8,7 @@ use aws_sdk_eks::types::{
 use bottlerocket_types::agent_config::{
     ClusterType, Ec2KarpenterConfig, AWS_CREDENTIALS_SECRET_NAME,
 };
+use configuration_derive::Configuration;
 use k8s_openapi::api::apps::v1::Deployment;
 use k8s_openapi::api::core::v1::Node;
 use kube::config::{KubeConfigOptions, Kubeconfig};
@@ -17,6 +18,7 @@ use resource_agent::clients::InfoClient;
 use resource_agent::provider::{
     Create, Destroy, IntoProviderError, ProviderError, ProviderResult, Resources, Spec,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashSet;
@@ -68,12 +70,12 @@ impl Configuration for ProductionMemo {}
 
 /// Once we have fulfilled the `Create` request, we return information about the batch of ec2 instances we
 /// created.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Configuration, Default,
+)]
 #[serde(rename_all = "camelCase")]
 pub struct CreatedEc2Instances {}
 
-impl Configuration for CreatedEc2Instances {}
-
 pub struct Ec2KarpenterCreator {}
 
 #[async_trait::async_trait]
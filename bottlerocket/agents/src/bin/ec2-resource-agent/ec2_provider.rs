@@ -14,11 +14,13 @@ use bottlerocket_agents::userdata::{decode_to_string, merge_values};
 use bottlerocket_types::agent_config::{
     ClusterType, CustomUserData, Ec2Config, AWS_CREDENTIALS_SECRET_NAME,
 };
+use configuration_derive::Configuration;
 use log::{debug, info, trace, warn};
 use resource_agent::clients::InfoClient;
 use resource_agent::provider::{
     AsResources, Create, Destroy, IntoProviderError, ProviderError, ProviderResult, Resources, Spec,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fmt::Debug;
@@ -65,15 +67,15 @@ impl Configuration for ProductionMemo {}
 
 /// Once we have fulfilled the `Create` request, we return information about the batch of ec2 instances we
 /// created.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Configuration, Default,
+)]
 #[serde(rename_all = "camelCase")]
 pub struct CreatedEc2Instances {
     /// The ids of all created instances
     pub ids: HashSet<String>,
 }
 
-impl Configuration for CreatedEc2Instances {}
-
 pub struct Ec2Creator {}
 
 #[async_trait::async_trait]
@@ -99,8 +101,13 @@ impl Create for Ec2Creator {
             .await
             .context(Resources::Unknown, "Unable to get info from info client")?;
 
-        // Set the uuid before we do anything so we know it is stored.
-        let instance_uuid = Uuid::new_v4().to_string();
+        // Set the uuid before we do anything so we know it is stored. If the controller is retrying
+        // a failed creation, the memo already has the uuid of the instances that may have been left
+        // behind, so we keep it.
+        let previous_uuid = memo.uuid_tag.clone();
+        let instance_uuid = previous_uuid
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
         info!(
             "Beginning instance creation with instance UUID: {}",
             instance_uuid
@@ -143,6 +150,25 @@ impl Create for Ec2Creator {
         .context(Resources::Clear, "Error creating config")?;
         let ec2_client = aws_sdk_ec2::Client::new(&shared_config);
 
+        if let Some(uuid) = &previous_uuid {
+            let leftover_ids = get_instances_by_uuid(&ec2_client, uuid, &memo).await?;
+            if !leftover_ids.is_empty() {
+                info!(
+                    "Terminating instances {:?} left by a previous attempt",
+                    leftover_ids
+                );
+                ec2_client
+                    .terminate_instances()
+                    .set_instance_ids(Some(Vec::from_iter(leftover_ids)))
+                    .send()
+                    .await
+                    .context(
+                        Resources::Remaining,
+                        "Unable to terminate instances left by a previous attempt",
+                    )?;
+            }
+        }
+
         info!("Launching instance(s)");
         memo.current_status = "Launching instance(s)".to_string();
         client
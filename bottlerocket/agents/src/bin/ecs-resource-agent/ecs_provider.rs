@@ -6,11 +6,13 @@ use aws_sdk_iam::operation::get_instance_profile::{
 };
 use aws_types::sdk_config::SdkConfig;
 use bottlerocket_types::agent_config::{EcsClusterConfig, AWS_CREDENTIALS_SECRET_NAME};
+use configuration_derive::Configuration;
 use log::{error, info};
 use resource_agent::clients::InfoClient;
 use resource_agent::provider::{
     Create, Destroy, IntoProviderError, ProviderResult, Resources, Spec,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use testsys_model::{Configuration, SecretName};
@@ -40,7 +42,9 @@ pub struct Memo {
 
 impl Configuration for Memo {}
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Configuration, Default,
+)]
 #[serde(rename_all = "camelCase")]
 pub struct CreatedCluster {
     /// The name of the cluster we created.
@@ -59,8 +63,6 @@ pub struct CreatedCluster {
     pub iam_instance_profile_arn: String,
 }
 
-impl Configuration for CreatedCluster {}
-
 pub struct EcsCreator {}
 
 #[async_trait::async_trait]
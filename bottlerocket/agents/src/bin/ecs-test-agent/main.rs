@@ -171,6 +171,7 @@ async fn test_results(
         num_failed: (task_count - running_count) as u64,
         num_skipped: 0,
         other_info: None,
+        ..Default::default()
     })
 }
 
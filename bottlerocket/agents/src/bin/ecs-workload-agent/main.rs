@@ -286,6 +286,7 @@ async fn test_results(
         num_failed: failed_count as u64,
         num_skipped: 0,
         other_info: None,
+        ..Default::default()
     })
 }
 
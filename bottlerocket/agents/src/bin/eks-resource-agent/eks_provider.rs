@@ -12,11 +12,13 @@ use bottlerocket_agents::is_cluster_creation_required;
 use bottlerocket_types::agent_config::{
     CreationPolicy, EksClusterConfig, EksctlConfig, K8sVersion, AWS_CREDENTIALS_SECRET_NAME,
 };
+use configuration_derive::Configuration;
 use log::{debug, info, trace};
 use resource_agent::clients::InfoClient;
 use resource_agent::provider::{
     Create, Destroy, IntoProviderError, ProviderError, ProviderResult, Resources, Spec,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::env::temp_dir;
@@ -57,7 +59,9 @@ pub struct ProductionMemo {
 impl Configuration for ProductionMemo {}
 impl_display_as_json!(ProductionMemo);
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Configuration, Default,
+)]
 #[serde(rename_all = "camelCase")]
 pub struct CreatedCluster {
     /// The name of the cluster we created.
@@ -94,7 +98,6 @@ pub struct CreatedCluster {
     pub encoded_kubeconfig: String,
 }
 
-impl Configuration for CreatedCluster {}
 impl_display_as_json!(CreatedCluster);
 
 #[derive(Debug)]
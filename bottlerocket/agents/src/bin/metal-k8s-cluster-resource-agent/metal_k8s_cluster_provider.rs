@@ -35,6 +35,7 @@ use bottlerocket_agents::clusters::{
 use bottlerocket_types::agent_config::{
     CustomUserData, MetalK8sClusterConfig, AWS_CREDENTIALS_SECRET_NAME,
 };
+use configuration_derive::Configuration;
 use k8s_openapi::api::core::v1::Node;
 use kube::config::Kubeconfig;
 use kube::{Api, Config};
@@ -44,6 +45,7 @@ use resource_agent::clients::InfoClient;
 use resource_agent::provider::{
     Create, Destroy, IntoProviderError, ProviderError, ProviderResult, Resources, Spec,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashSet;
@@ -81,7 +83,7 @@ pub struct ProductionMemo {
 impl Configuration for ProductionMemo {}
 
 /// Once we have fulfilled the `Create` request, we return information about the metal K8s cluster
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Configuration, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CreatedMetalK8sCluster {
     /// The base64 encoded kubeconfig for this cluster
@@ -91,8 +93,6 @@ pub struct CreatedMetalK8sCluster {
     pub instance_ids: HashSet<String>,
 }
 
-impl Configuration for CreatedMetalK8sCluster {}
-
 pub struct MetalK8sClusterCreator {}
 
 #[async_trait::async_trait]
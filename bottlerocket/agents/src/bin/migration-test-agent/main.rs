@@ -34,6 +34,9 @@ spec:
   resources: [x86-aws-k8s-1-21-ec2-instances, eks-1-21-ipv4]
 ```
 
+Once every instance has migrated, the agent publishes the version as the `version` output, which
+other tests and resources can use in their configuration with `${test:upgrade-ec2-test.version}`.
+
 !*/
 
 mod ssm;
@@ -45,7 +48,9 @@ use agent_utils::aws::aws_config;
 use agent_utils::init_agent_logger;
 use async_trait::async_trait;
 use bottlerocket_agents::error::{self, Error};
-use bottlerocket_types::agent_config::{MigrationConfig, AWS_CREDENTIALS_SECRET_NAME};
+use bottlerocket_types::agent_config::{
+    MigrationConfig, MigrationOutputs, AWS_CREDENTIALS_SECRET_NAME,
+};
 use log::{error, info};
 use maplit::hashmap;
 use snafu::ResultExt;
@@ -82,7 +87,7 @@ where
         })
     }
 
-    async fn run(&mut self, _info_client: &I) -> Result<TestResults, Self::E> {
+    async fn run(&mut self, info_client: &I) -> Result<TestResults, Self::E> {
         let shared_config = aws_config(
             &self.aws_secret_name.as_ref(),
             &self.config.assume_role,
@@ -179,6 +184,11 @@ where
                     "All instances successfully migrated to {}",
                     self.config.migrate_to_version
                 );
+                info_client
+                    .send_outputs(MigrationOutputs {
+                        version: self.config.migrate_to_version.clone(),
+                    })
+                    .await?;
                 Ok(TestResults {
                     outcome: Outcome::Pass,
                     num_passed: self.config.instance_ids.len() as u64,
@@ -188,6 +198,7 @@ where
                         "Instances '{:?}' successfully migrated to {}",
                         &self.config.instance_ids, &self.config.migrate_to_version
                     )),
+                    ..Default::default()
                 })
             }
             Err(e) => match e {
@@ -208,6 +219,7 @@ where
                             "Instance(s) '{:?}' successfully migrated to {}; Instance(s) '{:?}' failed to migrate",
                             &self.config.instance_ids, target_version, instance_ids
                         )),
+                        ..Default::default()
                     })
                 }
                 _ => Err(e),
@@ -12,6 +12,7 @@ use bottlerocket_agents::vsphere::vsphere_credentials;
 use bottlerocket_types::agent_config::{
     CreationPolicy, VSphereK8sClusterConfig, VSPHERE_CREDENTIALS_SECRET_NAME,
 };
+use configuration_derive::Configuration;
 use k8s_openapi::api::core::v1::Node;
 use kube::api::ListParams;
 use kube::config::{KubeConfigOptions, Kubeconfig};
@@ -21,6 +22,7 @@ use resource_agent::clients::InfoClient;
 use resource_agent::provider::{
     Create, Destroy, IntoProviderError, ProviderError, ProviderResult, Resources, Spec,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::convert::TryFrom;
@@ -58,7 +60,7 @@ pub struct ProductionMemo {
 impl Configuration for ProductionMemo {}
 
 /// Once we have fulfilled the `Create` request, we return information about the cluster we've created
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Configuration, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CreatedVSphereK8sCluster {
     /// The name of the cluster.
@@ -71,8 +73,6 @@ pub struct CreatedVSphereK8sCluster {
     pub encoded_kubeconfig: String,
 }
 
-impl Configuration for CreatedVSphereK8sCluster {}
-
 pub struct VSphereK8sClusterCreator {}
 
 #[async_trait::async_trait]
@@ -10,6 +10,7 @@ use bottlerocket_agents::vsphere::vsphere_credentials;
 use bottlerocket_types::agent_config::{
     CustomUserData, VSphereVmConfig, AWS_CREDENTIALS_SECRET_NAME, VSPHERE_CREDENTIALS_SECRET_NAME,
 };
+use configuration_derive::Configuration;
 use k8s_openapi::api::core::v1::{Node, Service};
 use kube::api::{DeleteParams, ListParams};
 use kube::config::{KubeConfigOptions, Kubeconfig};
@@ -19,6 +20,7 @@ use resource_agent::clients::InfoClient;
 use resource_agent::provider::{
     Create, Destroy, IntoProviderError, ProviderError, ProviderResult, Resources, Spec,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashSet;
@@ -77,15 +79,13 @@ impl Configuration for ProductionMemo {}
 
 /// Once we have fulfilled the `Create` request, we return information about the batch of VSphere VMs
 /// we've created
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Configuration, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CreatedVSphereVms {
     /// The instance IDs of all SSM-registered VMs
     pub instance_ids: HashSet<String>,
 }
 
-impl Configuration for CreatedVSphereVms {}
-
 pub struct VMCreator {}
 
 #[async_trait::async_trait]
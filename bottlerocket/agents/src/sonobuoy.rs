@@ -10,10 +10,13 @@ use std::path::Path;
 use std::process::Command;
 use std::time::Duration;
 use test_agent::InfoClient;
-use testsys_model::{Outcome, SecretName, TestResults};
+use testsys_model::{Outcome, SecretName, TestCase, TestCaseStatus, TestResults};
 
 /// Timeout for sonobuoy status to become available (seconds)
 const SONOBUOY_STATUS_TIMEOUT: u64 = 900;
+/// The maximum number of test cases reported in `TestResults::test_cases`. Failed test cases are
+/// reported first so they are not dropped in favor of passing ones.
+const MAX_TEST_CASES: usize = 100;
 
 /// Runs the sonobuoy conformance tests according to the provided configuration and returns a test
 /// result at the end.
@@ -381,7 +384,36 @@ pub fn results_sonobuoy(
         serde_json::from_str(&stdout).context(error::DeserializeJsonSnafu)?;
     trace!("The sonobuoy results are valid json");
 
-    process_sonobuoy_test_results(&run_status)
+    let detailed_results = sonobuoy_detailed_results(&results_filepath);
+    process_sonobuoy_test_results(&run_status, detailed_results.as_deref())
+}
+
+/// Lists the individual test cases in a sonobuoy results tarball using
+/// `sonobuoy results --mode detailed`. Test cases are informational, so failures are logged
+/// instead of returned.
+pub(crate) fn sonobuoy_detailed_results(results_filepath: &Path) -> Option<String> {
+    info!("Getting detailed sonobuoy results");
+    let output = match Command::new("/usr/bin/sonobuoy")
+        .arg("results")
+        .arg(results_filepath.as_os_str())
+        .arg("--mode")
+        .arg("detailed")
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => {
+            error!("Unable to run 'sonobuoy results --mode detailed': {}", e);
+            return None;
+        }
+    };
+    if !output.status.success() {
+        error!(
+            "Bad exit code from 'sonobuoy results --mode detailed': exit {}",
+            output.status.code().unwrap_or(1)
+        );
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
 /// process_incomplete_sonobuoy_test_results parses the output from `sonobuoy status --json` output for
@@ -427,13 +459,16 @@ pub(crate) fn process_incomplete_sonobuoy_test_results(
         num_failed,
         num_skipped: 0,
         other_info: Some("Running".to_string()),
+        ..Default::default()
     })
 }
 
 /// process_sonobuoy_test_results parses the output from `sonobuoy status --json` output and gets
-/// the overall status of the plugin results.
+/// the overall status of the plugin results. If the output of `sonobuoy results --mode detailed` is
+/// provided, the individual test cases are included in the results.
 pub(crate) fn process_sonobuoy_test_results(
     run_status: &serde_json::Value,
+    detailed_results: Option<&str>,
 ) -> Result<TestResults, error::Error> {
     let mut num_passed: u64 = 0;
     let mut num_failed: u64 = 0;
@@ -525,9 +560,66 @@ pub(crate) fn process_sonobuoy_test_results(
         num_failed,
         num_skipped,
         other_info: Some(progress.join(", ")),
+        test_cases: detailed_results.map(process_sonobuoy_test_cases),
+        ..Default::default()
     })
 }
 
+/// process_sonobuoy_test_cases parses the output from `sonobuoy results --mode detailed`, which
+/// contains a json object for each test case. Passing and skipped test cases are only included
+/// while there are fewer than `MAX_TEST_CASES` failures.
+pub(crate) fn process_sonobuoy_test_cases(detailed_results: &str) -> Vec<TestCase> {
+    let mut test_cases: Vec<TestCase> = detailed_results
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<Value>(line) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                trace!(
+                    "Skipping test case that is not valid json '{}': {}",
+                    line,
+                    e
+                );
+                None
+            }
+        })
+        .filter_map(|item| {
+            let name = item.get("name")?.as_str()?.to_string();
+            let status = match item.get("status").and_then(Value::as_str) {
+                Some("passed") => TestCaseStatus::Passed,
+                Some("failed") => TestCaseStatus::Failed,
+                Some("skipped") => TestCaseStatus::Skipped,
+                _ => TestCaseStatus::Unknown,
+            };
+            let details = item.get("details");
+            let duration = details
+                .and_then(|details| details.get("time"))
+                .and_then(|time| match time {
+                    Value::Number(number) => number.as_f64(),
+                    Value::String(time) => time.parse().ok(),
+                    _ => None,
+                })
+                .map(|seconds| format!("{}s", seconds));
+            let failure_message = details
+                .and_then(|details| details.get("failure"))
+                .map(|failure| match failure {
+                    Value::String(failure) => failure.to_owned(),
+                    other => other.to_string(),
+                })
+                .map(TestCase::truncate_failure_message);
+            Some(TestCase {
+                name,
+                status,
+                duration,
+                failure_message,
+            })
+        })
+        .collect();
+    test_cases.sort_by_key(|test_case| test_case.status != TestCaseStatus::Failed);
+    test_cases.truncate(MAX_TEST_CASES);
+    test_cases
+}
+
 /// Deletes all sonobuoy namespaces and associated resources in the target K8s cluster
 pub async fn delete_sonobuoy(kubeconfig_path: &str) -> Result<(), error::Error> {
     let kubeconfig_arg = vec!["--kubeconfig", kubeconfig_path];
@@ -555,7 +647,7 @@ mod test_sonobuoy {
     fn test_process_results_pass() {
         let result =
             process_sonobuoy_test_results(
-                &json!({"plugins":[{"plugin":"e2e","node":"global","status":"complete","result-status":"passed","result-counts":{"passed":6}}]})).unwrap();
+                &json!({"plugins":[{"plugin":"e2e","node":"global","status":"complete","result-status":"passed","result-counts":{"passed":6}}]}), None).unwrap();
         assert_eq!(result.num_passed, 6);
         assert_eq!(result.num_failed, 0);
         assert_eq!(result.num_skipped, 0);
@@ -567,7 +659,7 @@ mod test_sonobuoy {
     fn test_process_results_failed() {
         let result =
             process_sonobuoy_test_results(
-                &json!({"plugins":[{"plugin":"e2e","node":"global","status":"complete","result-status":"failed","result-counts":{"failed":1}}]})).unwrap();
+                &json!({"plugins":[{"plugin":"e2e","node":"global","status":"complete","result-status":"failed","result-counts":{"failed":1}}]}), None).unwrap();
         assert_eq!(result.num_passed, 0);
         assert_eq!(result.num_failed, 1);
         assert_eq!(result.num_skipped, 0);
@@ -579,7 +671,7 @@ mod test_sonobuoy {
     fn test_process_results_timeout() {
         let result =
             process_sonobuoy_test_results(
-                &json!({"plugins":[{"plugin":"e2e","node":"global","status":"complete","result-status":"timed-out","result-counts":{"failed":1}}]})).unwrap();
+                &json!({"plugins":[{"plugin":"e2e","node":"global","status":"complete","result-status":"timed-out","result-counts":{"failed":1}}]}), None).unwrap();
         assert_eq!(result.num_passed, 0);
         assert_eq!(result.num_failed, 1);
         assert_eq!(result.num_skipped, 0);
@@ -591,7 +683,7 @@ mod test_sonobuoy {
     fn test_process_results_progress_object() {
         let result =
             process_sonobuoy_test_results(
-                &json!({"plugins":[{"plugin":"e2e","progress":{"name":"e2e","node":"global","timestamp":"2022-12-08T15:37:23.007805243Z","msg":"Test Suite completed","total":1,"completed":1},"status":"complete","result-status":"timed-out","result-counts":{"failed":1}}]})).unwrap();
+                &json!({"plugins":[{"plugin":"e2e","progress":{"name":"e2e","node":"global","timestamp":"2022-12-08T15:37:23.007805243Z","msg":"Test Suite completed","total":1,"completed":1},"status":"complete","result-status":"timed-out","result-counts":{"failed":1}}]}), None).unwrap();
         assert_eq!(result.num_passed, 0);
         assert_eq!(result.num_failed, 1);
         assert_eq!(result.num_skipped, 0);
@@ -608,7 +700,8 @@ mod test_sonobuoy {
                     "plugins":[
                         {"plugin":"smoketest","node":"global","status":"complete","result-status":"pass","result-counts":{"passed":1}},
                         {"plugin":"workload","node":"global","status":"complete","result-status":"pass","result-counts":{"passed":1,"skipped":1}},
-                    ]})
+                    ]}),
+                None,
                 ).unwrap();
         assert_eq!(result.num_passed, 2);
         assert_eq!(result.num_failed, 0);
@@ -626,7 +719,8 @@ mod test_sonobuoy {
                     "plugins":[
                         {"plugin":"smoketest","node":"global","status":"complete","result-status":"pass","result-counts":{"passed":1}},
                         {"plugin":"workload","node":"global","status":"complete","result-status":"fail","result-counts":{"failed":1,"skipped":1}},
-                    ]})
+                    ]}),
+                None,
                 ).unwrap();
         assert_eq!(result.num_passed, 1);
         assert_eq!(result.num_failed, 1);
@@ -644,7 +738,8 @@ mod test_sonobuoy {
                     "plugins":[
                         {"plugin":"smoketest","node":"global","status":"complete","result-status":"pass","result-counts":{"passed":1}},
                         {"plugin":"workload","node":"global","status":"complete","result-status":"timeout","result-counts":{"failed":1,"skipped":1}},
-                    ]})
+                    ]}),
+                None,
                 ).unwrap();
         assert_eq!(result.num_passed, 1);
         assert_eq!(result.num_failed, 1);
@@ -662,7 +757,8 @@ mod test_sonobuoy {
                     "plugins":[
                         {"plugin":"smoketest","node":"global","status":"complete","result-status":"failed","result-counts":{"failed":1}},
                         {"plugin":"workload","node":"global","status":"complete","result-status":"timeout","result-counts":{"skipped":1}},
-                    ]})
+                    ]}),
+                None,
                 ).unwrap();
         assert_eq!(result.num_passed, 0);
         assert_eq!(result.num_failed, 1);
@@ -680,7 +776,8 @@ mod test_sonobuoy {
                     "plugins":[
                         {"plugin":"smoketest","progress":"one","status":"complete","result-status":"pass","result-counts":{"passed":1}},
                         {"plugin":"workload","progress":"two","status":"complete","result-status":"pass","result-counts":{"passed":1,"skipped":1}},
-                    ]})
+                    ]}),
+                None,
                 ).unwrap();
         assert_eq!(result.num_passed, 2);
         assert_eq!(result.num_failed, 0);
@@ -691,4 +788,42 @@ mod test_sonobuoy {
             "smoketest: \"one\", workload: \"two\""
         );
     }
+
+    #[test]
+    fn test_process_results_test_cases() {
+        let detailed_results = [
+            json!({"name":"[sig-node] passes","status":"passed","meta":{"path":"e2e|junit_01.xml"},"details":{"time":"1.5"}}),
+            json!({"name":"[sig-node] is skipped","status":"skipped","meta":{"path":"e2e|junit_01.xml"}}),
+            json!({"name":"[sig-node] fails","status":"failed","meta":{"path":"e2e|junit_01.xml"},"details":{"failure":"x".repeat(2000),"time":12}}),
+        ]
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+        let result =
+            process_sonobuoy_test_results(
+                &json!({"plugins":[{"plugin":"e2e","node":"global","status":"complete","result-status":"failed","result-counts":{"passed":1,"failed":1,"skipped":1}}]}),
+                Some(&detailed_results),
+            ).unwrap();
+        let test_cases = result.test_cases.as_ref().unwrap();
+        assert_eq!(test_cases.len(), 3);
+        // Failures are listed first.
+        assert_eq!(test_cases[0].name, "[sig-node] fails");
+        assert_eq!(test_cases[0].status, TestCaseStatus::Failed);
+        assert_eq!(test_cases[0].duration.as_deref(), Some("12s"));
+        let failure_message = test_cases[0].failure_message.as_ref().unwrap();
+        assert_eq!(
+            failure_message.len(),
+            testsys_model::TEST_CASE_FAILURE_MESSAGE_LIMIT
+        );
+        assert!(failure_message.ends_with("..."));
+        assert_eq!(test_cases[1].status, TestCaseStatus::Passed);
+        assert_eq!(test_cases[1].duration.as_deref(), Some("1.5s"));
+        assert_eq!(test_cases[2].status, TestCaseStatus::Skipped);
+        assert_eq!(test_cases[2].failure_message, None);
+        assert_eq!(
+            result.failed_test_cases().collect::<Vec<_>>(),
+            vec!["[sig-node] fails"]
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use crate::error;
 use crate::sonobuoy::{
-    process_sonobuoy_test_results, wait_for_sonobuoy_results, wait_for_sonobuoy_status,
+    process_sonobuoy_test_results, sonobuoy_detailed_results, wait_for_sonobuoy_results,
+    wait_for_sonobuoy_status,
 };
 use bottlerocket_types::agent_config::{WorkloadConfig, SONOBUOY_RESULTS_FILENAME};
 use log::{info, trace};
@@ -226,7 +227,8 @@ pub fn results_workload(
         serde_json::from_str(&stdout).context(error::DeserializeJsonSnafu)?;
     trace!("The workload results are valid json");
 
-    process_sonobuoy_test_results(&run_status)
+    let detailed_results = sonobuoy_detailed_results(&results_filepath);
+    process_sonobuoy_test_results(&run_status, detailed_results.as_deref())
 }
 
 /// Deletes all workload namespaces and associated resources in the target K8s cluster
@@ -1,5 +1,8 @@
 use builder_derive::Builder;
 use configuration_derive::Configuration;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_plain::{
     derive_deserialize_from_fromstr, derive_display_from_serialize,
@@ -13,7 +16,7 @@ pub const AWS_CREDENTIALS_SECRET_NAME: &str = "awsCredentials";
 pub const SONOBUOY_RESULTS_FILENAME: &str = "sonobuoy-results.tar.gz";
 pub const VSPHERE_CREDENTIALS_SECRET_NAME: &str = "vsphereCredentials";
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct VSphereK8sClusterInfo {
     pub name: String,
@@ -21,7 +24,7 @@ pub struct VSphereK8sClusterInfo {
     pub kubeconfig_base64: String,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum CustomUserData {
     Replace { encoded_userdata: String },
@@ -30,7 +33,9 @@ pub enum CustomUserData {
 
 /// What mode to run the e2e plugin in. Valid modes are `non-disruptive-conformance`,
 /// `certified-conformance` and `quick`.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, JsonSchema,
+)]
 // For most things we match Kubernetes style and use camelCase, but for this we want kebab case to
 // match the format in which the argument is passed to Sonobuoy.
 #[serde(rename_all = "kebab-case")]
@@ -57,7 +62,7 @@ impl Default for SonobuoyMode {
 derive_display_from_serialize!(SonobuoyMode);
 derive_fromstr_from_deserialize!(SonobuoyMode);
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Configuration, Builder)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Configuration, Builder)]
 #[serde(rename_all = "camelCase")]
 #[crd("Test")]
 pub struct SonobuoyConfig {
@@ -76,14 +81,14 @@ pub struct SonobuoyConfig {
     pub assume_role: Option<String>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TufRepoConfig {
     pub metadata_url: String,
     pub targets_url: String,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Configuration, Builder)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Configuration, Builder)]
 #[serde(rename_all = "camelCase")]
 #[crd("Test")]
 pub struct MigrationConfig {
@@ -94,8 +99,19 @@ pub struct MigrationConfig {
     pub assume_role: Option<String>,
 }
 
+/// The outputs that the migration test agent publishes once every instance has migrated, e.g. for
+/// `${test:<test name>.version}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Configuration)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationOutputs {
+    /// The version that the instances migrated to.
+    pub version: String,
+}
+
 /// The configuration information for a eks instance provider.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+)]
 #[serde(rename_all = "camelCase")]
 #[crd("Resource")]
 pub struct EksClusterConfig {
@@ -114,7 +130,7 @@ pub struct EksClusterConfig {
     pub config: EksctlConfig,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum EksctlConfig {
     #[serde(rename_all = "camelCase")]
@@ -141,7 +157,9 @@ impl Default for EksctlConfig {
 }
 
 /// The configuration information for a vSphere K8s cluster provider.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+)]
 #[serde(rename_all = "camelCase")]
 #[crd("Resource")]
 pub struct VSphereK8sClusterConfig {
@@ -193,7 +211,9 @@ pub struct VSphereK8sClusterConfig {
 }
 
 /// The configuration information for a eks instance provider.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+)]
 #[serde(rename_all = "camelCase")]
 #[crd("Resource")]
 pub struct MetalK8sClusterConfig {
@@ -217,7 +237,7 @@ pub struct MetalK8sClusterConfig {
     pub custom_user_data: Option<CustomUserData>,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum CreationPolicy {
     /// Create the item, it is an error if the item already exists. This is the default
@@ -238,7 +258,9 @@ impl Default for CreationPolicy {
 derive_display_from_serialize!(CreationPolicy);
 derive_fromstr_from_deserialize!(CreationPolicy);
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+)]
 #[serde(rename_all = "camelCase")]
 #[crd("Resource")]
 pub struct Ec2Config {
@@ -290,7 +312,7 @@ pub struct Ec2Config {
     pub security_groups: Vec<String>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct KarpenterDeviceMapping {
     pub name: String,
@@ -299,7 +321,9 @@ pub struct KarpenterDeviceMapping {
     pub delete_on_termination: bool,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+)]
 #[serde(rename_all = "camelCase")]
 #[crd("Resource")]
 pub struct Ec2KarpenterConfig {
@@ -340,7 +364,7 @@ pub struct Ec2KarpenterConfig {
     pub instance_types: Vec<String>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum ClusterType {
     Eks,
@@ -354,7 +378,9 @@ impl Default for ClusterType {
 }
 
 /// The configuration information for an ecs instance provider.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder,
+)]
 #[serde(rename_all = "camelCase")]
 #[crd("Resource")]
 pub struct EcsClusterConfig {
@@ -495,7 +521,18 @@ impl FromStr for K8sVersion {
 derive_serialize_from_display!(K8sVersion);
 derive_deserialize_from_fromstr!(K8sVersion, "k8s version such as v1.21 or 1.21.1");
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Configuration, Builder)]
+/// `K8sVersion` is serialized as a string such as `v1.21`.
+impl JsonSchema for K8sVersion {
+    fn schema_name() -> String {
+        "K8sVersion".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Configuration, Builder)]
 #[serde(rename_all = "camelCase")]
 #[crd("Test")]
 pub struct EcsTestConfig {
@@ -518,7 +555,7 @@ fn default_count() -> i32 {
     1
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Default, Configuration, Builder)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Default, Configuration, Builder)]
 #[serde(rename_all = "camelCase")]
 #[crd("Resource")]
 pub struct VSphereVmConfig {
@@ -576,7 +613,7 @@ fn k8s_version_valid() {
     assert_eq!("1.21.3", k8s_version.full_version_without_v());
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct WorkloadTest {
     pub name: String,
     pub image: String,
@@ -584,7 +621,7 @@ pub struct WorkloadTest {
     pub gpu: bool,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Configuration, Builder)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Configuration, Builder)]
 #[serde(rename_all = "camelCase")]
 #[crd("Test")]
 pub struct WorkloadConfig {
@@ -594,7 +631,7 @@ pub struct WorkloadConfig {
     pub sonobuoy_image: Option<String>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Configuration, Builder)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Configuration, Builder)]
 #[serde(rename_all = "camelCase")]
 #[crd("Test")]
 pub struct EcsWorkloadTestConfig {
@@ -0,0 +1,92 @@
+use crate::agent_config::{
+    Ec2Config, Ec2KarpenterConfig, EcsClusterConfig, EcsTestConfig, EcsWorkloadTestConfig,
+    EksClusterConfig, MetalK8sClusterConfig, MigrationConfig, SonobuoyConfig,
+    VSphereK8sClusterConfig, VSphereVmConfig, WorkloadConfig,
+};
+use testsys_model::validation::SchemaRegistry;
+
+/// The configuration schemas of the Bottlerocket agents, keyed by the names of the agents' images
+/// as they are listed in the `Makefile`.
+pub fn agent_schemas() -> SchemaRegistry {
+    SchemaRegistry::default()
+        .register::<SonobuoyConfig>("sonobuoy-test-agent")
+        .register::<Ec2Config>("ec2-resource-agent")
+        .register::<EksClusterConfig>("eks-resource-agent")
+        .register::<EcsClusterConfig>("ecs-resource-agent")
+        .register::<MigrationConfig>("migration-test-agent")
+        .register::<VSphereVmConfig>("vsphere-vm-resource-agent")
+        .register::<VSphereK8sClusterConfig>("vsphere-k8s-cluster-resource-agent")
+        .register::<EcsTestConfig>("ecs-test-agent")
+        .register::<WorkloadConfig>("k8s-workload-agent")
+        .register::<EcsWorkloadTestConfig>("ecs-workload-agent")
+        .register::<MetalK8sClusterConfig>("metal-k8s-cluster-resource-agent")
+        .register::<Ec2KarpenterConfig>("ec2-karpenter-resource-agent")
+}
+
+#[cfg(test)]
+mod test {
+    use super::agent_schemas;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+    use testsys_model::validation::validate_configuration;
+    use testsys_model::Crd;
+
+    /// Replaces the environment variables in a sample manifest the way `envsubst` would.
+    fn substitute(manifest: &str) -> String {
+        let mut substituted = manifest.replace("\\$", "$");
+        while let Some(start) = substituted.find("${") {
+            let end = start + substituted[start..].find('}').unwrap();
+            let variable = &substituted[start + 2..end];
+            if !variable
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            {
+                // `${cluster.endpoint}` templates are left for the resource client to resolve.
+                substituted.replace_range(start..start + 1, "\u{0}");
+                continue;
+            }
+            let value = match variable {
+                "CLUSTER_NAME" => "cluster".to_string(),
+                "GPU" => "false".to_string(),
+                "INSTANCE_TYPES" => r#"["m5.large"]"#.to_string(),
+                "SONOBUOY_MODE" => "quick".to_string(),
+                image if image.ends_with("_IMAGE_URI") => image
+                    .trim_end_matches("_IMAGE_URI")
+                    .to_lowercase()
+                    .replace('_', "-"),
+                _ => "value".to_string(),
+            };
+            substituted.replace_range(start..=end, &value);
+        }
+        substituted.replace('\u{0}', "$")
+    }
+
+    #[test]
+    fn samples_match_schemas() {
+        let registry = agent_schemas();
+        let samples = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("samples");
+        for dir in ["eks", "kind"] {
+            for entry in std::fs::read_dir(samples.join(dir)).unwrap() {
+                let path = entry.unwrap().path();
+                let manifest = substitute(&std::fs::read_to_string(&path).unwrap());
+                for document in serde_yaml::Deserializer::from_str(&manifest) {
+                    let crd = Crd::deserialize(document).unwrap();
+                    let agent = match &crd {
+                        Crd::Test(test) => &test.spec.agent,
+                        Crd::Resource(resource) => &resource.spec.agent,
+                        _ => continue,
+                    };
+                    assert!(
+                        registry.get(&agent.image).is_some(),
+                        "no schema for '{}'",
+                        agent.image
+                    );
+                    let problems = validate_configuration(agent, &registry);
+                    assert!(problems.is_empty(), "{}: {:?}", path.display(), problems);
+                }
+            }
+        }
+    }
+}
@@ -3,3 +3,4 @@ This library is for core types that may be used by testsys, agents, and any othe
 specific crates.
  */
 pub mod agent_config;
+pub mod agent_schemas;
@@ -0,0 +1,25 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use testsys_model::test_manager::TestManager;
+
+/// Cancel tests. Their agents stop the tests, keep the results they have so far and finish with a
+/// `cancelled` outcome.
+#[derive(Debug, Parser)]
+pub(crate) struct Cancel {
+    /// The names of the tests to cancel.
+    #[clap(required = true)]
+    test_names: Vec<String>,
+}
+
+impl Cancel {
+    pub(crate) async fn run(self, client: TestManager) -> Result<()> {
+        for test_name in &self.test_names {
+            client
+                .cancel_test(test_name)
+                .await
+                .context(format!("Unable to cancel test '{}'", test_name))?;
+            println!("Requested cancellation of test '{}'", test_name);
+        }
+        Ok(())
+    }
+}
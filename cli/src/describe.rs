@@ -1,34 +1,165 @@
 use anyhow::{Error, Result};
 use clap::Parser;
+use k8s_openapi::api::events::v1::Event;
+use k8s_openapi::chrono::SecondsFormat;
 use testsys_model::clients::CrdClient;
 use testsys_model::test_manager::TestManager;
-use testsys_model::CrdExt;
+use testsys_model::{CrdExt, Test, TestResults};
 
-/// Retrieve the YAML description of a test or resource.
+/// Retrieve the YAML description of a test, resource, test suite, resource pool or test schedule.
 #[derive(Debug, Parser)]
 pub(crate) struct Describe {
     /// The name of the test to return the description from.
-    #[clap(long, conflicts_with = "resource")]
+    #[clap(long, conflicts_with_all = &["resource", "suite", "pool", "schedule"])]
     test: Option<String>,
 
     /// The name of the resource to return the description from.
-    #[clap(long, conflicts_with = "test")]
+    #[clap(long, conflicts_with_all = &["test", "suite", "pool", "schedule"])]
     resource: Option<String>,
+
+    /// The name of the test suite to return the description from.
+    #[clap(long, conflicts_with_all = &["test", "resource", "pool", "schedule"])]
+    suite: Option<String>,
+
+    /// The name of the resource pool to return the description from.
+    #[clap(long, conflicts_with_all = &["test", "resource", "suite", "schedule"])]
+    pool: Option<String>,
+
+    /// The name of the test schedule to return the description from.
+    #[clap(long, conflicts_with_all = &["test", "resource", "suite", "pool"])]
+    schedule: Option<String>,
+
+    /// Only print the names of the test's failed test cases, for each run of the test.
+    #[clap(long, requires = "test", conflicts_with = "attempts")]
+    failed_cases: bool,
+
+    /// Only print a summary of each attempt at running the test, including retries.
+    #[clap(long, requires = "test")]
+    attempts: bool,
+
+    /// Only print the timeline of events that the controller published for the test or resource.
+    #[clap(long, conflicts_with_all = &["suite", "pool", "schedule", "failed_cases", "attempts"])]
+    events: bool,
 }
 
 impl Describe {
     pub(crate) async fn run(self, client: TestManager) -> Result<()> {
-        match (self.test, self.resource) {
-            (Some(test), None) => {
+        match (
+            self.test,
+            self.resource,
+            self.suite,
+            self.pool,
+            self.schedule,
+        ) {
+            (Some(test), None, None, None, None) if self.events => {
+                print_events(&client.events("Test", &test).await?);
+            }
+            (None, Some(resource), None, None, None) if self.events => {
+                print_events(&client.events("Resource", &resource).await?);
+            }
+            (Some(test), None, None, None, None) if self.failed_cases => {
+                let test = client.test_client().get(test).await?;
+                for (run, results) in test.agent_status().results.iter().enumerate() {
+                    println!("Attempt {}:", attempt_number(results, run));
+                    if results.test_cases.is_none() {
+                        println!("  The test agent did not report test cases");
+                    }
+                    for name in results.failed_test_cases() {
+                        println!("  {}", name);
+                    }
+                }
+            }
+            (Some(test), None, None, None, None) if self.attempts => {
+                let test = client.test_client().get(test).await?;
+                print_attempts(&test);
+            }
+            (Some(test), None, None, None, None) => {
                 let test_yaml = client.test_client().get(test).await?.to_yaml()?;
                 println!("{}", test_yaml);
             }
-            (None, Some(resource)) => {
+            (None, Some(resource), None, None, None) => {
                 let resource_yaml = client.resource_client().get(resource).await?.to_yaml()?;
                 println!("{}", resource_yaml);
             }
-            _ => return Err(Error::msg("Invalid arguments were provided. Exactly one of `--test` and `--resource` must be used.")),
+            (None, None, Some(suite), None, None) => {
+                let suite_yaml = client.test_suite_client().get(suite).await?.to_yaml()?;
+                println!("{}", suite_yaml);
+            }
+            (None, None, None, Some(pool), None) => {
+                let pool_yaml = client.resource_pool_client().get(pool).await?.to_yaml()?;
+                println!("{}", pool_yaml);
+            }
+            (None, None, None, None, Some(schedule)) => {
+                let schedule_yaml = client
+                    .test_schedule_client()
+                    .get(schedule)
+                    .await?
+                    .to_yaml()?;
+                println!("{}", schedule_yaml);
+            }
+            _ => return Err(Error::msg("Invalid arguments were provided. Exactly one of `--test`, `--resource`, `--suite`, `--pool` and `--schedule` must be used.")),
         };
         Ok(())
     }
 }
+
+/// The attempt number recorded by the test agent, or the position of the results for test agents
+/// that do not record attempts.
+fn attempt_number(results: &TestResults, index: usize) -> String {
+    results
+        .attempt
+        .as_ref()
+        .map(|attempt| attempt.number as usize)
+        .unwrap_or(index + 1)
+        .to_string()
+}
+
+/// Print a row for each completed attempt of `test`, followed by the attempt in progress, if any.
+fn print_attempts(test: &Test) {
+    let agent_status = test.agent_status();
+    println!(
+        "{:<8} {:<11} {:>7} {:>7} {:>8}  {:<21} {:<21} {:>9}",
+        "ATTEMPT", "OUTCOME", "PASSED", "FAILED", "SKIPPED", "START", "END", "DURATION"
+    );
+    let completed = agent_status.results.iter();
+    for (index, results) in completed.chain(&agent_status.current_test).enumerate() {
+        let attempt = results.attempt.clone().unwrap_or_default();
+        println!(
+            "{:<8} {:<11} {:>7} {:>7} {:>8}  {:<21} {:<21} {:>9}",
+            attempt_number(results, index),
+            results.outcome.to_string(),
+            results.num_passed,
+            results.num_failed,
+            results.num_skipped,
+            attempt.start_time.unwrap_or_default(),
+            attempt.end_time.unwrap_or_default(),
+            attempt.duration.unwrap_or_default(),
+        );
+    }
+}
+
+/// Print a row for each event, oldest first.
+fn print_events(events: &[Event]) {
+    println!("{:<21} {:<8} {:<22} MESSAGE", "TIME", "TYPE", "REASON");
+    for event in events {
+        let time = event
+            .event_time
+            .as_ref()
+            .map(|time| time.0)
+            .or_else(|| {
+                event
+                    .metadata
+                    .creation_timestamp
+                    .as_ref()
+                    .map(|time| time.0)
+            })
+            .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true));
+        println!(
+            "{:<21} {:<8} {:<22} {}",
+            time.unwrap_or_default(),
+            event.type_.as_deref().unwrap_or_default(),
+            event.reason.as_deref().unwrap_or_default(),
+            event.note.as_deref().unwrap_or_default(),
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use anyhow::{Context, Result};
 use clap::Parser;
+use std::path::PathBuf;
+use testsys_model::system::AdmissionConfig;
 use testsys_model::test_manager::{ImageConfig, TestManager};
 
 /// The install subcommand is responsible for putting all of the necessary components for testsys in
@@ -17,6 +19,33 @@ pub(crate) struct Install {
 
     #[clap(long = "archive-logs")]
     archive_logs: bool,
+
+    /// Serve a validating webhook from the controller that rejects tests and resources with
+    /// invalid references, timeouts or templates when they are created.
+    #[clap(long = "webhook")]
+    webhook: bool,
+
+    /// Path to a YAML file with the concurrency limits that the controller uses to queue tests and
+    /// resources.
+    #[clap(long = "admission-config")]
+    admission_config: Option<PathBuf>,
+
+    /// A namespace, in addition to `testsys` and `--namespace`, whose tests and resources the
+    /// controller runs. Agent roles and service accounts are created in each of them. Use `*` to
+    /// watch all namespaces. Can be repeated.
+    #[clap(long = "watch-namespace")]
+    watch_namespaces: Vec<String>,
+
+    /// A service account that agents are allowed to run as with `serviceAccount`, in addition to
+    /// the default agent service accounts. Agent pods are created by the controller, so only allow
+    /// service accounts that agents may be trusted with. Can be repeated.
+    #[clap(long = "agent-service-account")]
+    agent_service_accounts: Vec<String>,
+
+    /// The number of controller pods to run. The replicas elect a leader that reconciles objects
+    /// while the others stand by.
+    #[clap(long = "controller-replicas", default_value = "1")]
+    controller_replicas: i32,
 }
 
 impl Install {
@@ -26,12 +55,34 @@ impl Install {
             (None, image) => ImageConfig::Image(image),
         };
         client
-            .install(controller_image, self.archive_logs)
+            .install(
+                controller_image,
+                self.archive_logs,
+                self.webhook,
+                &self.watch_namespaces,
+                &self.agent_service_accounts,
+                self.controller_replicas,
+            )
             .await
             .context(
                 "Unable to install testsys to the cluster. (Some artifacts may be left behind)",
             )?;
 
+        if let Some(path) = self.admission_config {
+            let config: AdmissionConfig = serde_yaml::from_str(
+                &std::fs::read_to_string(&path)
+                    .context(format!("Unable to read '{}'", path.display()))?,
+            )
+            .context(format!(
+                "Unable to parse the admission config in '{}'",
+                path.display()
+            ))?;
+            client
+                .set_admission_config(&config)
+                .await
+                .context("Unable to set the admission config")?;
+        }
+
         println!("testsys components were successfully installed.");
 
         Ok(())
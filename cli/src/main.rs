@@ -5,6 +5,7 @@ This is the command line interface for setting up a TestSys Cluster and running
 !*/
 
 mod add_secret;
+mod cancel;
 mod delete;
 mod describe;
 mod install;
@@ -15,13 +16,16 @@ mod results;
 mod run;
 mod run_file;
 mod status;
+mod suspend;
 mod uninstall;
+mod validate;
 
 use anyhow::{Context, Result};
 use clap::Parser;
 use env_logger::Builder;
 use log::LevelFilter;
 use std::path::PathBuf;
+use testsys_model::constants::NAMESPACE;
 use testsys_model::test_manager::TestManager;
 
 /// The command line interface for setting up a Bottlerocket TestSys cluster and running tests.
@@ -35,6 +39,10 @@ struct Args {
     /// Path to the kubeconfig file. Also can be passed with the KUBECONFIG environment variable.
     #[clap(long = "kubeconfig")]
     kubeconfig: Option<PathBuf>,
+    /// The namespace of the testsys objects to work with. Objects in a manifest that name their own
+    /// namespace are created there.
+    #[clap(long = "namespace", short = 'n', default_value = NAMESPACE)]
+    namespace: String,
     #[clap(subcommand)]
     command: Command,
 }
@@ -47,6 +55,12 @@ enum Command {
     Uninstall(uninstall::Uninstall),
     /// Restart a test.
     Restart(restart::Restart),
+    /// Cancel running tests.
+    Cancel(cancel::Cancel),
+    /// Keep the controller from starting the agent jobs of tests and resources.
+    Suspend(suspend::Suspend),
+    /// Let the controller start the agent jobs of suspended tests and resources.
+    Resume(suspend::Suspend),
     /// Run a testsys test.
     Run(run::Run),
     /// Get logs from testsys objects.
@@ -61,6 +75,8 @@ enum Command {
     Delete(delete::Delete),
     /// Get the YAML representation of testsys objects.
     Describe(describe::Describe),
+    /// Check a manifest for problems without submitting it.
+    Validate(validate::Validate),
 }
 
 #[tokio::main]
@@ -74,6 +90,10 @@ async fn main() {
 }
 
 async fn run(args: Args) -> Result<()> {
+    // Manifests are validated offline, so no client is needed.
+    if let Command::Validate(validate) = &args.command {
+        return validate.run();
+    }
     let client = match args.kubeconfig {
         Some(path) => TestManager::new_from_kubeconfig_path(&path)
             .await
@@ -84,11 +104,15 @@ async fn run(args: Args) -> Result<()> {
         None => TestManager::new()
             .await
             .context("Unable to create default testsys client")?,
-    };
+    }
+    .with_namespace(args.namespace);
     match args.command {
         Command::Install(install) => install.run(client).await,
         Command::Uninstall(uninstall) => uninstall.run(client).await,
         Command::Restart(restart) => restart.run(client).await,
+        Command::Cancel(cancel) => cancel.run(client).await,
+        Command::Suspend(suspend) => suspend.run(client, true).await,
+        Command::Resume(resume) => resume.run(client, false).await,
         Command::Run(run) => run.run(client).await,
         Command::Logs(logs) => logs.run(client).await,
         Command::AddSecret(add_secret) => add_secret.run(client).await,
@@ -96,6 +120,7 @@ async fn run(args: Args) -> Result<()> {
         Command::Results(results) => results.run(client).await,
         Command::Delete(delete) => delete.run(client).await,
         Command::Describe(describe) => describe.run(client).await,
+        Command::Validate(validate) => validate.run(),
     }
 }
 
@@ -18,6 +18,10 @@ pub(crate) struct Status {
     #[clap(long, short = 'u')]
     with_time: bool,
 
+    /// Include the names of the failed test cases reported by each `Test`
+    #[clap(long)]
+    failed_cases: bool,
+
     /// Include `Test`s (if passed with `--resources`, `Test`s and `Resource`s will be shown)
     #[clap(long, short = 't')]
     tests: bool,
@@ -26,6 +30,18 @@ pub(crate) struct Status {
     #[clap(long, short = 'r')]
     resources: bool,
 
+    /// Only include `TestSuite`s
+    #[clap(long, short = 's', conflicts_with_all = &["tests", "resources"])]
+    suites: bool,
+
+    /// Only include `ResourcePool`s
+    #[clap(long, conflicts_with_all = &["tests", "resources", "suites"])]
+    pools: bool,
+
+    /// Only include `TestSchedule`s
+    #[clap(long, conflicts_with_all = &["tests", "resources", "suites", "pools"])]
+    schedules: bool,
+
     /// Only include objects with the specified labels ("foo=bar,biz=baz")
     #[clap(long)]
     labels: Option<String>,
@@ -38,13 +54,27 @@ pub(crate) struct Status {
     /// Only include objects with the specified name
     #[clap(long)]
     name: Option<String>,
+
+    /// Summarize the results of `Test`s created from a `TestMatrix` by their value of the given
+    /// matrix axis (e.g. "arch")
+    #[clap(long)]
+    group_by: Option<String>,
 }
 
 impl Status {
     pub(crate) async fn run(self, client: TestManager) -> Result<()> {
-        let crd_type = match (self.tests, self.resources) {
-            (true, false) => Some(CrdType::Test),
-            (false, true) => Some(CrdType::Resource),
+        let crd_type = match (
+            self.tests,
+            self.resources,
+            self.suites,
+            self.pools,
+            self.schedules,
+        ) {
+            (true, false, false, false, false) => Some(CrdType::Test),
+            (false, true, false, false, false) => Some(CrdType::Resource),
+            (false, false, true, false, false) => Some(CrdType::TestSuite),
+            (false, false, false, true, false) => Some(CrdType::ResourcePool),
+            (false, false, false, false, true) => Some(CrdType::TestSchedule),
             _ => None,
         };
         let selection_params = SelectionParams {
@@ -58,6 +88,20 @@ impl Status {
             .await
             .context("Unable to get status")?;
 
+        if let Some(axis) = self.group_by {
+            let summary = status.group_by_axis(axis);
+            if self.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&summary)
+                        .context("Could not create string from status.")?
+                );
+            } else {
+                println!("{}", summary);
+            }
+            return Ok(());
+        }
+
         status.add_column(StatusColumn::name());
         status.add_column(StatusColumn::crd_type());
         status.add_column(StatusColumn::state());
@@ -73,6 +117,10 @@ impl Status {
             status.add_column(StatusColumn::last_update());
         }
 
+        if self.failed_cases {
+            status.add_column(StatusColumn::failed_test_cases());
+        }
+
         if self.json {
             println!(
                 "{}",
@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use testsys_model::test_manager::{CrdState, CrdType, SelectionParams, TestManager};
+
+/// Suspend or resume the `Test`s, `Resource`s and `TestSchedule`s that match the filters, or all of
+/// them if there are no filters. The controller does not start the agent jobs of suspended tests
+/// and resources, or new runs of suspended schedules, but jobs and runs that have already started
+/// are not affected.
+#[derive(Debug, Parser)]
+pub(crate) struct Suspend {
+    /// Only include `Test`s
+    #[clap(long, short = 't', conflicts_with = "resources")]
+    tests: bool,
+
+    /// Only include `Resource`s
+    #[clap(long, short = 'r')]
+    resources: bool,
+
+    /// Only include `TestSchedule`s
+    #[clap(long, conflicts_with_all = &["tests", "resources"])]
+    schedules: bool,
+
+    /// Only include objects with the specified labels ("foo=bar,biz=baz")
+    #[clap(long)]
+    labels: Option<String>,
+
+    /// Only include objects with the specified state ("completed", "running", "not-finished",
+    /// "passed", "failed")
+    #[clap(long)]
+    state: Option<CrdState>,
+
+    /// Only include objects with the specified name
+    #[clap(long)]
+    name: Option<String>,
+}
+
+impl Suspend {
+    pub(crate) async fn run(self, client: TestManager, suspend: bool) -> Result<()> {
+        let crd_type = match (self.tests, self.resources, self.schedules) {
+            (true, false, false) => Some(CrdType::Test),
+            (false, true, false) => Some(CrdType::Resource),
+            (false, false, true) => Some(CrdType::TestSchedule),
+            _ => None,
+        };
+        let selection_params = SelectionParams {
+            crd_type,
+            labels: self.labels,
+            name: self.name,
+            state: self.state,
+        };
+        let action = if suspend { "suspend" } else { "resume" };
+        let updated = client
+            .suspend(&selection_params, suspend)
+            .await
+            .context(format!("Unable to {} objects", action))?;
+        for crd in &updated {
+            println!(
+                "{} '{}'",
+                if suspend { "Suspended" } else { "Resumed" },
+                crd.name().unwrap_or_default()
+            );
+        }
+        if updated.is_empty() {
+            println!(
+                "No tests, resources or schedules matched, nothing to {}",
+                action
+            );
+        }
+        Ok(())
+    }
+}
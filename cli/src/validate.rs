@@ -0,0 +1,152 @@
+use anyhow::{Context, Error, Result};
+use bottlerocket_types::agent_schemas::agent_schemas;
+use clap::{value_parser, Parser};
+use std::path::PathBuf;
+use testsys_model::test_manager::read_manifest;
+use testsys_model::validation::{
+    validate_configuration, validate_resource, validate_schedule, validate_test, KnownObjects,
+};
+use testsys_model::{Agent, Crd};
+
+/// Check the tests and resources in a YAML manifest for problems without submitting them. The
+/// agent configurations of known agent images are checked against their schemas, and templates
+/// must refer to tests and resources that are in the manifest or that are named with
+/// `--existing-test` and `--existing-resource`.
+#[derive(Debug, Parser)]
+pub(crate) struct Validate {
+    /// Path to the manifest YAML file.
+    #[clap(value_parser = value_parser!(PathBuf))]
+    path: PathBuf,
+
+    /// The name of a test that already exists in the cluster and may be referred to by the
+    /// manifest.
+    #[clap(long = "existing-test")]
+    existing_tests: Vec<String>,
+
+    /// The name of a resource that already exists in the cluster and may be referred to by the
+    /// manifest.
+    #[clap(long = "existing-resource")]
+    existing_resources: Vec<String>,
+}
+
+impl Validate {
+    pub(crate) fn run(&self) -> Result<()> {
+        let crds = read_manifest(&self.path)
+            .context(format!("Unable to read manifest '{}'", self.path.display()))?;
+        let mut known = KnownObjects {
+            tests: self.existing_tests.iter().cloned().collect(),
+            resources: self.existing_resources.iter().cloned().collect(),
+        };
+        for crd in &crds {
+            match crd {
+                Crd::Test(_) => known.tests.extend(crd.name()),
+                Crd::Resource(_) => known.resources.extend(crd.name()),
+                Crd::TestSuite(suite) => {
+                    known.tests.extend(suite.spec.tests.keys().cloned());
+                    known.resources.extend(suite.spec.resources.keys().cloned());
+                }
+                Crd::TestSchedule(schedule) => {
+                    known
+                        .tests
+                        .extend(schedule.spec.template.tests.keys().cloned());
+                    known
+                        .resources
+                        .extend(schedule.spec.template.resources.keys().cloned());
+                }
+                Crd::ResourcePool(_) => {}
+            }
+        }
+
+        let registry = agent_schemas();
+        let mut problems = Vec::new();
+        for crd in &crds {
+            if let Crd::TestSchedule(schedule) = crd {
+                problems.extend(
+                    validate_schedule(&schedule.spec)
+                        .into_iter()
+                        .map(|problem| {
+                            format!(
+                                "TestSchedule '{}': {}",
+                                crd.name().unwrap_or_default(),
+                                problem
+                            )
+                        }),
+                );
+            }
+        }
+        let mut check = |object: String, found: Vec<String>, agent: &Agent| {
+            let configuration = validate_configuration(agent, &registry);
+            problems.extend(
+                found
+                    .into_iter()
+                    .chain(configuration)
+                    .map(|problem| format!("{}: {}", object, problem)),
+            );
+        };
+        for crd in &crds {
+            let name = crd.name().unwrap_or_default();
+            match crd {
+                Crd::Test(test) => check(
+                    format!("Test '{}'", name),
+                    validate_test(&test.spec, &known),
+                    &test.spec.agent,
+                ),
+                Crd::Resource(resource) => check(
+                    format!("Resource '{}'", name),
+                    validate_resource(&resource.spec, &known),
+                    &resource.spec.agent,
+                ),
+                Crd::TestSuite(suite) => {
+                    for (test, spec) in &suite.spec.tests {
+                        check(
+                            format!("TestSuite '{}' test '{}'", name, test),
+                            validate_test(spec, &known),
+                            &spec.agent,
+                        );
+                    }
+                    for (resource, spec) in &suite.spec.resources {
+                        check(
+                            format!("TestSuite '{}' resource '{}'", name, resource),
+                            validate_resource(spec, &known),
+                            &spec.agent,
+                        );
+                    }
+                }
+                Crd::TestSchedule(schedule) => {
+                    for (test, spec) in &schedule.spec.template.tests {
+                        check(
+                            format!("TestSchedule '{}' test '{}'", name, test),
+                            validate_test(spec, &known),
+                            &spec.agent,
+                        );
+                    }
+                    for (resource, spec) in &schedule.spec.template.resources {
+                        check(
+                            format!("TestSchedule '{}' resource '{}'", name, resource),
+                            validate_resource(spec, &known),
+                            &spec.agent,
+                        );
+                    }
+                }
+                Crd::ResourcePool(pool) => check(
+                    format!("ResourcePool '{}'", name),
+                    validate_resource(&pool.spec.resource, &known),
+                    &pool.spec.resource.agent,
+                ),
+            }
+        }
+
+        if problems.is_empty() {
+            println!("'{}' is valid.", self.path.display());
+            return Ok(());
+        }
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        Err(Error::msg(format!(
+            "Found {} problem(s) in '{}'",
+            problems.len(),
+            self.path.display()
+        )))
+    }
+}
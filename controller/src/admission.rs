@@ -0,0 +1,436 @@
+use crate::error::Result;
+use crate::namespaces::controller_namespace;
+use anyhow::Context;
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use kube::{Api, Client};
+use kube_runtime::reflector::Store;
+use log::error;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use testsys_model::system::{
+    AdmissionConfig, AdmissionKind, TESTSYS_ADMISSION_CONFIG_KEY, TESTSYS_ADMISSION_CONFIG_MAP,
+};
+use testsys_model::{Agent, Condition, ConditionStatus, ConditionType, CrdExt, Resource, Test};
+
+/// The reason of the `Admitted` condition while an object waits for room under its concurrency
+/// limits.
+pub(crate) const QUEUED: &str = "Queued";
+
+/// The reason of the `Admitted` condition while an object is suspended. Suspended objects are not
+/// queued.
+pub(crate) const SUSPENDED: &str = "Suspended";
+
+/// Whether the controller has admitted the object with these `conditions` to run its agent job.
+pub(crate) fn is_admitted(conditions: &[Condition]) -> bool {
+    slot(conditions) == Slot::Running
+}
+
+/// How long an admission is reserved for an object if its `Admitted` condition is never seen, e.g.
+/// because the condition could not be written.
+const RESERVATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Decides which objects may start their agent jobs under the concurrency limits in the admission
+/// `ConfigMap`. Decisions are made one at a time from the controllers' caches. An object that has
+/// been admitted holds a reservation until its `Admitted` condition shows up in the cache, so that
+/// objects that are reconciled at the same time cannot all take the last room under a limit.
+#[derive(Clone)]
+pub(crate) struct Admission {
+    client: Client,
+    state: Arc<Mutex<AdmissionState>>,
+}
+
+#[derive(Default)]
+struct AdmissionState {
+    tests: Vec<Store<Test>>,
+    resources: Vec<Store<Resource>>,
+    reservations: Vec<Reservation>,
+}
+
+/// An object that has been admitted but that has not been seen as admitted yet.
+struct Reservation {
+    kind: AdmissionKind,
+    namespace: String,
+    name: String,
+    admitted: Instant,
+}
+
+impl Admission {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            state: Default::default(),
+        }
+    }
+
+    /// Counts the tests in `store` against the concurrency limits.
+    pub(crate) fn watch_tests(&self, store: Store<Test>) {
+        self.lock().tests.push(store);
+    }
+
+    /// Counts the resources in `store` against the concurrency limits.
+    pub(crate) fn watch_resources(&self, store: Store<Resource>) {
+        self.lock().resources.push(store);
+    }
+
+    /// Decides whether the object of `kind` named `name` in `namespace`, which is ready to start
+    /// its agent job, has to wait for room under the concurrency limits. Returns the reason to
+    /// wait, if any. Otherwise the object is admitted and keeps its room until it is seen with its
+    /// `Admitted` condition. The limits apply to the objects in all watched namespaces.
+    pub(crate) async fn wait_reason(
+        &self,
+        kind: AdmissionKind,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<String>> {
+        let config = match admission_config(self.client.clone()).await? {
+            Some(config) if !config.limits.is_empty() => config,
+            _ => return Ok(None),
+        };
+        // Every cache must be populated, or the objects that are missing from it would not count.
+        let (test_stores, resource_stores) = {
+            let state = self.lock();
+            (state.tests.clone(), state.resources.clone())
+        };
+        for store in &test_stores {
+            let _ = store.wait_until_ready().await;
+        }
+        for store in &resource_stores {
+            let _ = store.wait_until_ready().await;
+        }
+
+        let mut state = self.lock();
+        let tests: Vec<Arc<Test>> = state.tests.iter().flat_map(Store::state).collect();
+        let resources: Vec<Arc<Resource>> = state.resources.iter().flat_map(Store::state).collect();
+        let mut candidates: Vec<Candidate> = tests
+            .iter()
+            .map(|test| Candidate::test(test))
+            .chain(
+                resources
+                    .iter()
+                    .map(|resource| Candidate::resource(resource)),
+            )
+            .collect();
+        Ok(state.admit(
+            &config,
+            &mut candidates,
+            kind,
+            namespace,
+            name,
+            Instant::now(),
+        ))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AdmissionState> {
+        // The state stays consistent even if a thread panicked while holding the lock.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl AdmissionState {
+    /// Decides whether the object of `kind` named `name` in `namespace` has to wait, counting the
+    /// reserved `candidates` as running, and reserves room for it if it does not.
+    fn admit(
+        &mut self,
+        config: &AdmissionConfig,
+        candidates: &mut [Candidate],
+        kind: AdmissionKind,
+        namespace: &str,
+        name: &str,
+        now: Instant,
+    ) -> Option<String> {
+        // A reservation is released once the object is seen as admitted or no longer needs it.
+        self.reservations.retain(|reservation| {
+            now.saturating_duration_since(reservation.admitted) < RESERVATION_TIMEOUT
+                && candidates.iter().any(|candidate| {
+                    reservation.is(candidate)
+                        && !candidate.deleted
+                        && candidate.slot != Slot::Running
+                })
+        });
+        for candidate in candidates.iter_mut() {
+            if self
+                .reservations
+                .iter()
+                .any(|reservation| reservation.is(candidate))
+            {
+                candidate.slot = Slot::Running;
+            }
+        }
+        let candidate = candidates.iter().find(|candidate| {
+            candidate.kind == kind && candidate.namespace == namespace && candidate.name == name
+        })?;
+        let reason = queue_reason(config, candidate, candidates);
+        if reason.is_none() && candidate.slot != Slot::Running {
+            self.reservations.push(Reservation {
+                kind,
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+                admitted: now,
+            });
+        }
+        reason
+    }
+}
+
+impl Reservation {
+    fn is(&self, candidate: &Candidate) -> bool {
+        self.kind == candidate.kind
+            && self.namespace == candidate.namespace
+            && self.name == candidate.name
+    }
+}
+
+/// Reads the admission configuration from the namespace that the controller runs in. A
+/// configuration that cannot be parsed is logged and ignored so that a mistake in it does not stop
+/// every test.
+async fn admission_config(client: Client) -> Result<Option<AdmissionConfig>> {
+    let config_map = Api::<ConfigMap>::namespaced(client, &controller_namespace())
+        .get_opt(TESTSYS_ADMISSION_CONFIG_MAP)
+        .await
+        .context(format!(
+            "Unable to get config map '{}'",
+            TESTSYS_ADMISSION_CONFIG_MAP
+        ))?;
+    let yaml = match config_map
+        .and_then(|config_map| config_map.data)
+        .and_then(|mut data| data.remove(TESTSYS_ADMISSION_CONFIG_KEY))
+    {
+        Some(yaml) => yaml,
+        None => return Ok(None),
+    };
+    match serde_yaml::from_str(&yaml) {
+        Ok(config) => Ok(Some(config)),
+        Err(e) => {
+            error!(
+                "Ignoring the invalid admission config in '{}': {}",
+                TESTSYS_ADMISSION_CONFIG_MAP, e
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Whether an object counts against its concurrency limits.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Slot {
+    Running,
+    Queued,
+    None,
+}
+
+fn slot(conditions: &[Condition]) -> Slot {
+    match conditions
+        .iter()
+        .find(|condition| condition.condition_type == ConditionType::Admitted)
+    {
+        Some(condition) if condition.status == ConditionStatus::True => Slot::Running,
+        Some(condition) if condition.reason == QUEUED => Slot::Queued,
+        _ => Slot::None,
+    }
+}
+
+/// A `Test` or `Resource` as seen by the concurrency limits.
+struct Candidate<'a> {
+    kind: AdmissionKind,
+    namespace: &'a str,
+    name: &'a str,
+    agent: &'a Agent,
+    labels: &'a BTreeMap<String, String>,
+    priority: i32,
+    created: Option<&'a Time>,
+    slot: Slot,
+    deleted: bool,
+}
+
+impl<'a> Candidate<'a> {
+    /// A test stops counting against its limits when it is deleted, since its job is removed.
+    fn test(test: &'a Test) -> Self {
+        Self {
+            kind: AdmissionKind::Test,
+            namespace: test.object_namespace(),
+            name: test.object_name(),
+            agent: &test.spec.agent,
+            labels: labels(&test.metadata.labels),
+            priority: test.spec.priority.unwrap_or_default(),
+            created: test.metadata.creation_timestamp.as_ref(),
+            slot: if test.is_delete_requested() {
+                Slot::None
+            } else {
+                slot(test.conditions())
+            },
+            deleted: test.is_delete_requested(),
+        }
+    }
+
+    /// A resource counts against its limits until it has been destroyed.
+    fn resource(resource: &'a Resource) -> Self {
+        let slot = match slot(resource.conditions()) {
+            Slot::Queued if resource.is_delete_requested() => Slot::None,
+            slot => slot,
+        };
+        Self {
+            kind: AdmissionKind::Resource,
+            namespace: resource.object_namespace(),
+            name: resource.object_name(),
+            agent: &resource.spec.agent,
+            labels: labels(&resource.metadata.labels),
+            priority: resource.spec.priority.unwrap_or_default(),
+            created: resource.metadata.creation_timestamp.as_ref(),
+            slot,
+            deleted: resource.is_delete_requested(),
+        }
+    }
+
+    /// Queued objects start in order of priority, then in the order they were created.
+    fn queue_order(&self) -> (Reverse<i32>, Option<&Time>, &str) {
+        (Reverse(self.priority), self.created, self.name)
+    }
+
+    fn is(&self, other: &Candidate) -> bool {
+        self.kind == other.kind && self.namespace == other.namespace && self.name == other.name
+    }
+}
+
+fn labels(labels: &Option<BTreeMap<String, String>>) -> &BTreeMap<String, String> {
+    static EMPTY: BTreeMap<String, String> = BTreeMap::new();
+    labels.as_ref().unwrap_or(&EMPTY)
+}
+
+/// Explains why `candidate` must wait, if a limit that matches it is full. Running objects take up
+/// room under a limit, and so do the queued objects that are ahead of `candidate`.
+fn queue_reason(
+    config: &AdmissionConfig,
+    candidate: &Candidate,
+    candidates: &[Candidate],
+) -> Option<String> {
+    config
+        .limits
+        .iter()
+        .filter(|limit| limit.matches(candidate.kind, candidate.agent, candidate.labels))
+        .find_map(|limit| {
+            let taken = candidates
+                .iter()
+                .filter(|other| !other.is(candidate))
+                .filter(|other| limit.matches(other.kind, other.agent, other.labels))
+                .filter(|other| match other.slot {
+                    Slot::Running => true,
+                    Slot::Queued => other.queue_order() < candidate.queue_order(),
+                    Slot::None => false,
+                })
+                .count();
+            (taken >= limit.max_running as usize).then(|| {
+                format!(
+                    "Waiting for room under the concurrency limit '{}' of {}",
+                    limit.name, limit.max_running
+                )
+            })
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use k8s_openapi::chrono::{Duration, Utc};
+    use testsys_model::constants::NAMESPACE;
+    use testsys_model::system::ConcurrencyLimit;
+    use testsys_model::{TestSpec, TestStatus};
+
+    fn test(name: &str, age: i64, priority: i32, admitted: Option<bool>) -> Test {
+        let conditions = admitted
+            .map(|admitted| {
+                Condition::new(
+                    ConditionType::Admitted,
+                    admitted.into(),
+                    if admitted { "Admitted" } else { QUEUED },
+                    "",
+                )
+            })
+            .into_iter()
+            .collect();
+        Test {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                creation_timestamp: Some(Time(Utc::now() - Duration::seconds(age))),
+                ..Default::default()
+            },
+            spec: TestSpec {
+                priority: Some(priority),
+                ..Default::default()
+            },
+            status: Some(TestStatus {
+                conditions,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn config(max_running: u32) -> AdmissionConfig {
+        AdmissionConfig {
+            limits: vec![ConcurrencyLimit {
+                name: "global".to_string(),
+                max_running,
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn queue() {
+        let tests = [
+            test("running", 100, 0, Some(true)),
+            test("old", 50, 0, Some(false)),
+            test("new", 10, 0, Some(false)),
+            test("urgent", 5, 10, Some(false)),
+            test("waiting", 1, 0, None),
+        ];
+        let candidates: Vec<Candidate> = tests.iter().map(Candidate::test).collect();
+        let reason = |config: &AdmissionConfig, index: usize| {
+            queue_reason(config, &candidates[index], &candidates)
+        };
+        // One test is running, so there is room for one more: the queued test with the highest
+        // priority.
+        assert_eq!(reason(&config(2), 3), None);
+        assert!(reason(&config(2), 1).is_some());
+        // Then the oldest queued test.
+        assert_eq!(reason(&config(3), 1), None);
+        assert!(reason(&config(3), 2).is_some());
+        assert_eq!(
+            reason(&config(1), 3),
+            Some("Waiting for room under the concurrency limit 'global' of 1".to_string())
+        );
+        // Tests that are not queued yet are behind every queued test.
+        assert!(reason(&config(4), 4).is_some());
+        assert_eq!(reason(&config(5), 4), None);
+    }
+
+    #[test]
+    fn reservations() {
+        let tests = [test("first", 20, 0, None), test("second", 10, 0, None)];
+        let mut state = AdmissionState::default();
+        let now = Instant::now();
+        let admit = |state: &mut AdmissionState, name: &str, now: Instant| {
+            let mut candidates: Vec<Candidate> = tests.iter().map(Candidate::test).collect();
+            state.admit(
+                &config(1),
+                &mut candidates,
+                AdmissionKind::Test,
+                NAMESPACE,
+                name,
+                now,
+            )
+        };
+        // The first test takes the only room before its condition has been written, so the second
+        // test has to wait even though neither of them is seen as admitted.
+        assert_eq!(admit(&mut state, "first", now), None);
+        assert!(admit(&mut state, "second", now).is_some());
+        assert_eq!(admit(&mut state, "first", now), None);
+        // A reservation that is never seen as admitted is released eventually.
+        let later = now + RESERVATION_TIMEOUT;
+        assert_eq!(admit(&mut state, "second", later), None);
+        assert!(admit(&mut state, "first", later).is_some());
+    }
+}
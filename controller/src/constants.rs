@@ -6,9 +6,12 @@ pub(crate) fn requeue() -> Action {
     Action::requeue(Duration::from_secs(5))
 }
 
+/// How long [`requeue_slow`] waits.
+pub(crate) const REQUEUE_SLOW: Duration = Duration::from_secs(30);
+
 /// Requeue just in case, but we don't expect anything to happen.
 pub(crate) fn requeue_slow() -> Action {
-    Action::requeue(Duration::from_secs(30))
+    Action::requeue(REQUEUE_SLOW)
 }
 
 /// Do not requeue the object.
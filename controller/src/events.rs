@@ -0,0 +1,155 @@
+use k8s_openapi::api::core::v1::ObjectReference;
+use kube::{Client, Resource, ResourceExt};
+use kube_runtime::events::{Event, EventType, Recorder, Reporter};
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// The name that the controller reports as the source of its events.
+const REPORTING_CONTROLLER: &str = "testsys-controller";
+
+/// The k8s API rejects events with a note longer than 1kB.
+const MAX_NOTE_BYTES: usize = 1024;
+
+/// How long the last event of an object is remembered after the object was last reconciled. This
+/// is longer than the controllers' resync interval, so it only expires for objects that are gone.
+const PUBLISHED_TTL: Duration = Duration::from_secs(3600);
+
+/// The action, reason and note of a published event.
+type EventKey = (String, String, Option<String>);
+
+/// The last event published for an object and when the object was last reconciled.
+struct Published {
+    key: EventKey,
+    seen: Instant,
+}
+
+/// Publishes k8s `Event`s that describe the actions taken by a controller. An object is reconciled
+/// every few seconds, so an event is only published when the action taken for an object changes.
+pub(crate) struct EventRecorder {
+    client: Client,
+    reporter: Reporter,
+    /// The last event that was published for each object, keyed by the object's uid. Objects are
+    /// removed by `forget` or once they have not been seen for the [`PUBLISHED_TTL`], in case they
+    /// were deleted without being forgotten.
+    published: Mutex<HashMap<String, Published>>,
+}
+
+impl EventRecorder {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            reporter: Reporter {
+                controller: REPORTING_CONTROLLER.to_string(),
+                // Pods use their name as their hostname.
+                instance: std::env::var("HOSTNAME").ok(),
+            },
+            published: Default::default(),
+        }
+    }
+
+    /// Publish `event` for `object` unless it is the same as the last event published for
+    /// `object`. Events are informational, so failures are logged instead of returned.
+    pub(crate) async fn publish<K>(&self, object: &K, event: Event)
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        let uid = object.uid().unwrap_or_default();
+        let key = (
+            event.action.clone(),
+            event.reason.clone(),
+            event.note.clone(),
+        );
+        if let Some(published) = self.published().get_mut(&uid) {
+            published.seen = Instant::now();
+            if published.key == key {
+                return;
+            }
+        }
+        let reference: ObjectReference = object.object_ref(&());
+        let recorder = Recorder::new(self.client.clone(), self.reporter.clone(), reference);
+        match recorder.publish(event).await {
+            Ok(()) => {
+                let mut published = self.published();
+                published.retain(|_, published| published.seen.elapsed() < PUBLISHED_TTL);
+                published.insert(
+                    uid,
+                    Published {
+                        key,
+                        seen: Instant::now(),
+                    },
+                );
+            }
+            Err(e) => warn!("Unable to publish event for '{}': {}", object.name_any(), e),
+        }
+    }
+
+    /// Stop tracking the events of `object` once it is about to be deleted.
+    pub(crate) fn forget<K>(&self, object: &K)
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        self.published().remove(&object.uid().unwrap_or_default());
+    }
+
+    fn published(&self) -> MutexGuard<'_, HashMap<String, Published>> {
+        // The map is never left in an inconsistent state, so a poisoned lock is still usable.
+        self.published
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// An event with the `Normal` type.
+pub(crate) fn normal<S1, S2>(action: &str, reason: S1, note: S2) -> Event
+where
+    S1: Into<String>,
+    S2: Into<String>,
+{
+    event(EventType::Normal, action, reason, note)
+}
+
+/// An event with the `Warning` type.
+pub(crate) fn warning<S1, S2>(action: &str, reason: S1, note: S2) -> Event
+where
+    S1: Into<String>,
+    S2: Into<String>,
+{
+    event(EventType::Warning, action, reason, note)
+}
+
+fn event<S1, S2>(type_: EventType, action: &str, reason: S1, note: S2) -> Event
+where
+    S1: Into<String>,
+    S2: Into<String>,
+{
+    let mut note = note.into();
+    if note.len() > MAX_NOTE_BYTES {
+        let mut end = MAX_NOTE_BYTES;
+        while !note.is_char_boundary(end) {
+            end -= 1;
+        }
+        note.truncate(end);
+    }
+    Event {
+        type_,
+        reason: reason.into(),
+        note: Some(note),
+        action: action.to_string(),
+        secondary: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn long_notes_are_truncated() {
+        let event = warning("Error", "TestError", "é".repeat(MAX_NOTE_BYTES));
+        let note = event.note.unwrap_or_default();
+        assert_eq!(note.len(), MAX_NOTE_BYTES);
+        assert!(note.chars().all(|c| c == 'é'));
+    }
+}
@@ -0,0 +1,253 @@
+use futures::future::join;
+use futures::{Stream, StreamExt};
+use k8s_openapi::serde::de::DeserializeOwned;
+use kube::{Api, Resource};
+use kube_runtime::reflector::store::Writer;
+use kube_runtime::reflector::Store;
+use kube_runtime::{watcher, WatchStreamExt};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How often a monitored controller reconciles every object in its cache, so that it keeps
+/// reconciling while it has objects even if none of them change.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(300);
+
+/// How long the API server keeps each watch request open. The watch is then resumed from the last
+/// resource version it saw, without listing the objects again, so a watch whose connection has
+/// silently stopped delivering events is replaced.
+const WATCH_TIMEOUT_SECONDS: u32 = 290;
+
+/// How long a monitored controller's watch may keep failing before the controller is considered
+/// stalled.
+const STALL_TIMEOUT: Duration = Duration::from_secs(900);
+
+/// Tracks whether the controller streams are processing objects for the liveness and readiness
+/// endpoints. A replica is live unless one of its controller streams has stopped or stalled. A
+/// replica that is standing by is ready, and the leader is ready once its controller streams have
+/// listed their objects.
+#[derive(Clone, Default)]
+pub(crate) struct Health {
+    state: Arc<Mutex<HealthState>>,
+}
+
+#[derive(Default)]
+struct HealthState {
+    leader: bool,
+    streams: BTreeMap<String, StreamState>,
+}
+
+struct StreamState {
+    synced: bool,
+    stopped: bool,
+    /// When the watch started failing, if its last attempt failed.
+    failing_since: Option<Instant>,
+}
+
+impl StreamState {
+    /// Why the stream is not live at `now`, if it is not.
+    fn liveness_problem(&self, now: Instant) -> Option<String> {
+        if self.stopped {
+            return Some("has stopped".to_string());
+        }
+        let failing = now.saturating_duration_since(self.failing_since?);
+        if failing > STALL_TIMEOUT {
+            return Some(format!(
+                "has been unable to watch its objects for {}s",
+                failing.as_secs()
+            ));
+        }
+        None
+    }
+
+    /// Why the stream is not ready at `now`, if it is not.
+    fn readiness_problem(&self, now: Instant) -> Option<String> {
+        if !self.synced {
+            return Some("has not listed its objects yet".to_string());
+        }
+        self.liveness_problem(now)
+    }
+}
+
+impl Health {
+    /// Records that this replica has become the leader and runs the controllers.
+    pub(crate) fn set_leader(&self) {
+        self.lock().leader = true;
+    }
+
+    /// Starts monitoring the controller stream called `name`.
+    pub(crate) fn monitor(&self, name: String) -> StreamMonitor {
+        self.lock().streams.insert(
+            name.clone(),
+            StreamState {
+                synced: false,
+                stopped: false,
+                failing_since: None,
+            },
+        );
+        StreamMonitor {
+            health: self.clone(),
+            name,
+        }
+    }
+
+    /// The reasons that this replica is not live. It is live if there are none.
+    pub(crate) fn liveness_problems(&self) -> Vec<String> {
+        let now = Instant::now();
+        self.lock()
+            .streams
+            .iter()
+            .filter_map(|(name, stream)| {
+                stream
+                    .liveness_problem(now)
+                    .map(|problem| format!("{} {}", name, problem))
+            })
+            .collect()
+    }
+
+    /// The reasons that this replica is not ready. It is ready if there are none.
+    pub(crate) fn readiness_problems(&self) -> Vec<String> {
+        let now = Instant::now();
+        let state = self.lock();
+        if !state.leader {
+            return Vec::new();
+        }
+        state
+            .streams
+            .iter()
+            .filter_map(|(name, stream)| {
+                stream
+                    .readiness_problem(now)
+                    .map(|problem| format!("{} {}", name, problem))
+            })
+            .collect()
+    }
+
+    fn update(&self, name: &str, update: impl FnOnce(&mut StreamState)) {
+        if let Some(stream) = self.lock().streams.get_mut(name) {
+            update(stream);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HealthState> {
+        // The state stays consistent even if a thread panicked while holding the lock.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Records the progress of a controller stream that is monitored by [`Health`].
+#[derive(Clone)]
+pub(crate) struct StreamMonitor {
+    health: Health,
+    name: String,
+}
+
+impl StreamMonitor {
+    /// Watches the objects in `api`, caching them in `writer`, and records whether the watch is
+    /// failing.
+    pub(crate) fn watch<K>(
+        &self,
+        api: Api<K>,
+        writer: Writer<K>,
+    ) -> impl Stream<Item = Result<K, watcher::Error>> + Send + 'static
+    where
+        K: Resource + Clone + DeserializeOwned + Debug + Send + Sync + 'static,
+        K::DynamicType: Eq + Hash + Clone + Send + Sync,
+    {
+        let monitor = self.clone();
+        watcher(
+            api,
+            watcher::Config::default().timeout(WATCH_TIMEOUT_SECONDS),
+        )
+        .default_backoff()
+        .reflect(writer)
+        .inspect(move |event| {
+            let failed = event.is_err();
+            monitor.health.update(&monitor.name, |stream| {
+                stream.failing_since = match stream.failing_since {
+                    _ if !failed => None,
+                    Some(since) => Some(since),
+                    None => Some(Instant::now()),
+                }
+            });
+        })
+        .applied_objects()
+    }
+
+    /// Runs the `controller` future, which processes the stream, and records when `store` has been
+    /// populated and when the stream stops.
+    pub(crate) async fn run<K>(self, store: Store<K>, controller: impl Future<Output = ()>)
+    where
+        K: Resource + Clone + 'static,
+        K::DynamicType: Eq + Hash + Clone,
+    {
+        let synced = async {
+            if store.wait_until_ready().await.is_ok() {
+                self.health
+                    .update(&self.name, |stream| stream.synced = true);
+            }
+        };
+        let _ = join(controller, synced).await;
+        self.health
+            .update(&self.name, |stream| stream.stopped = true);
+    }
+}
+
+/// A trigger for `Controller::reconcile_all_on` that fires every [`RESYNC_INTERVAL`].
+pub(crate) fn resync() -> impl Stream<Item = ()> + Send + Sync + 'static {
+    futures::stream::unfold((), |()| async {
+        tokio::time::sleep(RESYNC_INTERVAL).await;
+        Some(((), ()))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stalled_streams() {
+        let started = Instant::now();
+        let mut stream = StreamState {
+            synced: false,
+            stopped: false,
+            failing_since: None,
+        };
+        let later = started + STALL_TIMEOUT + Duration::from_secs(1);
+        // A new stream is live but not ready until it has listed its objects.
+        assert_eq!(stream.liveness_problem(later), None);
+        assert!(stream.readiness_problem(later).is_some());
+        stream.synced = true;
+        assert_eq!(stream.readiness_problem(later), None);
+
+        // A quiet watch is live, only a watch that keeps failing is not.
+        stream.failing_since = Some(started + Duration::from_secs(60));
+        assert_eq!(stream.liveness_problem(later), None);
+        stream.failing_since = Some(started);
+        assert_eq!(
+            stream.liveness_problem(later).as_deref(),
+            Some("has been unable to watch its objects for 901s")
+        );
+
+        stream.stopped = true;
+        assert_eq!(
+            stream.liveness_problem(later).as_deref(),
+            Some("has stopped")
+        );
+    }
+
+    #[test]
+    fn standby_is_ready() {
+        let health = Health::default();
+        let _monitor = health.monitor("The test controller".to_string());
+        assert!(health.readiness_problems().is_empty());
+        health.set_leader();
+        assert_eq!(
+            health.readiness_problems(),
+            vec!["The test controller has not listed its objects yet".to_string()]
+        );
+    }
+}
@@ -7,6 +7,21 @@ pub(crate) type JobResult<T> = std::result::Result<T, JobError>;
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub(super)))]
 pub(crate) enum JobError {
+    #[snafu(display("Unable to create or get agent {}: {}", what, source))]
+    AgentRbac { what: String, source: kube::Error },
+
+    #[snafu(display(
+        "Agent {} '{}' in '{}' already exists and is not managed by TestSys",
+        what,
+        name,
+        namespace
+    ))]
+    AgentRbacNotManaged {
+        what: String,
+        name: String,
+        namespace: String,
+    },
+
     #[snafu(display("Job already exists: {}", source))]
     AlreadyExists { source: kube::Error },
 
@@ -46,12 +61,15 @@ pub(crate) enum JobError {
     #[snafu(display("Unable to read logs for pod '{}': {}", pod, source))]
     NoLogs { pod: String, source: kube::Error },
 
-    #[snafu(display("No pods found for job '{}'", job))]
-    NoPods { job: String },
-
     #[snafu(display("Job does not exist: {}", source))]
     NotFound { source: kube::Error },
 
+    #[snafu(display("{}", problem))]
+    ServiceAccountNotAllowed { problem: String },
+
+    #[snafu(display("Unable to stop the sidecars of the job: {}", source))]
+    StopSidecars { source: kube::Error },
+
     #[snafu(display("{}", source), context(false))]
     SystemTime { source: std::time::SystemTimeError },
 
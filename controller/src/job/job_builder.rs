@@ -1,18 +1,29 @@
 use crate::job::error::{JobError, JobResult};
+use crate::namespaces::Namespaces;
+use http::StatusCode;
 use k8s_openapi::api::batch::v1::{Job, JobSpec};
 use k8s_openapi::api::core::v1::{
     Capabilities, Container, EnvVar, LocalObjectReference, PodSpec, PodTemplateSpec,
     SecretVolumeSource, SecurityContext, Volume, VolumeMount,
 };
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::serde::de::DeserializeOwned;
+use k8s_openapi::serde::Serialize;
+use k8s_openapi::NamespaceResourceScope;
 use kube::api::PostParams;
 use kube::Api;
 use std::collections::BTreeMap;
+use std::fmt::Debug;
+use testsys_model::clients::HttpStatusCode;
 use testsys_model::constants::{
     APP_COMPONENT, APP_CREATED_BY, APP_INSTANCE, APP_MANAGED_BY, APP_NAME, APP_PART_OF, CONTROLLER,
-    NAMESPACE, RESOURCE_AGENT, RESOURCE_AGENT_SERVICE_ACCOUNT, SECRETS_PATH, TESTSYS, TEST_AGENT,
+    ENV_PREFIX, RESOURCE_AGENT, RESOURCE_AGENT_SERVICE_ACCOUNT, SECRETS_PATH, TESTSYS, TEST_AGENT,
     TEST_AGENT_SERVICE_ACCOUNT,
 };
+use testsys_model::system::{
+    agent_labels, agent_role, agent_role_binding, agent_service_account, AgentType,
+    TESTSYS_AGENT_SERVICE_ACCOUNTS,
+};
 use testsys_model::Agent;
 
 #[derive(Debug, Clone, Copy)]
@@ -24,6 +35,8 @@ pub(crate) enum JobType {
 #[derive(Debug, Clone)]
 pub(crate) struct JobBuilder<'a> {
     pub(crate) agent: &'a Agent,
+    /// The namespace of the `Test` or `Resource` that the job is for.
+    pub(crate) namespace: &'a str,
     pub(crate) job_name: &'a str,
     pub(crate) job_type: JobType,
     pub(crate) environment_variables: Vec<(&'a str, String)>,
@@ -31,15 +44,21 @@ pub(crate) struct JobBuilder<'a> {
 
 impl JobBuilder<'_> {
     pub(crate) async fn deploy(self, client: kube::Client) -> JobResult<Job> {
+        if let Some(problem) = service_account_problem(self.agent) {
+            return Err(JobError::ServiceAccountNotAllowed { problem });
+        }
+        if self.agent.service_account.is_none() && Namespaces::from_env() == Namespaces::All {
+            create_agent_rbac(&client, self.namespace, self.job_type).await?;
+        }
+        let api: Api<Job> = Api::namespaced(client, self.namespace);
         let job = self.build();
-        let api: Api<Job> = Api::namespaced(client, NAMESPACE);
         api.create(&PostParams::default(), &job)
             .await
             .map_err(JobError::create)
     }
 
     fn build(self) -> Job {
-        let vars = env_vars(self.environment_variables);
+        let vars = env_vars(self.environment_variables, self.agent);
         let labels = create_labels(self.job_type, &self.agent.name, self.job_name);
         // Set up the container's security context
         let security_context = Some(SecurityContext {
@@ -54,7 +73,7 @@ impl JobBuilder<'_> {
         Job {
             metadata: ObjectMeta {
                 name: Some(self.job_name.into()),
-                namespace: Some(NAMESPACE.to_owned()),
+                namespace: Some(self.namespace.to_owned()),
                 labels: Some(labels.clone()),
                 ..ObjectMeta::default()
             },
@@ -62,24 +81,30 @@ impl JobBuilder<'_> {
                 backoff_limit: Some(0),
                 template: PodTemplateSpec {
                     spec: Some(PodSpec {
-                        containers: vec![Container {
+                        // The agent container comes first, it is the one that the controller
+                        // watches to decide whether the agent is done.
+                        containers: std::iter::once(Container {
                             name: self.job_name.into(),
                             image: Some(self.agent.image.to_owned()),
                             env: if vars.is_empty() { None } else { Some(vars) },
                             volume_mounts: mounts(self.agent),
                             security_context,
+                            resources: self.agent.resources.clone(),
                             ..Container::default()
-                        }],
+                        })
+                        .chain(self.agent.sidecars.iter().flatten().cloned())
+                        .collect(),
+                        init_containers: self.agent.init_containers.clone(),
                         restart_policy: Some(String::from("Never")),
                         image_pull_secrets: self.agent.pull_secret.as_ref().map(|secret| {
                             vec![LocalObjectReference {
                                 name: Some(secret.into()),
                             }]
                         }),
-                        service_account: Some(match self.job_type {
-                            JobType::TestAgent => TEST_AGENT_SERVICE_ACCOUNT.to_owned(),
-                            JobType::ResourceAgent => RESOURCE_AGENT_SERVICE_ACCOUNT.to_owned(),
-                        }),
+                        service_account: Some(service_account(self.agent, self.job_type)),
+                        node_selector: self.agent.node_selector.clone(),
+                        tolerations: self.agent.tolerations.clone(),
+                        affinity: self.agent.affinity.clone(),
                         volumes: volumes(self.agent),
                         ..PodSpec::default()
                     }),
@@ -120,7 +145,123 @@ where
     .collect()
 }
 
-fn env_vars(raw_vars: Vec<(&str, String)>) -> Vec<EnvVar> {
+/// Explains why the agent may not run as its own service account, if it may not. Agent pods are
+/// created by the controller, so an agent may only run as a service account that the controller has
+/// been installed to allow in `TESTSYS_AGENT_SERVICE_ACCOUNTS`, or else any test author could use
+/// the permissions of any service account in the namespace.
+pub(crate) fn service_account_problem(agent: &Agent) -> Option<String> {
+    let service_account = agent.service_account.as_deref()?;
+    let allowed = std::env::var(TESTSYS_AGENT_SERVICE_ACCOUNTS).unwrap_or_default();
+    if is_allowed(service_account, &allowed) {
+        return None;
+    }
+    Some(format!(
+        "The agent may not run as service account '{}', it has not been allowed in '{}'",
+        service_account, TESTSYS_AGENT_SERVICE_ACCOUNTS
+    ))
+}
+
+/// Create the default service account, role and role binding for agents of `job_type` in
+/// `namespace` unless they already exist. These are installed with TestSys for the namespaces it is
+/// configured with, but the controller reconciles objects in any namespace when it watches all of
+/// them.
+async fn create_agent_rbac(
+    client: &kube::Client,
+    namespace: &str,
+    job_type: JobType,
+) -> JobResult<()> {
+    let agent_type = match job_type {
+        JobType::TestAgent => AgentType::Test,
+        JobType::ResourceAgent => AgentType::Resource,
+    };
+    create_agent_object(
+        client,
+        namespace,
+        agent_service_account(agent_type, namespace),
+        "service account",
+    )
+    .await?;
+    create_agent_object(client, namespace, agent_role(agent_type, namespace), "role").await?;
+    create_agent_object(
+        client,
+        namespace,
+        agent_role_binding(agent_type, namespace),
+        "role binding",
+    )
+    .await
+}
+
+/// Create `object`, the agent `what`, in `namespace`. If it already exists, it is only used if it
+/// is labeled as managed by TestSys so that a user's object of the same name is not taken over.
+async fn create_agent_object<K>(
+    client: &kube::Client,
+    namespace: &str,
+    object: K,
+    what: &str,
+) -> JobResult<()>
+where
+    K: kube::Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + Debug
+        + DeserializeOwned
+        + Serialize,
+{
+    let api: Api<K> = Api::namespaced(client.clone(), namespace);
+    match api.create(&PostParams::default(), &object).await {
+        Ok(_) => return Ok(()),
+        Err(e) if !e.is_status_code(StatusCode::CONFLICT) => {
+            return Err(JobError::AgentRbac {
+                what: what.to_string(),
+                source: e,
+            })
+        }
+        Err(_) => {}
+    }
+    let name = object.meta().name.clone().unwrap_or_default();
+    let existing = api.get(&name).await.map_err(|e| JobError::AgentRbac {
+        what: what.to_string(),
+        source: e,
+    })?;
+    let labels = existing.meta().labels.clone().unwrap_or_default();
+    if agent_labels()
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+    {
+        Ok(())
+    } else {
+        Err(JobError::AgentRbacNotManaged {
+            what: what.to_string(),
+            name,
+            namespace: namespace.to_string(),
+        })
+    }
+}
+
+/// Whether `service_account` is one of the default agent service accounts or one of the
+/// comma-separated `allowed` service accounts.
+fn is_allowed(service_account: &str, allowed: &str) -> bool {
+    [TEST_AGENT_SERVICE_ACCOUNT, RESOURCE_AGENT_SERVICE_ACCOUNT]
+        .into_iter()
+        .chain(allowed.split(',').map(str::trim))
+        .any(|allowed| !allowed.is_empty() && allowed == service_account)
+}
+
+/// The agent's own service account, if it has one, or the default for the job type.
+fn service_account(agent: &Agent, job_type: JobType) -> String {
+    agent
+        .service_account
+        .clone()
+        .unwrap_or_else(|| match job_type {
+            JobType::TestAgent => TEST_AGENT_SERVICE_ACCOUNT.to_owned(),
+            JobType::ResourceAgent => RESOURCE_AGENT_SERVICE_ACCOUNT.to_owned(),
+        })
+}
+
+/// The controller's environment variables followed by the agent's own.
+/// The controller's `raw_vars` followed by the agent's own environment variables. The agent may not
+/// set variables with the `TESTSYS_` prefix, which are reserved for the controller, so these are
+/// left out even if the webhook did not reject them.
+fn env_vars(raw_vars: Vec<(&str, String)>, agent: &Agent) -> Vec<EnvVar> {
     raw_vars
         .into_iter()
         .map(|(name, value)| EnvVar {
@@ -128,43 +269,216 @@ fn env_vars(raw_vars: Vec<(&str, String)>) -> Vec<EnvVar> {
             value: Some(value),
             value_from: None,
         })
+        .chain(
+            agent
+                .env
+                .iter()
+                .flatten()
+                .filter(|env| !env.name.starts_with(ENV_PREFIX))
+                .cloned(),
+        )
         .collect()
 }
 
+/// The secret mounts under `/secrets` followed by the agent's own volume mounts.
 fn mounts(agent: &Agent) -> Option<Vec<VolumeMount>> {
-    let secrets = agent.secret_names();
-    if secrets.is_empty() {
-        return None;
-    }
-    Some(
-        secrets
-            .iter()
-            .map(|&name| VolumeMount {
-                mount_path: format!("{}/{}", SECRETS_PATH, name),
-                name: name.as_str().into(),
-                read_only: Some(true),
-                ..VolumeMount::default()
-            })
-            .collect(),
-    )
+    let mounts: Vec<VolumeMount> = agent
+        .secret_names()
+        .iter()
+        .map(|&name| VolumeMount {
+            mount_path: format!("{}/{}", SECRETS_PATH, name),
+            name: name.as_str().into(),
+            read_only: Some(true),
+            ..VolumeMount::default()
+        })
+        .chain(agent.volume_mounts.iter().flatten().cloned())
+        .collect();
+    (!mounts.is_empty()).then_some(mounts)
 }
 
+/// The secret volumes followed by the agent's own volumes.
 fn volumes(agent: &Agent) -> Option<Vec<Volume>> {
-    let secrets = agent.secret_names();
-    if secrets.is_empty() {
-        return None;
+    let volumes: Vec<Volume> = agent
+        .secret_names()
+        .iter()
+        .map(|&name| Volume {
+            name: name.as_str().into(),
+            secret: Some(SecretVolumeSource {
+                secret_name: Some(name.as_str().into()),
+                ..SecretVolumeSource::default()
+            }),
+            ..Volume::default()
+        })
+        .chain(agent.volumes.iter().flatten().cloned())
+        .collect();
+    (!volumes.is_empty()).then_some(volumes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::api::core::v1::{ResourceRequirements, Toleration};
+    use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+    use testsys_model::constants::{ENV_RESOURCE_NAME, NAMESPACE};
+
+    #[test]
+    fn scheduling() {
+        let agent = Agent {
+            name: "sonobuoy".to_string(),
+            image: "sonobuoy-test-agent".to_string(),
+            node_selector: Some(BTreeMap::from([("pool".to_string(), "agents".to_string())])),
+            tolerations: Some(vec![Toleration {
+                key: Some("dedicated".to_string()),
+                operator: Some("Exists".to_string()),
+                ..Toleration::default()
+            }]),
+            resources: Some(ResourceRequirements {
+                requests: Some(BTreeMap::from([(
+                    "memory".to_string(),
+                    Quantity("4Gi".to_string()),
+                )])),
+                ..ResourceRequirements::default()
+            }),
+            ..Agent::default()
+        };
+        let builder = JobBuilder {
+            agent: &agent,
+            namespace: NAMESPACE,
+            job_name: "sonobuoy",
+            job_type: JobType::TestAgent,
+            environment_variables: Vec::new(),
+        };
+        let pod = builder
+            .build()
+            .spec
+            .and_then(|spec| spec.template.spec)
+            .unwrap_or_default();
+        assert_eq!(pod.node_selector, agent.node_selector);
+        assert_eq!(pod.tolerations, agent.tolerations);
+        assert_eq!(pod.containers[0].resources, agent.resources);
+        assert_eq!(
+            pod.service_account.as_deref(),
+            Some(TEST_AGENT_SERVICE_ACCOUNT)
+        );
+
+        let agent = Agent {
+            service_account: Some("sonobuoy".to_string()),
+            ..agent.clone()
+        };
+        let pod = JobBuilder {
+            agent: &agent,
+            namespace: NAMESPACE,
+            job_name: "sonobuoy",
+            job_type: JobType::TestAgent,
+            environment_variables: Vec::new(),
+        }
+        .build()
+        .spec
+        .and_then(|spec| spec.template.spec)
+        .unwrap_or_default();
+        assert_eq!(pod.service_account.as_deref(), Some("sonobuoy"));
     }
-    Some(
-        secrets
-            .iter()
-            .map(|&name| Volume {
-                name: name.as_str().into(),
-                secret: Some(SecretVolumeSource {
-                    secret_name: Some(name.as_str().into()),
-                    ..SecretVolumeSource::default()
-                }),
+
+    #[test]
+    fn allowed_service_accounts() {
+        assert!(is_allowed(TEST_AGENT_SERVICE_ACCOUNT, ""));
+        assert!(is_allowed("sonobuoy", "eks, sonobuoy"));
+        assert!(!is_allowed("sonobuoy", ""));
+        assert!(!is_allowed(
+            "testsys-controller-service-account",
+            "eks,sonobuoy"
+        ));
+        assert!(!is_allowed("", "eks,,sonobuoy"));
+    }
+
+    #[test]
+    fn pod_additions() {
+        let agent = Agent {
+            name: "eks".to_string(),
+            image: "eks-resource-agent".to_string(),
+            env: Some(vec![
+                EnvVar {
+                    name: "AWS_REGION".to_string(),
+                    value: Some("us-west-2".to_string()),
+                    value_from: None,
+                },
+                EnvVar {
+                    name: ENV_RESOURCE_NAME.to_string(),
+                    value: Some("another-resource".to_string()),
+                    value_from: None,
+                },
+            ]),
+            volumes: Some(vec![Volume {
+                name: "scratch".to_string(),
+                empty_dir: Some(Default::default()),
                 ..Volume::default()
-            })
-            .collect(),
-    )
+            }]),
+            volume_mounts: Some(vec![VolumeMount {
+                name: "scratch".to_string(),
+                mount_path: "/scratch".to_string(),
+                ..VolumeMount::default()
+            }]),
+            ..Agent::default()
+        };
+        let pod = JobBuilder {
+            agent: &agent,
+            namespace: NAMESPACE,
+            job_name: "eks",
+            job_type: JobType::ResourceAgent,
+            environment_variables: vec![(ENV_RESOURCE_NAME, "eks".to_string())],
+        }
+        .build()
+        .spec
+        .and_then(|spec| spec.template.spec)
+        .unwrap_or_default();
+        let env: Vec<(&str, Option<&str>)> = pod.containers[0]
+            .env
+            .iter()
+            .flatten()
+            .map(|env| (env.name.as_str(), env.value.as_deref()))
+            .collect();
+        assert_eq!(
+            env,
+            vec![
+                (ENV_RESOURCE_NAME, Some("eks")),
+                ("AWS_REGION", Some("us-west-2"))
+            ]
+        );
+        assert_eq!(pod.volumes, agent.volumes);
+        assert_eq!(pod.containers[0].volume_mounts, agent.volume_mounts);
+    }
+
+    #[test]
+    fn extra_containers() {
+        let container = |name: &str| Container {
+            name: name.to_string(),
+            image: Some(name.to_string()),
+            ..Container::default()
+        };
+        let agent = Agent {
+            name: "vsphere".to_string(),
+            image: "vsphere-k8s-cluster-resource-agent".to_string(),
+            init_containers: Some(vec![container("govc")]),
+            sidecars: Some(vec![container("proxy")]),
+            ..Agent::default()
+        };
+        let pod = JobBuilder {
+            agent: &agent,
+            namespace: NAMESPACE,
+            job_name: "vsphere-creation",
+            job_type: JobType::ResourceAgent,
+            environment_variables: Vec::new(),
+        }
+        .build()
+        .spec
+        .and_then(|spec| spec.template.spec)
+        .unwrap_or_default();
+        let names: Vec<&str> = pod
+            .containers
+            .iter()
+            .map(|container| container.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["vsphere-creation", "proxy"]);
+        assert_eq!(pod.init_containers, agent.init_containers);
+    }
 }
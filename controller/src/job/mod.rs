@@ -4,19 +4,23 @@ mod job_builder;
 pub(crate) use crate::job::error::{JobError, JobResult};
 use aws_config::BehaviorVersion;
 use aws_sdk_cloudwatchlogs::types::InputLogEvent;
-pub(crate) use job_builder::{JobBuilder, JobType};
+pub(crate) use job_builder::{service_account_problem, JobBuilder, JobType};
 use k8s_openapi::api::batch::v1::Job;
 use k8s_openapi::api::core::v1::Pod;
 use k8s_openapi::chrono::{Duration, Utc};
-use kube::api::{DeleteParams, ListParams, LogParams, PropagationPolicy};
+use kube::api::{DeleteParams, ListParams, LogParams, Patch, PatchParams, PropagationPolicy};
 use kube::{Api, ResourceExt};
-use log::{debug, info, warn};
-use snafu::{ensure, OptionExt, ResultExt};
+use log::{debug, error, info, warn};
+use snafu::{ensure, ResultExt};
 use std::env;
 use std::time::{SystemTime, UNIX_EPOCH};
-use testsys_model::constants::NAMESPACE;
 use testsys_model::system::TESTSYS_CONTROLLER_ARCHIVE_LOGS;
 
+/// The annotation that records how the agent container exited when the job's sidecars are stopped.
+const ANNOTATION_AGENT_STATE: &str = "testsys.system/agent-state";
+const AGENT_EXITED: &str = "Exited";
+const AGENT_FAILED: &str = "Failed";
+
 lazy_static::lazy_static! {
     /// The maximum amount of time for a test to begin running (in seconds).
     pub static ref TEST_START_TIME_LIMIT: Duration = {
@@ -26,8 +30,9 @@ lazy_static::lazy_static! {
 
 /// We run the test pod using a k8s `Job`. Jobs can run many containers and provide counts of how
 /// many containers are running or have completed (succeeded or failed). We are only running one
-/// container, so it is helpful to transform those counts into a simple enumeration of our job's
-/// state.
+/// pod, so it is helpful to transform those counts into a simple enumeration of our job's state.
+/// When the pod has sidecars, the state follows the agent container, and the job is suspended to
+/// stop the sidecars once the agent container has exited.
 #[derive(Debug, Copy, Clone)]
 pub(crate) enum JobState {
     /// The job does not exist.
@@ -44,22 +49,89 @@ pub(crate) enum JobState {
     Exited,
 }
 
-pub(crate) async fn get_job_state<S>(k8s_client: kube::Client, name: S) -> JobResult<JobState>
+pub(crate) async fn get_job_state<S>(
+    k8s_client: kube::Client,
+    namespace: &str,
+    name: S,
+) -> JobResult<JobState>
 where
     S: AsRef<str>,
 {
-    let api: Api<Job> = Api::namespaced(k8s_client, NAMESPACE);
+    let api: Api<Job> = Api::namespaced(k8s_client.clone(), namespace);
     let result = api.get(name.as_ref()).await.map_err(JobError::get);
     if let Err(JobError::NotFound { .. }) = &result {
         Ok(JobState::None)
     } else {
         let job = result?;
-        parse_job_state(&job)
+        if !has_sidecars(&job) || stopped_agent_state(&job).is_some() {
+            return parse_job_state(&job, None);
+        }
+        // Sidecars keep the pod running after the agent container exits, so the state of the agent
+        // container has to be read from the pod. Once it has exited the sidecars are stopped so
+        // that they do not keep the job running.
+        let pod = get_job_pod(k8s_client.clone(), namespace, name.as_ref()).await?;
+        let state = parse_job_state(&job, pod.as_ref())?;
+        if matches!(state, JobState::Exited | JobState::Failed) {
+            stop_sidecars(k8s_client, namespace, name.as_ref(), state).await?;
+        }
+        Ok(state)
+    }
+}
+
+/// Records the state of the exited agent container in the job and suspends the job, which stops
+/// the sidecars that are still running in its pod. The agent's logs are archived first since the
+/// pod is deleted.
+async fn stop_sidecars(
+    k8s_client: kube::Client,
+    namespace: &str,
+    name: &str,
+    state: JobState,
+) -> JobResult<()> {
+    debug!("Stopping the sidecars of job '{}'", name);
+    if let Err(e) = archive_logs(k8s_client.clone(), namespace, name).await {
+        error!("Unable to archive logs for job '{}': {}", name, e);
+    }
+    let agent_state = match state {
+        JobState::Failed => AGENT_FAILED,
+        _ => AGENT_EXITED,
+    };
+    let patch = serde_json::json!({
+        "metadata": { "annotations": { ANNOTATION_AGENT_STATE: agent_state } },
+        "spec": { "suspend": true },
+    });
+    let api: Api<Job> = Api::namespaced(k8s_client, namespace);
+    let _ = api
+        .patch(name, &PatchParams::default(), &Patch::Merge(&patch))
+        .await
+        .context(error::StopSidecarsSnafu)?;
+    Ok(())
+}
+
+/// The state of the agent container that was recorded when the job's sidecars were stopped.
+fn stopped_agent_state(job: &Job) -> Option<JobState> {
+    match job.annotations().get(ANNOTATION_AGENT_STATE)?.as_str() {
+        AGENT_EXITED => Some(JobState::Exited),
+        AGENT_FAILED => Some(JobState::Failed),
+        _ => None,
     }
 }
 
-/// Transform the container counts in `job.status` to a `JobState`
-fn parse_job_state(job: &Job) -> JobResult<JobState> {
+/// The agent container is the first container of the job's pod, any others are sidecars.
+fn has_sidecars(job: &Job) -> bool {
+    job.spec
+        .as_ref()
+        .and_then(|spec| spec.template.spec.as_ref())
+        .is_some_and(|pod_spec| pod_spec.containers.len() > 1)
+}
+
+/// Transform the container counts in `job.status` to a `JobState`. While the job is running, the
+/// agent container in `pod` may already have exited, in which case its exit code decides the state.
+fn parse_job_state(job: &Job, pod: Option<&Pod>) -> JobResult<JobState> {
+    // The pod of a job whose sidecars have been stopped is gone.
+    if let Some(state) = stopped_agent_state(job) {
+        return Ok(state);
+    }
+
     // Return early if `job.status` is somehow `None`.
     let status = match &job.status {
         None => {
@@ -95,6 +167,9 @@ fn parse_job_state(job: &Job) -> JobResult<JobState> {
     );
 
     if running == 1 {
+        if let Some(state) = pod.and_then(|pod| exited_agent_state(job, pod)) {
+            return Ok(state);
+        }
         let job_running_duration = status
             .start_time
             .as_ref()
@@ -107,8 +182,40 @@ fn parse_job_state(job: &Job) -> JobResult<JobState> {
     }
 }
 
-pub(crate) async fn delete_job(k8s_client: kube::Client, name: &str) -> JobResult<()> {
-    let api: Api<Job> = Api::namespaced(k8s_client, NAMESPACE);
+/// The state of the job's agent container in `pod`, if the agent container has exited.
+fn exited_agent_state(job: &Job, pod: &Pod) -> Option<JobState> {
+    let agent_container = job
+        .spec
+        .as_ref()?
+        .template
+        .spec
+        .as_ref()?
+        .containers
+        .first()?;
+    let terminated = pod
+        .status
+        .as_ref()?
+        .container_statuses
+        .as_ref()?
+        .iter()
+        .find(|status| status.name == agent_container.name)?
+        .state
+        .as_ref()?
+        .terminated
+        .as_ref()?;
+    Some(if terminated.exit_code == 0 {
+        JobState::Exited
+    } else {
+        JobState::Failed
+    })
+}
+
+pub(crate) async fn delete_job(
+    k8s_client: kube::Client,
+    namespace: &str,
+    name: &str,
+) -> JobResult<()> {
+    let api: Api<Job> = Api::namespaced(k8s_client, namespace);
     let result = api
         .delete(
             name,
@@ -129,9 +236,13 @@ pub(crate) async fn delete_job(k8s_client: kube::Client, name: &str) -> JobResul
     Ok(())
 }
 
-async fn get_pod(k8s_client: kube::Client, job_name: &str) -> JobResult<String> {
-    let pod_api: Api<Pod> = Api::namespaced(k8s_client, NAMESPACE);
-    let name = pod_api
+async fn get_job_pod(
+    k8s_client: kube::Client,
+    namespace: &str,
+    job_name: &str,
+) -> JobResult<Option<Pod>> {
+    let pod_api: Api<Pod> = Api::namespaced(k8s_client, namespace);
+    Ok(pod_api
         .list(&ListParams {
             label_selector: Some(format!("job-name={}", job_name)),
             ..Default::default()
@@ -139,22 +250,24 @@ async fn get_pod(k8s_client: kube::Client, job_name: &str) -> JobResult<String>
         .await
         .context(error::NotFoundSnafu {})?
         .items
-        .first()
-        .context(error::NoPodsSnafu {
-            job: job_name.to_string(),
-        })?
-        .name_any();
-
-    Ok(name)
+        .into_iter()
+        .next())
 }
 
-async fn pod_logs(k8s_client: kube::Client, pod_name: &str) -> JobResult<String> {
+/// Reads the logs of the agent container, which is named after the job.
+async fn pod_logs(
+    k8s_client: kube::Client,
+    namespace: &str,
+    pod_name: &str,
+    job_name: &str,
+) -> JobResult<String> {
     let log_params = LogParams {
+        container: Some(job_name.to_string()),
         follow: false,
         pretty: true,
         ..Default::default()
     };
-    let pod_api: Api<Pod> = Api::namespaced(k8s_client, NAMESPACE);
+    let pod_api: Api<Pod> = Api::namespaced(k8s_client, namespace);
 
     pod_api
         .logs(pod_name, &log_params)
@@ -162,7 +275,11 @@ async fn pod_logs(k8s_client: kube::Client, pod_name: &str) -> JobResult<String>
         .context(error::NoLogsSnafu { pod: pod_name })
 }
 
-pub(crate) async fn archive_logs(k8s_client: kube::Client, job_name: &str) -> JobResult<()> {
+pub(crate) async fn archive_logs(
+    k8s_client: kube::Client,
+    namespace: &str,
+    job_name: &str,
+) -> JobResult<()> {
     let archive_logs = match env::var(TESTSYS_CONTROLLER_ARCHIVE_LOGS) {
         Ok(s) => s == true.to_string(),
         Err(e) => {
@@ -201,8 +318,16 @@ pub(crate) async fn archive_logs(k8s_client: kube::Client, job_name: &str) -> Jo
         }
     }
 
-    let pod_name = get_pod(k8s_client.clone(), job_name).await?;
-    let logs = pod_logs(k8s_client, &pod_name).await?;
+    // The pod is gone if the job's sidecars were stopped, in which case its logs have already been
+    // archived.
+    let pod_name = match get_job_pod(k8s_client.clone(), namespace, job_name).await? {
+        Some(pod) => pod.name_any(),
+        None => {
+            debug!("Job '{}' has no pod to archive the logs of", job_name);
+            return Ok(());
+        }
+    };
+    let logs = pod_logs(k8s_client, namespace, &pod_name, job_name).await?;
     let name = format!(
         "{}-{}",
         job_name,
@@ -244,3 +369,90 @@ pub(crate) async fn archive_logs(k8s_client: kube::Client, job_name: &str) -> Jo
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::api::batch::v1::{JobSpec, JobStatus};
+    use k8s_openapi::api::core::v1::{
+        Container, ContainerState, ContainerStateTerminated, ContainerStatus, PodSpec, PodStatus,
+        PodTemplateSpec,
+    };
+
+    fn job_with_sidecar() -> Job {
+        let container = |name: &str| Container {
+            name: name.to_string(),
+            ..Container::default()
+        };
+        Job {
+            spec: Some(JobSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        containers: vec![container("agent"), container("proxy")],
+                        ..PodSpec::default()
+                    }),
+                    ..PodTemplateSpec::default()
+                },
+                ..JobSpec::default()
+            }),
+            status: Some(JobStatus {
+                active: Some(1),
+                ..JobStatus::default()
+            }),
+            ..Job::default()
+        }
+    }
+
+    fn pod(agent_exit_code: Option<i32>) -> Pod {
+        let status = |name: &str, exit_code: Option<i32>| ContainerStatus {
+            name: name.to_string(),
+            state: Some(ContainerState {
+                terminated: exit_code.map(|exit_code| ContainerStateTerminated {
+                    exit_code,
+                    ..ContainerStateTerminated::default()
+                }),
+                ..ContainerState::default()
+            }),
+            ..ContainerStatus::default()
+        };
+        Pod {
+            status: Some(PodStatus {
+                container_statuses: Some(vec![
+                    status("agent", agent_exit_code),
+                    status("proxy", None),
+                ]),
+                ..PodStatus::default()
+            }),
+            ..Pod::default()
+        }
+    }
+
+    #[test]
+    fn sidecars_do_not_keep_the_agent_running() {
+        let job = job_with_sidecar();
+        assert!(has_sidecars(&job));
+        assert!(matches!(
+            parse_job_state(&job, Some(&pod(None))),
+            Ok(JobState::Running(_))
+        ));
+        assert!(matches!(
+            parse_job_state(&job, Some(&pod(Some(0)))),
+            Ok(JobState::Exited)
+        ));
+        assert!(matches!(
+            parse_job_state(&job, Some(&pod(Some(1)))),
+            Ok(JobState::Failed)
+        ));
+        assert!(matches!(
+            parse_job_state(&job, None),
+            Ok(JobState::Running(_))
+        ));
+
+        // Once the sidecars have been stopped the recorded agent state is used.
+        let mut job = job_with_sidecar();
+        job.status = None;
+        job.annotations_mut()
+            .insert(ANNOTATION_AGENT_STATE.to_string(), AGENT_FAILED.to_string());
+        assert!(matches!(parse_job_state(&job, None), Ok(JobState::Failed)));
+    }
+}
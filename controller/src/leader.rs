@@ -0,0 +1,224 @@
+use crate::error::Result;
+use crate::namespaces::controller_namespace;
+use anyhow::Context;
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::MicroTime;
+use k8s_openapi::chrono::{DateTime, Duration as ChronoDuration, Utc};
+use kube::api::{ObjectMeta, Patch, PatchParams, PostParams};
+use kube::{Api, Client};
+use log::{debug, warn};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use testsys_model::clients::{HttpStatusCode, StatusCode};
+use testsys_model::constants::{LABEL_LEADER, NAMESPACE};
+use testsys_model::system::{TESTSYS_CONTROLLER_LEASE, TESTSYS_CONTROLLER_POD_NAME};
+
+/// How long the lease is valid after the leader last renewed it. Other replicas take over once it
+/// has expired.
+const LEASE_DURATION_SECONDS: i32 = 15;
+/// How long the leader keeps trying to renew the lease before it gives up. This is shorter than the
+/// lease duration so that the leader stops before another replica can take over.
+const RENEW_DEADLINE: Duration = Duration::from_secs(10);
+/// How often the leader renews the lease and the other replicas try to acquire it.
+const RETRY_PERIOD: Duration = Duration::from_secs(2);
+
+/// Elects one controller replica as the leader using the [`TESTSYS_CONTROLLER_LEASE`] `Lease` in
+/// the `testsys` namespace. Updates to the lease use its resource version, so only one replica can
+/// win when several try to acquire it at once.
+pub(crate) struct LeaderElector {
+    api: Api<Lease>,
+    pods: Api<Pod>,
+    identity: String,
+}
+
+impl LeaderElector {
+    /// Creates an elector that identifies this replica by the name of its pod.
+    pub(crate) fn new(client: Client) -> Self {
+        let identity = std::env::var(TESTSYS_CONTROLLER_POD_NAME)
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| format!("testsys-controller-{}", std::process::id()));
+        Self {
+            api: Api::namespaced(client.clone(), NAMESPACE),
+            pods: Api::namespaced(client, &controller_namespace()),
+            identity,
+        }
+    }
+
+    pub(crate) fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    /// Labels the pod of this replica with whether it is the leader, which routes the webhook
+    /// service to the leader. A replica that restarts after losing the lease clears its label.
+    pub(crate) async fn label_pod(&self, leader: bool) -> Result<()> {
+        let value = if leader { Some(true.to_string()) } else { None };
+        let patch = json!({ "metadata": { "labels": { LABEL_LEADER: value } } });
+        self.pods
+            .patch(
+                &self.identity,
+                &PatchParams::default(),
+                &Patch::Merge(patch),
+            )
+            .await
+            .context(format!("Unable to label pod '{}'", self.identity))?;
+        Ok(())
+    }
+
+    /// Waits until this replica holds the lease.
+    pub(crate) async fn acquire(&self) {
+        loop {
+            match self.try_acquire().await {
+                Ok(true) => return,
+                Ok(false) => debug!(
+                    "Lease '{}' is held by another replica",
+                    TESTSYS_CONTROLLER_LEASE
+                ),
+                Err(e) => warn!("Unable to acquire the leader lease: {:?}", e),
+            }
+            tokio::time::sleep(RETRY_PERIOD).await;
+        }
+    }
+
+    /// Keeps renewing the lease. Returns when another replica has taken the lease or when it could
+    /// not be renewed within the [`RENEW_DEADLINE`], after which this replica is no longer the
+    /// leader.
+    pub(crate) async fn hold(&self) {
+        let mut renewed = Instant::now();
+        loop {
+            tokio::time::sleep(RETRY_PERIOD).await;
+            match self.try_acquire().await {
+                Ok(true) => renewed = Instant::now(),
+                Ok(false) => {
+                    warn!(
+                        "Lease '{}' has been taken by another replica",
+                        TESTSYS_CONTROLLER_LEASE
+                    );
+                    return;
+                }
+                Err(e) => {
+                    warn!("Unable to renew the leader lease: {:?}", e);
+                    if renewed.elapsed() >= RENEW_DEADLINE {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Acquires or renews the lease and returns whether this replica holds it.
+    async fn try_acquire(&self) -> Result<bool> {
+        let now = Utc::now();
+        let lease = self
+            .api
+            .get_opt(TESTSYS_CONTROLLER_LEASE)
+            .await
+            .context(format!(
+                "Unable to get lease '{}'",
+                TESTSYS_CONTROLLER_LEASE
+            ))?;
+        let result = match lease {
+            None => {
+                let lease = Lease {
+                    metadata: ObjectMeta {
+                        name: Some(TESTSYS_CONTROLLER_LEASE.to_string()),
+                        namespace: Some(NAMESPACE.to_string()),
+                        ..Default::default()
+                    },
+                    spec: Some(lease_spec(None, &self.identity, now)),
+                };
+                self.api.create(&PostParams::default(), &lease).await
+            }
+            Some(lease) => {
+                if !can_acquire(lease.spec.as_ref(), &self.identity, now) {
+                    return Ok(false);
+                }
+                let spec = lease_spec(lease.spec.as_ref(), &self.identity, now);
+                let lease = Lease {
+                    spec: Some(spec),
+                    ..lease
+                };
+                self.api
+                    .replace(TESTSYS_CONTROLLER_LEASE, &PostParams::default(), &lease)
+                    .await
+            }
+        };
+        // Another replica created or updated the lease after we read it.
+        if result.is_status_code(StatusCode::CONFLICT) {
+            return Ok(false);
+        }
+        let _ = result.context(format!(
+            "Unable to update lease '{}'",
+            TESTSYS_CONTROLLER_LEASE
+        ))?;
+        Ok(true)
+    }
+}
+
+/// Whether `identity` may hold the lease with `spec` at `now`. That is the case if it already holds
+/// the lease, if nobody does or if the holder has not renewed it in time.
+fn can_acquire(spec: Option<&LeaseSpec>, identity: &str, now: DateTime<Utc>) -> bool {
+    let spec = match spec {
+        Some(spec) => spec,
+        None => return true,
+    };
+    match spec.holder_identity.as_deref() {
+        None | Some("") => return true,
+        Some(holder) if holder == identity => return true,
+        Some(_) => {}
+    }
+    let duration = ChronoDuration::seconds(spec.lease_duration_seconds.unwrap_or_default().into());
+    spec.renew_time
+        .as_ref()
+        .is_none_or(|renew_time| renew_time.0 + duration <= now)
+}
+
+/// The spec of the lease once `identity` has acquired or renewed it at `now`.
+fn lease_spec(current: Option<&LeaseSpec>, identity: &str, now: DateTime<Utc>) -> LeaseSpec {
+    let current = current.cloned().unwrap_or_default();
+    if current.holder_identity.as_deref() == Some(identity) {
+        return LeaseSpec {
+            lease_duration_seconds: Some(LEASE_DURATION_SECONDS),
+            renew_time: Some(MicroTime(now)),
+            ..current
+        };
+    }
+    LeaseSpec {
+        holder_identity: Some(identity.to_string()),
+        lease_duration_seconds: Some(LEASE_DURATION_SECONDS),
+        acquire_time: Some(MicroTime(now)),
+        renew_time: Some(MicroTime(now)),
+        lease_transitions: Some(current.lease_transitions.map_or(0, |count| count + 1)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lease_acquisition() {
+        let start = Utc::now();
+        let spec = lease_spec(None, "controller-a", start);
+        assert_eq!(spec.lease_transitions, Some(0));
+        assert!(can_acquire(None, "controller-a", start));
+        assert!(can_acquire(Some(&spec), "controller-a", start));
+        assert!(!can_acquire(Some(&spec), "controller-b", start));
+
+        let renewed = lease_spec(
+            Some(&spec),
+            "controller-a",
+            start + ChronoDuration::seconds(5),
+        );
+        assert_eq!(renewed.acquire_time, spec.acquire_time);
+        assert_eq!(renewed.lease_transitions, Some(0));
+
+        let expired = start + ChronoDuration::seconds(i64::from(LEASE_DURATION_SECONDS) + 5);
+        let before_expiry = expired - ChronoDuration::seconds(1);
+        assert!(!can_acquire(Some(&renewed), "controller-b", before_expiry));
+        assert!(can_acquire(Some(&renewed), "controller-b", expired));
+        let taken = lease_spec(Some(&renewed), "controller-b", expired);
+        assert_eq!(taken.holder_identity.as_deref(), Some("controller-b"));
+        assert_eq!(taken.lease_transitions, Some(1));
+    }
+}
@@ -1,7 +1,19 @@
 /*!
 
 This [controller] runs in a Kubernetes cluster and is responsible for running resource provider pods
-and test agent pods when TestSys [`Test`] and [`Resource`] CRD instance is added to the cluster.
+and test agent pods when TestSys [`Test`] and [`Resource`] CRD instance is added to the cluster. It
+also creates and tracks the [`Test`] and [`Resource`] objects of each TestSys [`TestSuite`], keeps
+each TestSys [`ResourcePool`] filled with [`Resource`]s that tests can lease, starts a
+[`TestSuite`] each time a TestSys [`TestSchedule`] is due, and can optionally serve a validating
+admission webhook that rejects invalid [`Test`] and [`Resource`] objects when they are created.
+Agent jobs are only started within the concurrency limits that are configured in the
+`testsys-admission` `ConfigMap`. A reaper deletes [`Resource`]s that outlive their `maxLifetime`
+and [`Test`]s whose `ttlAfterFinished` has passed. Objects are watched in the `testsys` namespace
+unless a comma-separated list of namespaces, or `*` for all of them, is given in the
+`TESTSYS_CONTROLLER_NAMESPACES` environment variable. When several replicas of the controller run,
+they elect a leader with a `Lease` and only the leader reconciles objects and serves the webhook.
+Each replica serves Prometheus metrics on port 8080 at `/metrics`, along with `/healthz` and
+`/readyz`, which report whether the test and resource controllers are processing objects.
 
 [controller]: https://kubernetes.io/docs/concepts/architecture/controller/
 
@@ -17,19 +29,42 @@ and test agent pods when TestSys [`Test`] and [`Resource`] CRD instance is added
     clippy::unwrap_used
 )]
 
+use crate::admission::Admission;
+use crate::health::Health;
+use crate::leader::LeaderElector;
+use crate::metrics::{run_state_metrics, Metrics};
+use crate::namespaces::Namespaces;
+use crate::reaper::run_reaper;
 use crate::resource_controller::run_resource_controller;
+use crate::resource_pool_controller::run_resource_pool_controller;
+use crate::server::run_server;
 use crate::test_controller::run_test_controller;
+use crate::test_schedule_controller::run_test_schedule_controller;
+use crate::test_suite_controller::run_test_suite_controller;
+use crate::webhook::run_webhook;
 use env_logger::Builder;
 use futures::join;
 use kube::Client;
-use log::{error, info, LevelFilter};
+use log::{error, info, warn, LevelFilter};
 
+mod admission;
 mod constants;
 mod error;
+mod events;
+mod health;
 mod job;
+mod leader;
+mod metrics;
+mod namespaces;
+mod reaper;
 mod resource_controller;
+mod resource_pool_controller;
+mod server;
 mod test_controller;
+mod test_schedule_controller;
+mod test_suite_controller;
 mod utils;
+mod webhook;
 
 #[tokio::main]
 async fn main() {
@@ -45,11 +80,56 @@ async fn main() {
         }
     };
 
+    let namespaces = Namespaces::from_env();
+    info!("Watching {}", namespaces);
+
+    let metrics = match Metrics::new() {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            error!("Unable to create metrics: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+    let health = Health::default();
+    tokio::spawn(run_server(metrics.clone(), health.clone()));
+
+    // Wait until this replica is the leader. Only the leader runs the controllers, and it exits if
+    // it loses the lease so that it cannot race the new leader.
+    let elector = LeaderElector::new(client.clone());
+    if let Err(e) = elector.label_pod(false).await {
+        warn!("{:?}", e);
+    }
+    info!("Waiting to become the leader as '{}'", elector.identity());
+    elector.acquire().await;
+    info!("Became the leader as '{}'", elector.identity());
+    health.set_leader();
+    if let Err(e) = elector.label_pod(true).await {
+        warn!("The webhook will not be reachable: {:?}", e);
+    }
+
     // Run the controllers.
-    let future_1 = run_test_controller(client.clone());
-    let future_2 = run_resource_controller(client);
+    let admission = Admission::new(client.clone());
+    let future_1 = run_test_controller(client.clone(), &namespaces, &metrics, &health, &admission);
+    let future_2 =
+        run_resource_controller(client.clone(), &namespaces, &metrics, &health, &admission);
+    let future_3 = run_test_suite_controller(client.clone(), &namespaces, &metrics);
+    let future_4 = run_resource_pool_controller(client.clone(), &namespaces, &metrics);
+    let future_5 = run_test_schedule_controller(client.clone(), &namespaces, &metrics);
+    let future_6 = run_webhook(client.clone());
+    let future_7 = run_reaper(client.clone(), &namespaces);
+    let future_8 = run_state_metrics(client, &namespaces, metrics.clone());
+
+    let controllers = async {
+        join!(future_1, future_2, future_3, future_4, future_5, future_6, future_7, future_8)
+    };
 
-    let _ = join!(future_1, future_2);
+    tokio::select! {
+        _ = controllers => {}
+        _ = elector.hold() => {
+            error!("Lost the leader lease, exiting");
+            std::process::exit(1);
+        }
+    }
 }
 
 /// The log level used when the `RUST_LOG` environment variable does not exist.
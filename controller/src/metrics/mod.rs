@@ -0,0 +1,380 @@
+use crate::error::Result;
+use crate::namespaces::Namespaces;
+use anyhow::Context;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::Client;
+use log::error;
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use testsys_model::clients::{CrdClient, ResourceClient, TestClient};
+use testsys_model::{
+    Condition, ConditionStatus, ConditionType, ErrorResources, Resource, ResourceAction, Test,
+};
+
+/// How often the tests and resources in each state are counted.
+const STATE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Histogram buckets, in seconds, for tasks that can take anywhere from seconds to hours.
+const TASK_BUCKETS: &[f64] = &[
+    10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0, 7200.0, 14400.0, 28800.0,
+];
+
+/// The Prometheus metrics of the controller. Clones share the same metrics.
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    registry: Registry,
+    reconciles: IntCounterVec,
+    reconcile_errors: IntCounterVec,
+    reconcile_duration: HistogramVec,
+    tests: IntGaugeVec,
+    resources: IntGaugeVec,
+    job_start_latency: HistogramVec,
+    test_duration: HistogramVec,
+    resource_creation_duration: HistogramVec,
+    orphaned_resources: IntCounterVec,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Result<Self> {
+        let metrics = Self {
+            registry: Registry::new_custom(Some("testsys".to_string()), None)
+                .context("Unable to create metrics registry")?,
+            reconciles: IntCounterVec::new(
+                Opts::new("reconciles_total", "Reconciliations by controller"),
+                &["controller"],
+            )?,
+            reconcile_errors: IntCounterVec::new(
+                Opts::new(
+                    "reconcile_errors_total",
+                    "Reconciliations that failed by controller",
+                ),
+                &["controller"],
+            )?,
+            reconcile_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "reconcile_duration_seconds",
+                    "Time taken to reconcile an object by controller",
+                ),
+                &["controller"],
+            )?,
+            tests: IntGaugeVec::new(
+                Opts::new("tests", "Tests by their user state"),
+                &["state"],
+            )?,
+            resources: IntGaugeVec::new(
+                Opts::new(
+                    "resources",
+                    "Resources by the state of their creation or destruction task",
+                ),
+                &["action", "state"],
+            )?,
+            job_start_latency: HistogramVec::new(
+                HistogramOpts::new(
+                    "job_start_latency_seconds",
+                    "Time from the creation of a test or resource until its agent job was started",
+                )
+                .buckets(TASK_BUCKETS.to_vec()),
+                &["job_type"],
+            )?,
+            test_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "test_duration_seconds",
+                    "Time from the start of the last attempt of a test until its agent finished by agent image",
+                )
+                .buckets(TASK_BUCKETS.to_vec()),
+                &["image"],
+            )?,
+            resource_creation_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "resource_creation_duration_seconds",
+                    "Time from the creation of a resource until its agent finished creating it by \
+                     agent image",
+                )
+                .buckets(TASK_BUCKETS.to_vec()),
+                &["image"],
+            )?,
+            orphaned_resources: IntCounterVec::new(
+                Opts::new(
+                    "orphaned_resources_total",
+                    "Resource agent errors that left resources behind which cannot be destroyed by \
+                     agent image",
+                ),
+                &["image"],
+            )?,
+        };
+        let collectors: Vec<Box<dyn Collector>> = vec![
+            Box::new(metrics.reconciles.clone()),
+            Box::new(metrics.reconcile_errors.clone()),
+            Box::new(metrics.reconcile_duration.clone()),
+            Box::new(metrics.tests.clone()),
+            Box::new(metrics.resources.clone()),
+            Box::new(metrics.job_start_latency.clone()),
+            Box::new(metrics.test_duration.clone()),
+            Box::new(metrics.resource_creation_duration.clone()),
+            Box::new(metrics.orphaned_resources.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .context("Unable to register metric")?;
+        }
+        Ok(metrics)
+    }
+
+    /// Wraps the `reconcile` function of `controller` so that its reconciliations are counted and
+    /// timed.
+    pub(crate) fn measured<K, C, F, Fut, T, E>(
+        &self,
+        controller: &'static str,
+        mut reconcile: F,
+    ) -> impl FnMut(Arc<K>, C) -> BoxFuture<'static, std::result::Result<T, E>>
+    where
+        F: FnMut(Arc<K>, C) -> Fut,
+        Fut: Future<Output = std::result::Result<T, E>> + Send + 'static,
+    {
+        let metrics = self.clone();
+        move |object, context| {
+            let metrics = metrics.clone();
+            let reconciliation = reconcile(object, context);
+            async move {
+                let start = Instant::now();
+                let result = reconciliation.await;
+                metrics.reconciles.with_label_values(&[controller]).inc();
+                if result.is_err() {
+                    metrics
+                        .reconcile_errors
+                        .with_label_values(&[controller])
+                        .inc();
+                }
+                metrics
+                    .reconcile_duration
+                    .with_label_values(&[controller])
+                    .observe(start.elapsed().as_secs_f64());
+                result
+            }
+            .boxed()
+        }
+    }
+
+    /// Records that the agent job of type `job_type` was started for an object created at
+    /// `created`.
+    pub(crate) fn job_started(&self, job_type: &str, created: Option<&Time>) {
+        if let Some(seconds) = seconds_since(created) {
+            self.job_start_latency
+                .with_label_values(&[job_type])
+                .observe(seconds);
+        }
+    }
+
+    /// Records that the agent of `test` has finished. The duration is measured from the start of
+    /// the last attempt so that the time the test waited to be admitted is not counted.
+    pub(crate) fn test_finished(&self, test: &Test) {
+        if let Some(seconds) = seconds_since(attempt_start_time(test).as_ref()) {
+            self.test_duration
+                .with_label_values(&[&test.spec.agent.image])
+                .observe(seconds);
+        }
+    }
+
+    /// Records that the agent of `resource` has finished creating it.
+    pub(crate) fn resource_created(&self, resource: &Resource) {
+        if let Some(seconds) = seconds_since(resource.metadata.creation_timestamp.as_ref()) {
+            self.resource_creation_duration
+                .with_label_values(&[&resource.spec.agent.image])
+                .observe(seconds);
+        }
+    }
+
+    /// Counts the error of `resource` if its agent left resources behind that it cannot destroy.
+    pub(crate) fn resource_failed(&self, resource: &Resource) {
+        let orphaned = [resource.creation_error(), resource.destruction_error()]
+            .into_iter()
+            .flatten()
+            .any(|error| error.error_resources == ErrorResources::Orphaned);
+        if orphaned {
+            self.orphaned_resources
+                .with_label_values(&[&resource.spec.agent.image])
+                .inc();
+        }
+    }
+
+    /// Sets the number of `tests` and `resources` in each state.
+    fn set_states(&self, tests: &[Test], resources: &[Resource]) {
+        self.tests.reset();
+        for test in tests {
+            self.tests
+                .with_label_values(&[&test.test_user_state().to_string()])
+                .inc();
+        }
+        self.resources.reset();
+        for resource in resources {
+            for (action, state) in [
+                (ResourceAction::Create, resource.creation_task_state()),
+                (ResourceAction::Destroy, resource.destruction_task_state()),
+            ] {
+                self.resources
+                    .with_label_values(&[&action.to_string(), &state.to_string()])
+                    .inc();
+            }
+        }
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub(crate) fn encode(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .context("Unable to encode metrics")?;
+        Ok(buffer)
+    }
+}
+
+/// Periodically counts the tests and resources in `namespaces` by their state.
+pub(crate) async fn run_state_metrics(client: Client, namespaces: &Namespaces, metrics: Metrics) {
+    loop {
+        if let Err(e) = update_states(&client, namespaces, &metrics).await {
+            error!("Unable to count tests and resources: {:?}", e);
+        }
+        tokio::time::sleep(STATE_INTERVAL).await;
+    }
+}
+
+async fn update_states(client: &Client, namespaces: &Namespaces, metrics: &Metrics) -> Result<()> {
+    let mut tests = Vec::new();
+    for api in namespaces.apis(client) {
+        tests.extend(
+            TestClient::new_from_api(api)
+                .get_all()
+                .await
+                .context("Unable to list tests")?,
+        );
+    }
+    let mut resources = Vec::new();
+    for api in namespaces.apis(client) {
+        resources.extend(
+            ResourceClient::new_from_api(api)
+                .get_all()
+                .await
+                .context("Unable to list resources")?,
+        );
+    }
+    metrics.set_states(&tests, &resources);
+    Ok(())
+}
+
+/// Whether the condition of `condition_type` is true in `updated` but was not in `current`. This
+/// is used to observe the moment that an object finished something only once.
+pub(crate) fn became_true(
+    current: &[Condition],
+    updated: &[Condition],
+    condition_type: ConditionType,
+) -> bool {
+    let is_true = |conditions: &[Condition]| {
+        conditions.iter().any(|condition| {
+            condition.condition_type == condition_type && condition.status == ConditionStatus::True
+        })
+    };
+    !is_true(current) && is_true(updated)
+}
+
+/// The start time of the last attempt that the agent of `test` reported.
+fn attempt_start_time(test: &Test) -> Option<Time> {
+    let agent_status = test.agent_status();
+    let start_time = agent_status
+        .results
+        .last()?
+        .attempt
+        .as_ref()?
+        .start_time
+        .as_deref()?;
+    DateTime::parse_from_rfc3339(start_time)
+        .ok()
+        .map(|start_time| Time(start_time.with_timezone(&Utc)))
+}
+
+/// The number of seconds that have passed since `time`.
+fn seconds_since(time: Option<&Time>) -> Option<f64> {
+    (Utc::now() - time?.0)
+        .to_std()
+        .ok()
+        .map(|duration| duration.as_secs_f64())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use testsys_model::{
+        ResourceError, ResourceStatus, TaskState, TestAttempt, TestResults, TestStatus,
+    };
+
+    #[test]
+    fn encode() {
+        let metrics = Metrics::new();
+        assert!(metrics.is_ok());
+        let metrics = match metrics {
+            Ok(metrics) => metrics,
+            Err(_) => return,
+        };
+        let mut resource = Resource {
+            metadata: ObjectMeta {
+                name: Some("cluster".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        resource.spec.agent.image = "cluster-agent".to_string();
+        let mut status = ResourceStatus::default();
+        status.creation.task_state = TaskState::Error;
+        status.creation.error = Some(ResourceError {
+            error: "Unable to delete the cluster".to_string(),
+            error_resources: ErrorResources::Orphaned,
+        });
+        resource.status = Some(status);
+        metrics.resource_failed(&resource);
+        metrics.set_states(&[Test::default()], &[resource]);
+
+        let text = metrics
+            .encode()
+            .map(|buffer| String::from_utf8_lossy(&buffer).to_string())
+            .unwrap_or_default();
+        assert!(text.contains("testsys_orphaned_resources_total{image=\"cluster-agent\"} 1"));
+        assert!(text.contains("testsys_tests{state=\"unknown\"} 1"));
+        assert!(text.contains("testsys_resources{action=\"create\",state=\"error\"} 1"));
+        assert!(text.contains("testsys_resources{action=\"destroy\",state=\"unknown\"} 1"));
+    }
+
+    #[test]
+    fn last_attempt_start_time() {
+        let mut test = Test::default();
+        assert!(attempt_start_time(&test).is_none());
+
+        let attempt = |number: u32, start_time: &str| TestResults {
+            attempt: Some(TestAttempt {
+                number,
+                start_time: Some(start_time.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut status = TestStatus::default();
+        status.agent.results = vec![
+            attempt(1, "2022-01-01T00:00:00Z"),
+            attempt(2, "2022-01-01T00:12:34Z"),
+        ];
+        test.status = Some(status);
+        assert_eq!(
+            attempt_start_time(&test).map(|time| time.0.to_rfc3339()),
+            Some("2022-01-01T00:12:34+00:00".to_string())
+        );
+    }
+}
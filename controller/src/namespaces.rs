@@ -0,0 +1,91 @@
+use k8s_openapi::NamespaceResourceScope;
+use kube::{Api, Client, Resource};
+use testsys_model::constants::NAMESPACE;
+use testsys_model::system::{
+    ALL_NAMESPACES, TESTSYS_CONTROLLER_NAMESPACES, TESTSYS_CONTROLLER_POD_NAMESPACE,
+};
+
+/// The namespace that the controller runs in, which is `testsys` unless the pod says otherwise.
+pub(crate) fn controller_namespace() -> String {
+    std::env::var(TESTSYS_CONTROLLER_POD_NAMESPACE)
+        .ok()
+        .filter(|namespace| !namespace.is_empty())
+        .unwrap_or_else(|| NAMESPACE.to_string())
+}
+
+/// The namespaces whose TestSys objects the controller reconciles. These are read from the
+/// `TESTSYS_CONTROLLER_NAMESPACES` environment variable, which holds a comma-separated list of
+/// namespaces or `*` for all of them. Only the `testsys` namespace is watched when it is not set.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum Namespaces {
+    All,
+    Only(Vec<String>),
+}
+
+impl Namespaces {
+    pub(crate) fn from_env() -> Self {
+        Self::parse(std::env::var(TESTSYS_CONTROLLER_NAMESPACES).ok().as_deref())
+    }
+
+    fn parse(value: Option<&str>) -> Self {
+        let namespaces: Vec<String> = value
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|namespace| !namespace.is_empty())
+            .map(str::to_string)
+            .collect();
+        if namespaces
+            .iter()
+            .any(|namespace| namespace == ALL_NAMESPACES)
+        {
+            Self::All
+        } else if namespaces.is_empty() {
+            Self::Only(vec![NAMESPACE.to_string()])
+        } else {
+            Self::Only(namespaces)
+        }
+    }
+
+    /// The `Api`s to watch and list objects of type `K` with, one for each namespace.
+    pub(crate) fn apis<K>(&self, client: &Client) -> Vec<Api<K>>
+    where
+        K: Resource<Scope = NamespaceResourceScope>,
+        <K as Resource>::DynamicType: Default,
+    {
+        match self {
+            Self::All => vec![Api::all(client.clone())],
+            Self::Only(namespaces) => namespaces
+                .iter()
+                .map(|namespace| Api::namespaced(client.clone(), namespace))
+                .collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Namespaces {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => write!(f, "all namespaces"),
+            Self::Only(namespaces) => write!(f, "namespaces '{}'", namespaces.join("', '")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Namespaces::parse(None),
+            Namespaces::Only(vec![NAMESPACE.to_string()])
+        );
+        assert_eq!(
+            Namespaces::parse(Some("testsys, team-a,")),
+            Namespaces::Only(vec!["testsys".to_string(), "team-a".to_string()])
+        );
+        assert_eq!(Namespaces::parse(Some("*")), Namespaces::All);
+    }
+}
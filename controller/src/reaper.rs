@@ -0,0 +1,425 @@
+use crate::error::Result;
+use crate::events::{warning, EventRecorder};
+use crate::namespaces::Namespaces;
+use crate::utils::{is_finished, parse_duration};
+use anyhow::Context;
+use k8s_openapi::chrono::{DateTime, Duration as ChronoDuration, SecondsFormat, Utc};
+use kube::{Client, ResourceExt};
+use log::{error, info, warn};
+use std::time::Duration;
+use testsys_model::clients::{CrdClient, ResourceClient, TestClient};
+use testsys_model::{
+    set_conditions, Condition, ConditionStatus, ConditionType, CrdExt, Resource, Test,
+};
+
+/// How often the reaper looks for expired objects.
+const REAP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Periodically deletes the `Resource`s that have outlived their `maxLifetime` and the `Test`s
+/// whose `ttlAfterFinished` has passed. Before an object is deleted, the reason is recorded in its
+/// `Expired` condition and in an event. An expired resource that is still used by tests or other
+/// resources is only deleted once they are done with it. Only the objects in the watched
+/// `namespaces` are reaped. An object that cannot be reaped is logged and tried again in the next
+/// pass.
+pub(crate) async fn run_reaper(client: Client, namespaces: &Namespaces) {
+    let reaper = Reaper {
+        client: client.clone(),
+        namespaces: namespaces.clone(),
+        recorder: EventRecorder::new(client),
+    };
+    loop {
+        if let Err(e) = reaper.reap_resources().await {
+            error!("Unable to reap expired resources: {:?}", e);
+        }
+        if let Err(e) = reaper.reap_tests().await {
+            error!("Unable to reap expired tests: {:?}", e);
+        }
+        tokio::time::sleep(REAP_INTERVAL).await;
+    }
+}
+
+/// Why an object has expired.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Expiry {
+    reason: &'static str,
+    message: String,
+}
+
+impl Expiry {
+    fn condition(&self, generation: Option<i64>) -> Condition {
+        Condition::new(
+            ConditionType::Expired,
+            ConditionStatus::True,
+            self.reason,
+            &self.message,
+        )
+        .observed_generation(generation)
+    }
+}
+
+struct Reaper {
+    client: Client,
+    namespaces: Namespaces,
+    recorder: EventRecorder,
+}
+
+impl Reaper {
+    async fn reap_resources(&self) -> Result<()> {
+        let now = Utc::now();
+        let mut resources = Vec::new();
+        let mut tests = Vec::new();
+        for api in self.namespaces.apis(&self.client) {
+            resources.extend(
+                ResourceClient::new_from_api(api)
+                    .get_all()
+                    .await
+                    .context("Unable to list resources")?,
+            );
+        }
+        for api in self.namespaces.apis(&self.client) {
+            tests.extend(
+                TestClient::new_from_api(api)
+                    .get_all()
+                    .await
+                    .context("Unable to list tests")?,
+            );
+        }
+        for resource in &resources {
+            if resource.is_delete_requested() {
+                continue;
+            }
+            if let Some(expiry) = resource_expiry(resource, now) {
+                let users = resource_users(resource, &tests, &resources);
+                let result = if users.is_empty() {
+                    self.reap_resource(resource, expiry).await
+                } else {
+                    self.keep_resource(resource, expiry, &users).await
+                };
+                if let Err(e) = result {
+                    error!("Unable to reap resource '{}': {:?}", resource.name_any(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records in the `Expired` condition of `resource` that it is kept until `users` no longer
+    /// use it.
+    async fn keep_resource(
+        &self,
+        resource: &Resource,
+        expiry: Expiry,
+        users: &[String],
+    ) -> Result<()> {
+        let name = resource.name_any();
+        let expiry = Expiry {
+            message: format!(
+                "{}, it is deleted once it is no longer used by {}",
+                expiry.message,
+                users.join(", ")
+            ),
+            ..expiry
+        };
+        let mut conditions = resource.conditions().to_vec();
+        if resource.status.is_none()
+            || !set_conditions(
+                &mut conditions,
+                vec![expiry.condition(resource.metadata.generation)],
+            )
+        {
+            return Ok(());
+        }
+        info!("Keeping expired resource '{}': {}", name, expiry.message);
+        ResourceClient::new_namespaced(self.client.clone(), resource.object_namespace())
+            .send_conditions(&name, conditions)
+            .await
+            .context(format!("Unable to send conditions for '{}'", name))?;
+        Ok(())
+    }
+
+    async fn reap_resource(&self, resource: &Resource, expiry: Expiry) -> Result<()> {
+        let name = resource.name_any();
+        let resource_client =
+            ResourceClient::new_namespaced(self.client.clone(), resource.object_namespace());
+        info!("Reaping resource '{}': {}", name, expiry.message);
+        if resource.status.is_some() {
+            let mut conditions = resource.conditions().to_vec();
+            set_conditions(
+                &mut conditions,
+                vec![expiry.condition(resource.metadata.generation)],
+            );
+            resource_client
+                .send_conditions(&name, conditions)
+                .await
+                .context(format!("Unable to send conditions for '{}'", name))?;
+        }
+        self.recorder
+            .publish(resource, warning("Reap", expiry.reason, &expiry.message))
+            .await;
+        resource_client
+            .delete(&name)
+            .await
+            .context(format!("Unable to delete resource '{}'", name))?;
+        Ok(())
+    }
+
+    async fn reap_tests(&self) -> Result<()> {
+        let now = Utc::now();
+        let mut tests = Vec::new();
+        for api in self.namespaces.apis(&self.client) {
+            tests.extend(
+                TestClient::new_from_api(api)
+                    .get_all()
+                    .await
+                    .context("Unable to list tests")?,
+            );
+        }
+        for test in tests {
+            if test.is_delete_requested() {
+                continue;
+            }
+            if let Some(expiry) = test_expiry(&test, now) {
+                if let Err(e) = self.reap_test(&test, expiry).await {
+                    error!("Unable to reap test '{}': {:?}", test.name_any(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn reap_test(&self, test: &Test, expiry: Expiry) -> Result<()> {
+        let name = test.name_any();
+        let test_client = TestClient::new_namespaced(self.client.clone(), test.object_namespace());
+        info!("Reaping test '{}': {}", name, expiry.message);
+        let mut conditions = test.conditions().to_vec();
+        set_conditions(
+            &mut conditions,
+            vec![expiry.condition(test.metadata.generation)],
+        );
+        test_client
+            .send_conditions(&name, conditions)
+            .await
+            .context(format!("Unable to send conditions for '{}'", name))?;
+        self.recorder
+            .publish(test, warning("Reap", expiry.reason, &expiry.message))
+            .await;
+        test_client
+            .delete(&name)
+            .await
+            .context(format!("Unable to delete test '{}'", name))?;
+        Ok(())
+    }
+}
+
+/// Why `resource` has expired at `now`, if it has outlived its `maxLifetime`. The destruction of a
+/// resource relies on this being worked out from its spec and creation time alone.
+pub(crate) fn resource_expiry(resource: &Resource, now: DateTime<Utc>) -> Option<Expiry> {
+    let max_lifetime = resource.spec.max_lifetime.as_ref()?;
+    let created = resource.metadata.creation_timestamp.as_ref()?.0;
+    if now < created + chrono_duration(&resource.name_any(), "maxLifetime", max_lifetime)? {
+        return None;
+    }
+    Some(Expiry {
+        reason: "MaxLifetimeExceeded",
+        message: format!(
+            "The resource was created at {} and exceeded its maxLifetime of '{}'",
+            created.to_rfc3339_opts(SecondsFormat::Secs, true),
+            max_lifetime
+        ),
+    })
+}
+
+/// The tests and resources that still use `resource`: the tests in its namespace that have not
+/// finished and use it, directly or through a lease, and the resources that depend on it.
+pub(crate) fn resource_users(
+    resource: &Resource,
+    tests: &[Test],
+    resources: &[Resource],
+) -> Vec<String> {
+    let name = resource.name_any();
+    let namespace = resource.object_namespace();
+    let tests = tests
+        .iter()
+        .filter(|test| test.object_namespace() == namespace && !is_finished(test))
+        .filter(|test| {
+            test.spec.resources.contains(&name)
+                || test
+                    .leased_resources()
+                    .values()
+                    .any(|leased| *leased == name)
+        })
+        .map(|test| format!("test '{}'", test.name_any()));
+    let resources = resources
+        .iter()
+        .filter(|dependent| dependent.object_namespace() == namespace)
+        .filter(|dependent| {
+            dependent
+                .spec
+                .depends_on
+                .iter()
+                .flatten()
+                .any(|needed| *needed == name)
+        })
+        .map(|dependent| format!("resource '{}'", dependent.name_any()));
+    tests.chain(resources).collect()
+}
+
+/// Why `test` has expired at `now`, if its agent finished longer than `ttlAfterFinished` ago. The
+/// test finished when its `Completed` condition became true.
+fn test_expiry(test: &Test, now: DateTime<Utc>) -> Option<Expiry> {
+    let ttl = test.spec.ttl_after_finished.as_ref()?;
+    let completed = test
+        .condition(ConditionType::Completed)
+        .filter(|condition| condition.status == ConditionStatus::True)?;
+    let finished = DateTime::parse_from_rfc3339(&completed.last_transition_time)
+        .ok()?
+        .with_timezone(&Utc);
+    if now < finished + chrono_duration(&test.name_any(), "ttlAfterFinished", ttl)? {
+        return None;
+    }
+    Some(Expiry {
+        reason: "TtlAfterFinishedExpired",
+        message: format!(
+            "The test finished at {} and its ttlAfterFinished of '{}' has passed",
+            completed.last_transition_time, ttl
+        ),
+    })
+}
+
+/// Parses the duration in the `field` of object `name`. Invalid durations are logged and ignored.
+fn chrono_duration(name: &str, field: &str, duration: &str) -> Option<ChronoDuration> {
+    match parse_duration(duration).map(ChronoDuration::from_std) {
+        Ok(Ok(duration)) => Some(duration),
+        _ => {
+            warn!("'{}' has an invalid {} '{}'", name, field, duration);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time};
+    use testsys_model::{AgentStatus, ResourceSpec, TaskState, TestSpec, TestStatus};
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn resources_expire_after_max_lifetime() {
+        let mut resource = Resource {
+            metadata: ObjectMeta {
+                name: Some("cluster".to_string()),
+                creation_timestamp: Some(Time(time("2024-01-01T00:00:00Z"))),
+                ..Default::default()
+            },
+            spec: ResourceSpec::default(),
+            status: None,
+        };
+        let now = time("2024-01-01T12:00:00Z");
+        assert_eq!(resource_expiry(&resource, now), None);
+        resource.spec.max_lifetime = Some("1d".to_string());
+        assert_eq!(resource_expiry(&resource, now), None);
+        resource.spec.max_lifetime = Some("12h".to_string());
+        assert_eq!(
+            resource_expiry(&resource, now),
+            Some(Expiry {
+                reason: "MaxLifetimeExceeded",
+                message: "The resource was created at 2024-01-01T00:00:00Z and exceeded its \
+                          maxLifetime of '12h'"
+                    .to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn expired_resources_in_use() {
+        let resource = |name: &str, depends_on: &[&str]| Resource {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("testsys".to_string()),
+                ..Default::default()
+            },
+            spec: ResourceSpec {
+                depends_on: Some(depends_on.iter().map(ToString::to_string).collect()),
+                ..Default::default()
+            },
+            status: None,
+        };
+        let test = |name: &str, namespace: &str, task_state: TaskState| Test {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some(namespace.to_string()),
+                ..Default::default()
+            },
+            spec: TestSpec {
+                resources: vec!["cluster".to_string()],
+                ..Default::default()
+            },
+            status: Some(TestStatus {
+                agent: AgentStatus {
+                    task_state,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        };
+        let cluster = resource("cluster", &[]);
+        let resources = vec![
+            cluster.clone(),
+            resource("instances", &["cluster"]),
+            resource("other", &[]),
+        ];
+        let tests = vec![
+            test("running", "testsys", TaskState::Running),
+            test("finished", "testsys", TaskState::Completed),
+            test("elsewhere", "other-namespace", TaskState::Running),
+        ];
+        assert_eq!(
+            resource_users(&cluster, &tests, &resources),
+            vec!["test 'running'", "resource 'instances'"]
+        );
+        assert!(resource_users(&resources[1], &tests, &resources).is_empty());
+    }
+
+    #[test]
+    fn tests_expire_after_they_finish() {
+        let mut test = Test {
+            metadata: ObjectMeta {
+                name: Some("sonobuoy".to_string()),
+                ..Default::default()
+            },
+            spec: TestSpec {
+                ttl_after_finished: Some("1h".to_string()),
+                ..Default::default()
+            },
+            status: Some(TestStatus::default()),
+        };
+        let now = time("2024-01-01T12:00:00Z");
+        assert_eq!(test_expiry(&test, now), None);
+        let mut completed = Condition::new(
+            ConditionType::Completed,
+            ConditionStatus::False,
+            "Running",
+            "",
+        );
+        completed.last_transition_time = "2024-01-01T10:00:00Z".to_string();
+        if let Some(status) = test.status.as_mut() {
+            status.conditions.push(completed.clone());
+        }
+        assert_eq!(test_expiry(&test, now), None);
+        completed.status = ConditionStatus::True;
+        if let Some(status) = test.status.as_mut() {
+            status.conditions = vec![completed];
+        }
+        assert_eq!(
+            test_expiry(&test, now).map(|expiry| expiry.reason),
+            Some("TtlAfterFinishedExpired")
+        );
+        assert_eq!(test_expiry(&test, time("2024-01-01T10:30:00Z")), None);
+    }
+}
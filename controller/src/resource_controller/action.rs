@@ -1,15 +1,26 @@
+use crate::admission::{is_admitted, QUEUED, SUSPENDED};
 use crate::error::Result;
-use crate::job::{JobState, TEST_START_TIME_LIMIT};
+use crate::events::{normal, warning};
+use crate::job::{service_account_problem, JobState, TEST_START_TIME_LIMIT};
+use crate::reaper::{resource_expiry, resource_users};
 use crate::resource_controller::context::ResourceInterface;
-use crate::utils::parse_duration;
+use crate::utils::{display_cycle, find_cycle, is_finished, missing_test_message, parse_duration};
+use k8s_openapi::chrono::{DateTime, Utc};
 use kube::core::object::HasSpec;
 use kube::ResourceExt;
+use kube_runtime::events::Event;
 use log::{debug, trace};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
 use testsys_model::clients::{AllowNotFound, CrdClient, TestClient};
 use testsys_model::constants::{
     FINALIZER_CLEANUP_REQUIRED, FINALIZER_CREATION_JOB, FINALIZER_MAIN, FINALIZER_RESOURCE,
 };
-use testsys_model::{CrdExt, DestructionPolicy, ResourceAction, TaskState, TestUserState};
+use testsys_model::system::AdmissionKind;
+use testsys_model::{
+    CrdExt, DestructionPolicy, Resource, ResourceAction, RetryPolicy, TaskState, TestUserState,
+};
 
 /// The action that the controller needs to take in order to reconcile the [`Resource`].
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -27,10 +38,23 @@ pub(super) enum CreationAction {
     StartJob,
     WaitForDependency(String),
     WaitForConflict(String),
+    WaitForTestOutputs(String),
+    /// Wait for a test whose outputs are used but which does not exist.
+    WaitForMissingTest(String),
     WaitForDependent,
     WaitForCreation,
     AddResourceFinalizer,
     Done,
+    /// Remove the failed creation job so that it can be retried.
+    RemoveFailedJob,
+    /// Retry the failed creation task, which failed with the contained error.
+    Retry(String),
+    /// Wait until the contained time to start the creation job again.
+    WaitForRetry(String),
+    /// Wait for room under the concurrency limits, for the contained reason.
+    WaitForAdmission(String),
+    /// Wait for the resource to be resumed before starting the creation job.
+    WaitForResume,
     Error(ErrorState),
 }
 
@@ -45,11 +69,25 @@ pub(super) enum DestructionAction {
     RemoveCleanupFinalizer,
     RemoveResourceFinalizer,
     RemoveMainFinalizer,
+    /// Remove the failed destruction job so that it can be retried.
+    RemoveFailedJob,
+    /// Retry the failed destruction task, which failed with the contained error.
+    Retry(String),
+    /// Wait until the contained time to start the destruction job again.
+    WaitForRetry(String),
+    /// Wait for the resource to be resumed before starting the destruction job.
+    WaitForResume,
+    /// Wait for the contained tests and resources to stop using the expired resource before
+    /// destroying it.
+    WaitForUsers(Vec<String>),
     Error(ErrorState),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub(super) enum ErrorState {
+    /// The resource is waiting for itself through a chain of `depends_on` and `conflicts_with`.
+    /// Contains the names of the resources in the cycle, beginning with this one.
+    DependencyCycle(Vec<String>),
     JobStart,
     JobExited,
     JobFailed,
@@ -57,6 +95,230 @@ pub(super) enum ErrorState {
     JobTimeout,
     TaskFailed,
     Zombie,
+    /// The agent may not run as the service account it asks for.
+    ServiceAccountNotAllowed(String),
+}
+
+impl Display for ErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorState::DependencyCycle(cycle) => write!(
+                f,
+                "Resource depends on itself through the cycle '{}'",
+                display_cycle(cycle)
+            ),
+            ErrorState::JobStart => Display::fmt("Timeout before resource started", f),
+            ErrorState::JobExited => Display::fmt("Container exited before it was done", f),
+            ErrorState::JobFailed => Display::fmt("Container exited with an error", f),
+            ErrorState::JobRemoved => Display::fmt("Container was killed before it was done", f),
+            ErrorState::JobTimeout => Display::fmt("Job did not complete within time limit", f),
+            ErrorState::TaskFailed => Display::fmt("Task failed", f),
+            ErrorState::Zombie => {
+                Display::fmt("Resource still exists after main finalizer was removed", f)
+            }
+            ErrorState::ServiceAccountNotAllowed(problem) => Display::fmt(problem, f),
+        }
+    }
+}
+
+impl ErrorState {
+    /// A CamelCase reason for the error.
+    fn reason(&self) -> &'static str {
+        match self {
+            ErrorState::DependencyCycle(_) => "DependencyCycle",
+            ErrorState::JobStart => "JobStart",
+            ErrorState::JobExited => "JobExited",
+            ErrorState::JobFailed => "JobFailed",
+            ErrorState::JobRemoved => "JobRemoved",
+            ErrorState::JobTimeout => "JobTimeout",
+            ErrorState::TaskFailed => "TaskFailed",
+            ErrorState::Zombie => "Zombie",
+            ErrorState::ServiceAccountNotAllowed(_) => "ServiceAccountNotAllowed",
+        }
+    }
+}
+
+impl Action {
+    /// The k8s `Event` that records the controller taking this action for a resource.
+    pub(super) fn event(&self) -> Event {
+        match self {
+            Action::Creation(action) => action.event(),
+            Action::Destruction(action) => action.event(),
+        }
+    }
+}
+
+impl CreationAction {
+    fn event(&self) -> Event {
+        match self {
+            CreationAction::Initialize => {
+                normal("Initialize", "Created", "Initialized the resource status")
+            }
+            CreationAction::AddMainFinalizer => {
+                normal("AddMainFinalizer", "Created", "Added the main finalizer")
+            }
+            CreationAction::AddJobFinalizer => normal(
+                "AddJobFinalizer",
+                "Creating",
+                "Added the creation job finalizer",
+            ),
+            CreationAction::AddCleanupFinalizer => normal(
+                "AddCleanupFinalizer",
+                "Creating",
+                "Added the cleanup finalizer",
+            ),
+            CreationAction::StartJob => normal("StartJob", "Creating", "Started the creation job"),
+            CreationAction::WaitForAdmission(reason) => normal("WaitForAdmission", QUEUED, reason),
+            CreationAction::WaitForResume => normal(
+                "WaitForResume",
+                SUSPENDED,
+                "The resource is suspended, its creation job starts once it is resumed",
+            ),
+            CreationAction::WaitForDependency(dependency) => normal(
+                "WaitForDependency",
+                "WaitingForDependency",
+                format!("Waiting for resource '{}' to be created", dependency),
+            ),
+            CreationAction::WaitForConflict(conflict) => normal(
+                "WaitForConflict",
+                "WaitingForConflict",
+                format!(
+                    "Waiting for conflicting resource '{}' to be deleted",
+                    conflict
+                ),
+            ),
+            CreationAction::WaitForTestOutputs(needed) => normal(
+                "WaitForTestOutputs",
+                "WaitingForTestOutputs",
+                format!(
+                    "Waiting for test '{}' to complete and publish its outputs",
+                    needed
+                ),
+            ),
+            CreationAction::WaitForMissingTest(needed) => warning(
+                "WaitForMissingTest",
+                "TestNotFound",
+                missing_test_message(needed),
+            ),
+            CreationAction::WaitForDependent => normal(
+                "WaitForDependent",
+                "WaitingForDependent",
+                "Waiting for a test that requires the resource",
+            ),
+            CreationAction::WaitForCreation => normal(
+                "WaitForCreation",
+                "Creating",
+                "Waiting for the resource agent to create the resource",
+            ),
+            CreationAction::AddResourceFinalizer => normal(
+                "AddResourceFinalizer",
+                "Created",
+                "Added the resource finalizer",
+            ),
+            CreationAction::Done => normal("Done", "Created", "The resource has been created"),
+            CreationAction::RemoveFailedJob => normal(
+                "RemoveFailedJob",
+                "Retrying",
+                "Removed the failed creation job",
+            ),
+            CreationAction::Retry(error) => warning(
+                "Retry",
+                "Retrying",
+                format!("Retrying the failed creation task: {}", error),
+            ),
+            CreationAction::WaitForRetry(retry_after) => normal(
+                "WaitForRetry",
+                "WaitingToRetry",
+                format!(
+                    "Waiting until {} to start the creation job again",
+                    retry_after
+                ),
+            ),
+            CreationAction::Error(state) => warning("Error", state.reason(), state.to_string()),
+        }
+    }
+}
+
+impl DestructionAction {
+    fn event(&self) -> Event {
+        match self {
+            DestructionAction::StartResourceDeletion => normal(
+                "StartResourceDeletion",
+                "Deleting",
+                "Deleting the resource according to its destruction policy",
+            ),
+            DestructionAction::RemoveCreationJob => {
+                normal("RemoveCreationJob", "Deleting", "Removed the creation job")
+            }
+            DestructionAction::RemoveCreationJobFinalizer => normal(
+                "RemoveCreationJobFinalizer",
+                "Deleting",
+                "Removed the creation job finalizer",
+            ),
+            DestructionAction::WaitForResume => normal(
+                "WaitForResume",
+                SUSPENDED,
+                "The resource is suspended, its destruction job starts once it is resumed",
+            ),
+            DestructionAction::StartDestructionJob => normal(
+                "StartDestructionJob",
+                "Destroying",
+                "Started the destruction job",
+            ),
+            DestructionAction::Wait => normal(
+                "Wait",
+                "Destroying",
+                "Waiting for the resource agent to destroy the resource",
+            ),
+            DestructionAction::RemoveDestructionJob => normal(
+                "RemoveDestructionJob",
+                "Deleting",
+                "Removed the destruction job",
+            ),
+            DestructionAction::RemoveCleanupFinalizer => normal(
+                "RemoveCleanupFinalizer",
+                "Deleting",
+                "Removed the cleanup finalizer",
+            ),
+            DestructionAction::RemoveResourceFinalizer => normal(
+                "RemoveResourceFinalizer",
+                "Deleting",
+                "Removed the resource finalizer",
+            ),
+            DestructionAction::RemoveMainFinalizer => normal(
+                "RemoveMainFinalizer",
+                "Deleted",
+                "Removed the main finalizer",
+            ),
+            DestructionAction::RemoveFailedJob => normal(
+                "RemoveFailedJob",
+                "Retrying",
+                "Removed the failed destruction job",
+            ),
+            DestructionAction::Retry(error) => warning(
+                "Retry",
+                "Retrying",
+                format!("Retrying the failed destruction task: {}", error),
+            ),
+            DestructionAction::WaitForRetry(retry_after) => normal(
+                "WaitForRetry",
+                "WaitingToRetry",
+                format!(
+                    "Waiting until {} to start the destruction job again",
+                    retry_after
+                ),
+            ),
+            DestructionAction::WaitForUsers(users) => normal(
+                "WaitForUsers",
+                "WaitingForUsers",
+                format!(
+                    "The resource has expired and is destroyed once it is no longer used by {}",
+                    users.join(", ")
+                ),
+            ),
+            DestructionAction::Error(state) => warning("Error", state.reason(), state.to_string()),
+        }
+    }
 }
 
 pub(super) async fn action(r: &ResourceInterface) -> Result<Action> {
@@ -77,10 +339,14 @@ async fn creation_action(r: &ResourceInterface) -> Result<CreationAction> {
     }
 
     if let Some(wait_action) = dependency_wait_action(r).await? {
-        return Ok(wait_action);
+        return deadlock_action(r, wait_action).await;
     }
 
     if let Some(wait_action) = conflicting_wait_action(r).await? {
+        return deadlock_action(r, wait_action).await;
+    }
+
+    if let Some(wait_action) = outputs_wait_action(r).await? {
         return Ok(wait_action);
     }
 
@@ -93,8 +359,76 @@ async fn creation_action(r: &ResourceInterface) -> Result<CreationAction> {
         TaskState::Unknown => creation_not_done_action(r, false).await,
         TaskState::Running => creation_not_done_action(r, true).await,
         TaskState::Completed => creation_completed_action(r).await,
-        TaskState::Error => Ok(CreationAction::Error(ErrorState::TaskFailed)),
+        TaskState::Error => Ok(match retry_step(r, ResourceAction::Create).await? {
+            Some(RetryStep::RemoveFailedJob) => CreationAction::RemoveFailedJob,
+            Some(RetryStep::Retry(error)) => CreationAction::Retry(error),
+            None => CreationAction::Error(ErrorState::TaskFailed),
+        }),
+    }
+}
+
+/// The steps taken to rerun a failed creation or destruction task.
+enum RetryStep {
+    RemoveFailedJob,
+    Retry(String),
+}
+
+/// If the resource's `RetryPolicy` allows the failed task to be rerun, returns the next step. The
+/// failed job is removed first since the new job has the same name.
+async fn retry_step(r: &ResourceInterface, op: ResourceAction) -> Result<Option<RetryStep>> {
+    if !r.resource().is_retrying(op) {
+        return Ok(None);
     }
+    if !matches!(r.get_job_state(op).await?, JobState::None) {
+        return Ok(Some(RetryStep::RemoveFailedJob));
+    }
+    let error = r
+        .resource()
+        .error(op)
+        .map(|error| error.error.clone())
+        .unwrap_or_default();
+    Ok(Some(RetryStep::Retry(error)))
+}
+
+/// How long to wait before rerunning a task that has already been retried `retries` times. The
+/// wait starts at `backoff` and doubles with each retry, up to `max_backoff`.
+pub(super) fn backoff(retry_policy: &RetryPolicy, retries: u32) -> Duration {
+    let duration = |duration: &Option<String>, default: &str| {
+        duration
+            .as_deref()
+            .and_then(|duration| parse_duration(duration).ok())
+            .or_else(|| parse_duration(default).ok())
+            .unwrap_or_default()
+    };
+    let backoff = duration(&retry_policy.backoff, RetryPolicy::DEFAULT_BACKOFF);
+    let max_backoff = duration(&retry_policy.max_backoff, RetryPolicy::DEFAULT_MAX_BACKOFF);
+    backoff
+        .checked_mul(2u32.saturating_pow(retries))
+        .unwrap_or(max_backoff)
+        .min(max_backoff)
+}
+
+/// The creation job only starts once there is room for the resource under the controller's
+/// concurrency limits. A resource that has been admitted keeps its place, e.g. while it is retried.
+async fn admission_wait_action(r: &ResourceInterface) -> Result<Option<CreationAction>> {
+    if is_admitted(r.resource().conditions()) {
+        return Ok(None);
+    }
+    Ok(r.admission()
+        .wait_reason(AdmissionKind::Resource, r.namespace(), r.name())
+        .await?
+        .map(CreationAction::WaitForAdmission))
+}
+
+/// The time before which a retried task must not start its job, if it has not passed yet.
+fn retry_wait(resource: &Resource, op: ResourceAction, now: DateTime<Utc>) -> Option<String> {
+    let state = match op {
+        ResourceAction::Create => &resource.status.as_ref()?.creation,
+        ResourceAction::Destroy => &resource.status.as_ref()?.destruction,
+    };
+    let retry_after = state.retry_after.as_ref()?;
+    let time = DateTime::parse_from_rfc3339(retry_after).ok()?;
+    (now < time).then(|| retry_after.clone())
 }
 
 async fn dependency_wait_action(r: &ResourceInterface) -> Result<Option<CreationAction>> {
@@ -108,7 +442,6 @@ async fn dependency_wait_action(r: &ResourceInterface) -> Result<Option<Creation
     };
 
     // Make sure each resource in depends_on is ready.
-    // TODO - error if cyclical dependencies https://github.com/bottlerocket-os/bottlerocket-test-system/issues/156
     for needed in depends_on {
         let needed_resource = r.resource_client().get(needed).await?;
         if needed_resource.created_resource().is_none() {
@@ -120,6 +453,27 @@ async fn dependency_wait_action(r: &ResourceInterface) -> Result<Option<Creation
     Ok(None)
 }
 
+/// The configuration of the resource can only be resolved once the tests whose outputs it uses have
+/// completed. This is only checked before the creation job starts. A test that ends in an error is
+/// not waited for, so that the resource agent reports that the outputs are unavailable.
+async fn outputs_wait_action(r: &ResourceInterface) -> Result<Option<CreationAction>> {
+    if r.resource().creation_task_state() != TaskState::Unknown {
+        return Ok(None);
+    }
+    let test_client = TestClient::new_namespaced(r.k8s_client(), r.namespace());
+    for needed in r.resource().spec.agent.referenced_tests() {
+        let test = test_client.get(&needed).await.allow_not_found(|_| ())?;
+        match test {
+            None => return Ok(Some(CreationAction::WaitForMissingTest(needed))),
+            Some(test) if !is_finished(&test) => {
+                return Ok(Some(CreationAction::WaitForTestOutputs(needed)))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(None)
+}
+
 async fn conflicting_wait_action(r: &ResourceInterface) -> Result<Option<CreationAction>> {
     let conflicts_with = if let Some(conflicts_with) = &r.resource().spec.conflicts_with {
         if conflicts_with.is_empty() {
@@ -144,7 +498,61 @@ async fn conflicting_wait_action(r: &ResourceInterface) -> Result<Option<Creatio
     Ok(None)
 }
 
+/// Resources that wait for each other through `depends_on` and `conflicts_with` will never be
+/// created. Returns an error if this resource is part of such a cycle, otherwise `wait_action`.
+async fn deadlock_action(
+    r: &ResourceInterface,
+    wait_action: CreationAction,
+) -> Result<CreationAction> {
+    let resources = r.resource_client().get_all().await?;
+    Ok(match find_cycle(r.name(), &waiting_for(&resources)) {
+        Some(cycle) => CreationAction::Error(ErrorState::DependencyCycle(cycle)),
+        None => wait_action,
+    })
+}
+
+/// Maps the name of each resource that has not been created to the resources it is waiting for:
+/// dependencies that have not been created and conflicting resources that are not being deleted.
+fn waiting_for(resources: &[Resource]) -> BTreeMap<String, Vec<String>> {
+    let not_created: BTreeSet<&str> = resources
+        .iter()
+        .filter(|resource| resource.created_resource().is_none())
+        .map(|resource| resource.object_name())
+        .collect();
+    let not_deleting: BTreeSet<&str> = resources
+        .iter()
+        .filter(|resource| !resource.is_delete_requested())
+        .map(|resource| resource.object_name())
+        .collect();
+    resources
+        .iter()
+        .filter(|resource| not_created.contains(resource.object_name()))
+        .map(|resource| {
+            let dependencies = resource
+                .spec
+                .depends_on
+                .iter()
+                .flatten()
+                .filter(|name| not_created.contains(name.as_str()));
+            let conflicts = resource
+                .spec
+                .conflicts_with
+                .iter()
+                .flatten()
+                .filter(|name| not_deleting.contains(name.as_str()));
+            (
+                resource.object_name().to_string(),
+                dependencies.chain(conflicts).cloned().collect(),
+            )
+        })
+        .collect()
+}
+
 async fn dependent_wait_action(r: &ResourceInterface) -> Result<Option<CreationAction>> {
+    // Members of a pool are created ahead of the tests that will lease them.
+    if r.resource().pool().is_some() {
+        return Ok(None);
+    }
     // If any resources still depend on this resource it should not be deleted.
     let resources = r.resource_client().get_all().await?;
     for resource in resources {
@@ -154,7 +562,7 @@ async fn dependent_wait_action(r: &ResourceInterface) -> Result<Option<CreationA
             }
         }
     }
-    let test_client = TestClient::new_from_k8s_client(r.k8s_client());
+    let test_client = TestClient::new_namespaced(r.k8s_client(), r.namespace());
     let tests = test_client.get_all().await?;
     for test in tests {
         if test.spec().resources.contains(&r.name().to_string()) {
@@ -176,7 +584,23 @@ async fn creation_not_done_action(
     }
     let job_state = r.get_job_state(ResourceAction::Create).await?;
     match job_state {
-        JobState::None if !is_task_state_running => Ok(CreationAction::StartJob),
+        JobState::None if !is_task_state_running => {
+            if r.resource().spec.suspend {
+                return Ok(CreationAction::WaitForResume);
+            }
+            if let Some(problem) = service_account_problem(&r.resource().spec.agent) {
+                return Ok(CreationAction::Error(ErrorState::ServiceAccountNotAllowed(
+                    problem,
+                )));
+            }
+            if let Some(retry_after) = retry_wait(r.resource(), ResourceAction::Create, Utc::now())
+            {
+                return Ok(CreationAction::WaitForRetry(retry_after));
+            }
+            Ok(admission_wait_action(r)
+                .await?
+                .unwrap_or(CreationAction::StartJob))
+        }
         JobState::None => Ok(CreationAction::Error(ErrorState::JobRemoved)),
         JobState::Unknown => Ok(CreationAction::WaitForCreation),
         JobState::Running(None) => Ok(CreationAction::WaitForCreation),
@@ -222,6 +646,8 @@ async fn is_deletion_required(r: &ResourceInterface) -> Result<bool> {
         destruction_policy,
         DestructionPolicy::OnTestCompletion | DestructionPolicy::OnTestSuccess
     ) || r.resource().created_resource().is_none()
+        // The pool decides when its members are deleted.
+        || r.resource().pool().is_some()
     {
         return Ok(false);
     }
@@ -234,7 +660,7 @@ async fn is_deletion_required(r: &ResourceInterface) -> Result<bool> {
             }
         }
     }
-    let test_client = TestClient::new_from_k8s_client(r.k8s_client());
+    let test_client = TestClient::new_namespaced(r.k8s_client(), r.namespace());
     let tests = test_client.get_all().await?;
     for test in tests {
         if test.spec().resources.contains(&r.name().to_string()) {
@@ -288,6 +714,19 @@ async fn destruction_action_with_resources(r: &ResourceInterface) -> Result<Dest
         DestructionPolicy::OnDeletion
         | DestructionPolicy::OnTestCompletion
         | DestructionPolicy::OnTestSuccess => { /* Ok, we are in the right place, continue */ }
+        // A resource that outlived its `maxLifetime` is destroyed regardless of its policy, once no
+        // tests or resources use it. This is worked out from its spec rather than its `Expired`
+        // condition, which can be overwritten.
+        DestructionPolicy::Never if resource_expiry(r.resource(), Utc::now()).is_some() => {
+            let resources = r.resource_client().get_all().await?;
+            let tests = TestClient::new_namespaced(r.k8s_client(), r.namespace())
+                .get_all()
+                .await?;
+            let users = resource_users(r.resource(), &tests, &resources);
+            if !users.is_empty() {
+                return Ok(DestructionAction::WaitForUsers(users));
+            }
+        }
         DestructionPolicy::Never => {
             // We will not be running a destruction job so remove the resource finalizer to proceed
             // with object deletion.
@@ -318,7 +757,11 @@ async fn destruction_action_with_resources(r: &ResourceInterface) -> Result<Dest
                 Ok(DestructionAction::RemoveResourceFinalizer)
             }
         }
-        TaskState::Error => Ok(DestructionAction::Error(ErrorState::TaskFailed)),
+        TaskState::Error => Ok(match retry_step(r, ResourceAction::Destroy).await? {
+            Some(RetryStep::RemoveFailedJob) => DestructionAction::RemoveFailedJob,
+            Some(RetryStep::Retry(error)) => DestructionAction::Retry(error),
+            None => DestructionAction::Error(ErrorState::TaskFailed),
+        }),
     }
 }
 
@@ -328,7 +771,20 @@ async fn destruction_not_done_action(
 ) -> Result<DestructionAction> {
     let job_state = r.get_job_state(ResourceAction::Destroy).await?;
     match job_state {
-        JobState::None if !is_task_state_running => Ok(DestructionAction::StartDestructionJob),
+        JobState::None if !is_task_state_running && r.resource().spec.suspend => {
+            Ok(DestructionAction::WaitForResume)
+        }
+        JobState::None if !is_task_state_running => Ok(
+            match retry_wait(r.resource(), ResourceAction::Destroy, Utc::now()) {
+                Some(retry_after) => DestructionAction::WaitForRetry(retry_after),
+                None => match service_account_problem(&r.resource().spec.agent) {
+                    Some(problem) => {
+                        DestructionAction::Error(ErrorState::ServiceAccountNotAllowed(problem))
+                    }
+                    None => DestructionAction::StartDestructionJob,
+                },
+            },
+        ),
         JobState::None => Ok(DestructionAction::Error(ErrorState::JobRemoved)),
         JobState::Unknown => Ok(DestructionAction::Wait),
         JobState::Running(None) => Ok(DestructionAction::Wait),
@@ -369,3 +825,108 @@ async fn destruction_action_without_resources(r: &ResourceInterface) -> Result<D
         Ok(DestructionAction::Error(ErrorState::Zombie))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::{find_cycle, names};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time};
+    use serde_json::Map;
+    use testsys_model::{ResourceSpec, ResourceStatus};
+
+    fn resource(name: &str, depends_on: &[&str], conflicts_with: &[&str]) -> Resource {
+        Resource {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            spec: ResourceSpec {
+                depends_on: Some(names(depends_on)),
+                conflicts_with: Some(names(conflicts_with)),
+                ..Default::default()
+            },
+            status: None,
+        }
+    }
+
+    fn created(mut resource: Resource) -> Resource {
+        resource.status = Some(ResourceStatus {
+            created_resource: Some(Map::new()),
+            ..Default::default()
+        });
+        resource
+    }
+
+    #[test]
+    fn dependency_cycle() {
+        let resources = vec![
+            resource("a", &["b"], &[]),
+            resource("b", &["c"], &[]),
+            resource("c", &["a"], &[]),
+            resource("d", &["a"], &[]),
+        ];
+        let edges = waiting_for(&resources);
+        assert_eq!(find_cycle("b", &edges), Some(names(&["b", "c", "a"])));
+        assert!(find_cycle("d", &edges).is_none());
+    }
+
+    #[test]
+    fn conflict_cycle() {
+        let resources = vec![
+            resource("a", &[], &["b"]),
+            resource("b", &["c"], &[]),
+            resource("c", &[], &["a"]),
+        ];
+        assert_eq!(
+            find_cycle("a", &waiting_for(&resources)),
+            Some(names(&["a", "b", "c"]))
+        );
+    }
+
+    #[test]
+    fn no_cycle_through_created_or_deleted() {
+        // `b` was created before `a` existed, so only `a` is waiting.
+        let resources = vec![
+            resource("a", &[], &["b"]),
+            created(resource("b", &[], &["a"])),
+        ];
+        assert!(find_cycle("a", &waiting_for(&resources)).is_none());
+        let mut deleting = resource("b", &[], &["a"]);
+        deleting.metadata.deletion_timestamp = Some(Time(k8s_openapi::chrono::Utc::now()));
+        let resources = vec![resource("a", &[], &["b"]), deleting];
+        assert!(find_cycle("a", &waiting_for(&resources)).is_none());
+    }
+
+    #[test]
+    fn retry_backoff() {
+        let retry_policy = RetryPolicy::default();
+        assert_eq!(backoff(&retry_policy, 0), Duration::from_secs(30));
+        assert_eq!(backoff(&retry_policy, 2), Duration::from_secs(120));
+        assert_eq!(backoff(&retry_policy, 5), Duration::from_secs(600));
+        assert_eq!(backoff(&retry_policy, u32::MAX), Duration::from_secs(600));
+        let retry_policy = RetryPolicy {
+            backoff: Some("1m".to_string()),
+            max_backoff: Some("1h".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(backoff(&retry_policy, 1), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn retry_after() {
+        let mut resource = created(resource("a", &[], &[]));
+        let now = Utc::now();
+        assert!(retry_wait(&resource, ResourceAction::Destroy, now).is_none());
+        let later = (now + k8s_openapi::chrono::Duration::seconds(30)).to_rfc3339();
+        if let Some(status) = resource.status.as_mut() {
+            status.destruction.retry_after = Some(later.clone());
+        }
+        assert_eq!(
+            retry_wait(&resource, ResourceAction::Destroy, now),
+            Some(later)
+        );
+        assert!(retry_wait(&resource, ResourceAction::Create, now).is_none());
+        let after = now + k8s_openapi::chrono::Duration::seconds(60);
+        assert!(retry_wait(&resource, ResourceAction::Destroy, after).is_none());
+    }
+}
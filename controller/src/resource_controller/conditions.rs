@@ -0,0 +1,255 @@
+use crate::admission::{is_admitted, QUEUED, SUSPENDED};
+use crate::error::Result;
+use crate::metrics::became_true;
+use crate::resource_controller::action::{Action, CreationAction, ErrorState};
+use crate::resource_controller::context::ResourceInterface;
+use crate::utils::missing_test_message;
+use anyhow::Context;
+use testsys_model::{
+    set_conditions, Condition, ConditionType, Resource, ResourceAction, TaskState,
+};
+
+/// Updates the `conditions` of the resource to reflect its state and the `action` that is about to
+/// be taken. The status is only patched if a condition has changed.
+pub(super) async fn update_conditions(r: &ResourceInterface, action: &Action) -> Result<()> {
+    if r.resource().status.is_none() {
+        return Ok(());
+    }
+    let mut conditions = r.resource().conditions().to_vec();
+    if !set_conditions(&mut conditions, condition_updates(r.resource(), action)) {
+        return Ok(());
+    }
+    let created = became_true(r.resource().conditions(), &conditions, ConditionType::Ready);
+    let failed = became_true(
+        r.resource().conditions(),
+        &conditions,
+        ConditionType::Failed,
+    );
+    r.resource_client()
+        .send_conditions(r.name(), conditions)
+        .await
+        .with_context(|| format!("Unable to send conditions for '{}'", r.name()))?;
+    if created {
+        r.metrics().resource_created(r.resource());
+    }
+    if failed {
+        r.metrics().resource_failed(r.resource());
+    }
+    Ok(())
+}
+
+/// The conditions implied by the state of `resource` and the `action` the controller is taking.
+/// `DependenciesMet` is only updated by the actions that follow the dependency and conflict checks.
+pub(super) fn condition_updates(resource: &Resource, action: &Action) -> Vec<Condition> {
+    let generation = resource.metadata.generation;
+    let condition = |condition_type, status: bool, reason: &str, message: &str| {
+        Condition::new(condition_type, status.into(), reason, message)
+            .observed_generation(generation)
+    };
+    let mut updates = Vec::new();
+    match action {
+        Action::Creation(CreationAction::WaitForDependency(dependency)) => updates.push(condition(
+            ConditionType::DependenciesMet,
+            false,
+            "WaitingForDependency",
+            &format!("Waiting for resource '{}' to be created", dependency),
+        )),
+        Action::Creation(CreationAction::WaitForConflict(conflict)) => updates.push(condition(
+            ConditionType::DependenciesMet,
+            false,
+            "WaitingForConflict",
+            &format!(
+                "Waiting for conflicting resource '{}' to be deleted",
+                conflict
+            ),
+        )),
+        Action::Creation(CreationAction::WaitForTestOutputs(needed)) => updates.push(condition(
+            ConditionType::DependenciesMet,
+            false,
+            "WaitingForTestOutputs",
+            &format!("Waiting for test '{}' to complete", needed),
+        )),
+        Action::Creation(CreationAction::WaitForMissingTest(needed)) => updates.push(condition(
+            ConditionType::DependenciesMet,
+            false,
+            "TestNotFound",
+            &missing_test_message(needed),
+        )),
+        Action::Creation(CreationAction::Error(state @ ErrorState::DependencyCycle(_))) => updates
+            .push(condition(
+                ConditionType::DependenciesMet,
+                false,
+                "DependencyCycle",
+                &state.to_string(),
+            )),
+        Action::Creation(
+            CreationAction::AddJobFinalizer
+            | CreationAction::AddCleanupFinalizer
+            | CreationAction::StartJob
+            | CreationAction::WaitForDependent
+            | CreationAction::WaitForCreation
+            | CreationAction::AddResourceFinalizer
+            | CreationAction::Done
+            | CreationAction::RemoveFailedJob
+            | CreationAction::Retry(_)
+            | CreationAction::WaitForRetry(_)
+            | CreationAction::WaitForAdmission(_)
+            | CreationAction::WaitForResume,
+        ) => updates.push(condition(
+            ConditionType::DependenciesMet,
+            true,
+            "DependenciesMet",
+            "",
+        )),
+        _ => {}
+    }
+    // A resource keeps its place under the concurrency limits until it has been destroyed.
+    match action {
+        Action::Creation(CreationAction::WaitForAdmission(reason)) => {
+            updates.push(condition(ConditionType::Admitted, false, QUEUED, reason))
+        }
+        Action::Creation(CreationAction::StartJob) => {
+            updates.push(condition(ConditionType::Admitted, true, "Admitted", ""))
+        }
+        Action::Creation(CreationAction::Error(_)) => {
+            updates.push(condition(ConditionType::Admitted, false, "Failed", ""))
+        }
+        Action::Creation(CreationAction::WaitForResume) if !is_admitted(resource.conditions()) => {
+            updates.push(condition(ConditionType::Admitted, false, SUSPENDED, ""))
+        }
+        Action::Creation(
+            CreationAction::WaitForDependency(_)
+            | CreationAction::WaitForConflict(_)
+            | CreationAction::WaitForTestOutputs(_)
+            | CreationAction::WaitForMissingTest(_)
+            | CreationAction::WaitForDependent,
+        ) if !is_admitted(resource.conditions()) => {
+            updates.push(condition(ConditionType::Admitted, false, "NotReady", ""))
+        }
+        _ => {}
+    }
+
+    let deleting = matches!(action, Action::Destruction(_));
+    // A task that will be retried has not failed yet.
+    let retrying = resource.is_retrying(ResourceAction::Create)
+        || resource.is_retrying(ResourceAction::Destroy);
+    let creation_state = resource.creation_task_state();
+    let error = resource
+        .creation_error()
+        .or_else(|| resource.destruction_error())
+        .map(|error| error.error.as_str());
+    let reason = if deleting {
+        "Deleting"
+    } else if retrying {
+        "Retrying"
+    } else if error.is_some() {
+        "Error"
+    } else {
+        match creation_state {
+            TaskState::Unknown => "Waiting",
+            TaskState::Running => "Creating",
+            TaskState::Completed => "Created",
+            TaskState::Error => "Error",
+        }
+    };
+    let message = error.unwrap_or_default();
+    updates.push(condition(
+        ConditionType::Ready,
+        !deleting && creation_state == TaskState::Completed && error.is_none(),
+        reason,
+        message,
+    ));
+    updates.push(condition(
+        ConditionType::Completed,
+        !retrying && matches!(creation_state, TaskState::Completed | TaskState::Error),
+        reason,
+        message,
+    ));
+    updates.push(condition(
+        ConditionType::Failed,
+        !retrying && (error.is_some() || creation_state == TaskState::Error),
+        reason,
+        message,
+    ));
+    updates
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use testsys_model::{ConditionStatus, ResourceStatus};
+
+    fn status(updates: &[Condition], condition_type: ConditionType) -> Option<ConditionStatus> {
+        updates
+            .iter()
+            .find(|condition| condition.condition_type == condition_type)
+            .map(|condition| condition.status)
+    }
+
+    #[test]
+    fn waiting_for_conflict() {
+        let resource = Resource {
+            status: Some(ResourceStatus::default()),
+            ..Default::default()
+        };
+        let updates = condition_updates(
+            &resource,
+            &Action::Creation(CreationAction::WaitForConflict("other".to_string())),
+        );
+        assert_eq!(
+            status(&updates, ConditionType::DependenciesMet),
+            Some(ConditionStatus::False)
+        );
+        assert_eq!(
+            status(&updates, ConditionType::Ready),
+            Some(ConditionStatus::False)
+        );
+    }
+
+    #[test]
+    fn created() {
+        let mut resource_status = ResourceStatus::default();
+        resource_status.creation.task_state = TaskState::Completed;
+        let resource = Resource {
+            status: Some(resource_status),
+            ..Default::default()
+        };
+        let updates = condition_updates(&resource, &Action::Creation(CreationAction::Done));
+        for (condition_type, expected) in [
+            (ConditionType::DependenciesMet, ConditionStatus::True),
+            (ConditionType::Ready, ConditionStatus::True),
+            (ConditionType::Completed, ConditionStatus::True),
+            (ConditionType::Failed, ConditionStatus::False),
+        ] {
+            assert_eq!(status(&updates, condition_type), Some(expected));
+        }
+    }
+
+    #[test]
+    fn admitted_resources_stay_admitted() {
+        let mut resource_status = ResourceStatus::default();
+        resource_status.conditions.push(Condition::new(
+            ConditionType::Admitted,
+            ConditionStatus::True,
+            "Admitted",
+            "",
+        ));
+        let resource = Resource {
+            status: Some(resource_status),
+            ..Default::default()
+        };
+        let updates = condition_updates(
+            &resource,
+            &Action::Creation(CreationAction::WaitForDependent),
+        );
+        assert_eq!(status(&updates, ConditionType::Admitted), None);
+        let updates = condition_updates(
+            &Resource::default(),
+            &Action::Creation(CreationAction::WaitForAdmission("full".to_string())),
+        );
+        assert_eq!(
+            status(&updates, ConditionType::Admitted),
+            Some(ConditionStatus::False)
+        );
+    }
+}
@@ -1,7 +1,9 @@
+use crate::admission::Admission;
 use crate::error::Result;
+use crate::events::EventRecorder;
 use crate::job::{archive_logs, delete_job, get_job_state, JobBuilder, JobState, JobType};
+use crate::metrics::Metrics;
 use anyhow::Context as AnyhowContext;
-use kube::Api;
 use log::{debug, error};
 use std::sync::Arc;
 use testsys_model::clients::{CrdClient, ResourceClient};
@@ -13,28 +15,29 @@ use testsys_model::{CrdExt, Resource, ResourceAction};
 /// called.
 pub(super) type Context = Arc<ContextData>;
 
-pub(super) fn new_context(client: kube::Client) -> Context {
+pub(super) fn new_context(client: kube::Client, metrics: Metrics, admission: Admission) -> Context {
     Arc::new(ContextData {
-        resource_client: ResourceClient::new_from_k8s_client(client),
+        admission,
+        client: client.clone(),
+        events: EventRecorder::new(client),
+        metrics,
     })
 }
 
 /// This type is wrapped by [`kube::Context`] and contains information we need during [`reconcile`].
-#[derive(Clone)]
 pub(crate) struct ContextData {
-    resource_client: ResourceClient,
-}
-
-impl ContextData {
-    pub(super) fn api(&self) -> &Api<Resource> {
-        self.resource_client.api()
-    }
+    admission: Admission,
+    client: kube::Client,
+    events: EventRecorder,
+    metrics: Metrics,
 }
 
 /// The [`reconcile`] function has [`Resource`] and [`Context`] as its inputs. For convenience, we
 /// combine these and provide accessor and helper functions.
 pub(super) struct ResourceInterface {
     resource: Resource,
+    /// A client for the namespace of the resource.
+    resource_client: ResourceClient,
     context: Context,
     creation_job: String,
     destruction_job: String,
@@ -44,8 +47,11 @@ impl ResourceInterface {
     pub(super) fn new(resource: Resource, context: Context) -> Result<Self> {
         let creation_job = resource.job_name(ResourceState::Creation);
         let destruction_job = resource.job_name(ResourceState::Destruction);
+        let resource_client =
+            ResourceClient::new_namespaced(context.client.clone(), resource.object_namespace());
         Ok(Self {
             resource,
+            resource_client,
             context,
             creation_job,
             destruction_job,
@@ -60,16 +66,31 @@ impl ResourceInterface {
         &self.resource
     }
 
-    pub(super) fn api(&self) -> &Api<Resource> {
-        self.context.api()
+    /// The namespace of the resource, which its agent jobs and the tests that use it are in too.
+    pub(super) fn namespace(&self) -> &str {
+        self.resource.object_namespace()
     }
 
     pub(super) fn resource_client(&self) -> &ResourceClient {
-        &self.context.resource_client
+        &self.resource_client
+    }
+
+    /// Publishes events for the resource.
+    pub(super) fn events(&self) -> &EventRecorder {
+        &self.context.events
+    }
+
+    pub(super) fn metrics(&self) -> &Metrics {
+        &self.context.metrics
+    }
+
+    /// Decides when the agent job may start under the concurrency limits.
+    pub(super) fn admission(&self) -> &Admission {
+        &self.context.admission
     }
 
     pub(super) fn k8s_client(&self) -> kube::Client {
-        self.api().clone().into_client()
+        self.context.client.clone()
     }
 
     pub(super) async fn get_job_state(&self, op: ResourceAction) -> Result<JobState> {
@@ -80,6 +101,7 @@ impl ResourceInterface {
         let job_name = self.job_name(op);
         let deploy_result = JobBuilder {
             agent: &self.resource().spec.agent,
+            namespace: self.namespace(),
             job_name,
             job_type: JobType::ResourceAgent,
             environment_variables: vec![
@@ -87,7 +109,7 @@ impl ResourceInterface {
                 (ENV_RESOURCE_NAME, self.name().to_owned()),
             ],
         }
-        .deploy(self.resource_client().api().clone().into_client())
+        .deploy(self.k8s_client())
         .await;
 
         if let Err(crate::job::JobError::AlreadyExists { .. }) = &deploy_result {
@@ -98,25 +120,32 @@ impl ResourceInterface {
             return Ok(());
         }
         let _ = deploy_result.with_context(|| format!("Unable to deploy job '{}'", job_name))?;
+        // Retries and destruction jobs do not measure how long the resource waited to be created.
+        if op == ResourceAction::Create && self.resource().retries(op) == 0 {
+            self.metrics().job_started(
+                "resource",
+                self.resource().metadata.creation_timestamp.as_ref(),
+            );
+        }
         Ok(())
     }
 
     pub(super) async fn remove_job(&self, op: ResourceAction) -> Result<()> {
-        if let Err(e) = archive_logs(self.k8s_client(), self.job_name(op)).await {
+        if let Err(e) = archive_logs(self.k8s_client(), self.namespace(), self.job_name(op)).await {
             error!(
                 "Unable to archive logs for job '{}': {}",
                 self.job_name(op),
                 e
             );
         }
-        delete_job(self.k8s_client(), self.job_name(op))
+        delete_job(self.k8s_client(), self.namespace(), self.job_name(op))
             .await
             .context(format!("Unable to remove job '{}'", self.job_name(op)))?;
         Ok(())
     }
 
     async fn get_job_state_by_name(&self, job_name: &str) -> Result<JobState> {
-        get_job_state(self.k8s_client(), job_name)
+        get_job_state(self.k8s_client(), self.namespace(), job_name)
             .await
             .context(format!("Unable to get state of job '{}'", job_name))
     }
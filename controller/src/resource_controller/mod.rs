@@ -1,45 +1,69 @@
 mod action;
+mod conditions;
 mod context;
 
+use crate::admission::Admission;
 use crate::constants::requeue;
 use crate::error::{ReconciliationError, ReconciliationResult, Result};
+use crate::health::{resync, Health};
+use crate::metrics::Metrics;
+use crate::namespaces::Namespaces;
 use crate::resource_controller::action::{
-    action, Action, CreationAction, DestructionAction, ErrorState,
+    action, backoff, Action, CreationAction, DestructionAction, ErrorState,
 };
+use crate::resource_controller::conditions::update_conditions;
 use crate::resource_controller::context::{new_context, Context, ResourceInterface};
 use anyhow::Context as AnyhowContext;
+use futures::future::join_all;
 use futures::StreamExt;
-use kube::{Api, Client};
+use k8s_openapi::chrono::{self, SecondsFormat, Utc};
+use kube::Client;
 use kube_runtime::controller::Action as RequeueAction;
-use kube_runtime::{controller, watcher, Controller};
-use log::{debug, error, trace, warn};
+use kube_runtime::{controller, reflector, Controller};
+use log::{debug, error, info, trace, warn};
 use std::ops::Deref;
 use std::sync::Arc;
 use testsys_model::clients::CrdClient;
 use testsys_model::constants::{
     FINALIZER_CLEANUP_REQUIRED, FINALIZER_CREATION_JOB, FINALIZER_MAIN, FINALIZER_RESOURCE,
-    NAMESPACE,
 };
 use testsys_model::{CrdExt, ErrorResources, Resource, ResourceAction, ResourceError};
 
-pub(crate) async fn run_resource_controller(client: Client) {
-    let context = new_context(client.clone());
-    Controller::new(
-        Api::<Resource>::namespaced(client, NAMESPACE),
-        watcher::Config::default(),
-    )
-    .run(reconcile, handle_reconciliation_error, context)
-    .for_each(|reconciliation_result| async move {
-        if let Err(reconciliation_err) = reconciliation_result {
-            match &reconciliation_err {
-                controller::Error::ObjectNotFound { .. } => {
-                    // TODO - not sure why we get this after object deletion
-                    debug!("Object is gone: {}", reconciliation_err)
+/// Runs the controller for the `Resource`s in each of `namespaces`.
+pub(crate) async fn run_resource_controller(
+    client: Client,
+    namespaces: &Namespaces,
+    metrics: &Metrics,
+    health: &Health,
+    admission: &Admission,
+) {
+    let context = new_context(client.clone(), metrics.clone(), admission.clone());
+    join_all(namespaces.apis::<Resource>(&client).into_iter().map(|api| {
+        let name = format!("The resource controller for '{}'", api.resource_url());
+        let (store, writer) = reflector::store();
+        let monitor = health.monitor(name);
+        let controller = Controller::for_stream(monitor.watch(api, writer), store.clone())
+            .reconcile_all_on(resync());
+        admission.watch_resources(store.clone());
+        let stream = controller
+            .run(
+                metrics.measured("resource", reconcile),
+                handle_reconciliation_error,
+                context.clone(),
+            )
+            .for_each(|reconciliation_result| async move {
+                if let Err(reconciliation_err) = reconciliation_result {
+                    match &reconciliation_err {
+                        controller::Error::ObjectNotFound { .. } => {
+                            // TODO - not sure why we get this after object deletion
+                            debug!("Object is gone: {}", reconciliation_err)
+                        }
+                        _ => error!("Error during reconciliation: {}", reconciliation_err),
+                    }
                 }
-                _ => error!("Error during reconciliation: {}", reconciliation_err),
-            }
-        }
-    })
+            });
+        monitor.run(store, stream)
+    }))
     .await;
 }
 
@@ -55,6 +79,11 @@ pub(super) async fn reconcile(
 
     let action = action(&interface).await?;
     trace!("Action: {:?}", action);
+    update_conditions(&interface, &action).await?;
+    interface
+        .events()
+        .publish(interface.resource(), action.event())
+        .await;
     match action {
         Action::Creation(creation_action) => do_creation_action(interface, creation_action).await?,
         Action::Destruction(destruction_action) => {
@@ -112,6 +141,20 @@ async fn do_creation_action(r: ResourceInterface, action: CreationAction) -> Res
                 conflict
             );
         }
+        CreationAction::WaitForTestOutputs(needed) => {
+            debug!(
+                "'{}' is waiting for the outputs of test '{}'",
+                r.name(),
+                needed
+            );
+        }
+        CreationAction::WaitForMissingTest(needed) => {
+            debug!(
+                "'{}' is waiting for test '{}', which does not exist",
+                r.name(),
+                needed
+            );
+        }
         CreationAction::WaitForDependent => {
             debug!("'{}' is waiting for test that requires it", r.name());
         }
@@ -123,6 +166,21 @@ async fn do_creation_action(r: ResourceInterface, action: CreationAction) -> Res
                 .with_context(|| format!("Unable to add resource finalizer to '{}'", r.name()))?;
         }
         CreationAction::Done => {}
+        CreationAction::WaitForAdmission(reason) => {
+            debug!("'{}' is queued: {}", r.name(), reason);
+        }
+        CreationAction::WaitForResume => {
+            debug!("'{}' is suspended, waiting to create it", r.name());
+        }
+        CreationAction::RemoveFailedJob => r.remove_job(ResourceAction::Create).await?,
+        CreationAction::Retry(_) => retry(&r, ResourceAction::Create).await?,
+        CreationAction::WaitForRetry(retry_after) => {
+            debug!(
+                "'{}' is waiting until {} to retry creation",
+                r.name(),
+                retry_after
+            );
+        }
         CreationAction::Error(error_state) => {
             handle_error_state(&r, ResourceAction::Create, error_state).await?
         }
@@ -153,6 +211,16 @@ async fn do_destruction_action(r: ResourceInterface, action: DestructionAction)
             r.start_job(ResourceAction::Destroy).await?;
         }
         DestructionAction::Wait => {}
+        DestructionAction::WaitForResume => {
+            debug!("'{}' is suspended, waiting to destroy it", r.name());
+        }
+        DestructionAction::WaitForUsers(users) => {
+            debug!(
+                "'{}' has expired, waiting for {} to stop using it",
+                r.name(),
+                users.join(", ")
+            );
+        }
         DestructionAction::RemoveDestructionJob => {
             r.remove_job(ResourceAction::Destroy).await?;
         }
@@ -177,6 +245,16 @@ async fn do_destruction_action(r: ResourceInterface, action: DestructionAction)
                 .remove_finalizer(FINALIZER_MAIN, r.resource())
                 .await
                 .with_context(|| format!("Unable to remove main finalizer from '{}'", r.name()))?;
+            r.events().forget(r.resource());
+        }
+        DestructionAction::RemoveFailedJob => r.remove_job(ResourceAction::Destroy).await?,
+        DestructionAction::Retry(_) => retry(&r, ResourceAction::Destroy).await?,
+        DestructionAction::WaitForRetry(retry_after) => {
+            debug!(
+                "'{}' is waiting until {} to retry destruction",
+                r.name(),
+                retry_after
+            );
         }
         DestructionAction::Error(error_state) => {
             handle_error_state(&r, ResourceAction::Destroy, error_state).await?
@@ -193,24 +271,25 @@ async fn handle_error_state(r: &ResourceInterface, a: ResourceAction, e: ErrorSt
             ResourceAction::Destroy => "Destruction",
         },
         r.name(),
-        match e {
-            ErrorState::JobStart => "Timeout before resource started",
-            ErrorState::JobExited => "Container exited before it was done",
-            ErrorState::JobFailed => "Container exited with an error",
-            ErrorState::JobRemoved => "Container was killed before it was done",
-            ErrorState::TaskFailed => "Task failed",
-            ErrorState::JobTimeout => "Job did not complete within time limit",
+        match &e {
             ErrorState::Zombie => {
-                warn!("Resource still exists after main finalizer was removed");
+                warn!("{}", e);
                 return Ok(());
             }
+            _ => e.to_string(),
         }
     );
     error!("{}", message);
     if r.resource().error(a).is_none() {
         let resource_error = ResourceError {
             error: message,
-            error_resources: ErrorResources::Unknown,
+            // A resource in a dependency cycle never starts its creation job.
+            error_resources: match e {
+                ErrorState::DependencyCycle(_) | ErrorState::ServiceAccountNotAllowed(_) => {
+                    ErrorResources::Clear
+                }
+                _ => ErrorResources::Unknown,
+            },
         };
         r.resource_client()
             .send_error(r.name(), a, &resource_error)
@@ -226,6 +305,33 @@ async fn handle_error_state(r: &ResourceInterface, a: ResourceAction, e: ErrorSt
     Ok(())
 }
 
+/// Resets the failed task so that its job is started again once the backoff of the resource's
+/// `RetryPolicy` has passed.
+async fn retry(r: &ResourceInterface, op: ResourceAction) -> Result<()> {
+    let retries = r.resource().retries(op);
+    let delay = r
+        .resource()
+        .spec
+        .retry_policy
+        .as_ref()
+        .map(|retry_policy| backoff(retry_policy, retries))
+        .unwrap_or_default();
+    let retry_after = Utc::now()
+        + chrono::Duration::from_std(delay).context("Unable to convert the retry backoff")?;
+    let retry_after = retry_after.to_rfc3339_opts(SecondsFormat::Secs, true);
+    info!(
+        "Retrying {:?} of resource '{}' after {}",
+        op,
+        r.name(),
+        retry_after
+    );
+    r.resource_client()
+        .send_retry(r.name(), op, retries + 1, &retry_after)
+        .await
+        .with_context(|| format!("Unable to send retry for '{}'", r.name()))?;
+    Ok(())
+}
+
 /// `handle_reconciliation_error` is called when `reconcile` returns an error.
 pub(crate) fn handle_reconciliation_error(
     _: Arc<Resource>,
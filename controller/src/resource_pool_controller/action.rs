@@ -0,0 +1,150 @@
+use crate::error::Result;
+use crate::resource_pool_controller::context::ResourcePoolInterface;
+use testsys_model::{
+    CrdExt, ReleasePolicy, Resource, ResourceAction, ResourcePoolStatus, TaskState,
+};
+
+/// The action that the controller needs to take in order to reconcile the `ResourcePool`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(super) enum Action {
+    Initialize,
+    UpdateStatus(Box<ResourcePoolStatus>),
+    /// Return a resource to the pool because the test that leased it is done with it.
+    ReleaseResource(String),
+    /// Delete a resource that was leased by a test that is done with it, failed to be created, or is
+    /// not needed to keep the pool at its size.
+    DeleteResource(String),
+    CreateResource,
+    Done,
+}
+
+/// Inspect the `resource_pool` to determine which `Action` the controller should take. Members of
+/// the pool are owned by it, so k8s deletes them when the pool is deleted.
+pub(super) async fn determine_action(p: &ResourcePoolInterface) -> Result<Action> {
+    if p.resource_pool().is_delete_requested() {
+        return Ok(Action::Done);
+    }
+
+    let status = match &p.resource_pool().status {
+        None => return Ok(Action::Initialize),
+        Some(status) => status,
+    };
+
+    let members = p.members().await?;
+    let summary = p.resource_pool().summarize(&members);
+    if &summary != status {
+        return Ok(Action::UpdateStatus(Box::new(summary)));
+    }
+
+    let members: Vec<&Resource> = members
+        .iter()
+        .filter(|member| !member.is_delete_requested())
+        .collect();
+
+    for member in &members {
+        if let Some(test) = member.leased_by() {
+            if is_lease_done(p, test).await? {
+                return Ok(match p.resource_pool().spec.release_policy {
+                    ReleasePolicy::Recycle => {
+                        Action::DeleteResource(member.object_name().to_string())
+                    }
+                    ReleasePolicy::Reuse => {
+                        Action::ReleaseResource(member.object_name().to_string())
+                    }
+                });
+            }
+        }
+    }
+
+    if let Some(failed) = members.iter().find(|member| has_failed(member)) {
+        return Ok(Action::DeleteResource(failed.object_name().to_string()));
+    }
+
+    Ok(size_action(p.resource_pool().spec.size, &members))
+}
+
+/// Creates or deletes unleased resources so that the pool has `size` of them. Resources that have
+/// not finished being created are deleted first.
+fn size_action(size: u32, members: &[&Resource]) -> Action {
+    let mut unleased: Vec<&Resource> = members
+        .iter()
+        .filter(|member| member.leased_by().is_none())
+        .copied()
+        .collect();
+    let size = size as usize;
+    if unleased.len() < size {
+        return Action::CreateResource;
+    }
+    if unleased.len() > size {
+        unleased.sort_by_key(|member| member.is_available_for_lease());
+        return Action::DeleteResource(unleased[0].object_name().to_string());
+    }
+    Action::Done
+}
+
+/// A test is done with its lease once it has finished or no longer exists.
+async fn is_lease_done(p: &ResourcePoolInterface, test_name: &str) -> Result<bool> {
+    Ok(match p.get_test(test_name).await? {
+        None => true,
+        Some(test) => {
+            test.resource_error().is_some()
+                || matches!(
+                    test.agent_status().task_state,
+                    TaskState::Completed | TaskState::Error
+                )
+        }
+    })
+}
+
+fn has_failed(resource: &Resource) -> bool {
+    !resource.is_retrying(ResourceAction::Create)
+        && (resource.creation_error().is_some()
+            || resource.creation_task_state() == TaskState::Error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use serde_json::Map;
+    use testsys_model::constants::{LABEL_LEASED_BY, LABEL_RESOURCE_POOL};
+    use testsys_model::ResourceStatus;
+
+    fn member(name: &str, created: bool, leased_by: Option<&str>) -> Resource {
+        let mut labels = std::collections::BTreeMap::new();
+        labels.insert(LABEL_RESOURCE_POOL.to_string(), "pool".to_string());
+        if let Some(test) = leased_by {
+            labels.insert(LABEL_LEASED_BY.to_string(), test.to_string());
+        }
+        Resource {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                labels: Some(labels),
+                ..Default::default()
+            },
+            status: Some(ResourceStatus {
+                created_resource: created.then(Map::new),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn leased_resources_do_not_count_toward_size() {
+        let members = [member("a", true, Some("test")), member("b", true, None)];
+        let members: Vec<&Resource> = members.iter().collect();
+        assert_eq!(size_action(2, &members), Action::CreateResource);
+        assert_eq!(size_action(1, &members), Action::Done);
+    }
+
+    #[test]
+    fn uncreated_resources_are_deleted_first() {
+        let members = [member("a", true, None), member("b", false, None)];
+        let members: Vec<&Resource> = members.iter().collect();
+        assert_eq!(
+            size_action(1, &members),
+            Action::DeleteResource("b".to_string())
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::error::Result;
+use anyhow::Context as AnyhowContext;
+use kube::api::ListParams;
+use kube::Client;
+use std::sync::Arc;
+use testsys_model::clients::{
+    AllowNotFound, CrdClient, ResourceClient, ResourcePoolClient, TestClient,
+};
+use testsys_model::{CrdExt, Resource, ResourcePool, Test};
+
+/// This is used by `kube-runtime` to pass any custom information we need when [`reconcile`] is
+/// called.
+pub(crate) type Context = Arc<ContextData>;
+
+pub(crate) fn new_context(client: Client) -> Context {
+    Arc::new(ContextData { client })
+}
+
+/// This type is wrapped by [`kube::Context`] and contains information we need during [`reconcile`].
+#[derive(Clone)]
+pub(crate) struct ContextData {
+    client: Client,
+}
+
+/// The [`reconcile`] function has [`ResourcePool`] and [`Context`] as its inputs. For convenience,
+/// we combine these and provide accessor and helper functions.
+pub(crate) struct ResourcePoolInterface {
+    /// The cached [`ResourcePool`] object.
+    resource_pool: ResourcePool,
+    /// Clients for the namespace of the `ResourcePool`, which its children are in too.
+    resource_pool_client: ResourcePoolClient,
+    test_client: TestClient,
+    resource_client: ResourceClient,
+}
+
+impl ResourcePoolInterface {
+    /// Create a new `ResourcePoolInterface` from the [`ResourcePool`] and [`Context`].
+    pub(crate) fn new(resource_pool: ResourcePool, context: Context) -> Result<Self> {
+        let namespace = resource_pool.object_namespace();
+        Ok(Self {
+            resource_pool_client: ResourcePoolClient::new_namespaced(
+                context.client.clone(),
+                namespace,
+            ),
+            test_client: TestClient::new_namespaced(context.client.clone(), namespace),
+            resource_client: ResourceClient::new_namespaced(context.client.clone(), namespace),
+            resource_pool,
+        })
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.resource_pool.object_name()
+    }
+
+    pub(crate) fn resource_pool(&self) -> &ResourcePool {
+        &self.resource_pool
+    }
+
+    /// Access the inner `ResourcePoolClient` object with fewer keystrokes.
+    pub(super) fn resource_pool_client(&self) -> &ResourcePoolClient {
+        &self.resource_pool_client
+    }
+
+    pub(super) fn resource_client(&self) -> &ResourceClient {
+        &self.resource_client
+    }
+
+    /// List the `Resource`s that belong to this pool.
+    pub(super) async fn members(&self) -> Result<Vec<Resource>> {
+        Ok(self
+            .resource_client()
+            .api()
+            .list(&ListParams::default().labels(&self.resource_pool().member_selector()))
+            .await
+            .with_context(|| format!("Unable to list resources of pool '{}'", self.name()))?
+            .items)
+    }
+
+    /// Get the `Test` named `name`, or `None` if it does not exist.
+    pub(super) async fn get_test(&self, name: &str) -> Result<Option<Test>> {
+        self.test_client
+            .get(name)
+            .await
+            .allow_not_found(|_| ())
+            .with_context(|| format!("Unable to get test '{}'", name))
+    }
+}
@@ -0,0 +1,75 @@
+use crate::constants::requeue;
+use crate::error::ReconciliationResult;
+use crate::resource_pool_controller::action::{determine_action, Action};
+use crate::resource_pool_controller::context::{Context, ResourcePoolInterface};
+use anyhow::Context as AnyhowContext;
+use kube::api::PostParams;
+use kube_runtime::controller::Action as RequeueAction;
+use log::{debug, trace};
+use std::ops::Deref;
+use std::sync::Arc;
+use testsys_model::clients::{AllowNotFound, CrdClient};
+use testsys_model::ResourcePool;
+
+/// `reconcile` is called when a new `ResourcePool` object arrives, or when a `ResourcePool` object
+/// has been re-queued. This is the entrypoint to the controller logic.
+pub(crate) async fn reconcile(
+    p: Arc<ResourcePool>,
+    context: Context,
+) -> ReconciliationResult<RequeueAction> {
+    let p = ResourcePoolInterface::new(p.deref().clone(), context)?;
+    let action = determine_action(&p).await?;
+    trace!("action {:?}", action);
+    match action {
+        Action::Initialize => {
+            p.resource_pool_client()
+                .initialize_status(p.name())
+                .await
+                .context(format!("Unable to initialize status for '{}'", p.name()))?;
+            Ok(requeue())
+        }
+        Action::UpdateStatus(status) => {
+            p.resource_pool_client()
+                .send_status(p.name(), *status)
+                .await
+                .context(format!("Unable to send status for '{}'", p.name()))?;
+            Ok(requeue())
+        }
+        Action::ReleaseResource(name) => {
+            debug!("Releasing resource '{}' to pool '{}'", name, p.name());
+            p.resource_client()
+                .send_release(&name)
+                .await
+                .context(format!(
+                    "Unable to release resource '{}' of '{}'",
+                    name,
+                    p.name()
+                ))?;
+            Ok(requeue())
+        }
+        Action::DeleteResource(name) => {
+            debug!("Deleting resource '{}' of pool '{}'", name, p.name());
+            p.resource_client()
+                .delete(&name)
+                .await
+                .allow_not_found(|_| ())
+                .context(format!(
+                    "Unable to delete resource '{}' of '{}'",
+                    name,
+                    p.name()
+                ))?;
+            Ok(requeue())
+        }
+        Action::CreateResource => {
+            let _ = p
+                .resource_client()
+                .api()
+                .create(&PostParams::default(), &p.resource_pool().new_member())
+                .await
+                .context(format!("Unable to create a resource for '{}'", p.name()))?;
+            Ok(requeue())
+        }
+        // Tests finishing do not change the pool, so it is requeued to notice them.
+        Action::Done => Ok(requeue()),
+    }
+}
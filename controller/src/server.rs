@@ -0,0 +1,87 @@
+use crate::health::Health;
+use crate::metrics::Metrics;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{debug, error, info};
+use prometheus::{Encoder, TextEncoder};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use testsys_model::system::{
+    TESTSYS_LIVENESS_PATH, TESTSYS_METRICS_PATH, TESTSYS_METRICS_PORT, TESTSYS_READINESS_PATH,
+};
+
+/// Serves `metrics` in the Prometheus text format along with the liveness and readiness endpoints
+/// that report `health`. Every replica serves its metrics, but only the leader reconciles objects
+/// and counts them by state.
+pub(crate) async fn run_server(metrics: Metrics, health: Health) {
+    let address = SocketAddr::from(([0, 0, 0, 0], TESTSYS_METRICS_PORT));
+    let server = match Server::try_bind(&address) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("Unable to listen on '{}': {}", address, e);
+            return;
+        }
+    };
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        let health = health.clone();
+        let service = service_fn(move |request| handle(metrics.clone(), health.clone(), request));
+        async move { Ok::<_, Infallible>(service) }
+    });
+    info!("Serving metrics and health checks on '{}'", address);
+    if let Err(e) = server.serve(make_service).await {
+        error!("The metrics server has stopped: {}", e);
+    }
+}
+
+async fn handle(
+    metrics: Metrics,
+    health: Health,
+    request: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    if request.method() != Method::GET {
+        return Ok(response(StatusCode::NOT_FOUND, Body::empty()));
+    }
+    Ok(match request.uri().path() {
+        TESTSYS_METRICS_PATH => metrics_response(&metrics),
+        TESTSYS_LIVENESS_PATH => health_response("live", health.liveness_problems()),
+        TESTSYS_READINESS_PATH => health_response("ready", health.readiness_problems()),
+        _ => response(StatusCode::NOT_FOUND, Body::empty()),
+    })
+}
+
+fn metrics_response(metrics: &Metrics) -> Response<Body> {
+    match metrics.encode() {
+        Ok(body) => {
+            let mut response = response(StatusCode::OK, Body::from(body));
+            if let Ok(content_type) = TextEncoder::new().format_type().parse() {
+                response.headers_mut().insert(CONTENT_TYPE, content_type);
+            }
+            response
+        }
+        Err(e) => {
+            error!("Unable to encode metrics: {:?}", e);
+            response(StatusCode::INTERNAL_SERVER_ERROR, Body::empty())
+        }
+    }
+}
+
+/// Responds with `OK` if there are no `problems`, otherwise with `Service Unavailable` and the
+/// problems.
+fn health_response(check: &str, problems: Vec<String>) -> Response<Body> {
+    if problems.is_empty() {
+        return response(StatusCode::OK, Body::from("ok"));
+    }
+    debug!("The controller is not {}: {}", check, problems.join("; "));
+    response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        Body::from(problems.join("\n")),
+    )
+}
+
+fn response(status: StatusCode, body: Body) -> Response<Body> {
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response
+}
@@ -1,14 +1,19 @@
+use crate::admission::{is_admitted, QUEUED, SUSPENDED};
 use crate::error::Result;
-use crate::job::{JobState, TEST_START_TIME_LIMIT};
+use crate::events::{normal, warning};
+use crate::job::{service_account_problem, JobState, TEST_START_TIME_LIMIT};
 use crate::test_controller::context::TestInterface;
-use crate::utils::parse_duration;
+use crate::utils::{display_cycle, find_cycle, is_finished, missing_test_message, parse_duration};
 use anyhow::Context;
 use kube::{Api, ResourceExt};
+use kube_runtime::events::Event;
 use log::trace;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
-use testsys_model::clients::{CrdClient, HttpStatusCode, StatusCode};
-use testsys_model::constants::{FINALIZER_MAIN, FINALIZER_TEST_JOB, NAMESPACE};
-use testsys_model::{CrdExt, Outcome, Resource, ResourceAction, TaskState};
+use testsys_model::clients::{AllowNotFound, CrdClient, HttpStatusCode, StatusCode};
+use testsys_model::constants::{FINALIZER_MAIN, FINALIZER_TEST_JOB};
+use testsys_model::system::AdmissionKind;
+use testsys_model::{CrdExt, Outcome, Resource, ResourceAction, TaskState, Test};
 
 // These values configure how long to delay between tries.
 const MAX_RETRIES: u32 = 3;
@@ -22,7 +27,18 @@ pub(super) enum Action {
     WaitForResources,
     RegisterResourceCreationError(String),
     WaitForDependency(String),
+    WaitForTestOutputs(String),
+    /// Wait for a test whose outputs are used but which does not exist.
+    WaitForMissingTest(String),
+    /// Wait for the test to be resumed before starting its agent job.
+    WaitForResume,
+    /// Wait for room under the concurrency limits, for the contained reason.
+    WaitForAdmission(String),
+    LeaseResource(String),
+    WaitForLease(String),
     AddJobFinalizer,
+    /// Finish a test that was cancelled before its agent job started.
+    CancelTest,
     StartTest,
     WaitForTest,
     DeleteJob,
@@ -34,6 +50,9 @@ pub(super) enum Action {
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub(super) enum ErrorState {
+    /// The test depends on itself through a chain of `depends_on`. Contains the names of the tests
+    /// in the cycle, beginning with this one.
+    DependencyCycle(Vec<String>),
     ResourceErrorExists(String),
     Zombie,
     TestError(String),
@@ -42,11 +61,18 @@ pub(super) enum ErrorState {
     JobExitBeforeDone,
     JobTimeout,
     HandleJobRemovedBeforeDone,
+    /// The agent may not run as the service account it asks for.
+    ServiceAccountNotAllowed(String),
 }
 
 impl Display for ErrorState {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            ErrorState::DependencyCycle(cycle) => write!(
+                f,
+                "The test depends on itself through the cycle '{}'",
+                display_cycle(cycle)
+            ),
             ErrorState::ResourceErrorExists(e) => Display::fmt(e, f),
             ErrorState::Zombie => Display::fmt(
                 "The main finalizer has been removed but the object still exists",
@@ -64,6 +90,106 @@ impl Display for ErrorState {
             ErrorState::HandleJobRemovedBeforeDone => {
                 Display::fmt("The job was removed before the test completed", f)
             }
+            ErrorState::ServiceAccountNotAllowed(problem) => Display::fmt(problem, f),
+        }
+    }
+}
+
+impl ErrorState {
+    /// A CamelCase reason for the error.
+    fn reason(&self) -> &'static str {
+        match self {
+            ErrorState::DependencyCycle(_) => "DependencyCycle",
+            ErrorState::ResourceErrorExists(_) => "ResourceError",
+            ErrorState::Zombie => "Zombie",
+            ErrorState::TestError(_) => "TestError",
+            ErrorState::JobFailure => "JobFailure",
+            ErrorState::JobStart => "JobStart",
+            ErrorState::JobExitBeforeDone => "JobExitBeforeDone",
+            ErrorState::JobTimeout => "JobTimeout",
+            ErrorState::HandleJobRemovedBeforeDone => "JobRemovedBeforeDone",
+            ErrorState::ServiceAccountNotAllowed(_) => "ServiceAccountNotAllowed",
+        }
+    }
+}
+
+impl Action {
+    /// The k8s `Event` that records the controller taking this action for a test.
+    pub(super) fn event(&self) -> Event {
+        match self {
+            Action::Initialize => normal("Initialize", "Created", "Initialized the test status"),
+            Action::AddMainFinalizer => {
+                normal("AddMainFinalizer", "Created", "Added the main finalizer")
+            }
+            Action::WaitForResources => normal(
+                "WaitForResources",
+                "WaitingForResources",
+                "Waiting for resources to be created",
+            ),
+            Action::RegisterResourceCreationError(e) => {
+                warning("RegisterResourceCreationError", "ResourceError", e)
+            }
+            Action::WaitForDependency(needed) => normal(
+                "WaitForDependency",
+                "WaitingForDependency",
+                format!("Waiting for test '{}' to pass", needed),
+            ),
+            Action::WaitForTestOutputs(needed) => normal(
+                "WaitForTestOutputs",
+                "WaitingForTestOutputs",
+                format!(
+                    "Waiting for test '{}' to complete and publish its outputs",
+                    needed
+                ),
+            ),
+            Action::WaitForMissingTest(needed) => warning(
+                "WaitForMissingTest",
+                "TestNotFound",
+                missing_test_message(needed),
+            ),
+            Action::WaitForResume => normal(
+                "WaitForResume",
+                SUSPENDED,
+                "The test is suspended, its agent job starts once it is resumed",
+            ),
+            Action::WaitForAdmission(reason) => normal("WaitForAdmission", QUEUED, reason),
+            Action::LeaseResource(lease) => normal(
+                "LeaseResource",
+                "Leasing",
+                format!("Leasing a pooled resource for '{}'", lease),
+            ),
+            Action::WaitForLease(lease) => normal(
+                "WaitForLease",
+                "WaitingForLease",
+                format!("Waiting for a pooled resource to lease for '{}'", lease),
+            ),
+            Action::AddJobFinalizer => {
+                normal("AddJobFinalizer", "Starting", "Added the job finalizer")
+            }
+            Action::CancelTest => normal(
+                "CancelTest",
+                "Cancelled",
+                "The test was cancelled before its agent job started",
+            ),
+            Action::StartTest => normal("StartTest", "Starting", "Started the test agent job"),
+            Action::WaitForTest => normal(
+                "WaitForTest",
+                "Running",
+                "Waiting for the test agent to finish",
+            ),
+            Action::DeleteJob => normal("DeleteJob", "Deleting", "Deleting the test agent job"),
+            Action::RemoveJobFinalizer => normal(
+                "RemoveJobFinalizer",
+                "Deleting",
+                "Removed the job finalizer",
+            ),
+            Action::RemoveMainFinalizer => normal(
+                "RemoveMainFinalizer",
+                "Deleted",
+                "Removed the main finalizer",
+            ),
+            Action::TestDone => normal("TestDone", "Done", "The test agent has finished"),
+            Action::Error(state) => warning("Error", state.reason(), state.to_string()),
         }
     }
 }
@@ -124,7 +250,7 @@ async fn resource_readiness(t: &TestInterface) -> Result<Resources> {
     if t.test().spec.resources.is_empty() {
         return Ok(Resources::Ready);
     }
-    let resource_client: Api<Resource> = Api::namespaced(t.k8s_client(), NAMESPACE);
+    let resource_client: Api<Resource> = Api::namespaced(t.k8s_client(), t.namespace());
     let resources_names = &t.test().spec.resources;
     for resource_name in resources_names {
         let result = resource_client.get(resource_name).await;
@@ -149,6 +275,10 @@ async fn resource_readiness(t: &TestInterface) -> Result<Resources> {
 
         let resource =
             result.with_context(|| format!("Unable to get resource '{}'", resource_name))?;
+        // The controller will rerun the creation of the resource.
+        if resource.is_retrying(ResourceAction::Create) {
+            return Ok(Resources::NotReady);
+        }
         if let Some(error) = resource.creation_error() {
             return Ok(Resources::Error(format!(
                 "Error creating resource '{}': {}",
@@ -180,28 +310,130 @@ async fn dependency_wait_action(t: &TestInterface) -> Result<Option<Action>> {
         return Ok(None);
     };
 
-    // Make sure each resource in depends_on is ready.
-    // TODO - error if cyclical dependencies https://github.com/bottlerocket-os/bottlerocket-test-system/issues/156
+    // Make sure each test in depends_on has passed.
     for needed in depends_on {
-        let needed_test = match t.test_client().get(needed).await {
-            Ok(test) => test,
-            Err(_) => return Ok(Some(Action::WaitForDependency(needed.clone()))),
+        let needed_test = match t
+            .test_client()
+            .get(needed)
+            .await
+            .allow_not_found(|_| ())
+            .context(format!("Unable to get test '{}'", needed))?
+        {
+            Some(test) => test,
+            None => return Ok(Some(Action::WaitForDependency(needed.clone()))),
         };
 
-        if needed_test
-            .agent_status()
-            .results
-            .last()
-            .map(|results| results.outcome != Outcome::Pass)
-            .unwrap_or(true)
-        {
-            return Ok(Some(Action::WaitForDependency(needed_test.name_any())));
+        if !has_passed(&needed_test) {
+            return Ok(Some(deadlock_action(t, needed_test.name_any()).await?));
+        }
+    }
+    Ok(None)
+}
+
+/// The configuration of the test can only be resolved once the tests whose outputs it uses have
+/// completed. A test that ends in an error is not waited for, so that the test agent reports that
+/// the outputs are unavailable instead of the test waiting forever.
+async fn outputs_wait_action(t: &TestInterface) -> Result<Option<Action>> {
+    for needed in t.test().spec.agent.referenced_tests() {
+        let test = t
+            .test_client()
+            .get(&needed)
+            .await
+            .allow_not_found(|_| ())
+            .context(format!("Unable to get test '{}'", needed))?;
+        match test {
+            None => return Ok(Some(Action::WaitForMissingTest(needed))),
+            Some(test) if !is_finished(&test) => {
+                return Ok(Some(Action::WaitForTestOutputs(needed)))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(None)
+}
+
+/// The test only starts once there is room for it under the controller's concurrency limits. A test
+/// that has been admitted keeps its place.
+async fn admission_wait_action(t: &TestInterface) -> Result<Option<Action>> {
+    if is_admitted(t.test().conditions()) {
+        return Ok(None);
+    }
+    Ok(t.admission()
+        .wait_reason(AdmissionKind::Test, t.namespace(), t.name())
+        .await?
+        .map(Action::WaitForAdmission))
+}
+
+/// Resources are leased from pools only once the test is otherwise ready to start, so that a
+/// leased resource is not held while the test waits.
+async fn lease_action(t: &TestInterface) -> Result<Option<Action>> {
+    let leased_resources = t.test().leased_resources();
+    for lease in t.test().spec.leases.keys() {
+        if leased_resources.contains_key(lease) {
+            continue;
         }
+        return Ok(Some(if t.lease_candidates(lease).await?.is_empty() {
+            Action::WaitForLease(lease.clone())
+        } else {
+            Action::LeaseResource(lease.clone())
+        }));
     }
     Ok(None)
 }
 
+fn has_passed(test: &Test) -> bool {
+    test.agent_status()
+        .results
+        .last()
+        .map(|results| results.outcome == Outcome::Pass)
+        .unwrap_or(false)
+}
+
+/// Tests that depend on each other will never run. Returns an error if this test is part of such a
+/// cycle, otherwise waits for the `needed` test.
+async fn deadlock_action(t: &TestInterface, needed: String) -> Result<Action> {
+    let tests = t.test_client().get_all().await?;
+    Ok(match find_cycle(t.name(), &waiting_for(&tests)) {
+        Some(cycle) => Action::Error(ErrorState::DependencyCycle(cycle)),
+        None => Action::WaitForDependency(needed),
+    })
+}
+
+/// Maps the name of each test that has not run to the tests in its `depends_on` that have not
+/// passed.
+fn waiting_for(tests: &[Test]) -> BTreeMap<String, Vec<String>> {
+    let not_passed: BTreeSet<&str> = tests
+        .iter()
+        .filter(|test| !has_passed(test))
+        .map(|test| test.object_name())
+        .collect();
+    tests
+        .iter()
+        .filter(|test| test.agent_status().results.is_empty())
+        .map(|test| {
+            (
+                test.object_name().to_string(),
+                test.spec
+                    .depends_on
+                    .iter()
+                    .flatten()
+                    .filter(|name| not_passed.contains(name.as_str()))
+                    .cloned()
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
 async fn task_not_done_action(t: &TestInterface, is_task_state_running: bool) -> Result<Action> {
+    // The agent handles the cancellation of a test that it is running, but a test whose agent has
+    // not started yet is finished without starting it.
+    if !is_task_state_running
+        && t.test().spec.cancel
+        && matches!(t.get_job_state().await?, JobState::None)
+    {
+        return Ok(Action::CancelTest);
+    }
     if !is_task_state_running && !t.test().has_finalizer(FINALIZER_TEST_JOB) {
         return Ok(Action::AddJobFinalizer);
     }
@@ -216,9 +448,25 @@ async fn task_not_done_action(t: &TestInterface, is_task_state_running: bool) ->
                     Ok(Action::Error(ErrorState::ResourceErrorExists(s)))
                 }
             }
-            Resources::Ready => Ok(dependency_wait_action(t)
-                .await?
-                .unwrap_or(Action::StartTest)),
+            Resources::Ready => {
+                if let Some(action) = dependency_wait_action(t).await? {
+                    return Ok(action);
+                }
+                if let Some(action) = outputs_wait_action(t).await? {
+                    return Ok(action);
+                }
+                // A suspended test does not take up room under the concurrency limits.
+                if t.test().spec.suspend {
+                    return Ok(Action::WaitForResume);
+                }
+                if let Some(problem) = service_account_problem(&t.test().spec.agent) {
+                    return Ok(Action::Error(ErrorState::ServiceAccountNotAllowed(problem)));
+                }
+                if let Some(action) = admission_wait_action(t).await? {
+                    return Ok(action);
+                }
+                Ok(lease_action(t).await?.unwrap_or(Action::StartTest))
+            }
         },
         JobState::None => Ok(Action::Error(ErrorState::HandleJobRemovedBeforeDone)),
         JobState::Unknown => {
@@ -259,3 +507,56 @@ async fn task_not_done_action(t: &TestInterface, is_task_state_running: bool) ->
         JobState::Exited => Ok(Action::Error(ErrorState::JobExitBeforeDone)),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::names;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use testsys_model::{AgentStatus, TestResults, TestSpec, TestStatus};
+
+    fn test(name: &str, depends_on: &[&str], outcome: Option<Outcome>) -> Test {
+        Test {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            spec: TestSpec {
+                depends_on: Some(names(depends_on)),
+                ..Default::default()
+            },
+            status: Some(TestStatus {
+                agent: AgentStatus {
+                    results: outcome
+                        .map(|outcome| {
+                            vec![TestResults {
+                                outcome,
+                                ..Default::default()
+                            }]
+                        })
+                        .unwrap_or_default(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn dependency_cycle() {
+        let tests = vec![
+            test("a", &["b"], None),
+            test("b", &["a", "c"], None),
+            test("c", &[], Some(Outcome::Pass)),
+            test("d", &["a"], None),
+        ];
+        let edges = waiting_for(&tests);
+        assert_eq!(find_cycle("a", &edges), Some(names(&["a", "b"])));
+        assert!(find_cycle("c", &edges).is_none());
+        assert!(find_cycle("d", &edges).is_none());
+        assert_eq!(
+            ErrorState::DependencyCycle(vec!["a".to_string(), "b".to_string()]).to_string(),
+            "The test depends on itself through the cycle 'a -> b -> a'"
+        );
+    }
+}
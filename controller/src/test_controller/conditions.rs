@@ -0,0 +1,336 @@
+use crate::admission::{QUEUED, SUSPENDED};
+use crate::error::Result;
+use crate::metrics::became_true;
+use crate::test_controller::action::{Action, ErrorState};
+use crate::test_controller::context::TestInterface;
+use crate::utils::missing_test_message;
+use anyhow::Context;
+use testsys_model::{
+    set_conditions, Condition, ConditionType, CrdExt, TaskState, Test, TestUserState,
+};
+
+/// Updates the `conditions` of the test to reflect its state and the `action` that is about to be
+/// taken. The status is only patched if a condition has changed.
+pub(super) async fn update_conditions(t: &TestInterface, action: &Action) -> Result<()> {
+    if t.test().status.is_none() || t.test().is_delete_requested() {
+        return Ok(());
+    }
+    let mut conditions = t.test().conditions().to_vec();
+    if !set_conditions(&mut conditions, condition_updates(t.test(), action)) {
+        return Ok(());
+    }
+    let finished = became_true(t.test().conditions(), &conditions, ConditionType::Completed);
+    t.test_client()
+        .send_conditions(t.name(), conditions)
+        .await
+        .context(format!("Unable to send conditions for '{}'", t.name()))?;
+    if finished {
+        t.metrics().test_finished(t.test());
+    }
+    Ok(())
+}
+
+/// The conditions implied by the state of `test` and the `action` the controller is taking.
+/// `ResourcesReady` and `DependenciesMet` are only known while the controller is deciding whether
+/// to start the test, so they are not updated by other actions.
+pub(super) fn condition_updates(test: &Test, action: &Action) -> Vec<Condition> {
+    let generation = test.metadata.generation;
+    let condition = |condition_type, status: bool, reason: &str, message: &str| {
+        Condition::new(condition_type, status.into(), reason, message)
+            .observed_generation(generation)
+    };
+    let mut updates = Vec::new();
+    match action {
+        Action::WaitForResources => updates.push(condition(
+            ConditionType::ResourcesReady,
+            false,
+            "WaitingForResources",
+            "Waiting for resources to be created",
+        )),
+        Action::RegisterResourceCreationError(message)
+        | Action::Error(ErrorState::ResourceErrorExists(message)) => updates.push(condition(
+            ConditionType::ResourcesReady,
+            false,
+            "ResourceError",
+            message,
+        )),
+        Action::WaitForDependency(needed) => {
+            updates.push(condition(
+                ConditionType::ResourcesReady,
+                true,
+                "ResourcesReady",
+                "",
+            ));
+            updates.push(condition(
+                ConditionType::DependenciesMet,
+                false,
+                "WaitingForDependency",
+                &format!("Waiting for test '{}' to pass", needed),
+            ));
+        }
+        Action::WaitForTestOutputs(needed) => {
+            updates.push(condition(
+                ConditionType::ResourcesReady,
+                true,
+                "ResourcesReady",
+                "",
+            ));
+            updates.push(condition(
+                ConditionType::DependenciesMet,
+                false,
+                "WaitingForTestOutputs",
+                &format!("Waiting for test '{}' to complete", needed),
+            ));
+        }
+        Action::WaitForMissingTest(needed) => {
+            updates.push(condition(
+                ConditionType::ResourcesReady,
+                true,
+                "ResourcesReady",
+                "",
+            ));
+            updates.push(condition(
+                ConditionType::DependenciesMet,
+                false,
+                "TestNotFound",
+                &missing_test_message(needed),
+            ));
+        }
+        Action::Error(state @ ErrorState::DependencyCycle(_)) => updates.push(condition(
+            ConditionType::DependenciesMet,
+            false,
+            "DependencyCycle",
+            &state.to_string(),
+        )),
+        Action::WaitForLease(lease) => {
+            updates.push(condition(
+                ConditionType::ResourcesReady,
+                false,
+                "WaitingForLease",
+                &format!("Waiting for a pooled resource to lease for '{}'", lease),
+            ));
+            updates.push(condition(
+                ConditionType::DependenciesMet,
+                true,
+                "DependenciesMet",
+                "",
+            ));
+        }
+        Action::WaitForResume | Action::WaitForAdmission(_) | Action::StartTest => {
+            updates.push(condition(
+                ConditionType::ResourcesReady,
+                true,
+                "ResourcesReady",
+                "",
+            ));
+            updates.push(condition(
+                ConditionType::DependenciesMet,
+                true,
+                "DependenciesMet",
+                "",
+            ));
+        }
+        _ => {}
+    }
+    match action {
+        Action::WaitForAdmission(reason) => {
+            updates.push(condition(ConditionType::Admitted, false, QUEUED, reason))
+        }
+        Action::StartTest => updates.push(condition(ConditionType::Admitted, true, "Admitted", "")),
+        Action::WaitForResume => {
+            updates.push(condition(ConditionType::Admitted, false, SUSPENDED, ""))
+        }
+        // A test that is waiting for something else or has finished leaves the queue.
+        Action::WaitForResources
+        | Action::WaitForDependency(_)
+        | Action::WaitForTestOutputs(_)
+        | Action::WaitForMissingTest(_) => {
+            updates.push(condition(ConditionType::Admitted, false, "NotReady", ""))
+        }
+        Action::RegisterResourceCreationError(_)
+        | Action::CancelTest
+        | Action::TestDone
+        | Action::Error(_) => {
+            updates.push(condition(ConditionType::Admitted, false, "Finished", ""))
+        }
+        _ => {}
+    }
+
+    let state = test.test_user_state();
+    let reason = reason(state);
+    let message = test
+        .agent_error()
+        .or(test.resource_error().map(String::as_str))
+        .unwrap_or_default();
+    updates.push(condition(
+        ConditionType::Ready,
+        matches!(state, TestUserState::Passed | TestUserState::NoTests),
+        reason,
+        message,
+    ));
+    updates.push(condition(
+        ConditionType::Completed,
+        matches!(
+            test.agent_status().task_state,
+            TaskState::Completed | TaskState::Error
+        ),
+        reason,
+        message,
+    ));
+    updates.push(condition(
+        ConditionType::Failed,
+        matches!(
+            state,
+            TestUserState::Failed | TestUserState::Error | TestUserState::ResourceError
+        ),
+        reason,
+        message,
+    ));
+    updates
+}
+
+/// A CamelCase `reason` for the `TestUserState`.
+fn reason(state: TestUserState) -> &'static str {
+    match state {
+        TestUserState::Unknown => "Unknown",
+        TestUserState::Waiting => "Waiting",
+        TestUserState::Running => "Running",
+        TestUserState::NoTests => "NoTests",
+        TestUserState::Passed => "Passed",
+        TestUserState::Failed => "Failed",
+        TestUserState::Error => "Error",
+        TestUserState::ResourceError => "ResourceError",
+        TestUserState::Deleting => "Deleting",
+        TestUserState::Cancelled => "Cancelled",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use testsys_model::{AgentStatus, ConditionStatus, Outcome, TestResults, TestStatus};
+
+    fn find(conditions: &[Condition], condition_type: ConditionType) -> Option<&Condition> {
+        conditions
+            .iter()
+            .find(|condition| condition.condition_type == condition_type)
+    }
+
+    #[test]
+    fn waiting_for_dependency() {
+        let test = Test {
+            status: Some(TestStatus::default()),
+            ..Default::default()
+        };
+        let updates = condition_updates(&test, &Action::WaitForDependency("other".to_string()));
+        let dependencies = find(&updates, ConditionType::DependenciesMet);
+        assert_eq!(
+            dependencies.map(|condition| condition.status),
+            Some(ConditionStatus::False)
+        );
+        assert_eq!(
+            dependencies.map(|condition| condition.message.as_str()),
+            Some("Waiting for test 'other' to pass")
+        );
+        assert_eq!(
+            find(&updates, ConditionType::ResourcesReady).map(|condition| condition.status),
+            Some(ConditionStatus::True)
+        );
+        assert_eq!(
+            find(&updates, ConditionType::Completed).map(|condition| condition.status),
+            Some(ConditionStatus::False)
+        );
+    }
+
+    #[test]
+    fn failed() {
+        let test = Test {
+            status: Some(TestStatus {
+                agent: AgentStatus {
+                    task_state: TaskState::Completed,
+                    results: vec![TestResults {
+                        outcome: Outcome::Fail,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let updates = condition_updates(&test, &Action::TestDone);
+        assert!(find(&updates, ConditionType::DependenciesMet).is_none());
+        for (condition_type, status) in [
+            (ConditionType::Ready, ConditionStatus::False),
+            (ConditionType::Completed, ConditionStatus::True),
+            (ConditionType::Failed, ConditionStatus::True),
+        ] {
+            let condition = find(&updates, condition_type);
+            assert_eq!(condition.map(|condition| condition.status), Some(status));
+            assert_eq!(
+                condition.map(|condition| condition.reason.as_str()),
+                Some("Failed")
+            );
+        }
+    }
+
+    #[test]
+    fn queued() {
+        let test = Test {
+            status: Some(TestStatus::default()),
+            ..Default::default()
+        };
+        let updates = condition_updates(&test, &Action::WaitForAdmission("full".to_string()));
+        let admitted = find(&updates, ConditionType::Admitted);
+        assert_eq!(
+            admitted.map(|condition| condition.status),
+            Some(ConditionStatus::False)
+        );
+        assert_eq!(
+            admitted.map(|condition| condition.reason.as_str()),
+            Some(QUEUED)
+        );
+        assert_eq!(
+            find(&updates, ConditionType::DependenciesMet).map(|condition| condition.status),
+            Some(ConditionStatus::True)
+        );
+    }
+
+    #[test]
+    fn suspended() {
+        let test = Test {
+            status: Some(TestStatus::default()),
+            ..Default::default()
+        };
+        let updates = condition_updates(&test, &Action::WaitForResume);
+        let admitted = find(&updates, ConditionType::Admitted);
+        assert_eq!(
+            admitted.map(|condition| condition.status),
+            Some(ConditionStatus::False)
+        );
+        // A suspended test is not queued, so it does not hold up the tests behind it.
+        assert_eq!(
+            admitted.map(|condition| condition.reason.as_str()),
+            Some(SUSPENDED)
+        );
+    }
+
+    #[test]
+    fn cancelled_before_start() {
+        let test = Test {
+            status: Some(TestStatus::default()),
+            ..Default::default()
+        };
+        let updates = condition_updates(&test, &Action::CancelTest);
+        // A cancelled test leaves the queue instead of waiting to start its agent job.
+        let admitted = find(&updates, ConditionType::Admitted);
+        assert_eq!(
+            admitted.map(|condition| condition.status),
+            Some(ConditionStatus::False)
+        );
+        assert_eq!(
+            admitted.map(|condition| condition.reason.as_str()),
+            Some("Finished")
+        );
+    }
+}
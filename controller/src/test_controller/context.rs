@@ -1,32 +1,37 @@
+use crate::admission::Admission;
 use crate::error::Result;
+use crate::events::EventRecorder;
 use crate::job::{archive_logs, delete_job, get_job_state, JobState};
+use crate::metrics::Metrics;
 use anyhow::Context as AnyhowContext;
+use kube::api::ListParams;
 use kube::{Api, Client};
 use log::error;
+use std::collections::BTreeSet;
 use std::sync::Arc;
 use testsys_model::clients::{CrdClient, TestClient};
-use testsys_model::Test;
+use testsys_model::constants::LABEL_RESOURCE_POOL;
+use testsys_model::{CrdExt, Resource, ResourcePool, Test};
 
 /// This is used by `kube-runtime` to pass any custom information we need when [`reconcile`] is
 /// called.
 pub(crate) type Context = Arc<ContextData>;
 
-pub(crate) fn new_context(client: Client) -> Context {
+pub(crate) fn new_context(client: Client, metrics: Metrics, admission: Admission) -> Context {
     Arc::new(ContextData {
-        test_client: TestClient::new_from_k8s_client(client),
+        admission,
+        client: client.clone(),
+        events: EventRecorder::new(client),
+        metrics,
     })
 }
 
 /// This type is wrapped by [`kube::Context`] and contains information we need during [`reconcile`].
-#[derive(Clone)]
 pub(crate) struct ContextData {
-    test_client: TestClient,
-}
-
-impl ContextData {
-    pub(crate) fn api(&self) -> &Api<Test> {
-        self.test_client.api()
-    }
+    admission: Admission,
+    client: Client,
+    events: EventRecorder,
+    metrics: Metrics,
 }
 
 /// The [`reconcile`] function has [`Test`] and [`Context`] as its inputs. For convenience, we
@@ -34,13 +39,21 @@ impl ContextData {
 pub(crate) struct TestInterface {
     /// The cached [`Test`] object.
     test: Test,
+    /// A client for the namespace of the test.
+    test_client: TestClient,
     context: Context,
 }
 
 impl TestInterface {
     /// Create a new `TestInterface` from the [`Test`] and [`Context`].
     pub(crate) fn new(test: Test, context: Context) -> Result<Self> {
-        Ok(Self { test, context })
+        let test_client =
+            TestClient::new_namespaced(context.client.clone(), test.object_namespace());
+        Ok(Self {
+            test,
+            test_client,
+            context,
+        })
     }
 
     /// Get the name of the test. In the `Test` struct the name field is optional, but in practice
@@ -58,30 +71,81 @@ impl TestInterface {
         &self.test
     }
 
-    pub(crate) fn k8s_client(&self) -> kube::Client {
-        self.api().clone().into_client()
+    /// The namespace of the test, which its resources and agent job are in too.
+    pub(crate) fn namespace(&self) -> &str {
+        self.test.object_namespace()
     }
 
-    pub(crate) fn api(&self) -> &Api<Test> {
-        self.context.api()
+    pub(crate) fn k8s_client(&self) -> kube::Client {
+        self.context.client.clone()
     }
 
     /// Access the inner `TestClient` object with fewer keystrokes.
     pub(super) fn test_client(&self) -> &TestClient {
-        &self.context.test_client
+        &self.test_client
+    }
+
+    /// Publishes events for the test.
+    pub(super) fn events(&self) -> &EventRecorder {
+        &self.context.events
+    }
+
+    pub(super) fn metrics(&self) -> &Metrics {
+        &self.context.metrics
+    }
+
+    /// Decides when the agent job may start under the concurrency limits.
+    pub(super) fn admission(&self) -> &Admission {
+        &self.context.admission
+    }
+
+    /// The pooled `Resource`s that can be leased for `lease`. A resource that this test has already
+    /// leased, but not yet recorded in its status, comes first so that an interrupted lease is
+    /// completed instead of leasing another resource.
+    pub(super) async fn lease_candidates(&self, lease: &str) -> Result<Vec<Resource>> {
+        let selector = match self.test().spec.leases.get(lease) {
+            Some(selector) => selector,
+            None => return Ok(Vec::new()),
+        };
+        let pools = Api::<ResourcePool>::namespaced(self.k8s_client(), self.namespace())
+            .list(&ListParams::default().labels(selector))
+            .await
+            .with_context(|| format!("Unable to list resource pools matching '{}'", selector))?
+            .items;
+        if pools.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pool_names: Vec<&str> = pools.iter().map(|pool| pool.object_name()).collect();
+        let member_selector = format!("{} in ({})", LABEL_RESOURCE_POOL, pool_names.join(","));
+        let members = Api::<Resource>::namespaced(self.k8s_client(), self.namespace())
+            .list(&ListParams::default().labels(&member_selector))
+            .await
+            .with_context(|| format!("Unable to list resources matching '{}'", member_selector))?
+            .items;
+        let recorded: BTreeSet<String> = self.test().leased_resources().into_values().collect();
+        let (mut candidates, available): (Vec<Resource>, Vec<Resource>) = members
+            .into_iter()
+            .filter(|member| !recorded.contains(member.object_name()))
+            .filter(|member| !member.is_delete_requested())
+            .filter(|member| {
+                member.leased_by() == Some(self.name()) || member.is_available_for_lease()
+            })
+            .partition(|member| member.leased_by() == Some(self.name()));
+        candidates.extend(available);
+        Ok(candidates)
     }
 
     pub(super) async fn get_job_state(&self) -> Result<JobState> {
-        get_job_state(self.k8s_client(), self.name())
+        get_job_state(self.k8s_client(), self.namespace(), self.name())
             .await
             .with_context(|| format!("Unable to get job state for test '{}'", self.name()))
     }
 
     pub(super) async fn delete_job(&self) -> Result<()> {
-        if let Err(e) = archive_logs(self.k8s_client(), self.name()).await {
+        if let Err(e) = archive_logs(self.k8s_client(), self.namespace(), self.name()).await {
             error!("Unable to archive logs for test '{}': {}", self.name(), e);
         }
-        delete_job(self.k8s_client(), self.name())
+        delete_job(self.k8s_client(), self.namespace(), self.name())
             .await
             .with_context(|| format!("Unable to delete job for test '{}'", self.name()))
     }
@@ -1,33 +1,59 @@
+use crate::admission::Admission;
 use crate::constants::requeue;
 use crate::error::ReconciliationError;
+use crate::health::{resync, Health};
+use crate::metrics::Metrics;
+use crate::namespaces::Namespaces;
 use crate::test_controller::context::{new_context, Context};
 use crate::test_controller::reconcile::reconcile;
+use futures::future::join_all;
 use futures::StreamExt;
 use kube_runtime::controller::Action as RequeueAction;
-use kube_runtime::{controller, watcher, Controller};
+use kube_runtime::{controller, reflector, Controller};
 use log::{debug, error};
 use std::sync::Arc;
 use testsys_model::Test;
 
 mod action;
+mod conditions;
 mod context;
 mod reconcile;
 
-pub(super) async fn run_test_controller(client: kube::Client) {
-    let context = new_context(client);
-    Controller::new(context.api().clone(), watcher::Config::default())
-        .run(reconcile, handle_reconciliation_error, context)
-        .for_each(|reconciliation_result| async move {
-            if let Err(reconciliation_err) = reconciliation_result {
-                match &reconciliation_err {
-                    controller::Error::ObjectNotFound { .. } => {
-                        debug!("Object is gone: {}", reconciliation_err)
+/// Runs the controller for the `Test`s in each of `namespaces`.
+pub(super) async fn run_test_controller(
+    client: kube::Client,
+    namespaces: &Namespaces,
+    metrics: &Metrics,
+    health: &Health,
+    admission: &Admission,
+) {
+    let context = new_context(client.clone(), metrics.clone(), admission.clone());
+    join_all(namespaces.apis::<Test>(&client).into_iter().map(|api| {
+        let name = format!("The test controller for '{}'", api.resource_url());
+        let (store, writer) = reflector::store();
+        let monitor = health.monitor(name);
+        let controller = Controller::for_stream(monitor.watch(api, writer), store.clone())
+            .reconcile_all_on(resync());
+        admission.watch_tests(store.clone());
+        let stream = controller
+            .run(
+                metrics.measured("test", reconcile),
+                handle_reconciliation_error,
+                context.clone(),
+            )
+            .for_each(|reconciliation_result| async move {
+                if let Err(reconciliation_err) = reconciliation_result {
+                    match &reconciliation_err {
+                        controller::Error::ObjectNotFound { .. } => {
+                            debug!("Object is gone: {}", reconciliation_err)
+                        }
+                        _ => error!("Error during reconciliation: {}", reconciliation_err),
                     }
-                    _ => error!("Error during reconciliation: {}", reconciliation_err),
                 }
-            }
-        })
-        .await;
+            });
+        monitor.run(store, stream)
+    }))
+    .await;
 }
 
 /// `handle_reconciliation_error` is called when `reconcile` returns an error.
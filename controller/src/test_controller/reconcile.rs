@@ -2,15 +2,17 @@ use crate::constants::{no_requeue, requeue, requeue_slow};
 use crate::error::{ReconciliationResult, Result};
 use crate::job::{JobBuilder, JobType};
 use crate::test_controller::action::{determine_action, Action};
+use crate::test_controller::conditions::update_conditions;
 use crate::test_controller::context::{Context, TestInterface};
 use anyhow::Context as AnyhowContext;
+use kube::ResourceExt;
 use kube_runtime::controller::Action as RequeueAction;
 use log::{debug, error, trace};
 use std::ops::Deref;
 use std::sync::Arc;
-use testsys_model::clients::CrdClient;
+use testsys_model::clients::{CrdClient, ResourceClient};
 use testsys_model::constants::{ENV_TEST_NAME, FINALIZER_MAIN, FINALIZER_TEST_JOB};
-use testsys_model::{TaskState, Test};
+use testsys_model::{Outcome, TaskState, Test, TestResults};
 
 /// `reconcile` is called when a new `Test` object arrives, or when a `Test` object has been
 /// re-queued. This is the entrypoint to the controller logic.
@@ -21,6 +23,8 @@ pub(crate) async fn reconcile(
     let mut t = TestInterface::new(t.deref().clone(), context)?;
     let action = determine_action(&t).await?;
     trace!("action {:?}", action);
+    update_conditions(&t, &action).await?;
+    t.events().publish(t.test(), action.event()).await;
     match action {
         Action::Initialize => {
             t.test_client()
@@ -49,7 +53,16 @@ pub(crate) async fn reconcile(
                 ))?;
             Ok(requeue())
         }
-        Action::WaitForDependency(_) => Ok(requeue()),
+        Action::WaitForDependency(_)
+        | Action::WaitForTestOutputs(_)
+        | Action::WaitForMissingTest(_) => Ok(requeue()),
+        Action::LeaseResource(lease) => {
+            lease_resource(&t, &lease).await?;
+            Ok(requeue())
+        }
+        Action::WaitForResume | Action::WaitForAdmission(_) | Action::WaitForLease(_) => {
+            Ok(requeue())
+        }
         Action::AddJobFinalizer => {
             t.test_client()
                 .add_finalizer(FINALIZER_TEST_JOB, t.test())
@@ -57,6 +70,22 @@ pub(crate) async fn reconcile(
                 .context(format!("Unable to add job finalizer for '{}'", t.name()))?;
             Ok(requeue())
         }
+        Action::CancelTest => {
+            t.test_client()
+                .send_test_completed(
+                    t.name(),
+                    TestResults {
+                        outcome: Outcome::Cancelled,
+                        other_info: Some(
+                            "The test was cancelled before its agent started".to_string(),
+                        ),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .context(format!("Unable to cancel '{}'", t.name()))?;
+            Ok(requeue())
+        }
         Action::StartTest => {
             create_job(&mut t).await?;
             Ok(requeue())
@@ -81,6 +110,7 @@ pub(crate) async fn reconcile(
                     "Unable to remove main finalizer for '{}'",
                     t.name()
                 ))?;
+            t.events().forget(t.test());
             Ok(no_requeue())
         }
         Action::TestDone => {
@@ -105,6 +135,36 @@ pub(crate) async fn reconcile(
     }
 }
 
+/// Leases a pooled resource for `lease` and records it in the status of the test. If another test
+/// leases the same resource first the lease fails, and another resource is tried when the test is
+/// requeued.
+async fn lease_resource(t: &TestInterface, lease: &str) -> Result<()> {
+    let resource = match t.lease_candidates(lease).await?.into_iter().next() {
+        Some(resource) => resource,
+        None => return Ok(()),
+    };
+    if resource.leased_by() != Some(t.name()) {
+        ResourceClient::new_namespaced(t.k8s_client(), t.namespace())
+            .send_lease(&resource, t.name())
+            .await
+            .context(format!(
+                "Unable to lease resource '{}' for '{}'",
+                resource.name_any(),
+                t.name()
+            ))?;
+    }
+    let mut leased_resources = t.test().leased_resources();
+    leased_resources.insert(lease.to_string(), resource.name_any());
+    t.test_client()
+        .send_leased_resources(t.name(), leased_resources)
+        .await
+        .context(format!(
+            "Unable to send leased resources for '{}'",
+            t.name()
+        ))?;
+    Ok(())
+}
+
 /// Runs a k8s `Job` to run our test pod. Adds the pod finalizer to ensure we don't forget to clean
 /// up the `Job` later.
 ///
@@ -116,6 +176,7 @@ pub(crate) async fn create_job(t: &mut TestInterface) -> Result<()> {
     debug!("Creating test job '{}'", t.name());
     JobBuilder {
         agent: &t.test().spec.agent,
+        namespace: t.namespace(),
         job_name: t.name(),
         job_type: JobType::TestAgent,
         environment_variables: vec![(ENV_TEST_NAME, t.name().to_owned())],
@@ -123,5 +184,10 @@ pub(crate) async fn create_job(t: &mut TestInterface) -> Result<()> {
     .deploy(t.k8s_client())
     .await
     .context(format!("Unable to create job '{}'", t.name()))?;
+    // Only the first job is counted, a job started after earlier attempts would include their time.
+    if t.test().agent_status().results.is_empty() {
+        t.metrics()
+            .job_started("test", t.test().metadata.creation_timestamp.as_ref());
+    }
     Ok(())
 }
@@ -0,0 +1,160 @@
+use crate::error::Result;
+use crate::test_schedule_controller::context::TestScheduleInterface;
+use k8s_openapi::chrono::{DateTime, Utc};
+use testsys_model::{ConcurrencyPolicy, CrdExt, TestSchedule, TestScheduleStatus};
+
+/// The action that the controller needs to take in order to reconcile the `TestSchedule`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(super) enum Action {
+    Initialize,
+    UpdateStatus(Box<TestScheduleStatus>),
+    /// Delete a run that is beyond the history limit, or an active run that is being replaced.
+    DeleteRun(String),
+    /// Start the run that is due at the contained time.
+    StartRun(DateTime<Utc>),
+    /// Record that the run due at the contained time was skipped, and why.
+    SkipRun(DateTime<Utc>, String),
+    /// Nothing is due until the contained time.
+    WaitForSchedule(DateTime<Utc>),
+    /// The `schedule` cannot be parsed, so no runs can be started.
+    InvalidSchedule(String),
+    Done,
+}
+
+/// Inspect the `test_schedule` to determine which `Action` the controller should take. Runs are
+/// owned by the schedule, so k8s deletes them when the schedule is deleted.
+pub(super) async fn determine_action(s: &TestScheduleInterface) -> Result<Action> {
+    if s.test_schedule().is_delete_requested() {
+        return Ok(Action::Done);
+    }
+
+    let status = match &s.test_schedule().status {
+        None => return Ok(Action::Initialize),
+        Some(status) => status,
+    };
+
+    let runs = s.runs().await?;
+    let now = Utc::now();
+    let summary = s.test_schedule().summarize(&runs, now);
+    if &summary != status {
+        return Ok(Action::UpdateStatus(Box::new(summary)));
+    }
+
+    if let Some(expired) = s.test_schedule().expired_runs(status).into_iter().next() {
+        return Ok(Action::DeleteRun(expired));
+    }
+
+    Ok(schedule_action(s.test_schedule(), status, now))
+}
+
+/// Decides what to do about the run that is due at `now`, if any, given the schedule's
+/// `concurrencyPolicy` and its `active` runs. Runs that are due while the schedule is suspended are
+/// skipped rather than started once it is resumed.
+fn schedule_action(
+    schedule: &TestSchedule,
+    status: &TestScheduleStatus,
+    now: DateTime<Utc>,
+) -> Action {
+    let due = match schedule.due_time(now) {
+        Ok(due) => due,
+        Err(e) => return Action::InvalidSchedule(e.to_string()),
+    };
+    let time = match due {
+        Some(time) => time,
+        None => {
+            return match schedule.next_time(now) {
+                Ok(next) => Action::WaitForSchedule(next),
+                Err(e) => Action::InvalidSchedule(e.to_string()),
+            }
+        }
+    };
+    if schedule.spec.suspend {
+        return Action::SkipRun(time, "the schedule is suspended".to_string());
+    }
+    match (status.active.first(), &schedule.spec.concurrency_policy) {
+        (None, _) | (Some(_), ConcurrencyPolicy::Allow) => Action::StartRun(time),
+        (Some(active), ConcurrencyPolicy::Forbid) => {
+            Action::SkipRun(time, format!("run '{}' has not finished", active))
+        }
+        (Some(active), ConcurrencyPolicy::Replace) => Action::DeleteRun(active.clone()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time};
+    use k8s_openapi::chrono::Duration;
+    use testsys_model::TestScheduleSpec;
+
+    fn schedule(policy: ConcurrencyPolicy, suspend: bool) -> TestSchedule {
+        TestSchedule {
+            metadata: ObjectMeta {
+                name: Some("nightly".to_string()),
+                creation_timestamp: Some(Time(Utc::now() - Duration::days(2))),
+                ..Default::default()
+            },
+            spec: TestScheduleSpec {
+                schedule: "0 0 * * *".to_string(),
+                concurrency_policy: policy,
+                suspend,
+                ..Default::default()
+            },
+            status: None,
+        }
+    }
+
+    fn status(active: &[&str]) -> TestScheduleStatus {
+        TestScheduleStatus {
+            active: active.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn concurrency_policy() {
+        let now = Utc::now();
+        let due = schedule(ConcurrencyPolicy::Forbid, false)
+            .due_time(now)
+            .ok()
+            .flatten();
+        assert!(due.is_some());
+        let action = |policy, active: &[&str]| {
+            Some(schedule_action(
+                &schedule(policy, false),
+                &status(active),
+                now,
+            ))
+        };
+        assert_eq!(
+            action(ConcurrencyPolicy::Forbid, &[]),
+            due.map(Action::StartRun)
+        );
+        assert_eq!(
+            action(ConcurrencyPolicy::Forbid, &["old"]),
+            due.map(|due| Action::SkipRun(due, "run 'old' has not finished".to_string()))
+        );
+        assert_eq!(
+            action(ConcurrencyPolicy::Replace, &["old"]),
+            Some(Action::DeleteRun("old".to_string()))
+        );
+        assert_eq!(
+            action(ConcurrencyPolicy::Allow, &["old"]),
+            due.map(Action::StartRun)
+        );
+        assert!(matches!(
+            schedule_action(&schedule(ConcurrencyPolicy::Allow, true), &status(&[]), now),
+            Action::SkipRun(..)
+        ));
+    }
+
+    #[test]
+    fn invalid_schedule() {
+        let mut schedule = schedule(ConcurrencyPolicy::Forbid, false);
+        schedule.spec.schedule = "every day".to_string();
+        assert!(matches!(
+            schedule_action(&schedule, &status(&[]), Utc::now()),
+            Action::InvalidSchedule(_)
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::error::Result;
+use anyhow::Context as AnyhowContext;
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::api::{ListParams, PostParams};
+use kube::Client;
+use log::debug;
+use std::sync::Arc;
+use testsys_model::clients::{
+    CrdClient, HttpStatusCode, StatusCode, TestScheduleClient, TestSuiteClient,
+};
+use testsys_model::{CrdExt, TestSchedule, TestSuite};
+
+/// This is used by `kube-runtime` to pass any custom information we need when [`reconcile`] is
+/// called.
+pub(crate) type Context = Arc<ContextData>;
+
+pub(crate) fn new_context(client: Client) -> Context {
+    Arc::new(ContextData { client })
+}
+
+/// This type is wrapped by [`kube::Context`] and contains information we need during [`reconcile`].
+#[derive(Clone)]
+pub(crate) struct ContextData {
+    client: Client,
+}
+
+/// The [`reconcile`] function has [`TestSchedule`] and [`Context`] as its inputs. For convenience,
+/// we combine these and provide accessor and helper functions.
+pub(crate) struct TestScheduleInterface {
+    /// The cached [`TestSchedule`] object.
+    test_schedule: TestSchedule,
+    /// Clients for the namespace of the `TestSchedule`, which its children are in too.
+    test_schedule_client: TestScheduleClient,
+    test_suite_client: TestSuiteClient,
+}
+
+impl TestScheduleInterface {
+    /// Create a new `TestScheduleInterface` from the [`TestSchedule`] and [`Context`].
+    pub(crate) fn new(test_schedule: TestSchedule, context: Context) -> Result<Self> {
+        let namespace = test_schedule.object_namespace();
+        Ok(Self {
+            test_schedule_client: TestScheduleClient::new_namespaced(
+                context.client.clone(),
+                namespace,
+            ),
+            test_suite_client: TestSuiteClient::new_namespaced(context.client.clone(), namespace),
+            test_schedule,
+        })
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.test_schedule.object_name()
+    }
+
+    pub(crate) fn test_schedule(&self) -> &TestSchedule {
+        &self.test_schedule
+    }
+
+    /// Access the inner `TestScheduleClient` object with fewer keystrokes.
+    pub(super) fn test_schedule_client(&self) -> &TestScheduleClient {
+        &self.test_schedule_client
+    }
+
+    pub(super) fn test_suite_client(&self) -> &TestSuiteClient {
+        &self.test_suite_client
+    }
+
+    /// List the `TestSuite`s that are runs of this schedule.
+    pub(super) async fn runs(&self) -> Result<Vec<TestSuite>> {
+        Ok(self
+            .test_suite_client()
+            .api()
+            .list(&ListParams::default().labels(&self.test_schedule().run_selector()))
+            .await
+            .with_context(|| format!("Unable to list runs of schedule '{}'", self.name()))?
+            .items)
+    }
+
+    /// Create the run that is due at `time`. A run that already exists, for example because the
+    /// last schedule time could not be recorded after it was created, is left as it is.
+    pub(super) async fn create_run(&self, time: DateTime<Utc>) -> Result<()> {
+        let run = self.test_schedule().new_run(time);
+        let name = run.object_name().to_string();
+        debug!("Starting run '{}' of schedule '{}'", name, self.name());
+        let result = self
+            .test_suite_client()
+            .api()
+            .create(&PostParams::default(), &run)
+            .await;
+        if result.is_status_code(StatusCode::CONFLICT) {
+            debug!("Run '{}' already exists", name);
+            return Ok(());
+        }
+        let _ = result.with_context(|| {
+            format!(
+                "Unable to create run '{}' for schedule '{}'",
+                name,
+                self.name()
+            )
+        })?;
+        Ok(())
+    }
+}
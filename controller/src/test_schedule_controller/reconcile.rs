@@ -0,0 +1,89 @@
+use crate::constants::{requeue, requeue_slow, REQUEUE_SLOW};
+use crate::error::ReconciliationResult;
+use crate::test_schedule_controller::action::{determine_action, Action};
+use crate::test_schedule_controller::context::{Context, TestScheduleInterface};
+use anyhow::Context as AnyhowContext;
+use k8s_openapi::chrono::{DateTime, SecondsFormat, Utc};
+use kube_runtime::controller::Action as RequeueAction;
+use log::{debug, error, info, trace};
+use std::ops::Deref;
+use std::sync::Arc;
+use testsys_model::clients::{AllowNotFound, CrdClient};
+use testsys_model::TestSchedule;
+
+/// `reconcile` is called when a new `TestSchedule` object arrives, or when a `TestSchedule` object
+/// has been re-queued. This is the entrypoint to the controller logic.
+pub(crate) async fn reconcile(
+    s: Arc<TestSchedule>,
+    context: Context,
+) -> ReconciliationResult<RequeueAction> {
+    let s = TestScheduleInterface::new(s.deref().clone(), context)?;
+    let action = determine_action(&s).await?;
+    trace!("action {:?}", action);
+    match action {
+        Action::Initialize => {
+            s.test_schedule_client()
+                .initialize_status(s.name())
+                .await
+                .context(format!("Unable to initialize status for '{}'", s.name()))?;
+            Ok(requeue())
+        }
+        Action::UpdateStatus(status) => {
+            s.test_schedule_client()
+                .send_status(s.name(), *status)
+                .await
+                .context(format!("Unable to send status for '{}'", s.name()))?;
+            Ok(requeue())
+        }
+        Action::DeleteRun(name) => {
+            debug!("Deleting run '{}' of schedule '{}'", name, s.name());
+            s.test_suite_client()
+                .delete(&name)
+                .await
+                .allow_not_found(|_| ())
+                .context(format!("Unable to delete run '{}' of '{}'", name, s.name()))?;
+            Ok(requeue())
+        }
+        Action::StartRun(time) => {
+            s.create_run(time).await?;
+            send_last_schedule_time(&s, time).await?;
+            Ok(requeue())
+        }
+        Action::SkipRun(time, reason) => {
+            info!(
+                "Skipping the run of schedule '{}' due at {} because {}",
+                s.name(),
+                time,
+                reason
+            );
+            send_last_schedule_time(&s, time).await?;
+            Ok(requeue())
+        }
+        // Runs finishing do not change the schedule, so it is requeued at least as often as
+        // `requeue_slow` to notice them.
+        Action::WaitForSchedule(next) => Ok(match (next - Utc::now()).to_std() {
+            Ok(until_next) if until_next < REQUEUE_SLOW => RequeueAction::requeue(until_next),
+            Ok(_) => requeue_slow(),
+            Err(_) => requeue(),
+        }),
+        Action::InvalidSchedule(e) => {
+            error!("Unable to run schedule '{}': {}", s.name(), e);
+            Ok(requeue_slow())
+        }
+        Action::Done => Ok(requeue_slow()),
+    }
+}
+
+async fn send_last_schedule_time(
+    s: &TestScheduleInterface,
+    time: DateTime<Utc>,
+) -> ReconciliationResult<()> {
+    s.test_schedule_client()
+        .send_last_schedule_time(s.name(), time.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .await
+        .context(format!(
+            "Unable to send the last schedule time for '{}'",
+            s.name()
+        ))?;
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use crate::error::Result;
+use crate::test_suite_controller::context::TestSuiteInterface;
+use std::fmt::{Display, Formatter};
+use testsys_model::constants::FINALIZER_MAIN;
+use testsys_model::{CrdExt, TestSuiteStatus};
+
+/// The action that the controller needs to take in order to reconcile the `TestSuite`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(super) enum Action {
+    Initialize,
+    AddMainFinalizer,
+    CreateChildren,
+    UpdateStatus(Box<TestSuiteStatus>),
+    WaitForTests,
+    Done,
+    DeleteTests,
+    DeleteResources,
+    WaitForChildDeletion,
+    RemoveMainFinalizer,
+    Error(ErrorState),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(super) enum ErrorState {
+    Zombie,
+}
+
+impl Display for ErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorState::Zombie => Display::fmt(
+                "The main finalizer has been removed but the object still exists",
+                f,
+            ),
+        }
+    }
+}
+
+/// Inspect the `test_suite` to determine which `Action` the controller should take.
+pub(super) async fn determine_action(t: &TestSuiteInterface) -> Result<Action> {
+    if t.test_suite().is_delete_requested() {
+        return determine_delete_action(t).await;
+    }
+
+    let status = match &t.test_suite().status {
+        None => return Ok(Action::Initialize),
+        Some(status) => status,
+    };
+
+    if !t.test_suite().has_finalizer(FINALIZER_MAIN) {
+        return Ok(Action::AddMainFinalizer);
+    }
+
+    if !status.children_created {
+        return Ok(Action::CreateChildren);
+    }
+
+    let tests = t.child_tests().await?;
+    let summary = t.test_suite().summarize(&tests);
+    if &summary != status {
+        return Ok(Action::UpdateStatus(Box::new(summary)));
+    }
+
+    if summary.num_finished() < summary.num_tests {
+        Ok(Action::WaitForTests)
+    } else {
+        Ok(Action::Done)
+    }
+}
+
+/// Determines what we should do next if the TestSys `TestSuite` CRD has been marked for deletion.
+/// Tests are deleted before resources so that no test is left running against a resource that is
+/// being destroyed.
+///
+/// # Preconditions
+///
+/// This function assumes that the test suite has been marked for deletion. This is checked in
+/// debug builds but not in release builds.
+///
+pub(super) async fn determine_delete_action(t: &TestSuiteInterface) -> Result<Action> {
+    debug_assert!(t.test_suite().is_delete_requested());
+    if !t.test_suite().has_finalizer(FINALIZER_MAIN) {
+        return Ok(Action::Error(ErrorState::Zombie));
+    }
+
+    let tests = t.child_tests().await?;
+    if tests.iter().any(|test| !test.is_delete_requested()) {
+        return Ok(Action::DeleteTests);
+    }
+    if !tests.is_empty() {
+        return Ok(Action::WaitForChildDeletion);
+    }
+
+    let resources = t.child_resources().await?;
+    if resources
+        .iter()
+        .any(|resource| !resource.is_delete_requested())
+    {
+        return Ok(Action::DeleteResources);
+    }
+    if !resources.is_empty() {
+        return Ok(Action::WaitForChildDeletion);
+    }
+
+    Ok(Action::RemoveMainFinalizer)
+}
@@ -0,0 +1,139 @@
+use crate::error::Result;
+use anyhow::Context as AnyhowContext;
+use kube::api::{ListParams, PostParams};
+use kube::Client;
+use log::debug;
+use std::sync::Arc;
+use testsys_model::clients::{
+    CrdClient, HttpStatusCode, ResourceClient, StatusCode, TestClient, TestSuiteClient,
+};
+use testsys_model::{CrdExt, Resource, Test, TestSuite};
+
+/// This is used by `kube-runtime` to pass any custom information we need when [`reconcile`] is
+/// called.
+pub(crate) type Context = Arc<ContextData>;
+
+pub(crate) fn new_context(client: Client) -> Context {
+    Arc::new(ContextData { client })
+}
+
+/// This type is wrapped by [`kube::Context`] and contains information we need during [`reconcile`].
+#[derive(Clone)]
+pub(crate) struct ContextData {
+    client: Client,
+}
+
+/// The [`reconcile`] function has [`TestSuite`] and [`Context`] as its inputs. For convenience, we
+/// combine these and provide accessor and helper functions.
+pub(crate) struct TestSuiteInterface {
+    /// The cached [`TestSuite`] object.
+    test_suite: TestSuite,
+    /// Clients for the namespace of the `TestSuite`, which its children are in too.
+    test_suite_client: TestSuiteClient,
+    test_client: TestClient,
+    resource_client: ResourceClient,
+}
+
+impl TestSuiteInterface {
+    /// Create a new `TestSuiteInterface` from the [`TestSuite`] and [`Context`].
+    pub(crate) fn new(test_suite: TestSuite, context: Context) -> Result<Self> {
+        let namespace = test_suite.object_namespace();
+        Ok(Self {
+            test_suite_client: TestSuiteClient::new_namespaced(context.client.clone(), namespace),
+            test_client: TestClient::new_namespaced(context.client.clone(), namespace),
+            resource_client: ResourceClient::new_namespaced(context.client.clone(), namespace),
+            test_suite,
+        })
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.test_suite.object_name()
+    }
+
+    pub(crate) fn test_suite(&self) -> &TestSuite {
+        &self.test_suite
+    }
+
+    /// Access the inner `TestSuiteClient` object with fewer keystrokes.
+    pub(super) fn test_suite_client(&self) -> &TestSuiteClient {
+        &self.test_suite_client
+    }
+
+    pub(super) fn test_client(&self) -> &TestClient {
+        &self.test_client
+    }
+
+    pub(super) fn resource_client(&self) -> &ResourceClient {
+        &self.resource_client
+    }
+
+    /// List the `Test`s that were created for this suite.
+    pub(super) async fn child_tests(&self) -> Result<Vec<Test>> {
+        Ok(self
+            .test_client()
+            .api()
+            .list(&self.child_list_params())
+            .await
+            .with_context(|| format!("Unable to list tests of test suite '{}'", self.name()))?
+            .items)
+    }
+
+    /// List the `Resource`s that were created for this suite.
+    pub(super) async fn child_resources(&self) -> Result<Vec<Resource>> {
+        Ok(self
+            .resource_client()
+            .api()
+            .list(&self.child_list_params())
+            .await
+            .with_context(|| format!("Unable to list resources of test suite '{}'", self.name()))?
+            .items)
+    }
+
+    /// Create the `Resource`s and `Test`s described by this suite. Objects that already exist, for
+    /// example from an earlier attempt that was interrupted, are left as they are.
+    pub(super) async fn create_children(&self) -> Result<()> {
+        for resource in self.test_suite().child_resources() {
+            let name = resource.object_name().to_string();
+            let result = self
+                .resource_client()
+                .api()
+                .create(&PostParams::default(), &resource)
+                .await;
+            if result.is_status_code(StatusCode::CONFLICT) {
+                debug!("Resource '{}' already exists", name);
+                continue;
+            }
+            let _ = result.with_context(|| {
+                format!(
+                    "Unable to create resource '{}' for test suite '{}'",
+                    name,
+                    self.name()
+                )
+            })?;
+        }
+        for test in self.test_suite().child_tests() {
+            let name = test.object_name().to_string();
+            let result = self
+                .test_client()
+                .api()
+                .create(&PostParams::default(), &test)
+                .await;
+            if result.is_status_code(StatusCode::CONFLICT) {
+                debug!("Test '{}' already exists", name);
+                continue;
+            }
+            let _ = result.with_context(|| {
+                format!(
+                    "Unable to create test '{}' for test suite '{}'",
+                    name,
+                    self.name()
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    fn child_list_params(&self) -> ListParams {
+        ListParams::default().labels(&self.test_suite().child_selector())
+    }
+}
@@ -0,0 +1,60 @@
+use crate::constants::requeue;
+use crate::error::ReconciliationError;
+use crate::metrics::Metrics;
+use crate::namespaces::Namespaces;
+use crate::test_suite_controller::context::{new_context, Context};
+use crate::test_suite_controller::reconcile::reconcile;
+use futures::future::join_all;
+use futures::StreamExt;
+use kube_runtime::controller::Action as RequeueAction;
+use kube_runtime::{controller, watcher, Controller};
+use log::{debug, error};
+use std::sync::Arc;
+use testsys_model::TestSuite;
+
+mod action;
+mod context;
+mod reconcile;
+
+/// Runs the controller for the `TestSuite`s in each of `namespaces`.
+pub(super) async fn run_test_suite_controller(
+    client: kube::Client,
+    namespaces: &Namespaces,
+    metrics: &Metrics,
+) {
+    let context = new_context(client.clone());
+    join_all(
+        namespaces
+            .apis::<TestSuite>(&client)
+            .into_iter()
+            .map(|api| {
+                Controller::new(api, watcher::Config::default())
+                    .run(
+                        metrics.measured("test-suite", reconcile),
+                        handle_reconciliation_error,
+                        context.clone(),
+                    )
+                    .for_each(|reconciliation_result| async move {
+                        if let Err(reconciliation_err) = reconciliation_result {
+                            match &reconciliation_err {
+                                controller::Error::ObjectNotFound { .. } => {
+                                    debug!("Object is gone: {}", reconciliation_err)
+                                }
+                                _ => error!("Error during reconciliation: {}", reconciliation_err),
+                            }
+                        }
+                    })
+            }),
+    )
+    .await;
+}
+
+/// `handle_reconciliation_error` is called when `reconcile` returns an error.
+fn handle_reconciliation_error(
+    _: Arc<TestSuite>,
+    e: &ReconciliationError,
+    _: Context,
+) -> RequeueAction {
+    error!("Test suite reconciliation error: {}", e);
+    requeue()
+}
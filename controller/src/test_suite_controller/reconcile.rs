@@ -0,0 +1,105 @@
+use crate::constants::{no_requeue, requeue, requeue_slow};
+use crate::error::ReconciliationResult;
+use crate::test_suite_controller::action::{determine_action, Action};
+use crate::test_suite_controller::context::{Context, TestSuiteInterface};
+use anyhow::Context as AnyhowContext;
+use kube_runtime::controller::Action as RequeueAction;
+use log::{debug, error, trace};
+use std::ops::Deref;
+use std::sync::Arc;
+use testsys_model::clients::{AllowNotFound, CrdClient};
+use testsys_model::constants::FINALIZER_MAIN;
+use testsys_model::{CrdExt, TestSuite};
+
+/// `reconcile` is called when a new `TestSuite` object arrives, or when a `TestSuite` object has
+/// been re-queued. This is the entrypoint to the controller logic.
+pub(crate) async fn reconcile(
+    t: Arc<TestSuite>,
+    context: Context,
+) -> ReconciliationResult<RequeueAction> {
+    let t = TestSuiteInterface::new(t.deref().clone(), context)?;
+    let action = determine_action(&t).await?;
+    trace!("action {:?}", action);
+    match action {
+        Action::Initialize => {
+            t.test_suite_client()
+                .initialize_status(t.name())
+                .await
+                .context(format!("Unable to initialize status for '{}'", t.name()))?;
+            Ok(requeue())
+        }
+        Action::AddMainFinalizer => {
+            t.test_suite_client()
+                .add_finalizer(FINALIZER_MAIN, t.test_suite())
+                .await
+                .context(format!("Unable to add main finalizer for '{}'", t.name()))?;
+            Ok(requeue())
+        }
+        Action::CreateChildren => {
+            t.create_children().await?;
+            t.test_suite_client()
+                .send_children_created(t.name())
+                .await
+                .context(format!(
+                    "Unable to mark children created for '{}'",
+                    t.name()
+                ))?;
+            Ok(requeue())
+        }
+        Action::UpdateStatus(status) => {
+            t.test_suite_client()
+                .send_status(t.name(), *status)
+                .await
+                .context(format!("Unable to send status for '{}'", t.name()))?;
+            Ok(requeue())
+        }
+        Action::WaitForTests => Ok(requeue()),
+        Action::Done => {
+            debug!("Test suite '{}' is done", t.name());
+            Ok(requeue_slow())
+        }
+        Action::DeleteTests => {
+            for test in t.child_tests().await? {
+                t.test_client()
+                    .delete(test.object_name())
+                    .await
+                    .allow_not_found(|_| ())
+                    .context(format!(
+                        "Unable to delete test '{}' of '{}'",
+                        test.object_name(),
+                        t.name()
+                    ))?;
+            }
+            Ok(requeue())
+        }
+        Action::DeleteResources => {
+            for resource in t.child_resources().await? {
+                t.resource_client()
+                    .delete(resource.object_name())
+                    .await
+                    .allow_not_found(|_| ())
+                    .context(format!(
+                        "Unable to delete resource '{}' of '{}'",
+                        resource.object_name(),
+                        t.name()
+                    ))?;
+            }
+            Ok(requeue())
+        }
+        Action::WaitForChildDeletion => Ok(requeue()),
+        Action::RemoveMainFinalizer => {
+            t.test_suite_client()
+                .remove_finalizer(FINALIZER_MAIN, t.test_suite())
+                .await
+                .context(format!(
+                    "Unable to remove main finalizer for '{}'",
+                    t.name()
+                ))?;
+            Ok(no_requeue())
+        }
+        Action::Error(state) => {
+            error!("Error state for test suite '{}': {}", t.name(), state);
+            Ok(requeue_slow())
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use crate::error::Result;
 use anyhow::Context;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::time::Duration;
+use testsys_model::{TaskState, Test};
 
 const UNITS: [(char, u64); 3] = [('d', 86400), ('h', 3600), ('m', 60)];
 
@@ -28,6 +29,65 @@ pub(crate) fn parse_duration(input: &str) -> Result<Duration> {
     Ok(Duration::from_secs(secs))
 }
 
+/// Finds a cycle that passes through `start` in a graph where `edges` maps each node to the nodes
+/// it is waiting for. The nodes of the shortest such cycle are returned in order beginning with
+/// `start`; the last node waits for `start`.
+pub(crate) fn find_cycle(
+    start: &str,
+    edges: &BTreeMap<String, Vec<String>>,
+) -> Option<Vec<String>> {
+    // Breadth-first search from `start`, remembering how each node was first reached.
+    let mut reached_from: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for next in edges.get(node).into_iter().flatten() {
+            if next == start {
+                let mut cycle = vec![node.to_string()];
+                let mut current = node;
+                while current != start {
+                    current = reached_from.get(current)?;
+                    cycle.push(current.to_string());
+                }
+                cycle.reverse();
+                return Some(cycle);
+            }
+            if !reached_from.contains_key(next.as_str()) {
+                reached_from.insert(next, node);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Formats the nodes of a cycle found by `find_cycle`, e.g. `a -> b -> a`.
+pub(crate) fn display_cycle(cycle: &[String]) -> String {
+    cycle
+        .iter()
+        .chain(cycle.first())
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// Whether the agent of `test` has finished, successfully or not, so that its outputs will not
+/// change.
+pub(crate) fn is_finished(test: &Test) -> bool {
+    !matches!(
+        test.agent_status().task_state,
+        TaskState::Unknown | TaskState::Running
+    )
+}
+
+/// Describes a test whose outputs are used but which does not exist. The test may not have been
+/// created yet, so this is waited for instead of being an error.
+pub(crate) fn missing_test_message(needed: &str) -> String {
+    format!(
+        "Test '{}' is used for its outputs but does not exist",
+        needed
+    )
+}
+
 #[test]
 fn all_units() {
     let input = "1d2h3m4s";
@@ -97,3 +157,30 @@ fn missing_value() {
     let input = "5hm4s";
     assert!(parse_duration(input).is_err())
 }
+
+#[cfg(test)]
+pub(crate) fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn cycles() {
+    let edges: BTreeMap<String, Vec<String>> = [
+        ("a", names(&["b"])),
+        ("b", names(&["c", "d"])),
+        ("c", names(&["a"])),
+        ("d", names(&["d"])),
+        ("e", names(&["a"])),
+    ]
+    .into_iter()
+    .map(|(node, next)| (node.to_string(), next))
+    .collect();
+    let cycle = names(&["a", "b", "c"]);
+    assert_eq!(find_cycle("a", &edges), Some(cycle.clone()));
+    assert_eq!(display_cycle(&cycle), "a -> b -> c -> a");
+    assert_eq!(find_cycle("c", &edges), Some(names(&["c", "a", "b"])));
+    assert_eq!(find_cycle("d", &edges), Some(names(&["d"])));
+    // `e` waits for a cycle but is not part of it.
+    assert!(find_cycle("e", &edges).is_none());
+    assert!(find_cycle("f", &edges).is_none());
+}
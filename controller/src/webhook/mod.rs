@@ -0,0 +1,206 @@
+use crate::error::Result;
+use crate::namespaces::controller_namespace;
+use crate::webhook::validate::review;
+use anyhow::Context;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use k8s_openapi::api::admissionregistration::v1::ValidatingWebhookConfiguration;
+use k8s_openapi::ByteString;
+use kube::api::{Patch, PatchParams};
+use kube::core::admission::{AdmissionRequest, AdmissionReview};
+use kube::core::DynamicObject;
+use kube::{Api, Client};
+use log::{debug, error, info, warn};
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use testsys_model::system::{
+    TESTSYS_CONTROLLER_WEBHOOK, TESTSYS_WEBHOOK, TESTSYS_WEBHOOK_PATH, TESTSYS_WEBHOOK_PORT,
+};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+mod validate;
+
+/// How often the controller makes sure that the webhook configuration trusts its certificate.
+const CA_BUNDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Serves the validating admission webhook if it has been enabled with the
+/// `TESTSYS_CONTROLLER_WEBHOOK` environment variable. The webhook checks `Test`s and `Resource`s
+/// for mistakes when they are created so that they are rejected by the k8s API instead of failing
+/// during reconciliation.
+pub(super) async fn run_webhook(client: Client) {
+    let enabled = match env::var(TESTSYS_CONTROLLER_WEBHOOK) {
+        Ok(s) => s == true.to_string(),
+        Err(e) => {
+            debug!(
+                "Unable to read environment variable '{}': {}",
+                TESTSYS_CONTROLLER_WEBHOOK, e
+            );
+            false
+        }
+    };
+    if !enabled {
+        return;
+    }
+    if let Err(e) = serve(client).await {
+        error!("The validating webhook has stopped: {:?}", e);
+    }
+}
+
+async fn serve(client: Client) -> Result<()> {
+    // The API server reaches the webhook through its service, which is in the controller's
+    // namespace, so the certificate only needs to be valid for the service's DNS names.
+    let namespace = controller_namespace();
+    let certificate = rcgen::generate_simple_self_signed(vec![
+        format!("{}.{}.svc", TESTSYS_WEBHOOK, namespace),
+        format!("{}.{}.svc.cluster.local", TESTSYS_WEBHOOK, namespace),
+    ])
+    .context("Unable to generate the webhook certificate")?;
+    let ca_bundle = ByteString(
+        certificate
+            .serialize_pem()
+            .context("Unable to serialize the webhook certificate")?
+            .into_bytes(),
+    );
+    let tls_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![Certificate(
+                certificate
+                    .serialize_der()
+                    .context("Unable to serialize the webhook certificate")?,
+            )],
+            PrivateKey(certificate.serialize_private_key_der()),
+        )
+        .context("Unable to create the webhook TLS configuration")?;
+    let acceptor = TlsAcceptor::from(Arc::new(tls_config));
+
+    tokio::spawn(maintain_ca_bundle(client.clone(), ca_bundle));
+
+    let address = SocketAddr::from(([0, 0, 0, 0], TESTSYS_WEBHOOK_PORT));
+    let listener = TcpListener::bind(address)
+        .await
+        .context(format!("Unable to listen on '{}'", address))?;
+    info!("Serving the validating webhook on '{}'", address);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Unable to accept webhook connection: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let client = client.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!("TLS handshake with '{}' failed: {}", peer, e);
+                    return;
+                }
+            };
+            let service = service_fn(move |request| handle(client.clone(), request));
+            if let Err(e) = Http::new().serve_connection(stream, service).await {
+                debug!("Webhook connection with '{}' failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    client: Client,
+    request: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    if request.method() != Method::POST || request.uri().path() != TESTSYS_WEBHOOK_PATH {
+        return Ok(response(StatusCode::NOT_FOUND, Body::empty()));
+    }
+    let admission_request = match parse_request(request).await {
+        Ok(admission_request) => admission_request,
+        Err(e) => {
+            warn!("Unable to parse admission review: {:?}", e);
+            return Ok(response(StatusCode::BAD_REQUEST, Body::from(e.to_string())));
+        }
+    };
+    let review = review(&client, &admission_request).await.into_review();
+    Ok(match serde_json::to_vec(&review) {
+        Ok(body) => response(StatusCode::OK, Body::from(body)),
+        Err(e) => {
+            error!("Unable to serialize admission review: {}", e);
+            response(StatusCode::INTERNAL_SERVER_ERROR, Body::empty())
+        }
+    })
+}
+
+async fn parse_request(request: Request<Body>) -> Result<AdmissionRequest<DynamicObject>> {
+    let body = hyper::body::to_bytes(request.into_body())
+        .await
+        .context("Unable to read request body")?;
+    let review: AdmissionReview<DynamicObject> =
+        serde_json::from_slice(&body).context("Unable to deserialize admission review")?;
+    review
+        .try_into()
+        .context("The admission review does not contain a request")
+}
+
+fn response(status: StatusCode, body: Body) -> Response<Body> {
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response
+}
+
+/// The webhook configuration is created by `TestManager::install` without a `caBundle`, and
+/// reinstalling testsys replaces it. Periodically make sure the API server trusts the certificate
+/// this controller generated.
+async fn maintain_ca_bundle(client: Client, ca_bundle: ByteString) {
+    let api: Api<ValidatingWebhookConfiguration> = Api::all(client);
+    loop {
+        if let Err(e) = update_ca_bundle(&api, &ca_bundle).await {
+            error!(
+                "Unable to update the webhook certificate authority: {:?}",
+                e
+            );
+        }
+        tokio::time::sleep(CA_BUNDLE_INTERVAL).await;
+    }
+}
+
+async fn update_ca_bundle(
+    api: &Api<ValidatingWebhookConfiguration>,
+    ca_bundle: &ByteString,
+) -> Result<()> {
+    let mut configuration = api.get(TESTSYS_WEBHOOK).await.context(format!(
+        "Unable to get webhook configuration '{}'",
+        TESTSYS_WEBHOOK
+    ))?;
+    let webhooks = configuration.webhooks.iter_mut().flatten();
+    let mut changed = false;
+    for webhook in webhooks {
+        if webhook.client_config.ca_bundle.as_ref() != Some(ca_bundle) {
+            webhook.client_config.ca_bundle = Some(ca_bundle.clone());
+            changed = true;
+        }
+    }
+    if !changed {
+        return Ok(());
+    }
+    // The `resourceVersion` of the retrieved object makes the patch fail if it changed meanwhile.
+    api.patch(
+        TESTSYS_WEBHOOK,
+        &PatchParams::default(),
+        &Patch::Merge(&configuration),
+    )
+    .await
+    .context(format!(
+        "Unable to patch webhook configuration '{}'",
+        TESTSYS_WEBHOOK
+    ))?;
+    info!("Updated the certificate authority of the validating webhook");
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use crate::error::Result;
+use crate::job::service_account_problem;
+use anyhow::Context;
+use kube::api::ListParams;
+use kube::core::admission::{AdmissionRequest, AdmissionResponse};
+use kube::core::DynamicObject;
+use kube::{Api, Client, ResourceExt};
+use log::{debug, error};
+use std::collections::BTreeMap;
+use testsys_model::constants::{LABEL_TEST_SUITE, NAMESPACE};
+use testsys_model::validation::{
+    check_resource, check_test, missing_resource_references, missing_test_references, KnownObjects,
+};
+use testsys_model::{Resource, Test, TestSuite};
+
+/// Checks the `Test` or `Resource` in the admission `request` and denies it if any problems are
+/// found. References to objects that do not exist yet only cause warnings, since the objects of a
+/// manifest are created one at a time in any order. Requests are allowed if they cannot be checked,
+/// just as they would be if the webhook could not be reached.
+pub(super) async fn review(
+    client: &Client,
+    request: &AdmissionRequest<DynamicObject>,
+) -> AdmissionResponse {
+    let mut response = AdmissionResponse::from(request);
+    let object = match &request.object {
+        Some(object) => object.clone(),
+        None => return response,
+    };
+    let kind = request.kind.kind.clone();
+    let name = object.name_any();
+    let (problems, warnings) = match problems(client, request, object).await {
+        Ok(findings) => findings,
+        Err(e) => {
+            error!("Unable to validate {} '{}': {:?}", kind, name, e);
+            return response;
+        }
+    };
+    if !warnings.is_empty() {
+        response.warnings = Some(warnings);
+    }
+    if problems.is_empty() {
+        debug!("{} '{}' is valid", kind, name);
+        response
+    } else {
+        response.deny(format!(
+            "{} '{}' is invalid: {}",
+            kind,
+            name,
+            problems.join("; ")
+        ))
+    }
+}
+
+/// The problems with the object, which are reason to deny it, and the references to objects that do
+/// not exist yet, which are only warned about.
+async fn problems(
+    client: &Client,
+    request: &AdmissionRequest<DynamicObject>,
+    object: DynamicObject,
+) -> Result<(Vec<String>, Vec<String>)> {
+    let namespace = request
+        .namespace
+        .clone()
+        .unwrap_or_else(|| NAMESPACE.to_string());
+    let known = known_objects(client, &namespace, object.labels()).await?;
+    match request.kind.kind.as_str() {
+        "Test" => {
+            let test: Test = object.try_parse().context("Unable to parse test")?;
+            Ok((
+                check_test(&test.spec)
+                    .into_iter()
+                    .chain(service_account_problem(&test.spec.agent))
+                    .collect(),
+                missing_test_references(&test.spec, &known),
+            ))
+        }
+        "Resource" => {
+            let resource: Resource = object.try_parse().context("Unable to parse resource")?;
+            Ok((
+                check_resource(&resource.spec)
+                    .into_iter()
+                    .chain(service_account_problem(&resource.spec.agent))
+                    .collect(),
+                missing_resource_references(&resource.spec, &known),
+            ))
+        }
+        _ => Ok(Default::default()),
+    }
+}
+
+/// The names of the `Test`s and `Resource`s that exist in `namespace`. If the object belongs to a
+/// `TestSuite`, the suite's children are included since the suite creates them in no particular
+/// order.
+async fn known_objects(
+    client: &Client,
+    namespace: &str,
+    labels: &BTreeMap<String, String>,
+) -> Result<KnownObjects> {
+    let mut known = KnownObjects::default();
+    known.tests.extend(
+        Api::<Test>::namespaced(client.clone(), namespace)
+            .list_metadata(&ListParams::default())
+            .await
+            .context("Unable to list tests")?
+            .into_iter()
+            .map(|test| test.name_any()),
+    );
+    known.resources.extend(
+        Api::<Resource>::namespaced(client.clone(), namespace)
+            .list_metadata(&ListParams::default())
+            .await
+            .context("Unable to list resources")?
+            .into_iter()
+            .map(|resource| resource.name_any()),
+    );
+    if let Some(suite_name) = labels.get(LABEL_TEST_SUITE) {
+        let suite = Api::<TestSuite>::namespaced(client.clone(), namespace)
+            .get_opt(suite_name)
+            .await
+            .context(format!("Unable to get test suite '{}'", suite_name))?;
+        if let Some(suite) = suite {
+            known.tests.extend(suite.spec.tests.into_keys());
+            known.resources.extend(suite.spec.resources.into_keys());
+        }
+    }
+    Ok(known)
+}
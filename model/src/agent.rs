@@ -1,4 +1,8 @@
+use crate::clients::{config_strings, Source, Template};
 use crate::error::{self, Error, Result};
+use k8s_openapi::api::core::v1::{
+    Affinity, Container, EnvVar, ResourceRequirements, Toleration, Volume, VolumeMount,
+};
 use k8s_openapi::serde::Deserializer;
 use regex::Regex;
 use schemars::gen::SchemaGenerator;
@@ -33,7 +37,7 @@ impl Default for TaskState {
 
 serde_plain::derive_display_from_serialize!(TaskState);
 
-#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Agent {
     /// The name of the agent.
@@ -59,6 +63,40 @@ pub struct Agent {
     pub capabilities: Option<Vec<String>>,
     /// Whether the agent container needs to be privileged or not
     pub privileged: Option<bool>,
+    /// Node labels that a node must have for the agent pod to be scheduled on it.
+    pub node_selector: Option<BTreeMap<String, String>>,
+    /// Tolerations that allow the agent pod to be scheduled on nodes with matching taints.
+    #[schemars(schema_with = "crate::schema_utils::k8s_object_list")]
+    pub tolerations: Option<Vec<Toleration>>,
+    /// Node and pod affinity rules for scheduling the agent pod.
+    #[schemars(schema_with = "crate::schema_utils::k8s_object")]
+    pub affinity: Option<Affinity>,
+    /// The CPU and memory requests and limits of the agent container.
+    #[schemars(schema_with = "crate::schema_utils::k8s_object")]
+    pub resources: Option<ResourceRequirements>,
+    /// The service account that the agent pod runs as instead of the default test agent or resource
+    /// agent service account. The controller only runs agents as the service accounts that it has
+    /// been installed to allow, since the agent pod is created with the controller's permissions.
+    pub service_account: Option<String>,
+    /// Environment variables for the agent container, in addition to the `TESTSYS_` variables that
+    /// the controller sets. Variables with the `TESTSYS_` prefix are reserved and are not set.
+    #[schemars(schema_with = "crate::schema_utils::k8s_object_list")]
+    pub env: Option<Vec<EnvVar>>,
+    /// Volumes to add to the agent pod, e.g. a `configMap`, `emptyDir` or `persistentVolumeClaim`.
+    #[schemars(schema_with = "crate::schema_utils::k8s_object_list")]
+    pub volumes: Option<Vec<Volume>>,
+    /// Where to mount the agent's `volumes` in the agent container.
+    #[schemars(schema_with = "crate::schema_utils::k8s_object_list")]
+    pub volume_mounts: Option<Vec<VolumeMount>>,
+    /// Containers that run to completion, in order, before the agent container starts, e.g. to
+    /// fetch tools at pinned versions.
+    #[schemars(schema_with = "crate::schema_utils::k8s_object_list")]
+    pub init_containers: Option<Vec<Container>>,
+    /// Containers that run alongside the agent container, e.g. a proxy or a log shipper. The agent
+    /// is done when the agent container exits, and its sidecars are stopped then by suspending the
+    /// agent's job.
+    #[schemars(schema_with = "crate::schema_utils::k8s_object_list")]
+    pub sidecars: Option<Vec<Container>>,
 }
 
 impl Agent {
@@ -68,6 +106,23 @@ impl Agent {
             .map(|secrets_map| secrets_map.values().collect::<BTreeSet<&SecretName>>())
             .unwrap_or_default()
     }
+
+    /// The names of the tests whose outputs are referred to by `${test:<test>.<field>}` templates
+    /// in the configuration. Invalid templates are ignored.
+    pub fn referenced_tests(&self) -> BTreeSet<String> {
+        self.configuration
+            .iter()
+            .flat_map(config_strings)
+            .filter_map(|input| Template::parse(input).ok())
+            .flat_map(|template| {
+                template
+                    .references()
+                    .filter(|reference| reference.source == Source::Test)
+                    .map(|reference| reference.name.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
 }
 
 pub fn config_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
@@ -85,6 +140,19 @@ pub fn config_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema
     schema.into()
 }
 
+/// The last component of the image's path without its tag or digest, e.g. `eks-resource-agent` for
+/// `public.ecr.aws/bottlerocket-test-system/eks-resource-agent:v0.0.14`.
+pub(crate) fn image_name(image: &str) -> &str {
+    let image = image.split('@').next().unwrap_or(image);
+    let name = image.rsplit('/').next().unwrap_or(image);
+    name.split(':').next().unwrap_or(name)
+}
+
+/// The pattern that durations, like an agent's `timeout`, must match, e.g. `1d2h3m4s` or `90`
+/// (seconds).
+pub(crate) const TIMEOUT_PATTERN_REGEX: &str =
+    r"^((([0-9]+)d)?(([0-9]+)h)?(([0-9]+)m)?(([0-9]+)s)?|\d+)$";
+
 pub fn timeout_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
     let mut extensions = BTreeMap::<String, Value>::new();
     extensions.insert("nullable".to_string(), Value::Bool(true));
@@ -92,7 +160,7 @@ pub fn timeout_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schem
         string: Some(Box::new(StringValidation {
             max_length: Some(253),
             min_length: Some(1),
-            pattern: Some(r"^((([0-9]+)d)?(([0-9]+)h)?(([0-9]+)m)?(([0-9]+)s)?|\d+)$".to_string()),
+            pattern: Some(TIMEOUT_PATTERN_REGEX.to_string()),
         })),
         instance_type: Some(InstanceType::String.into()),
         extensions,
@@ -36,6 +36,7 @@ pub trait CrdClient: Sized {
     fn kind(&self) -> &'static str;
     fn api(&self) -> &Api<Self::Crd>;
 
+    /// Create a client for the `testsys` namespace.
     async fn new() -> Result<Self> {
         let k8s_client = kube::Client::try_default()
             .await
@@ -43,10 +44,26 @@ pub trait CrdClient: Sized {
         Ok(Self::new_from_k8s_client(k8s_client))
     }
 
+    /// Create a client for the default namespace of the inferred `kube::Client`. Inside of a pod,
+    /// e.g. an agent's, this is the namespace of the pod.
+    async fn new_default_namespaced() -> Result<Self> {
+        let k8s_client = kube::Client::try_default()
+            .await
+            .context(error::InitializationSnafu)?;
+        let namespace = k8s_client.default_namespace().to_string();
+        Ok(Self::new_namespaced(k8s_client, &namespace))
+    }
+
+    /// Create a client for the `testsys` namespace.
     fn new_from_k8s_client(k8s_client: kube::Client) -> Self {
         Self::new_from_api(Self::create_api(k8s_client))
     }
 
+    /// Create a client for `namespace`.
+    fn new_namespaced(k8s_client: kube::Client, namespace: &str) -> Self {
+        Self::new_from_api(Api::namespaced(k8s_client, namespace))
+    }
+
     fn create_api(k8s_client: kube::Client) -> Api<Self::Crd> {
         Api::<Self::Crd>::namespaced(k8s_client, NAMESPACE)
     }
@@ -5,9 +5,9 @@ use snafu::Snafu;
 /// The `Result` type returned by `clients`.
 pub type Result<T> = std::result::Result<T, Error>;
 
-/// The public error type returned by `clients`.
+/// The public error type returned by `clients`. The inner error is boxed so that results stay small.
 #[derive(Debug, Snafu)]
-pub struct Error(InnerError);
+pub struct Error(Box<InnerError>);
 
 /// The private error type returned by `clients`.
 #[derive(Debug, Snafu)]
@@ -62,9 +62,15 @@ pub(crate) enum InnerError {
     DeleteFail { name: String },
 }
 
+impl From<InnerError> for Error {
+    fn from(e: InnerError) -> Self {
+        Error(Box::new(e))
+    }
+}
+
 impl From<ModelError> for Error {
     fn from(e: ModelError) -> Self {
-        Error(InnerError::ConfigSerde { source: e })
+        Error(Box::new(InnerError::ConfigSerde { source: e }))
     }
 }
 
@@ -1,14 +1,22 @@
 pub use error::{Error, Result};
 pub use resource_client::ResourceClient;
+pub use resource_pool_client::ResourcePoolClient;
 pub use test_client::TestClient;
+pub use test_schedule_client::TestScheduleClient;
+pub use test_suite_client::TestSuiteClient;
 
 mod crd_client;
 mod error;
 mod http_status_code;
 mod resource_client;
+mod resource_pool_client;
+mod template;
 mod test_client;
+mod test_schedule_client;
+mod test_suite_client;
 
 pub use crd_client::CrdClient;
 pub use http_status_code::{AllowNotFound, HttpStatusCode, StatusCode};
 pub use resource_client::create_resource_crd;
+pub(crate) use template::{config_strings, Source, Template};
 pub use test_client::create_test_crd;
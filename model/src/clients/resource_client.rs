@@ -1,10 +1,12 @@
 use super::error::{self, Result};
-use super::HttpStatusCode;
+use super::{AllowNotFound, HttpStatusCode};
 use crate::clients::crd_client::JsonPatch;
+use crate::clients::template::{Reference, Source, Template};
 use crate::clients::CrdClient;
-use crate::constants::{FINALIZER_RESOURCE, NAMESPACE};
+use crate::clients::TestClient;
+use crate::constants::{FINALIZER_RESOURCE, LABEL_LEASED_BY, NAMESPACE};
 use crate::resource::{ResourceAction, ResourceError};
-use crate::{Configuration, Resource, ResourceSpec, ResourceStatus, TaskState};
+use crate::{Condition, Configuration, Resource, ResourceSpec, ResourceStatus, TaskState};
 use async_recursion::async_recursion;
 use futures::stream::{self, StreamExt};
 use http::StatusCode;
@@ -12,22 +14,11 @@ use kube::core::object::HasStatus;
 use kube::core::ObjectMeta;
 use kube::{Api, ResourceExt};
 use log::trace;
-use regex::Regex;
 use serde_json::{Map, Value};
 use snafu::{ensure, OptionExt, ResultExt};
 use std::collections::BTreeMap;
 use std::time::Duration;
 
-const TEMPLATE_PATTERN_REGEX: &str = r"^\$\{(.+)\.(.+)\}$";
-
-lazy_static::lazy_static! {
-
-    static ref REGEX: Regex = {
-        #[allow(clippy::unwrap_used)]
-        Regex::new(TEMPLATE_PATTERN_REGEX).unwrap()
-    };
-}
-
 /// An API Client for TestSys Resource CRD objects.
 ///
 /// # Example
@@ -42,9 +33,25 @@ lazy_static::lazy_static! {
 #[derive(Clone)]
 pub struct ResourceClient {
     api: Api<Resource>,
+    /// The namespace that this client manages `Resource`s in, unless it was created from an `Api`.
+    namespace: Option<String>,
 }
 
 impl ResourceClient {
+    /// Set the `suspend` flag of the TestSys [`Resource`], which keeps the controller from starting
+    /// its creation and destruction jobs.
+    pub async fn send_suspend<S>(&self, name: S, suspend: bool) -> Result<Resource>
+    where
+        S: AsRef<str> + Send,
+    {
+        self.patch(
+            name,
+            vec![JsonPatch::new_add_operation("/spec/suspend", suspend)],
+            "set 'suspend'",
+        )
+        .await
+    }
+
     pub async fn get_agent_info<C>(&self, name: &str) -> Result<C>
     where
         C: Configuration,
@@ -73,6 +80,23 @@ impl ResourceClient {
         .await
     }
 
+    /// Replace the conditions of the TestSys [`Resource`].
+    pub async fn send_conditions(
+        &self,
+        name: &str,
+        conditions: Vec<Condition>,
+    ) -> Result<Resource> {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_timestamp(),
+                JsonPatch::new_add_operation("/status/conditions", conditions),
+            ],
+            "send conditions",
+        )
+        .await
+    }
+
     pub async fn get_resource_request<R>(&self, name: &str) -> Result<R>
     where
         R: Configuration,
@@ -91,19 +115,63 @@ impl ResourceClient {
     /// This function resolves an agents config by populating it's templated fields.
     /// An agent may use the syntax `${resource_name.field_name}` to have the field
     /// named `field_name` of the resource named `resource_name` populated in the
-    /// configuration map.
+    /// configuration map. The field may be a path into nested objects and arrays, e.g.
+    /// `${resource_name.status.subnets[0]}`, and templates may be part of a larger string, e.g.
+    /// `https://${resource_name.endpoint}/api`. Strings are resolved in nested objects and arrays.
     pub async fn resolve_templated_config(
         &self,
         raw_config: Map<String, Value>,
+    ) -> Result<Map<String, Value>> {
+        self.resolve_templated_config_with_leases(raw_config, &BTreeMap::new())
+            .await
+    }
+
+    /// Resolves an agents config like `resolve_templated_config`, except that a template may also
+    /// name a resource by its key in `leased_resources`, in which case the leased resource is used.
+    pub async fn resolve_templated_config_with_leases(
+        &self,
+        raw_config: Map<String, Value>,
+        leased_resources: &BTreeMap<String, String>,
     ) -> Result<Map<String, Value>> {
         stream::iter(raw_config)
-            .then(|(k, v)| async move { self.resolve_input(v).await.map(|v| (k, v)) })
+            .then(|(k, v)| async move {
+                self.resolve_input(v, leased_resources)
+                    .await
+                    .map(|v| (k, v))
+            })
             .collect::<Vec<Result<(_, _)>>>()
             .await
             .into_iter()
             .collect::<Result<Map<String, Value>>>()
     }
 
+    /// Lease the pooled TestSys [`Resource`] to the test named `test_name`. The lease fails if the
+    /// resource has changed since `resource` was retrieved, e.g. because another test leased it.
+    pub async fn send_lease(&self, resource: &Resource, test_name: &str) -> Result<Resource> {
+        self.patch(
+            resource.name_any(),
+            vec![
+                JsonPatch::new_test_operation(
+                    "/metadata/resourceVersion",
+                    resource.resource_version(),
+                ),
+                JsonPatch::new_add_operation(label_path(LABEL_LEASED_BY), test_name),
+            ],
+            "send lease",
+        )
+        .await
+    }
+
+    /// Return a leased TestSys [`Resource`] to its pool.
+    pub async fn send_release(&self, name: &str) -> Result<Resource> {
+        self.patch(
+            name,
+            vec![JsonPatch::new_remove_operation(label_path(LABEL_LEASED_BY))],
+            "send release",
+        )
+        .await
+    }
+
     pub async fn send_creation_success<R>(
         &self,
         name: &str,
@@ -195,6 +263,47 @@ impl ResourceClient {
         .await
     }
 
+    /// Clear the error of the failed creation or destruction task so that the controller runs the
+    /// task again. `retries` is the new number of retries and `retry_after` is the time, in RFC 3339
+    /// format, before which the controller will not start the job.
+    pub async fn send_retry(
+        &self,
+        name: &str,
+        op: ResourceAction,
+        retries: u32,
+        retry_after: &str,
+    ) -> Result<Resource> {
+        trace!(
+            "patching {:?} retry {} after '{}' for resource '{}'",
+            op,
+            retries,
+            retry_after,
+            name
+        );
+        let path_prefix = match op {
+            ResourceAction::Create => "/status/creation",
+            ResourceAction::Destroy => "/status/destruction",
+        };
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_timestamp(),
+                JsonPatch::new_add_operation(
+                    format!("{}/error", path_prefix),
+                    None::<ResourceError>,
+                ),
+                JsonPatch::new_add_operation(
+                    format!("{}/taskState", path_prefix),
+                    TaskState::Unknown,
+                ),
+                JsonPatch::new_add_operation(format!("{}/retries", path_prefix), retries),
+                JsonPatch::new_add_operation(format!("{}/retryAfter", path_prefix), retry_after),
+            ],
+            "send retry",
+        )
+        .await
+    }
+
     /// Force delete a resource that has an errored destruction pod.
     /// The created resource will need to be cleaned up by the user.
     /// The finalizers for the resource will be deleted and then the resource will be deleted.
@@ -222,7 +331,8 @@ impl ResourceClient {
                 Ok(resource) => {
                     if let Some(status) = resource.status() {
                         ensure!(
-                            status.destruction.task_state != TaskState::Error,
+                            status.destruction.task_state != TaskState::Error
+                                || resource.is_retrying(ResourceAction::Destroy),
                             error::DeleteFailSnafu {
                                 name: resource.name_any()
                             }
@@ -246,76 +356,124 @@ impl ResourceClient {
     }
 
     #[async_recursion]
-    async fn resolve_input(&self, input: Value) -> Result<Value> {
+    async fn resolve_input(
+        &self,
+        input: Value,
+        leased_resources: &BTreeMap<String, String>,
+    ) -> Result<Value> {
         match input {
-            Value::String(input_string) => self.resolve_input_string(input_string).await,
-            Value::Object(map) => self.resolve_templated_config(map).await.map(Value::Object),
+            Value::String(input_string) => {
+                self.resolve_input_string(input_string, leased_resources)
+                    .await
+            }
+            Value::Object(map) => self
+                .resolve_templated_config_with_leases(map, leased_resources)
+                .await
+                .map(Value::Object),
+            Value::Array(values) => stream::iter(values)
+                .then(|v| self.resolve_input(v, leased_resources))
+                .collect::<Vec<Result<_>>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<Value>>>()
+                .map(Value::Array),
             non_string_input => Ok(non_string_input),
         }
     }
 
-    async fn resolve_input_string(&self, input: String) -> Result<Value> {
-        if let Some((resource_name, field_name)) = resource_name_and_field_name(&input)? {
-            let resource = self.get(resource_name).await?;
-            let results = resource
-                .created_resource()
-                .context(error::ConfigResolutionSnafu {
-                    what: "Created resource missing from resource.".to_string(),
-                })?;
-            let updated_value = results
-                .get(&field_name)
-                .context(error::ConfigResolutionSnafu {
-                    what: format!("No field '{}' in created resource", field_name),
-                })?;
-            Ok(updated_value.to_owned())
-        } else {
-            Ok(Value::String(input))
+    async fn resolve_input_string(
+        &self,
+        input: String,
+        leased_resources: &BTreeMap<String, String>,
+    ) -> Result<Value> {
+        let template = Template::parse(&input)?;
+        let mut values = Vec::new();
+        for reference in template.references() {
+            if reference.source == Source::Test {
+                let outputs = self.test_outputs(&reference.name).await?;
+                values.push(reference.lookup(&outputs)?);
+                continue;
+            }
+            values.push(self.resolve_reference(reference, leased_resources).await?);
+        }
+        Ok(template.render(&values))
+    }
+
+    /// The value in the created resource of the resource that `reference` refers to. Each of the
+    /// reference's alternatives is tried in turn since resource names may contain dots.
+    async fn resolve_reference(
+        &self,
+        reference: &Reference,
+        leased_resources: &BTreeMap<String, String>,
+    ) -> Result<Value> {
+        let mut first_error = None;
+        for alternative in reference.alternatives() {
+            let resource_name = leased_resources
+                .get(&alternative.name)
+                .unwrap_or(&alternative.name);
+            let value = match self.get(resource_name).await.allow_not_found(|_| ())? {
+                Some(resource) => lookup_created_resource(&resource, &alternative),
+                None => Err(error::ConfigResolutionSnafu {
+                    what: format!("Resource '{}' does not exist", resource_name),
+                }
+                .build()
+                .into()),
+            };
+            match value {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
         }
+        Err(first_error.unwrap_or_else(|| {
+            error::ConfigResolutionSnafu {
+                what: format!("Unable to resolve '{}'", reference),
+            }
+            .build()
+            .into()
+        }))
+    }
+
+    /// The outputs of the TestSys `Test` named `name`. Outputs are only used once the test has
+    /// completed so that a partial set of outputs is never used.
+    async fn test_outputs(&self, name: &str) -> Result<Map<String, Value>> {
+        let k8s_client = self.api().clone().into_client();
+        // `Test`s that a resource refers to are in the same namespace as the resource.
+        let namespace = self
+            .namespace
+            .as_deref()
+            .context(error::ConfigResolutionSnafu {
+                what: format!("The namespace of test '{}' is not known", name),
+            })?;
+        let test_client = TestClient::new_namespaced(k8s_client, namespace);
+        let test = test_client.get(name).await?;
+        ensure!(
+            test.agent_status().task_state == TaskState::Completed,
+            error::ConfigResolutionSnafu {
+                what: format!("Test '{}' has not completed", name),
+            }
+        );
+        Ok(test.agent_status().outputs.clone().unwrap_or_default())
     }
 }
 
-fn resource_name_and_field_name(input: &str) -> Result<Option<(String, String)>> {
-    let captures = match REGEX.captures(input) {
-        None => return Ok(None),
-        Some(some) => some,
-    };
-    let resource_name = captures
-        .get(1)
-        .context(error::ConfigResolutionSnafu {
-            what: "Resource name could not be extracted from capture.".to_string(),
-        })?
-        .as_str();
-    let field_name = captures
-        .get(2)
+/// The value that `reference` refers to in the created resource of `resource`.
+fn lookup_created_resource(resource: &Resource, reference: &Reference) -> Result<Value> {
+    let results = resource
+        .created_resource()
         .context(error::ConfigResolutionSnafu {
-            what: "Resource value could not be extracted from capture.".to_string(),
-        })?
-        .as_str();
-    Ok(Some((resource_name.to_string(), field_name.to_string())))
+            what: "Created resource missing from resource.".to_string(),
+        })?;
+    reference.lookup(results)
 }
 
-#[test]
-fn test_pattern1() {
-    let (resource_name, field_name) = resource_name_and_field_name(r"${dup1.info}")
-        .unwrap()
-        .unwrap();
-    assert_eq!(resource_name, "dup1");
-    assert_eq!(field_name, "info");
-    assert!(resource_name_and_field_name(r"hello").unwrap().is_none());
-    assert!(resource_name_and_field_name(r"${hello}").unwrap().is_none());
-    assert!(resource_name_and_field_name(r"foo${x.y}")
-        .unwrap()
-        .is_none());
-    assert!(resource_name_and_field_name(r"${x.y}foo")
-        .unwrap()
-        .is_none());
-    assert!(resource_name_and_field_name(r"foo${x.y}bar")
-        .unwrap()
-        .is_none());
-    assert!(resource_name_and_field_name(r"${.x}").unwrap().is_none());
-    assert!(resource_name_and_field_name(r"${x.}").unwrap().is_none());
-    assert!(resource_name_and_field_name(r"${.}").unwrap().is_none());
-    assert!(resource_name_and_field_name(r"${.}").unwrap().is_none());
+/// The JSON pointer to the metadata label `key`.
+fn label_path(key: &str) -> String {
+    format!(
+        "/metadata/labels/{}",
+        key.replace('~', "~0").replace('/', "~1")
+    )
 }
 
 impl CrdClient for ResourceClient {
@@ -323,7 +481,21 @@ impl CrdClient for ResourceClient {
     type CrdStatus = ResourceStatus;
 
     fn new_from_api(api: Api<Self::Crd>) -> Self {
-        Self { api }
+        Self {
+            api,
+            namespace: None,
+        }
+    }
+
+    fn new_from_k8s_client(k8s_client: kube::Client) -> Self {
+        Self::new_namespaced(k8s_client, NAMESPACE)
+    }
+
+    fn new_namespaced(k8s_client: kube::Client, namespace: &str) -> Self {
+        Self {
+            api: Api::namespaced(k8s_client, namespace),
+            namespace: Some(namespace.to_string()),
+        }
     }
 
     fn kind(&self) -> &'static str {
@@ -566,6 +738,23 @@ mod test {
         assert_eq!(status.destruction.error.unwrap(), destroy_error);
         assert!(matches!(status.destruction.task_state, TaskState::Error));
 
+        rc.send_retry(
+            RESOURCE_NAME,
+            ResourceAction::Destroy,
+            1,
+            "2024-01-01T00:00:00Z",
+        )
+        .await
+        .unwrap();
+        let status = rc.get_status(RESOURCE_NAME).await.unwrap();
+        assert!(status.destruction.error.is_none());
+        assert!(matches!(status.destruction.task_state, TaskState::Unknown));
+        assert_eq!(status.destruction.retries, 1);
+        assert_eq!(
+            status.destruction.retry_after.as_deref(),
+            Some("2024-01-01T00:00:00Z")
+        );
+
         // Add a finalizer
         rc.add_finalizer("foobar", &rc.get(RESOURCE_NAME).await.unwrap())
             .await
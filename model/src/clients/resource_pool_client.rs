@@ -0,0 +1,57 @@
+use super::error::Result;
+use crate::clients::crd_client::JsonPatch;
+use crate::clients::CrdClient;
+use crate::{ResourcePool, ResourcePoolStatus};
+use kube::Api;
+
+/// An API Client for TestSys ResourcePool CRD objects.
+///
+/// # Example
+///
+/// ```
+///# use testsys_model::clients::{CrdClient, ResourcePoolClient};
+///# async fn no_run() {
+/// let resource_pool_client = ResourcePoolClient::new().await.unwrap();
+/// let resource_pool = resource_pool_client.get("my-resource-pool").await.unwrap();
+///# }
+/// ```
+#[derive(Clone)]
+pub struct ResourcePoolClient {
+    api: Api<ResourcePool>,
+}
+
+impl ResourcePoolClient {
+    /// Replace the status of the TestSys [`ResourcePool`].
+    pub async fn send_status(
+        &self,
+        name: &str,
+        status: ResourcePoolStatus,
+    ) -> Result<ResourcePool> {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_add_operation("/status", status),
+                JsonPatch::new_timestamp(),
+            ],
+            "send resource pool status",
+        )
+        .await
+    }
+}
+
+impl CrdClient for ResourcePoolClient {
+    type Crd = ResourcePool;
+    type CrdStatus = ResourcePoolStatus;
+
+    fn new_from_api(api: Api<Self::Crd>) -> Self {
+        Self { api }
+    }
+
+    fn kind(&self) -> &'static str {
+        "resource pool"
+    }
+
+    fn api(&self) -> &Api<Self::Crd> {
+        &self.api
+    }
+}
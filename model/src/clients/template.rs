@@ -0,0 +1,357 @@
+use super::error::{self, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+use snafu::OptionExt;
+use std::fmt::{Display, Formatter};
+
+/// Matches each `${...}` in a string.
+const PLACEHOLDER_PATTERN_REGEX: &str = r"\$\{([^{}]*)\}";
+
+/// Matches the contents of a placeholder that refers to a resource, i.e. `<resource>.<path>`, or
+/// to a test, i.e. `test:<test>.<path>`. Placeholders that do not match, like `${HOME}`, are left as
+/// they are.
+const REFERENCE_PATTERN_REGEX: &str = r"^(test:)?([A-Za-z0-9_-]+)\.(.*)$";
+
+lazy_static::lazy_static! {
+    static ref PLACEHOLDER_REGEX: Regex = {
+        #[allow(clippy::unwrap_used)]
+        Regex::new(PLACEHOLDER_PATTERN_REGEX).unwrap()
+    };
+    static ref REFERENCE_REGEX: Regex = {
+        #[allow(clippy::unwrap_used)]
+        Regex::new(REFERENCE_PATTERN_REGEX).unwrap()
+    };
+}
+
+/// A string value in an agent's configuration that may refer to the created resources of TestSys
+/// `Resource`s. A reference has the form `${<resource>.<path>}` where `path` is a chain of field
+/// names and array indexes into the created resource, e.g. `${cluster.status.subnets[0]}`. The
+/// outputs of a completed TestSys `Test` are referred to with `${test:<test>.<path>}`.
+///
+/// If the string is a single reference, the referenced value replaces it whatever its type.
+/// Otherwise each reference is replaced by its value as text, e.g. `https://${cluster.endpoint}/api`.
+/// Strings like this were passed to agents unchanged before references could be part of a larger
+/// string.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Part {
+    Literal(String),
+    Reference(Reference),
+}
+
+/// A `${<resource>.<path>}` or `${test:<test>.<path>}` reference in a `Template`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Reference {
+    pub(crate) source: Source,
+    /// The name of the resource or test. A resource may also be named by a lease that refers to it.
+    pub(crate) name: String,
+    /// The path into the created resource as it was written, e.g. `status.subnets[0]`.
+    pub(crate) path: String,
+    segments: Vec<Segment>,
+}
+
+/// The kind of object that a `Reference` refers to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Source {
+    /// The `createdResource` of a `Resource`.
+    Resource,
+    /// The `outputs` of a `Test`.
+    Test,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+impl Template {
+    /// Parses `input`. A placeholder that names a resource but has an invalid path, like `${x.}`
+    /// or `${x.ids[first]}`, is an error.
+    pub(crate) fn parse(input: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut end = 0;
+        for captures in PLACEHOLDER_REGEX.captures_iter(input) {
+            let (placeholder, contents) = match (captures.get(0), captures.get(1)) {
+                (Some(placeholder), Some(contents)) => (placeholder, contents.as_str()),
+                _ => continue,
+            };
+            let reference = match REFERENCE_REGEX.captures(contents) {
+                Some(reference) => reference,
+                None => continue,
+            };
+            let source = match reference.get(1) {
+                Some(_) => Source::Test,
+                None => Source::Resource,
+            };
+            let name = reference.get(2).map_or("", |m| m.as_str());
+            let path = reference.get(3).map_or("", |m| m.as_str());
+            let segments = parse_path(path).context(error::ConfigResolutionSnafu {
+                what: format!("Invalid path '{}' in template '{}'", path, input),
+            })?;
+            if placeholder.start() > end {
+                parts.push(Part::Literal(input[end..placeholder.start()].to_string()));
+            }
+            parts.push(Part::Reference(Reference {
+                source,
+                name: name.to_string(),
+                path: path.to_string(),
+                segments,
+            }));
+            end = placeholder.end();
+        }
+        if end < input.len() {
+            parts.push(Part::Literal(input[end..].to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The references in the template, in the order they appear.
+    pub(crate) fn references(&self) -> impl Iterator<Item = &Reference> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Literal(_) => None,
+            Part::Reference(reference) => Some(reference),
+        })
+    }
+
+    /// Replaces the references with `values`, which are given in the same order as `references`.
+    pub(crate) fn render(&self, values: &[Value]) -> Value {
+        let mut values = values.iter();
+        if let [Part::Reference(_)] = self.parts.as_slice() {
+            return values.next().cloned().unwrap_or(Value::Null);
+        }
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Reference(_) => match values.next() {
+                    Some(Value::String(value)) => rendered.push_str(value),
+                    Some(value) => rendered.push_str(&value.to_string()),
+                    None => {}
+                },
+            }
+        }
+        Value::String(rendered)
+    }
+}
+
+impl Reference {
+    /// Finds the value that the reference refers to in `values`, which are the created resource of
+    /// a resource or the outputs of a test.
+    pub(crate) fn lookup(&self, values: &Map<String, Value>) -> Result<Value> {
+        let mut segments = self.segments.iter();
+        let mut value = match segments.next() {
+            Some(Segment::Field(field)) => values.get(field),
+            _ => None,
+        };
+        for segment in segments {
+            value = match segment {
+                Segment::Field(field) => value.and_then(|value| value.get(field)),
+                Segment::Index(index) => value.and_then(|value| value.get(index)),
+            };
+        }
+        let value = value.cloned().context(error::ConfigResolutionSnafu {
+            what: match self.source {
+                Source::Resource => format!(
+                    "No field '{}' in created resource of '{}'",
+                    self.path, self.name
+                ),
+                Source::Test => format!(
+                    "No field '{}' in outputs of test '{}'",
+                    self.path, self.name
+                ),
+            },
+        })?;
+        Ok(value)
+    }
+
+    /// The ways that the reference can be split into the name of a resource and a path, starting
+    /// with the split at the first `.`. Resource names may contain dots, and references to them like
+    /// `${my.cluster.endpoint}` were split at the last `.` before paths were supported, so the
+    /// resource that such a reference refers to is only known once it is looked up. Test
+    /// references are always split at the first `.`.
+    pub(crate) fn alternatives(&self) -> Vec<Reference> {
+        let mut alternatives = vec![self.clone()];
+        if self.source == Source::Test {
+            return alternatives;
+        }
+        let contents = format!("{}.{}", self.name, self.path);
+        for (index, _) in contents.match_indices('.').skip(1) {
+            let (name, path) = (&contents[..index], &contents[index + 1..]);
+            if let Some(segments) = parse_path(path) {
+                alternatives.push(Reference {
+                    source: Source::Resource,
+                    name: name.to_string(),
+                    path: path.to_string(),
+                    segments,
+                });
+            }
+        }
+        alternatives
+    }
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.source {
+            Source::Resource => write!(f, "${{{}.{}}}", self.name, self.path),
+            Source::Test => write!(f, "${{test:{}.{}}}", self.name, self.path),
+        }
+    }
+}
+
+/// Every string in `configuration`, including the strings in nested objects and arrays.
+pub(crate) fn config_strings(configuration: &Map<String, Value>) -> Vec<&str> {
+    fn collect<'a>(value: &'a Value, strings: &mut Vec<&'a str>) {
+        match value {
+            Value::String(string) => strings.push(string),
+            Value::Object(map) => map.values().for_each(|value| collect(value, strings)),
+            Value::Array(values) => values.iter().for_each(|value| collect(value, strings)),
+            _ => {}
+        }
+    }
+    let mut strings = Vec::new();
+    configuration
+        .values()
+        .for_each(|value| collect(value, &mut strings));
+    strings
+}
+
+/// Splits a path like `status.subnets[0]` into its field names and indexes. Returns `None` if the
+/// path is invalid.
+fn parse_path(path: &str) -> Option<Vec<Segment>> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (field, mut indexes) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if field.is_empty() || field.contains(']') {
+            return None;
+        }
+        segments.push(Segment::Field(field.to_string()));
+        while !indexes.is_empty() {
+            let rest = indexes.strip_prefix('[')?;
+            let close = rest.find(']')?;
+            segments.push(Segment::Index(rest[..close].parse().ok()?));
+            indexes = &rest[close + 1..];
+        }
+    }
+    Some(segments)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn references(input: &str) -> Vec<String> {
+        Template::parse(input)
+            .unwrap()
+            .references()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(references("${dup1.info}"), vec!["${dup1.info}"]);
+        assert_eq!(
+            references("https://${cluster.endpoint}:${cluster.ports[1]}/api"),
+            vec!["${cluster.endpoint}", "${cluster.ports[1]}"]
+        );
+        assert!(references("hello").is_empty());
+        assert!(references("${hello}").is_empty());
+        assert!(references("echo ${HOME} ${file%.txt}").is_empty());
+        let template = Template::parse("ami-${test:migration.image.id}").unwrap();
+        let reference = template.references().next().unwrap();
+        assert_eq!(reference.source, Source::Test);
+        assert_eq!(reference.name, "migration");
+        assert_eq!(reference.to_string(), "${test:migration.image.id}");
+        assert!(Template::parse("${x.}").is_err());
+        assert!(Template::parse("${x.a..b}").is_err());
+        assert!(Template::parse("${x.ids[first]}").is_err());
+        assert!(Template::parse("${x.[0]}").is_err());
+        assert!(Template::parse("${x.ids[0}").is_err());
+    }
+
+    #[test]
+    fn test_pattern1() {
+        let template = Template::parse(r"${dup1.info}").unwrap();
+        let reference = template.references().next().unwrap();
+        assert_eq!(reference.name, "dup1");
+        assert_eq!(reference.path, "info");
+        assert!(references(r"hello").is_empty());
+        assert!(references(r"${hello}").is_empty());
+        // References that are part of a larger string are resolved, they used to be left as they
+        // were.
+        assert_eq!(references(r"foo${x.y}"), vec!["${x.y}"]);
+        assert_eq!(references(r"${x.y}foo"), vec!["${x.y}"]);
+        assert_eq!(references(r"foo${x.y}bar"), vec!["${x.y}"]);
+        assert!(references(r"${.x}").is_empty());
+        assert!(references(r"${.}").is_empty());
+        // A reference without a path used to be left as it was and is now an error.
+        assert!(Template::parse(r"${x.}").is_err());
+    }
+
+    #[test]
+    fn lookup() {
+        let created_resource = json!({
+            "endpoint": "example.com",
+            "status": {"subnets": ["subnet-1", "subnet-2"]},
+        });
+        let created_resource = created_resource.as_object().unwrap();
+        let template = Template::parse("${cluster.status.subnets[1]}").unwrap();
+        let reference = template.references().next().unwrap();
+        assert_eq!(
+            reference.lookup(created_resource).unwrap(),
+            json!("subnet-2")
+        );
+        let template = Template::parse("${cluster.status.subnets[2]}").unwrap();
+        let reference = template.references().next().unwrap();
+        assert!(reference.lookup(created_resource).is_err());
+    }
+
+    #[test]
+    fn dotted_resource_names() {
+        let template = Template::parse("${my.cluster.endpoint}").unwrap();
+        let reference = template.references().next().unwrap();
+        let alternatives: Vec<_> = reference
+            .alternatives()
+            .into_iter()
+            .map(|alternative| (alternative.name, alternative.path))
+            .collect();
+        assert_eq!(
+            alternatives,
+            vec![
+                ("my".to_string(), "cluster.endpoint".to_string()),
+                ("my.cluster".to_string(), "endpoint".to_string()),
+            ]
+        );
+        // The resource `my.cluster` is found by its second alternative.
+        let created_resource = json!({"endpoint": "example.com"});
+        let value = reference
+            .alternatives()
+            .iter()
+            .filter(|alternative| alternative.name == "my.cluster")
+            .find_map(|alternative| alternative.lookup(created_resource.as_object()?).ok());
+        assert_eq!(value, Some(json!("example.com")));
+        let template = Template::parse("${test:my.test.version}").unwrap();
+        let reference = template.references().next().unwrap();
+        assert_eq!(reference.alternatives().len(), 1);
+    }
+
+    #[test]
+    fn render() {
+        let template = Template::parse("${cluster.ports}").unwrap();
+        assert_eq!(template.render(&[json!([80, 443])]), json!([80, 443]));
+        let template = Template::parse("https://${cluster.endpoint}:${cluster.port}/api").unwrap();
+        assert_eq!(
+            template.render(&[json!("example.com"), json!(443)]),
+            json!("https://example.com:443/api")
+        );
+        let template = Template::parse("no templates").unwrap();
+        assert_eq!(template.render(&[]), json!("no templates"));
+    }
+}
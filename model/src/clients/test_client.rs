@@ -2,7 +2,9 @@ use super::error::Result;
 use crate::clients::crd_client::JsonPatch;
 use crate::clients::CrdClient;
 use crate::constants::NAMESPACE;
-use crate::{AgentStatus, TaskState, Test, TestResults, TestSpec, TestStatus};
+use crate::{
+    AgentStatus, Condition, Configuration, TaskState, Test, TestResults, TestSpec, TestStatus,
+};
 use kube::core::ObjectMeta;
 use kube::Api;
 use std::collections::BTreeMap;
@@ -41,6 +43,33 @@ impl TestClient {
         .await
     }
 
+    /// Ask the agent of the TestSys [`Test`] to cancel the test by setting the `cancel` flag.
+    pub async fn send_cancel<S>(&self, name: S) -> Result<Test>
+    where
+        S: AsRef<str> + Send,
+    {
+        self.patch(
+            name,
+            vec![JsonPatch::new_add_operation("/spec/cancel", true)],
+            "set 'cancel'",
+        )
+        .await
+    }
+
+    /// Set the `suspend` flag of the TestSys [`Test`], which keeps the controller from starting its
+    /// agent job.
+    pub async fn send_suspend<S>(&self, name: S, suspend: bool) -> Result<Test>
+    where
+        S: AsRef<str> + Send,
+    {
+        self.patch(
+            name,
+            vec![JsonPatch::new_add_operation("/spec/suspend", suspend)],
+            "set 'suspend'",
+        )
+        .await
+    }
+
     /// Get the TestSys [`Test`]'s `status.agent` field.
     pub async fn get_agent_status<S>(&self, name: S) -> Result<AgentStatus>
     where
@@ -49,6 +78,19 @@ impl TestClient {
         Ok(self.get(name).await?.status.unwrap_or_default().agent)
     }
 
+    /// Replace the conditions of the TestSys [`Test`].
+    pub async fn send_conditions(&self, name: &str, conditions: Vec<Condition>) -> Result<Test> {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_timestamp(),
+                JsonPatch::new_add_operation("/status/conditions", conditions),
+            ],
+            "send conditions",
+        )
+        .await
+    }
+
     pub async fn send_resource_error(&self, test_name: &str, error: &str) -> Result<Test> {
         self.patch_status(
             test_name,
@@ -61,6 +103,27 @@ impl TestClient {
         .await
     }
 
+    /// Record the resources leased for the TestSys [`Test`], keyed by the names used in the test's
+    /// `leases`.
+    pub async fn send_leased_resources(
+        &self,
+        name: &str,
+        leased_resources: BTreeMap<String, String>,
+    ) -> Result<Test> {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_timestamp(),
+                JsonPatch::new_add_operation(
+                    "/status/controller/leasedResources",
+                    leased_resources,
+                ),
+            ],
+            "send leased resources",
+        )
+        .await
+    }
+
     pub async fn send_agent_task_state(&self, name: &str, task_state: TaskState) -> Result<Test> {
         self.patch_status(
             name,
@@ -86,6 +149,22 @@ impl TestClient {
         .await
     }
 
+    /// Replace the outputs that the test agent publishes for other tests and resources to use.
+    pub async fn send_outputs<O>(&self, name: &str, outputs: O) -> Result<Test>
+    where
+        O: Configuration,
+    {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_timestamp(),
+                JsonPatch::new_add_operation("/status/agent/outputs", outputs),
+            ],
+            "send outputs",
+        )
+        .await
+    }
+
     pub async fn send_test_update(&self, name: &str, results: TestResults) -> Result<Test> {
         self.patch_status(
             name,
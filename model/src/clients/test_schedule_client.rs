@@ -0,0 +1,84 @@
+use super::error::Result;
+use crate::clients::crd_client::JsonPatch;
+use crate::clients::CrdClient;
+use crate::{TestSchedule, TestScheduleStatus};
+use kube::Api;
+
+/// An API Client for TestSys TestSchedule CRD objects.
+///
+/// # Example
+///
+/// ```
+///# use testsys_model::clients::{CrdClient, TestScheduleClient};
+///# async fn no_run() {
+/// let test_schedule_client = TestScheduleClient::new().await.unwrap();
+/// let test_schedule = test_schedule_client.get("my-test-schedule").await.unwrap();
+///# }
+/// ```
+#[derive(Clone)]
+pub struct TestScheduleClient {
+    api: Api<TestSchedule>,
+}
+
+impl TestScheduleClient {
+    /// Replace the status of the TestSys [`TestSchedule`].
+    pub async fn send_status(
+        &self,
+        name: &str,
+        status: TestScheduleStatus,
+    ) -> Result<TestSchedule> {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_add_operation("/status", status),
+                JsonPatch::new_timestamp(),
+            ],
+            "send test schedule status",
+        )
+        .await
+    }
+
+    /// Record the time of the most recent run of the TestSys [`TestSchedule`] that was due.
+    pub async fn send_last_schedule_time(&self, name: &str, time: String) -> Result<TestSchedule> {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_add_operation("/status/lastScheduleTime", time),
+                JsonPatch::new_timestamp(),
+            ],
+            "send last schedule time",
+        )
+        .await
+    }
+
+    /// Set the `suspend` flag of the TestSys [`TestSchedule`], which keeps the controller from
+    /// starting new runs.
+    pub async fn send_suspend<S>(&self, name: S, suspend: bool) -> Result<TestSchedule>
+    where
+        S: AsRef<str> + Send,
+    {
+        self.patch(
+            name,
+            vec![JsonPatch::new_add_operation("/spec/suspend", suspend)],
+            "set 'suspend'",
+        )
+        .await
+    }
+}
+
+impl CrdClient for TestScheduleClient {
+    type Crd = TestSchedule;
+    type CrdStatus = TestScheduleStatus;
+
+    fn new_from_api(api: Api<Self::Crd>) -> Self {
+        Self { api }
+    }
+
+    fn kind(&self) -> &'static str {
+        "test schedule"
+    }
+
+    fn api(&self) -> &Api<Self::Crd> {
+        &self.api
+    }
+}
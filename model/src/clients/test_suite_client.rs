@@ -0,0 +1,66 @@
+use super::error::Result;
+use crate::clients::crd_client::JsonPatch;
+use crate::clients::CrdClient;
+use crate::{TestSuite, TestSuiteStatus};
+use kube::Api;
+
+/// An API Client for TestSys TestSuite CRD objects.
+///
+/// # Example
+///
+/// ```
+///# use testsys_model::clients::{CrdClient, TestSuiteClient};
+///# async fn no_run() {
+/// let test_suite_client = TestSuiteClient::new().await.unwrap();
+/// let test_suite = test_suite_client.get("my-test-suite").await.unwrap();
+///# }
+/// ```
+#[derive(Clone)]
+pub struct TestSuiteClient {
+    api: Api<TestSuite>,
+}
+
+impl TestSuiteClient {
+    /// Mark the child `Test`s and `Resource`s of the TestSys [`TestSuite`] as created.
+    pub async fn send_children_created(&self, name: &str) -> Result<TestSuite> {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_timestamp(),
+                JsonPatch::new_add_operation("/status/childrenCreated", true),
+            ],
+            "send children created",
+        )
+        .await
+    }
+
+    /// Replace the aggregate status of the TestSys [`TestSuite`].
+    pub async fn send_status(&self, name: &str, status: TestSuiteStatus) -> Result<TestSuite> {
+        self.patch_status(
+            name,
+            vec![
+                JsonPatch::new_add_operation("/status", status),
+                JsonPatch::new_timestamp(),
+            ],
+            "send test suite status",
+        )
+        .await
+    }
+}
+
+impl CrdClient for TestSuiteClient {
+    type Crd = TestSuite;
+    type CrdStatus = TestSuiteStatus;
+
+    fn new_from_api(api: Api<Self::Crd>) -> Self {
+        Self { api }
+    }
+
+    fn kind(&self) -> &'static str {
+        "test suite"
+    }
+
+    fn api(&self) -> &Api<Self::Crd> {
+        &self.api
+    }
+}
@@ -0,0 +1,167 @@
+use chrono::{SecondsFormat, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_plain::derive_display_from_serialize;
+
+/// An observation of one aspect of a `Test` or `Resource`, following the conventions of the k8s
+/// `metav1.Condition` type so that standard tooling, e.g. `kubectl wait --for=condition=Ready`, can
+/// understand the state of TestSys objects. The controller maintains the conditions.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Condition {
+    #[serde(rename = "type")]
+    pub condition_type: ConditionType,
+    pub status: ConditionStatus,
+    /// A CamelCase identifier for the reason of the condition's last change, e.g. `Waiting`.
+    pub reason: String,
+    /// A human readable description of the condition.
+    pub message: String,
+    /// The last time the condition's `status` changed, in RFC 3339 format.
+    pub last_transition_time: String,
+    /// The `metadata.generation` of the object that the condition was set for.
+    pub observed_generation: Option<i64>,
+}
+
+/// The types of `Condition` that the controller maintains.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, JsonSchema)]
+pub enum ConditionType {
+    /// For a `Test`, the test has passed. For a `Resource`, the resource has been created.
+    Ready,
+    /// Every `Resource` needed by a `Test` has been created.
+    ResourcesReady,
+    /// Every object in `dependsOn` is done and, for a `Resource`, no object in `conflictsWith`
+    /// exists.
+    DependenciesMet,
+    /// The agent has finished its task, whether or not it succeeded.
+    Completed,
+    /// The test failed or an error occurred.
+    Failed,
+    /// The object outlived its `maxLifetime` or `ttlAfterFinished`, so the controller is deleting
+    /// it.
+    Expired,
+    /// The controller has admitted the object to start its agent job within its concurrency limits.
+    /// The reason is `Queued` while the object waits for room.
+    Admitted,
+}
+
+derive_display_from_serialize!(ConditionType);
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, JsonSchema)]
+pub enum ConditionStatus {
+    True,
+    False,
+    Unknown,
+}
+
+derive_display_from_serialize!(ConditionStatus);
+
+impl From<bool> for ConditionStatus {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::True
+        } else {
+            Self::False
+        }
+    }
+}
+
+impl Condition {
+    /// Create a `Condition` that transitioned now.
+    pub fn new<S1, S2>(
+        condition_type: ConditionType,
+        status: ConditionStatus,
+        reason: S1,
+        message: S2,
+    ) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        Self {
+            condition_type,
+            status,
+            reason: reason.into(),
+            message: message.into(),
+            last_transition_time: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            observed_generation: None,
+        }
+    }
+
+    /// Set the `observed_generation` of the condition.
+    pub fn observed_generation(mut self, generation: Option<i64>) -> Self {
+        self.observed_generation = generation;
+        self
+    }
+}
+
+/// Replaces the conditions in `conditions` with those of the same type in `updates`, adding any
+/// that are missing. As with `metav1.Condition`, `last_transition_time` is only changed when the
+/// `status` of a condition changes. Returns `true` if anything other than a timestamp changed.
+pub fn set_conditions(conditions: &mut Vec<Condition>, updates: Vec<Condition>) -> bool {
+    let mut changed = false;
+    for mut update in updates {
+        match conditions
+            .iter_mut()
+            .find(|condition| condition.condition_type == update.condition_type)
+        {
+            Some(condition) => {
+                if condition.status == update.status {
+                    update.last_transition_time = condition.last_transition_time.clone();
+                }
+                if *condition != update {
+                    *condition = update;
+                    changed = true;
+                }
+            }
+            None => {
+                conditions.push(update);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn set_conditions_keeps_transition_time() {
+        let mut ready = Condition::new(ConditionType::Ready, ConditionStatus::False, "Waiting", "");
+        ready.last_transition_time = "2022-01-01T00:00:00Z".to_string();
+        let mut conditions = vec![ready];
+
+        // The same status with a new reason keeps the original transition time.
+        let update = Condition::new(ConditionType::Ready, ConditionStatus::False, "Running", "");
+        assert!(set_conditions(&mut conditions, vec![update.clone()]));
+        assert_eq!(conditions[0].reason, "Running");
+        assert_eq!(conditions[0].last_transition_time, "2022-01-01T00:00:00Z");
+        assert!(!set_conditions(&mut conditions, vec![update]));
+
+        // A new status changes the transition time, and new types are added.
+        let updates = vec![
+            Condition::new(ConditionType::Ready, ConditionStatus::True, "Passed", ""),
+            Condition::new(ConditionType::Failed, ConditionStatus::False, "Passed", ""),
+        ];
+        assert!(set_conditions(&mut conditions, updates));
+        assert_eq!(conditions.len(), 2);
+        assert_ne!(conditions[0].last_transition_time, "2022-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn serialize_condition() {
+        let condition = Condition::new(
+            ConditionType::DependenciesMet,
+            ConditionStatus::True,
+            "DependenciesMet",
+            "",
+        )
+        .observed_generation(Some(2));
+        let value = serde_json::to_value(condition).unwrap();
+        assert_eq!(value["type"], "DependenciesMet");
+        assert_eq!(value["status"], "True");
+        assert_eq!(value["observedGeneration"], 2);
+        assert!(value["lastTransitionTime"].is_string());
+    }
+}
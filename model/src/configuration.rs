@@ -1,4 +1,5 @@
 use crate::error::{self, Result};
+use schemars::schema::RootSchema;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::{Map, Value};
@@ -44,6 +45,13 @@ pub trait Configuration:
     fn from_value(value: Value) -> Result<Self> {
         Ok(serde_json::from_value(value).context(error::ConfigDeserializationSnafu)?)
     }
+
+    /// The JSON Schema of the `Configuration` object, if it has one. The `Configuration` derive
+    /// macro generates the schema from the type's `JsonSchema` implementation so that manifests can
+    /// be checked before they are applied.
+    fn schema() -> Option<RootSchema> {
+        None
+    }
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -30,14 +30,26 @@ pub const TEST_AGENT_SERVICE_ACCOUNT: &str = "testsys-test-agent-account";
 // Label keys
 pub const LABEL_TEST_NAME: &str = testsys!("test-name");
 pub const LABEL_TEST_UID: &str = testsys!("test-uid");
+pub const LABEL_TEST_SUITE: &str = testsys!("test-suite");
+pub const LABEL_TEST_SCHEDULE: &str = testsys!("test-schedule");
+pub const LABEL_RESOURCE_POOL: &str = testsys!("resource-pool");
+pub const LABEL_LEASED_BY: &str = testsys!("leased-by");
+pub const LABEL_MATRIX: &str = testsys!("matrix");
+pub const LABEL_MATRIX_AXIS_PREFIX: &str = testsys!("matrix-");
 pub const LABEL_PROVIDER_NAME: &str = testsys!("provider-name");
 pub const LABEL_COMPONENT: &str = testsys!("component");
+pub const LABEL_LEADER: &str = testsys!("leader");
+
+// Annotation keys
+pub const ANNOTATION_SCHEDULED_TIME: &str = testsys!("scheduled-time");
 
 // Environment variables
 pub const ENV_PROVIDER_NAME: &str = "TESTSYS_PROVIDER_NAME";
 pub const ENV_RESOURCE_ACTION: &str = "TESTSYS_RESOURCE_ACTION";
 pub const ENV_RESOURCE_NAME: &str = "TESTSYS_RESOURCE_NAME";
 pub const ENV_TEST_NAME: &str = "TESTSYS_TEST_NAME";
+/// The prefix of the environment variables that the controller sets for agents.
+pub const ENV_PREFIX: &str = "TESTSYS_";
 
 // Paths
 pub const SECRETS_PATH: &str = "/secrets";
@@ -1,3 +1,4 @@
+use crate::constants::NAMESPACE;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use serde::Serialize;
 use std::collections::HashSet;
@@ -17,6 +18,12 @@ pub trait CrdExt: Serialize {
         self.object_meta().name.as_deref().unwrap_or("")
     }
 
+    /// Returns the object.metadata.namespace field. Objects that have not been created yet may not
+    /// name a namespace, in which case they belong to the default `testsys` namespace.
+    fn object_namespace(&self) -> &str {
+        self.object_meta().namespace.as_deref().unwrap_or(NAMESPACE)
+    }
+
     /// Returns this object's YAML representation as a String.
     fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
         serde_yaml::to_string(self)
@@ -18,6 +18,12 @@ pub(crate) enum OpaqueError {
     ))]
     ConfigWrongValueType {},
 
+    #[snafu(display("Invalid cron schedule '{}': {}", schedule, source))]
+    CronSchedule {
+        schedule: String,
+        source: croner::errors::CronError,
+    },
+
     #[snafu(display(
         "The secret name '{}' is invalid, it must match regex pattern '{}'",
         secret_name,
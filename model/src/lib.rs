@@ -16,37 +16,52 @@ This library provides the Kubernetes custom resource definitions and their API c
 
 pub use agent::{Agent, SecretName, SecretType, TaskState};
 pub use clients::{create_resource_crd, create_test_crd, AllowNotFound};
+pub use condition::{set_conditions, Condition, ConditionStatus, ConditionType};
 pub use configuration::{ConfigValue, Configuration};
 pub use crd_ext::CrdExt;
 pub use error::{Error, Result};
 use kube::ResourceExt;
 pub use resource::{
     DestructionPolicy, ErrorResources, Resource, ResourceAction, ResourceError, ResourceSpec,
-    ResourceStatus,
+    ResourceStatus, RetryPolicy,
 };
+pub use resource_pool::{ReleasePolicy, ResourcePool, ResourcePoolSpec, ResourcePoolStatus};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 pub use test::{
-    AgentStatus, ControllerStatus, Outcome, Test, TestResults, TestSpec, TestStatus, TestUserState,
+    AgentStatus, ControllerStatus, Outcome, Test, TestAttempt, TestCase, TestCaseStatus,
+    TestResults, TestSpec, TestStatus, TestUserState, TEST_CASE_FAILURE_MESSAGE_LIMIT,
 };
+pub use test_schedule::{
+    ConcurrencyPolicy, TestSchedule, TestScheduleRun, TestScheduleSpec, TestScheduleStatus,
+};
+pub use test_suite::{TestSuite, TestSuiteSpec, TestSuiteStatus};
 
 mod agent;
 pub mod clients;
+mod condition;
 mod configuration;
 pub mod constants;
 mod crd_ext;
 mod error;
 mod resource;
+mod resource_pool;
 mod schema_utils;
 pub mod system;
 mod test;
 pub mod test_manager;
+mod test_schedule;
+mod test_suite;
+pub mod validation;
 
 /// `CrdName` provides a way of determining which type of testsys object a name refers to.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum CrdName {
     Test(String),
     Resource(String),
+    TestSuite(String),
+    ResourcePool(String),
+    TestSchedule(String),
 }
 
 impl CrdName {
@@ -54,16 +69,23 @@ impl CrdName {
         match self {
             CrdName::Test(name) => name,
             CrdName::Resource(name) => name,
+            CrdName::TestSuite(name) => name,
+            CrdName::ResourcePool(name) => name,
+            CrdName::TestSchedule(name) => name,
         }
     }
 }
 
-/// `Crd` provides an interface to combine `Test` and `Resource` when actions can be performed on both.
+/// `Crd` provides an interface to combine `Test`, `Resource`, `TestSuite`, `ResourcePool` and
+/// `TestSchedule` when actions can be performed on all of them.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Crd {
     Test(Test),
     Resource(Resource),
+    TestSuite(TestSuite),
+    ResourcePool(ResourcePool),
+    TestSchedule(TestSchedule),
 }
 
 impl Crd {
@@ -71,6 +93,9 @@ impl Crd {
         match self {
             Self::Test(test) => test.metadata.name.to_owned(),
             Self::Resource(resource) => resource.metadata.name.to_owned(),
+            Self::TestSuite(test_suite) => test_suite.metadata.name.to_owned(),
+            Self::ResourcePool(resource_pool) => resource_pool.metadata.name.to_owned(),
+            Self::TestSchedule(test_schedule) => test_schedule.metadata.name.to_owned(),
         }
     }
 
@@ -78,6 +103,15 @@ impl Crd {
         match self {
             Self::Test(test) => test.metadata.labels.to_owned().unwrap_or_default(),
             Self::Resource(resource) => resource.metadata.labels.to_owned().unwrap_or_default(),
+            Self::TestSuite(test_suite) => {
+                test_suite.metadata.labels.to_owned().unwrap_or_default()
+            }
+            Self::ResourcePool(resource_pool) => {
+                resource_pool.metadata.labels.to_owned().unwrap_or_default()
+            }
+            Self::TestSchedule(test_schedule) => {
+                test_schedule.metadata.labels.to_owned().unwrap_or_default()
+            }
         }
     }
 }
@@ -87,6 +121,9 @@ impl From<Crd> for CrdName {
         match crd {
             Crd::Test(test) => CrdName::Test(test.name_any()),
             Crd::Resource(resource) => CrdName::Resource(resource.name_any()),
+            Crd::TestSuite(test_suite) => CrdName::TestSuite(test_suite.name_any()),
+            Crd::ResourcePool(resource_pool) => CrdName::ResourcePool(resource_pool.name_any()),
+            Crd::TestSchedule(test_schedule) => CrdName::TestSchedule(test_schedule.name_any()),
         }
     }
 }
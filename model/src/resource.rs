@@ -1,6 +1,8 @@
 use crate::constants::TRUNC_LEN;
 use crate::test_manager::ResourceState;
-use crate::{agent::config_schema, Agent, CrdExt, TaskState};
+use crate::{
+    agent::config_schema, agent::timeout_schema, Agent, Condition, ConditionType, CrdExt, TaskState,
+};
 use core::option::Option;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use kube::{CustomResource, Resource as Kresource};
@@ -13,9 +15,7 @@ use std::fmt::{Display, Formatter};
 /// A resource required by a test. For example, a compute instance or cluster. The `CustomResource`
 /// derive also produces a struct named `Resource` which represents a resource CRD object in the k8s
 /// API.
-#[derive(
-    Clone, CustomResource, Debug, Default, Deserialize, JsonSchema, PartialEq, Eq, Serialize,
-)]
+#[derive(Clone, CustomResource, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
 #[kube(
     derive = "Default",
     derive = "PartialEq",
@@ -45,6 +45,24 @@ pub struct ResourceSpec {
     #[serde(default)]
     #[schemars(schema_with = "crate::schema_utils::nullable_enum::<DestructionPolicy>")]
     pub destruction_policy: DestructionPolicy,
+    /// The longest the resource may exist, e.g. `12h`, measured from when the `Resource` object was
+    /// created. The controller deletes an expired resource and destroys it even if its
+    /// `destructionPolicy` is `Never`, once no unfinished tests or other resources use it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "timeout_schema")]
+    pub max_lifetime: Option<String>,
+    /// Whether/how the controller will rerun the creation or destruction job after it fails (by
+    /// default failed jobs are not rerun).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_policy: Option<RetryPolicy>,
+    /// When the controller's concurrency limits queue the resource, resources with a higher
+    /// priority start first (`0` is the default).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// While `true`, the controller does not start the creation or destruction job. A job that has
+    /// already started keeps running.
+    #[serde(default)]
+    pub suspend: bool,
 }
 
 impl Resource {
@@ -55,6 +73,21 @@ impl Resource {
             .and_then(|s| s.created_resource.as_ref())
     }
 
+    /// The conditions of the resource that have been set by the controller.
+    pub fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|status| status.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The condition of the given type, if the controller has set it.
+    pub fn condition(&self, condition_type: ConditionType) -> Option<&Condition> {
+        self.conditions()
+            .iter()
+            .find(|condition| condition.condition_type == condition_type)
+    }
+
     /// Gets the error that occurred during resource creation (if any).
     pub fn creation_error(&self) -> Option<&ResourceError> {
         self.status.as_ref().and_then(|s| s.creation.error.as_ref())
@@ -117,6 +150,30 @@ impl Resource {
         }
     }
 
+    /// Gets the number of times the creation or destruction task has been retried, depending on
+    /// the given `resource_action`.
+    pub fn retries(&self, resource_action: ResourceAction) -> u32 {
+        self.status
+            .as_ref()
+            .map(|s| match resource_action {
+                ResourceAction::Create => s.creation.retries,
+                ResourceAction::Destroy => s.destruction.retries,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether the controller will retry the failed creation or destruction task, depending on the
+    /// given `resource_action`, instead of leaving it in the `Error` state. This is the case when
+    /// the resource's `RetryPolicy` allows the error and attempts remain.
+    pub fn is_retrying(&self, resource_action: ResourceAction) -> bool {
+        match (&self.spec.retry_policy, self.error(resource_action)) {
+            (Some(retry_policy), Some(error)) => {
+                retry_policy.is_retryable(error, self.retries(resource_action))
+            }
+            _ => false,
+        }
+    }
+
     /// Gets either the current creation task state or the destruction task state based on
     /// `resource_action`. `Unknown` is returned if the desired `resource_action` task state does
     /// not exist.
@@ -204,6 +261,10 @@ pub struct ResourceStatus {
     #[schemars(schema_with = "config_schema")]
     pub created_resource: Option<Map<String, Value>>,
 
+    /// Standard k8s conditions describing the state of the resource, maintained by the controller.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+
     /// The time of the last change to this CRD.
     pub last_update: Option<String>,
 }
@@ -219,6 +280,12 @@ impl CrdExt for Resource {
 pub struct ResourceAgentState {
     pub task_state: TaskState,
     pub error: Option<ResourceError>,
+    /// The number of times the controller has rerun the task after it failed.
+    #[serde(default)]
+    pub retries: u32,
+    /// When the controller may rerun the task, if it is waiting to retry it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
@@ -242,6 +309,57 @@ impl Display for ResourceError {
     }
 }
 
+/// Describes when the controller reruns the creation or destruction job of a resource after the
+/// task fails. The controller removes the failed job and starts a new one once the backoff has
+/// passed. The resource agent's `agentInfo` is kept so that the new job can find anything that the
+/// failed one left behind.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// The number of times the job is run before the task is left in the `Error` state, including
+    /// the first attempt (`3` is the default).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// How long to wait before the first retry, e.g. `30s` (the default). The wait doubles with
+    /// each retry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "timeout_schema")]
+    pub backoff: Option<String>,
+    /// The longest wait between attempts, e.g. `10m` (the default).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "timeout_schema")]
+    pub max_backoff: Option<String>,
+    /// The `errorResources` of the errors that are retried. Errors that left `Clear`, `Remaining`
+    /// or `Unknown` resources are retried by default, but `Orphaned` resources are not since the
+    /// agent is unable to clean them up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_on: Option<Vec<ErrorResources>>,
+}
+
+impl RetryPolicy {
+    /// The number of attempts used when `max_attempts` is not given.
+    pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+    /// The first wait used when `backoff` is not given.
+    pub const DEFAULT_BACKOFF: &'static str = "30s";
+    /// The longest wait used when `max_backoff` is not given.
+    pub const DEFAULT_MAX_BACKOFF: &'static str = "10m";
+
+    /// The number of times the job is run, including the first attempt.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.unwrap_or(Self::DEFAULT_MAX_ATTEMPTS)
+    }
+
+    /// Whether a task that has already been retried `retries` times is retried again after
+    /// failing with `error`.
+    pub fn is_retryable(&self, error: &ResourceError, retries: u32) -> bool {
+        let retryable = match &self.retry_on {
+            Some(retry_on) => retry_on.contains(&error.error_resources),
+            None => error.error_resources != ErrorResources::Orphaned,
+        };
+        retryable && retries.saturating_add(1) < self.max_attempts()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum DestructionPolicy {
@@ -263,3 +381,49 @@ impl Default for DestructionPolicy {
 
 derive_display_from_serialize!(DestructionPolicy);
 derive_fromstr_from_deserialize!(DestructionPolicy);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn error(error_resources: ErrorResources) -> ResourceError {
+        ResourceError {
+            error: "throttled".to_string(),
+            error_resources,
+        }
+    }
+
+    #[test]
+    fn retryable_errors() {
+        let retry_policy = RetryPolicy::default();
+        assert!(retry_policy.is_retryable(&error(ErrorResources::Remaining), 0));
+        assert!(retry_policy.is_retryable(&error(ErrorResources::Unknown), 1));
+        assert!(!retry_policy.is_retryable(&error(ErrorResources::Unknown), 2));
+        assert!(!retry_policy.is_retryable(&error(ErrorResources::Orphaned), 0));
+
+        let retry_policy = RetryPolicy {
+            max_attempts: Some(1),
+            ..Default::default()
+        };
+        assert!(!retry_policy.is_retryable(&error(ErrorResources::Clear), 0));
+
+        let retry_policy = RetryPolicy {
+            retry_on: Some(vec![ErrorResources::Clear]),
+            ..Default::default()
+        };
+        assert!(retry_policy.is_retryable(&error(ErrorResources::Clear), 0));
+        assert!(!retry_policy.is_retryable(&error(ErrorResources::Remaining), 0));
+    }
+
+    #[test]
+    fn retry_policy_omits_defaults() {
+        let retry_policy = RetryPolicy {
+            backoff: Some("1m".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(retry_policy).unwrap(),
+            serde_json::json!({"backoff": "1m"})
+        );
+    }
+}
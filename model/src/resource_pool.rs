@@ -0,0 +1,252 @@
+use crate::constants::{LABEL_LEASED_BY, LABEL_RESOURCE_POOL, NAMESPACE};
+use crate::crd_ext::CrdExt;
+use crate::{Resource, ResourceSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::{CustomResource, Resource as KubeResource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_plain::{derive_display_from_serialize, derive_fromstr_from_deserialize};
+use std::collections::BTreeMap;
+
+/// A pool of pre-provisioned TestSys `Resource`s that tests can lease instead of creating their own.
+/// The `CustomResource` derive also produces a struct named `ResourcePool` which represents a
+/// resource pool CRD object in the k8s API.
+///
+/// The controller keeps `size` unleased `Resource`s with the given `resource` spec in the pool.
+/// Members of the pool are labeled with the name of the pool and owned by it. A `Test` leases a
+/// member by listing a label selector that matches the pool's labels in its `leases`. When the test
+/// finishes, the member is returned to the pool or replaced according to the `releasePolicy`.
+#[derive(Clone, CustomResource, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[kube(
+    derive = "Default",
+    derive = "PartialEq",
+    group = "testsys.system",
+    kind = "ResourcePool",
+    namespaced,
+    plural = "resourcepools",
+    singular = "resourcepool",
+    status = "ResourcePoolStatus",
+    category = "testsys",
+    version = "v1",
+    printcolumn = r#"{"name":"Size", "type":"integer", "jsonPath":".spec.size"}"#,
+    printcolumn = r#"{"name":"Available", "type":"integer", "jsonPath":".status.numAvailable"}"#,
+    printcolumn = r#"{"name":"Leased", "type":"integer", "jsonPath":".status.numLeased"}"#
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcePoolSpec {
+    /// The number of unleased resources that the controller keeps in the pool.
+    pub size: u32,
+    /// What happens to a leased resource when the test that leased it is done (`Recycle` is the
+    /// default).
+    #[serde(default)]
+    pub release_policy: ReleasePolicy,
+    /// The spec of each `Resource` in the pool.
+    pub resource: ResourceSpec,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ReleasePolicy {
+    /// The resource is deleted and the pool creates a new one in its place.
+    #[default]
+    Recycle,
+    /// The resource is returned to the pool to be leased by another test.
+    Reuse,
+}
+
+derive_display_from_serialize!(ReleasePolicy);
+derive_fromstr_from_deserialize!(ReleasePolicy);
+
+/// The status field of the TestSys ResourcePool CRD. This is where the controller writes the state
+/// of the pool's resources.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcePoolStatus {
+    /// The number of resources in the pool that are not being deleted.
+    pub num_resources: u64,
+    /// The number of resources that have been created and can be leased.
+    pub num_available: u64,
+    /// The number of resources that are leased by a test.
+    pub num_leased: u64,
+    /// The name of the test holding each leased resource, keyed by the name of the resource.
+    pub leases: BTreeMap<String, String>,
+    /// The time of the last change to this CRD.
+    pub last_update: Option<String>,
+}
+
+impl ResourcePool {
+    /// The label selector that matches all `Resource`s belonging to this pool.
+    pub fn member_selector(&self) -> String {
+        format!("{}={}", LABEL_RESOURCE_POOL, self.object_name())
+    }
+
+    /// Builds a new `Resource` for the pool. The name of the resource is generated by the k8s API
+    /// from the name of the pool. The pool must have been retrieved from the k8s API (i.e. it has a
+    /// `uid`) for the owner reference to be set.
+    pub fn new_member(&self) -> Resource {
+        let mut labels = self.metadata.labels.clone().unwrap_or_default();
+        labels.insert(
+            LABEL_RESOURCE_POOL.to_string(),
+            self.object_name().to_string(),
+        );
+        Resource {
+            metadata: ObjectMeta {
+                generate_name: Some(format!("{}-", self.object_name())),
+                namespace: Some(
+                    self.metadata
+                        .namespace
+                        .clone()
+                        .unwrap_or_else(|| NAMESPACE.to_string()),
+                ),
+                labels: Some(labels),
+                owner_references: self.controller_owner_ref(&()).map(|owner| vec![owner]),
+                ..Default::default()
+            },
+            spec: self.spec.resource.clone(),
+            status: None,
+        }
+    }
+
+    /// Computes the status of the pool from its `members`.
+    pub fn summarize(&self, members: &[Resource]) -> ResourcePoolStatus {
+        let mut status = ResourcePoolStatus {
+            last_update: self
+                .status
+                .as_ref()
+                .and_then(|status| status.last_update.clone()),
+            ..Default::default()
+        };
+        for member in members
+            .iter()
+            .filter(|member| !member.is_delete_requested())
+        {
+            status.num_resources += 1;
+            if let Some(test) = member.leased_by() {
+                status.num_leased += 1;
+                status
+                    .leases
+                    .insert(member.object_name().to_string(), test.to_string());
+            } else if member.is_available_for_lease() {
+                status.num_available += 1;
+            }
+        }
+        status
+    }
+}
+
+impl CrdExt for ResourcePool {
+    fn object_meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+}
+
+impl Resource {
+    /// The name of the `ResourcePool` that this resource belongs to, if any.
+    pub fn pool(&self) -> Option<&str> {
+        self.object_meta()
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get(LABEL_RESOURCE_POOL))
+            .map(String::as_str)
+    }
+
+    /// The name of the `Test` that has leased this resource from its pool, if any.
+    pub fn leased_by(&self) -> Option<&str> {
+        self.object_meta()
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get(LABEL_LEASED_BY))
+            .map(String::as_str)
+    }
+
+    /// Whether this resource belongs to a pool, has been created, and can be leased by a test.
+    pub fn is_available_for_lease(&self) -> bool {
+        self.pool().is_some()
+            && self.leased_by().is_none()
+            && self.created_resource().is_some()
+            && !self.is_delete_requested()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ResourceStatus;
+    use maplit::btreemap;
+    use serde_json::Map;
+
+    fn pool() -> ResourcePool {
+        ResourcePool {
+            metadata: ObjectMeta {
+                name: Some("clusters".to_string()),
+                namespace: Some(NAMESPACE.to_string()),
+                uid: Some("1234".to_string()),
+                labels: Some(btreemap! { "version".to_string() => "1.24".to_string() }),
+                ..Default::default()
+            },
+            spec: ResourcePoolSpec {
+                size: 2,
+                ..Default::default()
+            },
+            status: None,
+        }
+    }
+
+    fn member(name: &str, created: bool, leased_by: Option<&str>) -> Resource {
+        let mut resource = pool().new_member();
+        resource.metadata.name = Some(name.to_string());
+        if let Some(test) = leased_by {
+            resource
+                .metadata
+                .labels
+                .get_or_insert_with(Default::default)
+                .insert(LABEL_LEASED_BY.to_string(), test.to_string());
+        }
+        resource.status = Some(ResourceStatus {
+            created_resource: created.then(Map::new),
+            ..Default::default()
+        });
+        resource
+    }
+
+    #[test]
+    fn new_member() {
+        let resource = pool().new_member();
+        assert_eq!(
+            resource.metadata.generate_name.as_deref(),
+            Some("clusters-")
+        );
+        assert_eq!(resource.pool(), Some("clusters"));
+        assert_eq!(
+            resource.metadata.labels.as_ref().unwrap().get("version"),
+            Some(&"1.24".to_string())
+        );
+        let owner = &resource.metadata.owner_references.as_ref().unwrap()[0];
+        assert_eq!(owner.kind, "ResourcePool");
+        assert_eq!(owner.uid, "1234");
+        assert_eq!(
+            pool().member_selector(),
+            "testsys.system/resource-pool=clusters"
+        );
+    }
+
+    #[test]
+    fn summarize() {
+        let members = vec![
+            member("clusters-a", true, None),
+            member("clusters-b", false, None),
+            member("clusters-c", true, Some("my-test")),
+        ];
+        assert!(members[0].is_available_for_lease());
+        assert!(!members[1].is_available_for_lease());
+        assert!(!members[2].is_available_for_lease());
+        let status = pool().summarize(&members);
+        assert_eq!(status.num_resources, 3);
+        assert_eq!(status.num_available, 1);
+        assert_eq!(status.num_leased, 1);
+        assert_eq!(
+            status.leases,
+            btreemap! { "clusters-c".to_string() => "my-test".to_string() }
+        );
+    }
+}
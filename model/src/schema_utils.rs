@@ -1,5 +1,8 @@
+use schemars::schema::{ArrayValidation, InstanceType, SchemaObject};
 use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::BTreeMap;
 
 /// Instead of making new struct model fields `Option`s, we can use this function when deserializing
 /// to assign the default value. This makes the structs more ergonomic to use, and makes yaml/json
@@ -38,3 +41,76 @@ where
 
     schema.into()
 }
+
+/// A schema for a field holding a k8s object, e.g. an `Affinity`, that only requires an object.
+/// Embedding the full schemas of k8s types makes every CRD that holds an `Agent` too large to
+/// `kubectl apply`, so these fields are validated by the k8s API server when the agent's pod is
+/// created instead.
+pub(crate) fn k8s_object(_: &mut SchemaGenerator) -> Schema {
+    let mut schema = preserve_unknown_fields();
+    schema
+        .extensions
+        .insert("nullable".to_owned(), Value::Bool(true));
+    schema.into()
+}
+
+/// A schema for a field holding a list of k8s objects, e.g. `Container`s. See [`k8s_object`].
+pub(crate) fn k8s_object_list(_: &mut SchemaGenerator) -> Schema {
+    let mut extensions = BTreeMap::new();
+    extensions.insert("nullable".to_owned(), Value::Bool(true));
+    SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(Schema::from(preserve_unknown_fields()).into()),
+            ..ArrayValidation::default()
+        })),
+        extensions,
+        ..SchemaObject::default()
+    }
+    .into()
+}
+
+/// An object whose fields are not pruned or validated.
+fn preserve_unknown_fields() -> SchemaObject {
+    let mut extensions = BTreeMap::new();
+    extensions.insert(
+        "x-kubernetes-preserve-unknown-fields".to_owned(),
+        Value::Bool(true),
+    );
+    SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        extensions,
+        ..SchemaObject::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Resource, ResourcePool, Test, TestSchedule, TestSuite};
+    use kube::CustomResourceExt;
+
+    /// `kubectl apply` stores the applied manifest in an annotation, which is limited to 256 KiB.
+    /// Leave plenty of room for the CRDs to grow.
+    const CRD_SIZE_LIMIT: usize = 64 * 1024;
+
+    #[test]
+    fn crd_sizes() {
+        for crd in [
+            Test::crd(),
+            Resource::crd(),
+            TestSuite::crd(),
+            ResourcePool::crd(),
+            TestSchedule::crd(),
+        ] {
+            let size = serde_json::to_string(&crd)
+                .map(|json| json.len())
+                .unwrap_or(usize::MAX);
+            assert!(
+                size < CRD_SIZE_LIMIT,
+                "The CRD '{}' is {} bytes",
+                crd.metadata.name.unwrap_or_default(),
+                size
+            );
+        }
+    }
+}
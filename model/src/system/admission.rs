@@ -0,0 +1,125 @@
+use crate::agent::image_name;
+use crate::constants::NAMESPACE;
+use crate::Agent;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::ObjectMeta;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The name of the `ConfigMap` holding the controller's `AdmissionConfig`.
+pub const TESTSYS_ADMISSION_CONFIG_MAP: &str = "testsys-admission";
+/// The key of the YAML `AdmissionConfig` in the admission `ConfigMap`.
+pub const TESTSYS_ADMISSION_CONFIG_KEY: &str = "config";
+
+/// Limits on the number of `Test`s and `Resource`s that the controller runs at once. A test or
+/// resource that is ready to start its agent job only starts once every limit that matches it has
+/// room; until then it is queued. Queued objects start in order of their `priority`, then in the
+/// order they were created.
+///
+/// The controller reads the configuration from the `testsys-admission` `ConfigMap`, so limits can
+/// be changed without restarting it. Without the `ConfigMap`, nothing is queued.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionConfig {
+    #[serde(default)]
+    pub limits: Vec<ConcurrencyLimit>,
+}
+
+/// The most objects matching `kind`, `image` and `labels` that may run at once. A limit without
+/// any of them is a global limit. A `Test` runs from when its job starts until it finishes. A
+/// `Resource` runs from when its creation job starts until it has been destroyed, since the
+/// resources it creates count against quotas until then.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConcurrencyLimit {
+    /// The name of the limit, used to explain why an object is queued.
+    pub name: String,
+    /// The kind of object that the limit applies to, `Test` or `Resource`. Both by default.
+    pub kind: Option<AdmissionKind>,
+    /// The name of the agent image that the limit applies to, e.g. `eks-resource-agent`. The
+    /// registry, tag and digest of the agent's image are ignored when comparing.
+    pub image: Option<String>,
+    /// The labels that an object must have for the limit to apply to it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// The number of matching objects that may run at once.
+    pub max_running: u32,
+}
+
+/// The kinds of object whose agent jobs are subject to admission.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, JsonSchema)]
+pub enum AdmissionKind {
+    Test,
+    Resource,
+}
+
+impl ConcurrencyLimit {
+    /// Whether the limit applies to an object of `kind` with `agent` and `labels`.
+    pub fn matches(
+        &self,
+        kind: AdmissionKind,
+        agent: &Agent,
+        labels: &BTreeMap<String, String>,
+    ) -> bool {
+        self.kind.is_none_or(|limit_kind| limit_kind == kind)
+            && self
+                .image
+                .as_ref()
+                .is_none_or(|image| image_name(image) == image_name(&agent.image))
+            && self
+                .labels
+                .iter()
+                .all(|(key, value)| labels.get(key) == Some(value))
+    }
+}
+
+/// Defines the `ConfigMap` that holds the controller's admission configuration.
+pub fn admission_config_map(config: &AdmissionConfig) -> serde_yaml::Result<ConfigMap> {
+    Ok(ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(TESTSYS_ADMISSION_CONFIG_MAP.to_string()),
+            namespace: Some(NAMESPACE.to_string()),
+            ..Default::default()
+        },
+        data: Some(BTreeMap::from([(
+            TESTSYS_ADMISSION_CONFIG_KEY.to_string(),
+            serde_yaml::to_string(config)?,
+        )])),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limit_matches() {
+        let config: AdmissionConfig = serde_yaml::from_str(
+            r#"
+limits:
+  - name: clusters
+    kind: Resource
+    image: eks-resource-agent
+    maxRunning: 2
+  - name: team
+    labels:
+      team: eks
+    maxRunning: 5
+"#,
+        )
+        .unwrap();
+        let agent = Agent {
+            image: "public.ecr.aws/bottlerocket-test-system/eks-resource-agent:v0.0.14".to_string(),
+            ..Default::default()
+        };
+        let labels = BTreeMap::from([("team".to_string(), "eks".to_string())]);
+        let clusters = &config.limits[0];
+        assert!(clusters.matches(AdmissionKind::Resource, &agent, &BTreeMap::new()));
+        assert!(!clusters.matches(AdmissionKind::Test, &agent, &BTreeMap::new()));
+        let team = &config.limits[1];
+        assert!(team.matches(AdmissionKind::Test, &agent, &labels));
+        assert!(!team.matches(AdmissionKind::Test, &agent, &BTreeMap::new()));
+    }
+}
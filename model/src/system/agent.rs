@@ -1,11 +1,13 @@
 use crate::constants::{
-    NAMESPACE, RESOURCE_AGENT_BINDING, RESOURCE_AGENT_ROLE, RESOURCE_AGENT_SERVICE_ACCOUNT,
-    TESTSYS, TEST_AGENT_BINDING, TEST_AGENT_ROLE, TEST_AGENT_SERVICE_ACCOUNT,
+    APP_MANAGED_BY, APP_PART_OF, RESOURCE_AGENT_BINDING, RESOURCE_AGENT_ROLE,
+    RESOURCE_AGENT_SERVICE_ACCOUNT, TESTSYS, TEST_AGENT_BINDING, TEST_AGENT_ROLE,
+    TEST_AGENT_SERVICE_ACCOUNT,
 };
 use k8s_openapi::api::core::v1::ServiceAccount;
-use k8s_openapi::api::rbac::v1::{ClusterRole, ClusterRoleBinding, PolicyRule, RoleRef, Subject};
+use k8s_openapi::api::rbac::v1::{PolicyRule, Role, RoleBinding, RoleRef, Subject};
 use kube::api::ObjectMeta;
 use maplit::btreemap;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Copy)]
 pub enum AgentType {
@@ -13,58 +15,71 @@ pub enum AgentType {
     Resource,
 }
 
-/// Defines the service account for an agent of type `agent_type`.
-pub fn agent_service_account(agent_type: AgentType) -> ServiceAccount {
+/// Defines the service account for an agent of type `agent_type` in `namespace`. Agents run in the
+/// namespace of their `Test` or `Resource`.
+pub fn agent_service_account(agent_type: AgentType, namespace: &str) -> ServiceAccount {
     ServiceAccount {
         metadata: ObjectMeta {
             name: Some(agent_type.service_account_name()),
-            namespace: Some(NAMESPACE.to_string()),
+            namespace: Some(namespace.to_string()),
             annotations: Some(btreemap! {
                 "kubernetes.io/service-account.name".to_string() => agent_type.service_account_name()
             }),
+            labels: Some(agent_labels()),
             ..Default::default()
         },
         ..Default::default()
     }
 }
 
-/// Defines the cluster role for an agent of type `agent_type`.
-pub fn agent_cluster_role(agent_type: AgentType) -> ClusterRole {
-    ClusterRole {
+/// Defines the role for an agent of type `agent_type` in `namespace`. The role only grants access to
+/// the TestSys objects in `namespace`.
+pub fn agent_role(agent_type: AgentType, namespace: &str) -> Role {
+    Role {
         metadata: ObjectMeta {
             name: Some(agent_type.role_name()),
-            namespace: Some(NAMESPACE.to_string()),
+            namespace: Some(namespace.to_string()),
+            labels: Some(agent_labels()),
             ..Default::default()
         },
         rules: Some(agent_type.policy_rules()),
-        ..Default::default()
     }
 }
 
-/// Defines the cluster role binding for an agent of type `agent_type`.
-pub fn agent_cluster_role_binding(agent_type: AgentType) -> ClusterRoleBinding {
-    ClusterRoleBinding {
+/// Defines the role binding for an agent of type `agent_type` in `namespace`.
+pub fn agent_role_binding(agent_type: AgentType, namespace: &str) -> RoleBinding {
+    RoleBinding {
         metadata: ObjectMeta {
             name: Some(agent_type.binding_name()),
-            namespace: Some(NAMESPACE.to_string()),
+            namespace: Some(namespace.to_string()),
+            labels: Some(agent_labels()),
             ..Default::default()
         },
         role_ref: RoleRef {
-            kind: "ClusterRole".to_string(),
+            kind: "Role".to_string(),
             name: agent_type.role_name(),
             api_group: "rbac.authorization.k8s.io".to_string(),
         },
         subjects: Some(vec![Subject {
             kind: "ServiceAccount".to_string(),
             name: agent_type.service_account_name(),
-            namespace: Some(NAMESPACE.to_string()),
+            namespace: Some(namespace.to_string()),
             ..Default::default()
         }]),
     }
 }
 
+/// The labels of the agent service accounts and roles. The controller only uses existing agent
+/// service accounts and roles that have these labels.
+pub fn agent_labels() -> BTreeMap<String, String> {
+    btreemap! {
+        APP_MANAGED_BY.to_string() => TESTSYS.to_string(),
+        APP_PART_OF.to_string() => TESTSYS.to_string(),
+    }
+}
+
 impl AgentType {
-    fn role_name(&self) -> String {
+    pub(crate) fn role_name(&self) -> String {
         match self {
             AgentType::Test => TEST_AGENT_ROLE.to_string(),
             AgentType::Resource => RESOURCE_AGENT_ROLE.to_string(),
@@ -78,7 +93,7 @@ impl AgentType {
         }
     }
 
-    fn binding_name(&self) -> String {
+    pub(crate) fn binding_name(&self) -> String {
         match self {
             AgentType::Test => TEST_AGENT_BINDING.to_string(),
             AgentType::Resource => RESOURCE_AGENT_BINDING.to_string(),
@@ -1,22 +1,66 @@
 use crate::constants::{
-    APP_COMPONENT, APP_MANAGED_BY, APP_PART_OF, LABEL_COMPONENT, NAMESPACE, TESTSYS,
+    APP_COMPONENT, APP_MANAGED_BY, APP_PART_OF, LABEL_COMPONENT, LABEL_LEADER, NAMESPACE, TESTSYS,
+};
+use crate::system::TESTSYS_ADMISSION_CONFIG_MAP;
+use k8s_openapi::api::admissionregistration::v1::{
+    RuleWithOperations, ServiceReference, ValidatingWebhook, ValidatingWebhookConfiguration,
+    WebhookClientConfig,
 };
 use k8s_openapi::api::apps::v1::{
     Deployment, DeploymentSpec, DeploymentStrategy, RollingUpdateDeployment,
 };
 use k8s_openapi::api::core::v1::{
-    Affinity, Container, EnvVar, LocalObjectReference, NodeAffinity, NodeSelector,
-    NodeSelectorRequirement, NodeSelectorTerm, PodSpec, PodTemplateSpec, ServiceAccount,
+    Affinity, Container, ContainerPort, EnvVar, EnvVarSource, HTTPGetAction, LocalObjectReference,
+    NodeAffinity, NodeSelector, NodeSelectorRequirement, NodeSelectorTerm, ObjectFieldSelector,
+    PodSpec, PodTemplateSpec, Probe, Service, ServiceAccount, ServicePort, ServiceSpec,
+};
+use k8s_openapi::api::rbac::v1::{
+    ClusterRole, ClusterRoleBinding, PolicyRule, Role, RoleBinding, RoleRef, Subject,
 };
-use k8s_openapi::api::rbac::v1::{ClusterRole, ClusterRoleBinding, PolicyRule, RoleRef, Subject};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
 use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
 use kube::api::ObjectMeta;
 use maplit::btreemap;
+use std::collections::BTreeSet;
 
 const TESTSYS_CONTROLLER_SERVICE_ACCOUNT: &str = "testsys-controller-service-account";
 const TESTSYS_CONTROLLER_CLUSTER_ROLE: &str = "testsys-controller-role";
+const TESTSYS_CONTROLLER_LEASE_ROLE: &str = "testsys-controller-lease-role";
 pub const TESTSYS_CONTROLLER_ARCHIVE_LOGS: &str = "TESTSYS_CONTROLLER_ARCHIVE_LOGS";
+/// The environment variable with the comma-separated namespaces whose TestSys objects the
+/// controller reconciles, or `*` for all namespaces. Only the `testsys` namespace is watched when
+/// it is not set.
+pub const TESTSYS_CONTROLLER_NAMESPACES: &str = "TESTSYS_CONTROLLER_NAMESPACES";
+/// The value of `TESTSYS_CONTROLLER_NAMESPACES` that has the controller watch all namespaces.
+pub const ALL_NAMESPACES: &str = "*";
+/// The environment variable with the name of the controller pod, which identifies the replica
+/// that holds the leader election `Lease`.
+pub const TESTSYS_CONTROLLER_POD_NAME: &str = "TESTSYS_CONTROLLER_POD_NAME";
+/// The environment variable with the namespace that the controller pod runs in.
+pub const TESTSYS_CONTROLLER_POD_NAMESPACE: &str = "TESTSYS_CONTROLLER_POD_NAMESPACE";
+/// The name of the `Lease` in the `testsys` namespace that controller replicas elect a leader with.
+pub const TESTSYS_CONTROLLER_LEASE: &str = "testsys-controller";
+/// The environment variable with the comma-separated service accounts, besides the default agent
+/// service accounts, that agents are allowed to run as.
+pub const TESTSYS_AGENT_SERVICE_ACCOUNTS: &str = "TESTSYS_AGENT_SERVICE_ACCOUNTS";
+/// The environment variable that enables the controller's validating admission webhook.
+pub const TESTSYS_CONTROLLER_WEBHOOK: &str = "TESTSYS_CONTROLLER_WEBHOOK";
+/// The name of both the webhook `Service` and the `ValidatingWebhookConfiguration`.
+pub const TESTSYS_WEBHOOK: &str = "testsys-webhook";
+/// The port that the controller serves the validating webhook on.
+pub const TESTSYS_WEBHOOK_PORT: u16 = 8443;
+/// The path that the controller serves the validating webhook on.
+pub const TESTSYS_WEBHOOK_PATH: &str = "/validate";
+/// The port that the controller serves its Prometheus metrics and health checks on.
+pub const TESTSYS_METRICS_PORT: u16 = 8080;
+/// The path that the controller serves its Prometheus metrics on.
+pub const TESTSYS_METRICS_PATH: &str = "/metrics";
+/// The path of the controller's liveness check, which fails if the test or resource controller has
+/// stopped processing objects.
+pub const TESTSYS_LIVENESS_PATH: &str = "/healthz";
+/// The path of the controller's readiness check, which passes for a replica that is standing by and,
+/// once the replica is the leader, when its test and resource controllers are processing objects.
+pub const TESTSYS_READINESS_PATH: &str = "/readyz";
 
 /// Defines the testsys-controller service account
 pub fn controller_service_account() -> ServiceAccount {
@@ -33,9 +77,11 @@ pub fn controller_service_account() -> ServiceAccount {
     }
 }
 
-/// Defines the testsys-controller cluster role
-pub fn controller_cluster_role() -> ClusterRole {
-    ClusterRole {
+/// Defines the testsys-controller cluster role. If `watch_all_namespaces` is set, the controller may
+/// also create the agent service accounts and roles in the namespaces that TestSys was not installed
+/// to.
+pub fn controller_cluster_role(watch_all_namespaces: bool) -> ClusterRole {
+    let mut cluster_role = ClusterRole {
         metadata: ObjectMeta {
             name: Some(TESTSYS_CONTROLLER_CLUSTER_ROLE.to_string()),
             namespace: Some(NAMESPACE.to_string()),
@@ -81,6 +127,72 @@ pub fn controller_cluster_role() -> ClusterRole {
                 .collect(),
                 ..Default::default()
             },
+            PolicyRule {
+                api_groups: Some(vec![TESTSYS.to_string()]),
+                resources: Some(vec![
+                    "testsuites".to_string(),
+                    "testsuites/status".to_string(),
+                    "testsuites/finalizers".to_string(),
+                ]),
+                verbs: [
+                    "create",
+                    "delete",
+                    "deletecollection",
+                    "get",
+                    "list",
+                    "patch",
+                    "update",
+                    "watch",
+                ]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec![TESTSYS.to_string()]),
+                resources: Some(vec![
+                    "resourcepools".to_string(),
+                    "resourcepools/status".to_string(),
+                    "resourcepools/finalizers".to_string(),
+                ]),
+                verbs: [
+                    "create",
+                    "delete",
+                    "deletecollection",
+                    "get",
+                    "list",
+                    "patch",
+                    "update",
+                    "watch",
+                ]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec![TESTSYS.to_string()]),
+                resources: Some(vec![
+                    "testschedules".to_string(),
+                    "testschedules/status".to_string(),
+                    "testschedules/finalizers".to_string(),
+                ]),
+                verbs: [
+                    "create",
+                    "delete",
+                    "deletecollection",
+                    "get",
+                    "list",
+                    "patch",
+                    "update",
+                    "watch",
+                ]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+                ..Default::default()
+            },
             PolicyRule {
                 api_groups: Some(vec!["apps".to_string()]),
                 resources: Some(vec!["deployments".to_string()]),
@@ -121,9 +233,46 @@ pub fn controller_cluster_role() -> ClusterRole {
                 verbs: ["get", "list"].iter().map(|s| s.to_string()).collect(),
                 ..Default::default()
             },
+            PolicyRule {
+                api_groups: Some(vec!["".to_string()]),
+                resources: Some(vec!["configmaps".to_string()]),
+                resource_names: Some(vec![TESTSYS_ADMISSION_CONFIG_MAP.to_string()]),
+                verbs: ["get"].iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec!["events.k8s.io".to_string()]),
+                resources: Some(vec!["events".to_string()]),
+                verbs: ["create"].iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec!["admissionregistration.k8s.io".to_string()]),
+                resources: Some(vec!["validatingwebhookconfigurations".to_string()]),
+                resource_names: Some(vec![TESTSYS_WEBHOOK.to_string()]),
+                verbs: ["get", "patch"].iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            },
         ]),
         ..Default::default()
+    };
+    if watch_all_namespaces {
+        cluster_role.rules.get_or_insert_with(Vec::new).extend([
+            PolicyRule {
+                api_groups: Some(vec!["".to_string()]),
+                resources: Some(vec!["serviceaccounts".to_string()]),
+                verbs: ["create", "get"].iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec!["rbac.authorization.k8s.io".to_string()]),
+                resources: Some(vec!["roles".to_string(), "rolebindings".to_string()]),
+                verbs: ["create", "get"].iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            },
+        ]);
     }
+    cluster_role
 }
 
 /// Defines the testsys-controller cluster role binding
@@ -148,14 +297,102 @@ pub fn controller_cluster_role_binding() -> ClusterRoleBinding {
     }
 }
 
-/// Defines the testsys-controller deployment
+/// Defines the role that lets the controller replicas elect a leader with the
+/// [`TESTSYS_CONTROLLER_LEASE`] in the `testsys` namespace, and label their pods so that the webhook
+/// service routes to the leader. Creating a lease cannot be limited to a name, but reading and
+/// renewing it is.
+pub fn controller_lease_role() -> Role {
+    Role {
+        metadata: ObjectMeta {
+            name: Some(TESTSYS_CONTROLLER_LEASE_ROLE.to_string()),
+            namespace: Some(NAMESPACE.to_string()),
+            ..Default::default()
+        },
+        rules: Some(vec![
+            PolicyRule {
+                api_groups: Some(vec!["coordination.k8s.io".to_string()]),
+                resources: Some(vec!["leases".to_string()]),
+                verbs: vec!["create".to_string()],
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec!["coordination.k8s.io".to_string()]),
+                resources: Some(vec!["leases".to_string()]),
+                resource_names: Some(vec![TESTSYS_CONTROLLER_LEASE.to_string()]),
+                verbs: ["get", "update"].iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec!["".to_string()]),
+                resources: Some(vec!["pods".to_string()]),
+                verbs: vec!["patch".to_string()],
+                ..Default::default()
+            },
+        ]),
+    }
+}
+
+/// Defines the testsys-controller lease role binding
+pub fn controller_lease_role_binding() -> RoleBinding {
+    RoleBinding {
+        metadata: ObjectMeta {
+            name: Some("testsys-controller-lease-role-binding".to_string()),
+            namespace: Some(NAMESPACE.to_string()),
+            ..Default::default()
+        },
+        role_ref: RoleRef {
+            api_group: "rbac.authorization.k8s.io".to_string(),
+            kind: "Role".to_string(),
+            name: TESTSYS_CONTROLLER_LEASE_ROLE.to_string(),
+        },
+        subjects: Some(vec![Subject {
+            kind: "ServiceAccount".to_string(),
+            name: TESTSYS_CONTROLLER_SERVICE_ACCOUNT.to_string(),
+            namespace: Some(NAMESPACE.to_string()),
+            ..Default::default()
+        }]),
+    }
+}
+
+/// Defines the testsys-controller deployment. The controller watches the `testsys` namespace and
+/// each of `namespaces`, which may be [`ALL_NAMESPACES`]. Of the `replicas`, only the one that holds
+/// the [`TESTSYS_CONTROLLER_LEASE`] reconciles objects; the others stand by to take over.
 pub fn controller_deployment(
     controller_image: String,
     image_pull_secret: Option<String>,
     enable_logging: bool,
+    enable_webhook: bool,
+    namespaces: &[String],
+    agent_service_accounts: &[String],
+    replicas: i32,
 ) -> Deployment {
     let image_pull_secrets =
         image_pull_secret.map(|secret| vec![LocalObjectReference { name: Some(secret) }]);
+    let namespaces = if namespaces
+        .iter()
+        .any(|namespace| namespace == ALL_NAMESPACES)
+    {
+        ALL_NAMESPACES.to_string()
+    } else {
+        std::iter::once(NAMESPACE)
+            .chain(namespaces.iter().map(String::as_str))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let mut ports = vec![ContainerPort {
+        container_port: TESTSYS_METRICS_PORT.into(),
+        name: Some("metrics".to_string()),
+        ..Default::default()
+    }];
+    if enable_webhook {
+        ports.push(ContainerPort {
+            container_port: TESTSYS_WEBHOOK_PORT.into(),
+            name: Some("webhook".to_string()),
+            ..Default::default()
+        });
+    }
 
     Deployment {
         metadata: ObjectMeta {
@@ -175,17 +412,19 @@ pub fn controller_deployment(
             ..Default::default()
         },
         spec: Some(DeploymentSpec {
-            replicas: Some(1),
+            replicas: Some(replicas),
             selector: LabelSelector {
                 match_labels: Some(
                     btreemap! { LABEL_COMPONENT.to_string() => "controller".to_string()},
                 ),
                 ..Default::default()
             },
+            // Start each new replica before stopping an old one, a new replica takes over the lease
+            // once the old leader has stopped.
             strategy: Some(DeploymentStrategy {
                 rolling_update: Some(RollingUpdateDeployment {
-                    max_unavailable: Some(IntOrString::String("100%".to_string())),
-                    ..Default::default()
+                    max_unavailable: Some(IntOrString::Int(0)),
+                    max_surge: Some(IntOrString::Int(1)),
                 }),
                 ..Default::default()
             }),
@@ -194,6 +433,12 @@ pub fn controller_deployment(
                     labels: Some(btreemap! {
                         LABEL_COMPONENT.to_string() => "controller".to_string(),
                     }),
+                    // Lets a Prometheus that discovers pods by annotation scrape the controller.
+                    annotations: Some(btreemap! {
+                        "prometheus.io/scrape".to_string() => "true".to_string(),
+                        "prometheus.io/port".to_string() => TESTSYS_METRICS_PORT.to_string(),
+                        "prometheus.io/path".to_string() => TESTSYS_METRICS_PATH.to_string(),
+                    }),
                     namespace: Some(NAMESPACE.to_string()),
                     ..Default::default()
                 }),
@@ -222,11 +467,53 @@ pub fn controller_deployment(
                         image: Some(controller_image),
                         image_pull_policy: None,
                         name: "controller".to_string(),
-                        env: Some(vec![EnvVar {
-                            name: TESTSYS_CONTROLLER_ARCHIVE_LOGS.to_string(),
-                            value: Some(enable_logging.to_string()),
-                            ..Default::default()
-                        }]),
+                        env: Some(vec![
+                            EnvVar {
+                                name: TESTSYS_CONTROLLER_ARCHIVE_LOGS.to_string(),
+                                value: Some(enable_logging.to_string()),
+                                ..Default::default()
+                            },
+                            EnvVar {
+                                name: TESTSYS_CONTROLLER_WEBHOOK.to_string(),
+                                value: Some(enable_webhook.to_string()),
+                                ..Default::default()
+                            },
+                            EnvVar {
+                                name: TESTSYS_CONTROLLER_NAMESPACES.to_string(),
+                                value: Some(namespaces),
+                                ..Default::default()
+                            },
+                            EnvVar {
+                                name: TESTSYS_AGENT_SERVICE_ACCOUNTS.to_string(),
+                                value: Some(agent_service_accounts.join(",")),
+                                ..Default::default()
+                            },
+                            EnvVar {
+                                name: TESTSYS_CONTROLLER_POD_NAME.to_string(),
+                                value_from: Some(EnvVarSource {
+                                    field_ref: Some(ObjectFieldSelector {
+                                        field_path: "metadata.name".to_string(),
+                                        ..Default::default()
+                                    }),
+                                    ..Default::default()
+                                }),
+                                ..Default::default()
+                            },
+                            EnvVar {
+                                name: TESTSYS_CONTROLLER_POD_NAMESPACE.to_string(),
+                                value_from: Some(EnvVarSource {
+                                    field_ref: Some(ObjectFieldSelector {
+                                        field_path: "metadata.namespace".to_string(),
+                                        ..Default::default()
+                                    }),
+                                    ..Default::default()
+                                }),
+                                ..Default::default()
+                            },
+                        ]),
+                        ports: Some(ports),
+                        liveness_probe: Some(http_probe(TESTSYS_LIVENESS_PATH, 3)),
+                        readiness_probe: Some(http_probe(TESTSYS_READINESS_PATH, 1)),
                         ..Default::default()
                     }],
                     image_pull_secrets,
@@ -239,3 +526,82 @@ pub fn controller_deployment(
         ..Default::default()
     }
 }
+
+/// A probe of the controller's health check at `path` that fails after `failure_threshold`
+/// consecutive failures.
+fn http_probe(path: &str, failure_threshold: i32) -> Probe {
+    Probe {
+        http_get: Some(HTTPGetAction {
+            path: Some(path.to_string()),
+            port: IntOrString::Int(TESTSYS_METRICS_PORT.into()),
+            ..Default::default()
+        }),
+        initial_delay_seconds: Some(5),
+        period_seconds: Some(10),
+        failure_threshold: Some(failure_threshold),
+        ..Default::default()
+    }
+}
+
+/// Defines the service that routes validating webhook requests to the testsys-controller
+pub fn controller_webhook_service() -> Service {
+    Service {
+        metadata: ObjectMeta {
+            name: Some(TESTSYS_WEBHOOK.to_string()),
+            namespace: Some(NAMESPACE.to_string()),
+            ..Default::default()
+        },
+        spec: Some(ServiceSpec {
+            // Only the leader serves the webhook, and it labels its pod as the leader.
+            selector: Some(btreemap! {
+                LABEL_COMPONENT.to_string() => "controller".to_string(),
+                LABEL_LEADER.to_string() => true.to_string(),
+            }),
+            ports: Some(vec![ServicePort {
+                name: Some("webhook".to_string()),
+                port: 443,
+                target_port: Some(IntOrString::Int(TESTSYS_WEBHOOK_PORT.into())),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Defines the validating webhook that checks `Test` and `Resource` objects when they are created.
+/// The controller generates its own serving certificate and fills in the `caBundle` when it starts.
+/// Requests are allowed if the controller cannot be reached so that the webhook is never required
+/// for testsys to function.
+pub fn controller_validating_webhook() -> ValidatingWebhookConfiguration {
+    ValidatingWebhookConfiguration {
+        metadata: ObjectMeta {
+            name: Some(TESTSYS_WEBHOOK.to_string()),
+            ..Default::default()
+        },
+        webhooks: Some(vec![ValidatingWebhook {
+            name: format!("validate.{}", TESTSYS),
+            admission_review_versions: vec!["v1".to_string()],
+            client_config: WebhookClientConfig {
+                service: Some(ServiceReference {
+                    name: TESTSYS_WEBHOOK.to_string(),
+                    namespace: NAMESPACE.to_string(),
+                    path: Some(TESTSYS_WEBHOOK_PATH.to_string()),
+                    port: Some(443),
+                }),
+                ..Default::default()
+            },
+            failure_policy: Some("Ignore".to_string()),
+            rules: Some(vec![RuleWithOperations {
+                api_groups: Some(vec![TESTSYS.to_string()]),
+                api_versions: Some(vec!["v1".to_string()]),
+                operations: Some(vec!["CREATE".to_string()]),
+                resources: Some(vec!["tests".to_string(), "resources".to_string()]),
+                ..Default::default()
+            }]),
+            side_effects: "None".to_string(),
+            timeout_seconds: Some(10),
+            ..Default::default()
+        }]),
+    }
+}
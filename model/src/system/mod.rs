@@ -1,11 +1,21 @@
 /// Encapsulates testsys related K8S object definitions
+mod admission;
 mod agent;
 mod controller;
 mod namespace;
 
-pub use agent::{agent_cluster_role, agent_cluster_role_binding, agent_service_account, AgentType};
+pub use admission::{
+    admission_config_map, AdmissionConfig, AdmissionKind, ConcurrencyLimit,
+    TESTSYS_ADMISSION_CONFIG_KEY, TESTSYS_ADMISSION_CONFIG_MAP,
+};
+pub use agent::{agent_labels, agent_role, agent_role_binding, agent_service_account, AgentType};
 pub use controller::{
     controller_cluster_role, controller_cluster_role_binding, controller_deployment,
-    controller_service_account, TESTSYS_CONTROLLER_ARCHIVE_LOGS,
+    controller_lease_role, controller_lease_role_binding, controller_service_account,
+    controller_validating_webhook, controller_webhook_service, ALL_NAMESPACES,
+    TESTSYS_AGENT_SERVICE_ACCOUNTS, TESTSYS_CONTROLLER_ARCHIVE_LOGS, TESTSYS_CONTROLLER_LEASE,
+    TESTSYS_CONTROLLER_NAMESPACES, TESTSYS_CONTROLLER_POD_NAME, TESTSYS_CONTROLLER_POD_NAMESPACE,
+    TESTSYS_CONTROLLER_WEBHOOK, TESTSYS_LIVENESS_PATH, TESTSYS_METRICS_PATH, TESTSYS_METRICS_PORT,
+    TESTSYS_READINESS_PATH, TESTSYS_WEBHOOK, TESTSYS_WEBHOOK_PATH, TESTSYS_WEBHOOK_PORT,
 };
 pub use namespace::testsys_namespace;
@@ -1,18 +1,20 @@
+use crate::agent::{config_schema, timeout_schema};
 use crate::constants::FINALIZER_MAIN;
 use crate::crd_ext::CrdExt;
-use crate::{Agent, TaskState};
+use crate::{Agent, Condition, ConditionType, TaskState};
+use chrono::{DateTime, SecondsFormat, Utc};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use serde_plain::derive_display_from_serialize;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 
 /// A TestSys Test. The `CustomResource` derive also produces a struct named `Test` which represents
 /// a test CRD object in the k8s API.
-#[derive(
-    Clone, CustomResource, Debug, Default, Deserialize, JsonSchema, PartialEq, Eq, Serialize,
-)]
+#[derive(Clone, CustomResource, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
 #[kube(
     derive = "Default",
     derive = "PartialEq",
@@ -38,6 +40,29 @@ pub struct TestSpec {
     pub agent: Agent,
     /// The number of retries the agent is allowed to perform after a failed test.
     pub retries: Option<u32>,
+    /// Resources to lease from `ResourcePool`s instead of naming them in `resources`. Each key is
+    /// the name that the test agent's configuration uses for the leased resource in templates, e.g.
+    /// `${cluster.endpoint}`, and each value is a label selector matching the pool's labels.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub leases: BTreeMap<String, String>,
+    /// How long the `Test` object is kept after the test agent has finished, e.g. `1d`. The
+    /// controller deletes the test once this time has passed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "timeout_schema")]
+    pub ttl_after_finished: Option<String>,
+    /// When the controller's concurrency limits queue the test, tests with a higher priority start
+    /// first (`0` is the default).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Set to `true` to ask the test agent to stop the test. The agent terminates its runner, keeps
+    /// the results that it has so far, and finishes with a `cancelled` outcome. A test whose agent
+    /// job has not started yet finishes with a `cancelled` outcome without starting it.
+    #[serde(default)]
+    pub cancel: bool,
+    /// While `true`, the controller does not start the test agent job. A test whose job has already
+    /// started keeps running.
+    #[serde(default)]
+    pub suspend: bool,
 }
 
 /// The status field of the TestSys Test CRD. This is where the controller and agents will write
@@ -49,6 +74,9 @@ pub struct TestStatus {
     pub controller: ControllerStatus,
     /// Information written by the test agent.
     pub agent: AgentStatus,
+    /// Standard k8s conditions describing the state of the test, maintained by the controller.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
     /// The time of the last change to this CRD.
     pub last_update: Option<String>,
 }
@@ -62,6 +90,8 @@ pub enum Outcome {
     Timeout,
     Unknown,
     InProgress,
+    /// The test was cancelled before it finished.
+    Cancelled,
 }
 
 derive_display_from_serialize!(Outcome);
@@ -80,6 +110,12 @@ pub struct TestResults {
     pub num_failed: u64,
     pub num_skipped: u64,
     pub other_info: Option<String>,
+    /// The individual test cases of this run, if the test agent reports them. To keep the size of
+    /// the `Test` object bounded, agents may list only a subset of the cases (e.g. the failures).
+    pub test_cases: Option<Vec<TestCase>>,
+    /// Which attempt of the test produced these results, and when it ran. This is written by the
+    /// test agent, not by the test runner.
+    pub attempt: Option<TestAttempt>,
 }
 
 impl TestResults {
@@ -87,8 +123,108 @@ impl TestResults {
     pub fn total(&self) -> u64 {
         self.num_passed + self.num_failed + self.num_skipped
     }
+
+    /// The names of the test cases that failed.
+    pub fn failed_test_cases(&self) -> impl Iterator<Item = &str> {
+        self.test_cases
+            .iter()
+            .flatten()
+            .filter(|test_case| test_case.status == TestCaseStatus::Failed)
+            .map(|test_case| test_case.name.as_str())
+    }
+}
+
+/// Describes one attempt at running a test. The first run of a test is attempt `1` and each retry
+/// increments the attempt number.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TestAttempt {
+    pub number: u32,
+    /// The time the attempt started, in RFC 3339 format.
+    pub start_time: Option<String>,
+    /// The time the attempt finished, in RFC 3339 format.
+    pub end_time: Option<String>,
+    /// How long the attempt took, e.g. `754s`.
+    pub duration: Option<String>,
+}
+
+impl TestAttempt {
+    /// Create a `TestAttempt` that starts now.
+    pub fn start(number: u32) -> Self {
+        Self {
+            number,
+            start_time: Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+            ..Default::default()
+        }
+    }
+
+    /// Record that the attempt has finished now.
+    pub fn finish(&mut self) {
+        self.finish_at(Utc::now())
+    }
+
+    fn finish_at(&mut self, end_time: DateTime<Utc>) {
+        self.duration = self
+            .start_time
+            .as_ref()
+            .and_then(|start_time| DateTime::parse_from_rfc3339(start_time).ok())
+            .map(|start_time| {
+                format!(
+                    "{}s",
+                    (end_time - start_time.with_timezone(&Utc)).num_seconds()
+                )
+            });
+        self.end_time = Some(end_time.to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
 }
 
+/// The maximum length of `TestCase::failure_message`.
+pub const TEST_CASE_FAILURE_MESSAGE_LIMIT: usize = 1024;
+
+/// The result of a single test case within a test run.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TestCase {
+    pub name: String,
+    pub status: TestCaseStatus,
+    /// How long the test case took to run, e.g. `12.5s`.
+    pub duration: Option<String>,
+    /// The reason the test case failed, truncated to `TEST_CASE_FAILURE_MESSAGE_LIMIT` bytes.
+    pub failure_message: Option<String>,
+}
+
+impl TestCase {
+    /// Truncates `message` to at most `TEST_CASE_FAILURE_MESSAGE_LIMIT` bytes, ending with `...`
+    /// if anything was removed.
+    pub fn truncate_failure_message<S>(message: S) -> String
+    where
+        S: Into<String>,
+    {
+        let mut message = message.into();
+        if message.len() > TEST_CASE_FAILURE_MESSAGE_LIMIT {
+            let mut end = TEST_CASE_FAILURE_MESSAGE_LIMIT - 3;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+            message.push_str("...");
+        }
+        message
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Copy, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum TestCaseStatus {
+    Passed,
+    Failed,
+    Skipped,
+    #[default]
+    Unknown,
+}
+
+derive_display_from_serialize!(TestCaseStatus);
+
 #[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentStatus {
@@ -100,22 +236,32 @@ pub struct AgentStatus {
     pub error: Option<String>,
     pub results: Vec<TestResults>,
     pub current_test: Option<TestResults>,
+    /// Values published by the test agent for other tests and resources to use in their
+    /// configuration with `${test:<test name>.<field name>}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "config_schema")]
+    pub outputs: Option<Map<String, Value>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ControllerStatus {
     pub resource_error: Option<String>,
+    /// The names of the resources leased for the test, keyed by the names used in the test's
+    /// `leases`.
+    #[serde(default)]
+    pub leased_resources: BTreeMap<String, String>,
 }
 
 /// A simplified summary of the test's current state. This can be used by a user interface to
 /// describe what is happening with the test. This is not included in the model, but is derived
 /// from the state of the `Test` CRD. Note that resource state cannot be represented here
 /// because the `Resource` CRDs would need to be queried.
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub enum TestUserState {
     /// The test state cannot be determined.
+    #[default]
     Unknown,
     /// The test has not yet started its test agent, it might be waiting for resources.
     Waiting,
@@ -133,12 +279,8 @@ pub enum TestUserState {
     ResourceError,
     /// The test is in the process of being deleted.
     Deleting,
-}
-
-impl Default for TestUserState {
-    fn default() -> Self {
-        Self::Unknown
-    }
+    /// The test was cancelled before it finished.
+    Cancelled,
 }
 
 serde_plain::derive_display_from_serialize!(TestUserState);
@@ -158,6 +300,14 @@ impl Test {
             .and_then(|agent_status| agent_status.error.as_deref())
     }
 
+    /// The names of the resources leased for the test, keyed by the names used in `spec.leases`.
+    pub fn leased_resources(&self) -> BTreeMap<String, String> {
+        self.status
+            .as_ref()
+            .map(|status| status.controller.leased_resources.clone())
+            .unwrap_or_default()
+    }
+
     pub fn resource_error(&self) -> Option<&String> {
         self.status
             .as_ref()
@@ -165,6 +315,21 @@ impl Test {
             .and_then(|some| some.resource_error.as_ref())
     }
 
+    /// The conditions of the test that have been set by the controller.
+    pub fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map(|status| status.conditions.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The condition of the given type, if the controller has set it.
+    pub fn condition(&self, condition_type: ConditionType) -> Option<&Condition> {
+        self.conditions()
+            .iter()
+            .find(|condition| condition.condition_type == condition_type)
+    }
+
     pub fn test_user_state(&self) -> TestUserState {
         let agent_status = self.agent_status();
         if self.is_delete_requested() && !matches!(agent_status.task_state, TaskState::Unknown) {
@@ -188,6 +353,7 @@ impl Test {
                         Outcome::Pass => TestUserState::Passed,
                         Outcome::Fail => TestUserState::Failed,
                         Outcome::Timeout => TestUserState::Failed,
+                        Outcome::Cancelled => TestUserState::Cancelled,
                         Outcome::Unknown | Outcome::InProgress => {
                             if results.total() == 0 {
                                 TestUserState::NoTests
@@ -212,3 +378,39 @@ impl CrdExt for Test {
         &self.metadata
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attempt_finish() {
+        let mut attempt = TestAttempt::start(2);
+        assert_eq!(attempt.number, 2);
+        assert!(attempt.start_time.is_some());
+        assert!(attempt.end_time.is_none());
+        attempt.finish();
+        assert!(attempt.end_time.is_some());
+        assert!(attempt.duration.is_some());
+
+        let mut attempt = TestAttempt {
+            number: 1,
+            start_time: Some("2022-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let end_time = DateTime::parse_from_rfc3339("2022-01-01T00:12:34Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        attempt.finish_at(end_time);
+        assert_eq!(attempt.end_time.as_deref(), Some("2022-01-01T00:12:34Z"));
+        assert_eq!(attempt.duration.as_deref(), Some("754s"));
+    }
+
+    #[test]
+    fn truncate_failure_message() {
+        assert_eq!(TestCase::truncate_failure_message("short"), "short");
+        let message = TestCase::truncate_failure_message("é".repeat(1000));
+        assert!(message.len() <= TEST_CASE_FAILURE_MESSAGE_LIMIT);
+        assert!(message.ends_with("..."));
+    }
+}
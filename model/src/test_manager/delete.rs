@@ -1,6 +1,9 @@
 use super::{error, Result, TestManager};
-use crate::clients::{AllowNotFound, CrdClient, ResourceClient, TestClient};
-use crate::{Crd, CrdName, TaskState};
+use crate::clients::{
+    AllowNotFound, CrdClient, ResourceClient, ResourcePoolClient, TestClient, TestScheduleClient,
+    TestSuiteClient,
+};
+use crate::{Crd, CrdName, ResourceAction, TaskState};
 use futures::channel::mpsc::{channel, Sender};
 use futures::executor::block_on;
 use futures::{SinkExt, Stream};
@@ -26,10 +29,21 @@ impl TestManager {
         let (mut tx, rx) = channel(100);
         let test_client = self.test_client();
         let resource_client = self.resource_client();
+        let test_suite_client = self.test_suite_client();
+        let resource_pool_client = self.resource_pool_client();
+        let test_schedule_client = self.test_schedule_client();
         // Delete our sorted resources
         tokio::task::spawn(async move {
-            if let Err(e) =
-                async_deletion(&mut tx, &mut deletion_order, test_client, resource_client).await
+            if let Err(e) = async_deletion(
+                &mut tx,
+                &mut deletion_order,
+                test_client,
+                resource_client,
+                test_suite_client,
+                resource_pool_client,
+                test_schedule_client,
+            )
+            .await
             {
                 if let Err(e) = block_on(tx.send(Err(e))) {
                     eprintln!("Deletion error failed to send: {}", e);
@@ -44,6 +58,49 @@ impl TestManager {
     /// Creates a `TopologicalSort` containing all objects in a testsys cluster.
     pub(super) async fn all_objects_deletion_order(&self) -> Result<TopologicalSort<CrdName>> {
         let mut topo_sort = TopologicalSort::new();
+        // Test suites have no dependencies, their children are deleted by the controller when the
+        // test suite is deleted.
+        let test_suites = self
+            .test_suite_client()
+            .get_all()
+            .await
+            .context(error::ClientSnafu {
+                action: "get all test suites",
+            })?;
+        for test_suite in &test_suites {
+            topo_sort.insert(CrdName::TestSuite(test_suite.name_any()));
+        }
+        let test_schedules =
+            self.test_schedule_client()
+                .get_all()
+                .await
+                .context(error::ClientSnafu {
+                    action: "get all test schedules",
+                })?;
+        for test_schedule in &test_schedules {
+            topo_sort.insert(CrdName::TestSchedule(test_schedule.name_any()));
+        }
+        // A schedule starts new runs, so it must be deleted before them.
+        for test_suite in &test_suites {
+            if let Some(schedule) = test_suite.schedule() {
+                if test_schedules.iter().any(|s| s.name_any() == schedule) {
+                    topo_sort.add_dependency(
+                        CrdName::TestSchedule(schedule.to_string()),
+                        CrdName::TestSuite(test_suite.name_any()),
+                    );
+                }
+            }
+        }
+        let resource_pools =
+            self.resource_pool_client()
+                .get_all()
+                .await
+                .context(error::ClientSnafu {
+                    action: "get all resource pools",
+                })?;
+        for resource_pool in &resource_pools {
+            topo_sort.insert(CrdName::ResourcePool(resource_pool.name_any()));
+        }
         let resource_client = self.resource_client();
         let resources = resource_client
             .get_all()
@@ -51,8 +108,17 @@ impl TestManager {
             .context(error::ClientSnafu {
                 action: "get all resources",
             })?;
-        for resource in resources {
+        for resource in &resources {
             topo_sort.insert(CrdName::Resource(resource.name_any()));
+            // A pool replaces its deleted members, so it must be deleted before them.
+            if let Some(pool) = resource.pool() {
+                if resource_pools.iter().any(|p| p.name_any() == pool) {
+                    topo_sort.add_dependency(
+                        CrdName::ResourcePool(pool.to_string()),
+                        CrdName::Resource(resource.name_any()),
+                    );
+                }
+            }
             if let Some(depended_resources) = &resource.spec.depends_on {
                 for depended_resource in depended_resources {
                     topo_sort.add_dependency(
@@ -66,6 +132,17 @@ impl TestManager {
         let tests = test_client.get_all().await.context(error::ClientSnafu {
             action: "get all tests",
         })?;
+        for resource in &resources {
+            // A leased resource should outlive the test that is using it.
+            if let Some(test) = resource.leased_by() {
+                if tests.iter().any(|t| t.name_any() == test) {
+                    topo_sort.add_dependency(
+                        CrdName::Test(test.to_string()),
+                        CrdName::Resource(resource.name_any()),
+                    );
+                }
+            }
+        }
         for test in tests {
             if test.spec.resources.is_empty() {
                 topo_sort.insert(CrdName::Test(test.name_any()));
@@ -112,6 +189,40 @@ impl TestManager {
                         }
                     }
                 }
+                Crd::TestSuite(test_suite) => {
+                    topo_sort.insert(CrdName::TestSuite(test_suite.name_any()));
+                }
+                Crd::ResourcePool(resource_pool) => {
+                    let pool_crd_name = CrdName::ResourcePool(resource_pool.name_any());
+                    topo_sort.insert(pool_crd_name.clone());
+                    for object_name in &object_names {
+                        if let CrdName::Resource(resource_name) = object_name {
+                            let is_member = objects.iter().any(|crd| {
+                                matches!(crd, Crd::Resource(resource)
+                                    if &resource.name_any() == resource_name
+                                        && resource.pool() == Some(resource_pool.name_any().as_str()))
+                            });
+                            if is_member {
+                                topo_sort
+                                    .add_dependency(pool_crd_name.clone(), object_name.clone());
+                            }
+                        }
+                    }
+                }
+                Crd::TestSchedule(test_schedule) => {
+                    let schedule_crd_name = CrdName::TestSchedule(test_schedule.name_any());
+                    topo_sort.insert(schedule_crd_name.clone());
+                    for crd in &objects {
+                        if let Crd::TestSuite(test_suite) = crd {
+                            if test_suite.schedule() == Some(test_schedule.name_any().as_str()) {
+                                topo_sort.add_dependency(
+                                    schedule_crd_name.clone(),
+                                    CrdName::TestSuite(test_suite.name_any()),
+                                );
+                            }
+                        }
+                    }
+                }
             }
         }
         topo_sort
@@ -124,6 +235,9 @@ async fn async_deletion(
     deletion_order: &mut TopologicalSort<CrdName>,
     test_client: TestClient,
     resource_client: ResourceClient,
+    test_suite_client: TestSuiteClient,
+    resource_pool_client: ResourcePoolClient,
+    test_schedule_client: TestScheduleClient,
 ) -> Result<()> {
     let mut awaiting_deletion = Vec::<CrdName>::new();
     loop {
@@ -164,6 +278,7 @@ async fn async_deletion(
                             .status()
                             .map(|status| status.destruction.task_state == TaskState::Error)
                             .unwrap_or_default()
+                            && !resource.is_retrying(ResourceAction::Destroy)
                         {
                             tx.send(Ok(DeleteEvent::Failed(CrdName::Resource(
                                 resource_name.to_string(),
@@ -181,6 +296,60 @@ async fn async_deletion(
                         .context(error::SenderSnafu)?;
                     }
                 }
+                CrdName::ResourcePool(resource_pool_name) => {
+                    let resource_pool = resource_pool_client
+                        .get(resource_pool_name)
+                        .await
+                        .allow_not_found(|_| ())
+                        .context(error::ClientSnafu {
+                            action: format!("get '{}'", resource_pool_name),
+                        })?;
+                    if resource_pool.is_some() {
+                        still_awaiting.push(CrdName::ResourcePool(resource_pool_name.to_string()));
+                    } else {
+                        tx.send(Ok(DeleteEvent::Deleted(CrdName::ResourcePool(
+                            resource_pool_name.to_string(),
+                        ))))
+                        .await
+                        .context(error::SenderSnafu)?;
+                    }
+                }
+                CrdName::TestSchedule(test_schedule_name) => {
+                    let test_schedule = test_schedule_client
+                        .get(test_schedule_name)
+                        .await
+                        .allow_not_found(|_| ())
+                        .context(error::ClientSnafu {
+                            action: format!("get '{}'", test_schedule_name),
+                        })?;
+                    if test_schedule.is_some() {
+                        still_awaiting.push(CrdName::TestSchedule(test_schedule_name.to_string()));
+                    } else {
+                        tx.send(Ok(DeleteEvent::Deleted(CrdName::TestSchedule(
+                            test_schedule_name.to_string(),
+                        ))))
+                        .await
+                        .context(error::SenderSnafu)?;
+                    }
+                }
+                CrdName::TestSuite(test_suite_name) => {
+                    let test_suite = test_suite_client
+                        .get(test_suite_name)
+                        .await
+                        .allow_not_found(|_| ())
+                        .context(error::ClientSnafu {
+                            action: format!("get '{}'", test_suite_name),
+                        })?;
+                    if test_suite.is_some() {
+                        still_awaiting.push(CrdName::TestSuite(test_suite_name.to_string()));
+                    } else {
+                        tx.send(Ok(DeleteEvent::Deleted(CrdName::TestSuite(
+                            test_suite_name.to_string(),
+                        ))))
+                        .await
+                        .context(error::SenderSnafu)?;
+                    }
+                }
             };
         }
         awaiting_deletion = still_awaiting;
@@ -213,6 +382,30 @@ async fn async_deletion(
                             action: format!("delete '{}'", resource_name),
                         })
                         .map(|_| ()),
+                    CrdName::TestSuite(test_suite_name) => test_suite_client
+                        .delete(test_suite_name)
+                        .await
+                        .allow_not_found(|_| ())
+                        .context(error::ClientSnafu {
+                            action: format!("delete '{}'", test_suite_name),
+                        })
+                        .map(|_| ()),
+                    CrdName::ResourcePool(resource_pool_name) => resource_pool_client
+                        .delete(resource_pool_name)
+                        .await
+                        .allow_not_found(|_| ())
+                        .context(error::ClientSnafu {
+                            action: format!("delete '{}'", resource_pool_name),
+                        })
+                        .map(|_| ()),
+                    CrdName::TestSchedule(test_schedule_name) => test_schedule_client
+                        .delete(test_schedule_name)
+                        .await
+                        .allow_not_found(|_| ())
+                        .context(error::ClientSnafu {
+                            action: format!("delete '{}'", test_schedule_name),
+                        })
+                        .map(|_| ()),
                 }?
             }
         }
@@ -35,6 +35,9 @@ pub enum Error {
         source: std::io::Error,
     },
 
+    #[snafu(display("Invalid test matrix: {}", what))]
+    InvalidMatrix { what: String },
+
     #[snafu(display("Unable to {}: {}", action, source))]
     Io {
         action: String,
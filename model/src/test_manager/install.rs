@@ -2,14 +2,19 @@ use super::{error, Result};
 use crate::clients::AllowNotFound;
 use crate::constants::NAMESPACE;
 use crate::system::{
-    agent_cluster_role, agent_cluster_role_binding, agent_service_account, controller_cluster_role,
-    controller_cluster_role_binding, controller_deployment, controller_service_account,
-    testsys_namespace, AgentType,
+    agent_role, agent_role_binding, agent_service_account, controller_cluster_role,
+    controller_cluster_role_binding, controller_lease_role, controller_lease_role_binding,
+    controller_service_account, controller_validating_webhook, controller_webhook_service,
+    testsys_namespace, AgentType, TESTSYS_WEBHOOK,
 };
 use crate::test_manager::TestManager;
-use crate::{Resource, Test};
+use crate::{Resource, ResourcePool, Test, TestSchedule, TestSuite};
+use k8s_openapi::api::admissionregistration::v1::ValidatingWebhookConfiguration;
+use k8s_openapi::api::apps::v1::Deployment;
 use k8s_openapi::api::core::v1::Namespace;
+use k8s_openapi::api::rbac::v1::{ClusterRole, ClusterRoleBinding};
 use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::api::ObjectMeta;
 use kube::{Api, CustomResourceExt, ResourceExt};
 use log::info;
 use snafu::ResultExt;
@@ -43,22 +48,52 @@ impl TestManager {
         let testcrd = Test::crd();
         // Create the `Resource` crd.
         let resourcecrd = Resource::crd();
+        // Create the `TestSuite` crd.
+        let testsuitecrd = TestSuite::crd();
+        // Create the `ResourcePool` crd.
+        let resourcepoolcrd = ResourcePool::crd();
+        // Create the `TestSchedule` crd.
+        let testschedulecrd = TestSchedule::crd();
 
         self.create_or_update(self.api(), &testcrd, "Test CRD")
             .await?;
         self.create_or_update(self.api(), &resourcecrd, "Resource Provider CRD")
+            .await?;
+        self.create_or_update(self.api(), &testsuitecrd, "TestSuite CRD")
+            .await?;
+        self.create_or_update(self.api(), &resourcepoolcrd, "ResourcePool CRD")
+            .await?;
+        self.create_or_update(self.api(), &testschedulecrd, "TestSchedule CRD")
             .await
     }
 
-    pub(super) async fn create_roles(&self, agent_type: AgentType) -> Result<()> {
-        // If the role exists merge the new role, if not create the role.
-        let test_agent_cluster_role = agent_cluster_role(agent_type);
-        self.create_or_update(self.api(), &test_agent_cluster_role, "Agent Cluster Role")
-            .await?;
+    /// Create `namespace` for agents to run in if it does not exist yet.
+    pub(super) async fn create_agent_namespace(&self, namespace: &str) -> Result<()> {
+        let api = self.api::<Namespace>();
+        let exists = api
+            .get_opt(namespace)
+            .await
+            .context(error::KubeSnafu {
+                action: "get namespace",
+            })?
+            .is_some();
+        if exists {
+            return Ok(());
+        }
+        let ns = Namespace {
+            metadata: ObjectMeta {
+                name: Some(namespace.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        self.create_or_update(api, &ns, "namespace").await
+    }
 
+    pub(super) async fn create_controller_roles(&self, watch_all_namespaces: bool) -> Result<()> {
         // If the role already exists, update it with the new one using Patch. If not create a new
         // role.
-        let controller_cluster_role = controller_cluster_role();
+        let controller_cluster_role = controller_cluster_role(watch_all_namespaces);
         self.create_or_update(
             self.api(),
             &controller_cluster_role,
@@ -68,33 +103,86 @@ impl TestManager {
 
         // If the cluster role binding already exists, update it with the new one using Patch. If
         // not create a new cluster role binding.
-        let agent_cluster_role_binding = agent_cluster_role_binding(agent_type);
+        let controller_cluster_role_binding = controller_cluster_role_binding();
         self.create_or_update(
             self.api(),
-            &agent_cluster_role_binding,
-            "Agent Cluster Role Binding",
+            &controller_cluster_role_binding,
+            "Controller Cluster Role Binding",
         )
         .await?;
 
-        // If the cluster role binding already exists, update it with the new one using Patch. If
-        // not create a new cluster role binding.
-        let controller_cluster_role_binding = controller_cluster_role_binding();
+        // The leader election lease only needs to be accessed in the `testsys` namespace.
         self.create_or_update(
-            self.api(),
-            &controller_cluster_role_binding,
-            "Controller Cluster Role Binding",
+            Api::namespaced(self.k8s_client.clone(), NAMESPACE),
+            &controller_lease_role(),
+            "Controller Lease Role",
+        )
+        .await?;
+        self.create_or_update(
+            Api::namespaced(self.k8s_client.clone(), NAMESPACE),
+            &controller_lease_role_binding(),
+            "Controller Lease Role Binding",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub(super) async fn create_roles(&self, agent_type: AgentType, namespace: &str) -> Result<()> {
+        // If the role exists merge the new role, if not create the role.
+        let agent_role = agent_role(agent_type, namespace);
+        self.create_or_update(
+            Api::namespaced(self.k8s_client.clone(), namespace),
+            &agent_role,
+            "Agent Role",
+        )
+        .await?;
+
+        // If the role binding already exists, update it with the new one using Patch. If not
+        // create a new role binding.
+        let agent_role_binding = agent_role_binding(agent_type, namespace);
+        self.create_or_update(
+            Api::namespaced(self.k8s_client.clone(), namespace),
+            &agent_role_binding,
+            "Agent Role Binding",
         )
         .await?;
 
         Ok(())
     }
 
-    pub(super) async fn create_service_accts(&self, agent_type: AgentType) -> Result<()> {
+    /// Agents used to be granted access to the TestSys objects in all namespaces with a cluster
+    /// role. Remove the cluster roles and bindings left behind by earlier installs.
+    pub(super) async fn delete_agent_cluster_roles(&self) -> Result<()> {
+        for agent_type in [AgentType::Test, AgentType::Resource] {
+            self.api::<ClusterRoleBinding>()
+                .delete(&agent_type.binding_name(), &Default::default())
+                .await
+                .allow_not_found(|_| ())
+                .context(error::KubeSnafu {
+                    action: "delete agent cluster role binding",
+                })?;
+            self.api::<ClusterRole>()
+                .delete(&agent_type.role_name(), &Default::default())
+                .await
+                .allow_not_found(|_| ())
+                .context(error::KubeSnafu {
+                    action: "delete agent cluster role",
+                })?;
+        }
+        Ok(())
+    }
+
+    pub(super) async fn create_service_accts(
+        &self,
+        agent_type: AgentType,
+        namespace: &str,
+    ) -> Result<()> {
         // If the service accounts already exist, update them with the new ones using Patch. If not
         // create new service accounts.
-        let agent_service_account = agent_service_account(agent_type);
+        let agent_service_account = agent_service_account(agent_type, namespace);
         self.create_or_update(
-            self.namespaced_api(),
+            Api::namespaced(self.k8s_client.clone(), namespace),
             &agent_service_account,
             "Agent Service Account",
         )
@@ -106,7 +194,7 @@ impl TestManager {
     pub(super) async fn create_controller_service_acct(&self) -> Result<()> {
         let controller_service_account = controller_service_account();
         self.create_or_update(
-            self.namespaced_api(),
+            self.system_api(),
             &controller_service_account,
             "Controller Service Account",
         )
@@ -115,21 +203,47 @@ impl TestManager {
         Ok(())
     }
 
-    pub(super) async fn create_deployment(
-        &self,
-        uri: String,
-        secret: Option<String>,
-        enable_logging: bool,
-    ) -> Result<()> {
-        let controller_deployment = controller_deployment(uri, secret, enable_logging);
-
+    pub(super) async fn create_deployment(&self, controller_deployment: &Deployment) -> Result<()> {
         // If the controller deployment already exists, update it with the new one using Patch. If
         // not create a new controller deployment.
-        self.create_or_update(self.namespaced_api(), &controller_deployment, "namespace")
+        self.create_or_update(self.system_api(), controller_deployment, "namespace")
             .await
     }
 
+    /// Register the controller's validating webhook, or remove it if it is not enabled.
+    pub(super) async fn create_webhook(&self, enable_webhook: bool) -> Result<()> {
+        if !enable_webhook {
+            return self.delete_webhook().await;
+        }
+        self.create_or_update(
+            self.system_api(),
+            &controller_webhook_service(),
+            "Webhook Service",
+        )
+        .await?;
+        self.create_or_update(
+            self.api(),
+            &controller_validating_webhook(),
+            "Validating Webhook Configuration",
+        )
+        .await
+    }
+
+    async fn delete_webhook(&self) -> Result<()> {
+        let webhook_api: Api<ValidatingWebhookConfiguration> = self.api();
+        webhook_api
+            .delete(TESTSYS_WEBHOOK, &Default::default())
+            .await
+            .allow_not_found(|_| ())
+            .context(error::KubeSnafu {
+                action: "delete TestSys validating webhook",
+            })?;
+        Ok(())
+    }
+
     pub(super) async fn uninstall_testsys(&self) -> Result<()> {
+        // The webhook configuration is not namespaced so it is not removed with the namespace.
+        self.delete_webhook().await?;
         let namespace_api: Api<Namespace> = self.api();
         namespace_api
             .delete(NAMESPACE, &Default::default())
@@ -150,6 +264,24 @@ impl TestManager {
             .context(error::KubeSnafu {
                 action: "delete TestSys Resource CRD",
             })?;
+        crd_api
+            .delete(&TestSuite::crd().name_any(), &Default::default())
+            .await
+            .context(error::KubeSnafu {
+                action: "delete TestSys TestSuite CRD",
+            })?;
+        crd_api
+            .delete(&ResourcePool::crd().name_any(), &Default::default())
+            .await
+            .context(error::KubeSnafu {
+                action: "delete TestSys ResourcePool CRD",
+            })?;
+        crd_api
+            .delete(&TestSchedule::crd().name_any(), &Default::default())
+            .await
+            .context(error::KubeSnafu {
+                action: "delete TestSys TestSchedule CRD",
+            })?;
         Ok(())
     }
 
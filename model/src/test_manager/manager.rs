@@ -1,19 +1,29 @@
+use super::matrix::TEST_MATRIX_KIND;
 use super::{
     error, CrdState, CrdType, DeleteEvent, DockerConfigJson, ImageConfig, ResourceState, Result,
-    SelectionParams, StatusSnapshot,
+    SelectionParams, StatusSnapshot, TestMatrix,
+};
+use crate::clients::{
+    AllowNotFound, CrdClient, ResourceClient, ResourcePoolClient, TestClient, TestScheduleClient,
+    TestSuiteClient,
+};
+use crate::constants::{NAMESPACE, TESTSYS_RESULTS_FILE};
+use crate::system::{
+    admission_config_map, controller_deployment, AdmissionConfig, AgentType, ALL_NAMESPACES,
+};
+use crate::{
+    Crd, CrdName, Outcome, Resource, SecretName, TaskState, Test, TestSuite, TestUserState,
 };
-use crate::clients::{AllowNotFound, CrdClient, ResourceClient, TestClient};
-use crate::constants::TESTSYS_RESULTS_FILE;
-use crate::system::AgentType;
-use crate::{Crd, CrdName, Resource, SecretName, TaskState, Test, TestUserState};
 use futures::{AsyncBufRead, Stream, StreamExt};
-use k8s_openapi::api::core::v1::{Pod, Secret};
+use k8s_openapi::api::core::v1::{ConfigMap, Pod, Secret};
+use k8s_openapi::api::events::v1::Event;
 use kube::api::{ListParams, LogParams};
 use kube::config::{KubeConfigOptions, Kubeconfig};
 use kube::{Api, Client, Config, ResourceExt};
 use serde::Deserialize;
 use snafu::{OptionExt, ResultExt};
-use std::{collections::BTreeMap, path::Path};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
 use tokio::io::AsyncWriteExt;
 
 /// # Test Manager
@@ -36,8 +46,14 @@ use tokio::io::AsyncWriteExt;
 /// independently or obtained from the `TestManager` using `test_client()` and `resource_client()`
 /// functions.
 ///
+/// # Namespaces
+///
+/// The test manager works with the TestSys objects in one namespace, `testsys` unless another one
+/// is chosen with `with_namespace`. The controller is always installed in the `testsys` namespace.
+///
 pub struct TestManager {
     pub k8s_client: Client,
+    pub namespace: String,
 }
 
 impl TestManager {
@@ -51,6 +67,7 @@ impl TestManager {
             k8s_client: config.try_into().context(error::KubeSnafu {
                 action: "create client from `Kubeconfig`",
             })?,
+            namespace: NAMESPACE.to_string(),
         })
     }
 
@@ -60,17 +77,42 @@ impl TestManager {
             k8s_client: Client::try_default().await.context(error::KubeSnafu {
                 action: "create client from `Kubeconfig`",
             })?,
+            namespace: NAMESPACE.to_string(),
         })
     }
 
+    /// Work with the TestSys objects in `namespace` instead of the `testsys` namespace.
+    pub fn with_namespace<S>(mut self, namespace: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.namespace = namespace.into();
+        self
+    }
+
     /// Create a `TestClient`
     pub fn test_client(&self) -> TestClient {
-        TestClient::new_from_k8s_client(self.k8s_client.clone())
+        TestClient::new_namespaced(self.k8s_client.clone(), &self.namespace)
     }
 
     /// Create a `ResourceClient`
     pub fn resource_client(&self) -> ResourceClient {
-        ResourceClient::new_from_k8s_client(self.k8s_client.clone())
+        ResourceClient::new_namespaced(self.k8s_client.clone(), &self.namespace)
+    }
+
+    /// Create a `TestSuiteClient`
+    pub fn test_suite_client(&self) -> TestSuiteClient {
+        TestSuiteClient::new_namespaced(self.k8s_client.clone(), &self.namespace)
+    }
+
+    /// Create a `ResourcePoolClient`
+    pub fn resource_pool_client(&self) -> ResourcePoolClient {
+        ResourcePoolClient::new_namespaced(self.k8s_client.clone(), &self.namespace)
+    }
+
+    /// Create a `TestScheduleClient`
+    pub fn test_schedule_client(&self) -> TestScheduleClient {
+        TestScheduleClient::new_namespaced(self.k8s_client.clone(), &self.namespace)
     }
 
     /// Create a secret for image pulls using `DockerConfigJson`
@@ -129,30 +171,90 @@ impl TestManager {
         Ok(secret)
     }
 
-    /// Install testsys to a cluster.
-    pub async fn install(&self, controller_config: ImageConfig, store_logs: bool) -> Result<()> {
+    /// Create or replace the `ConfigMap` holding the controller's concurrency limits.
+    pub async fn set_admission_config(&self, config: &AdmissionConfig) -> Result<ConfigMap> {
+        let config_map = admission_config_map(config).context(error::SerdeYamlSnafu {
+            action: "serialize admission config",
+        })?;
+        self.create_or_update(self.system_api(), &config_map, "admission config")
+            .await?;
+        Ok(config_map)
+    }
+
+    /// Install testsys to a cluster. If `enable_webhook` is set, the controller also serves a
+    /// validating webhook that rejects invalid `Test`s and `Resource`s when they are created.
+    ///
+    /// The controller watches the `testsys` namespace, the namespace of the `TestManager` and each
+    /// of `namespaces`, which may include [`ALL_NAMESPACES`]. Agents run in the namespace of their
+    /// `Test` or `Resource`, so the agent service accounts and roles are installed in each of these
+    /// namespaces. Namespaces that do not exist are created. If all namespaces are watched, the
+    /// controller is allowed to create the agent service accounts and roles in other namespaces
+    /// before it starts the first agent there.
+    ///
+    /// Agents may run as any of `agent_service_accounts` instead of the default agent service
+    /// accounts. These must exist in the namespaces of the agents.
+    ///
+    /// The controller runs with `replicas` pods, which elect a leader to reconcile objects so that
+    /// another replica takes over if the leader goes away.
+    pub async fn install(
+        &self,
+        controller_config: ImageConfig,
+        store_logs: bool,
+        enable_webhook: bool,
+        namespaces: &[String],
+        agent_service_accounts: &[String],
+        replicas: i32,
+    ) -> Result<()> {
         self.create_namespace().await?;
         self.create_crd().await?;
-        self.create_roles(AgentType::Test).await?;
-        self.create_roles(AgentType::Resource).await?;
-        self.create_service_accts(AgentType::Test).await?;
-        self.create_service_accts(AgentType::Resource).await?;
+        self.create_controller_roles(
+            namespaces
+                .iter()
+                .any(|namespace| namespace == ALL_NAMESPACES),
+        )
+        .await?;
         self.create_controller_service_acct().await?;
+        let mut namespaces = namespaces.to_vec();
+        namespaces.push(self.namespace.clone());
+        let agent_namespaces: BTreeSet<&str> = namespaces
+            .iter()
+            .map(String::as_str)
+            .chain([NAMESPACE])
+            .filter(|namespace| *namespace != ALL_NAMESPACES)
+            .collect();
+        for namespace in agent_namespaces {
+            self.create_agent_namespace(namespace).await?;
+            for agent_type in [AgentType::Test, AgentType::Resource] {
+                self.create_roles(agent_type, namespace).await?;
+                self.create_service_accts(agent_type, namespace).await?;
+            }
+        }
+        self.delete_agent_cluster_roles().await?;
 
         // Add the controller to the cluster
         let (image, secret) = match controller_config {
             ImageConfig::WithCreds { secret, image } => (image, Some(secret)),
             ImageConfig::Image(image) => (image, None),
         };
-        self.create_deployment(image, secret, store_logs).await?;
+        self.create_deployment(&controller_deployment(
+            image,
+            secret,
+            store_logs,
+            enable_webhook,
+            &namespaces,
+            agent_service_accounts,
+            replicas,
+        ))
+        .await?;
+        self.create_webhook(enable_webhook).await?;
 
         Ok(())
     }
 
-    /// Uninstall testsys from a cluster.
+    /// Uninstall testsys from a cluster. Fails if `Resource`s are left in any namespace, since
+    /// deleting the CRD would leave the resources they created behind.
     pub async fn uninstall(&self) -> Result<()> {
-        if !self
-            .resource_client()
+        if !ResourceClient::new_from_api(self.api())
             .get_all()
             .await
             .allow_not_found(|_| ())
@@ -171,7 +273,7 @@ impl TestManager {
     /// Restart a crd object by deleting the crd from the cluster and adding a copy of it with its
     /// status cleared.
     pub async fn restart_test(&self, name: &str) -> Result<()> {
-        let test_client = TestClient::new_from_k8s_client(self.k8s_client.clone());
+        let test_client = self.test_client();
         let mut test = test_client
             .get(name)
             .await
@@ -189,11 +291,74 @@ impl TestManager {
         Ok(())
     }
 
-    /// Add a testsys crd (`Test`, `Resource`) to the cluster.
+    /// Ask the agent of the test `name` to cancel the test. The agent keeps the results it has so
+    /// far and finishes with a `Cancelled` outcome.
+    pub async fn cancel_test(&self, name: &str) -> Result<()> {
+        self.test_client()
+            .send_cancel(name)
+            .await
+            .context(error::ClientSnafu {
+                action: "cancel test",
+            })?;
+        Ok(())
+    }
+
+    /// Set the `suspend` flag of the `Test`s, `Resource`s and `TestSchedule`s selected by
+    /// `selection_params`. The controller does not start the agent jobs of suspended tests and
+    /// resources, or new runs of suspended schedules. Returns the objects that were updated; other
+    /// kinds of objects are not affected.
+    pub async fn suspend(
+        &self,
+        selection_params: &SelectionParams,
+        suspend: bool,
+    ) -> Result<Vec<Crd>> {
+        let mut updated = Vec::new();
+        for crd in self.list(selection_params).await? {
+            match crd {
+                Crd::Test(test) => updated.push(Crd::Test(
+                    self.test_client()
+                        .send_suspend(test.name_any(), suspend)
+                        .await
+                        .context(error::ClientSnafu {
+                            action: "set 'suspend' for test",
+                        })?,
+                )),
+                Crd::Resource(resource) => updated.push(Crd::Resource(
+                    self.resource_client()
+                        .send_suspend(resource.name_any(), suspend)
+                        .await
+                        .context(error::ClientSnafu {
+                            action: "set 'suspend' for resource",
+                        })?,
+                )),
+                Crd::TestSchedule(test_schedule) => updated.push(Crd::TestSchedule(
+                    self.test_schedule_client()
+                        .send_suspend(test_schedule.name_any(), suspend)
+                        .await
+                        .context(error::ClientSnafu {
+                            action: "set 'suspend' for test schedule",
+                        })?,
+                )),
+                Crd::TestSuite(_) | Crd::ResourcePool(_) => {}
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Add a testsys crd (`Test`, `Resource`, `TestSuite`, `ResourcePool`, `TestSchedule`) to the
+    /// cluster. The object is created in the namespace it names, or in the namespace of the
+    /// `TestManager` if it does not name one.
     pub async fn create_object(&self, crd: Crd) -> Result<Crd> {
         match &crd {
             Crd::Test(test) => self.create_test(test.clone()).await?,
             Crd::Resource(resource) => self.create_resource(resource.clone()).await?,
+            Crd::TestSuite(test_suite) => self.create_test_suite(test_suite.clone()).await?,
+            Crd::ResourcePool(resource_pool) => {
+                self.create_resource_pool(resource_pool.clone()).await?
+            }
+            Crd::TestSchedule(test_schedule) => {
+                self.create_test_schedule(test_schedule.clone()).await?
+            }
         }
         Ok(crd)
     }
@@ -236,6 +401,58 @@ impl TestManager {
                     .map(Crd::Resource),
             );
         }
+        if matches!(selection_params.crd_type, Some(CrdType::TestSuite) | None) {
+            objects.extend(
+                self.test_suite_client()
+                    .api()
+                    .list(&list_params)
+                    .await
+                    .context(error::KubeSnafu {
+                        action: "list test suites from label params",
+                    })?
+                    .into_iter()
+                    .filter(|test_suite| {
+                        filter_test_suite_by_state(test_suite, &selection_params.state)
+                    })
+                    .map(Crd::TestSuite),
+            );
+        }
+        // Resource pools have no state to filter by, so they are only listed without a state filter.
+        if matches!(
+            selection_params.crd_type,
+            Some(CrdType::ResourcePool) | None
+        ) && selection_params.state.is_none()
+        {
+            objects.extend(
+                self.resource_pool_client()
+                    .api()
+                    .list(&list_params)
+                    .await
+                    .context(error::KubeSnafu {
+                        action: "list resource pools from label params",
+                    })?
+                    .into_iter()
+                    .map(Crd::ResourcePool),
+            );
+        }
+        // Test schedules have no state to filter by either.
+        if matches!(
+            selection_params.crd_type,
+            Some(CrdType::TestSchedule) | None
+        ) && selection_params.state.is_none()
+        {
+            objects.extend(
+                self.test_schedule_client()
+                    .api()
+                    .list(&list_params)
+                    .await
+                    .context(error::KubeSnafu {
+                        action: "list test schedules from label params",
+                    })?
+                    .into_iter()
+                    .map(Crd::TestSchedule),
+            );
+        }
 
         Ok(objects)
     }
@@ -285,6 +502,30 @@ impl TestManager {
                             action: "delete test",
                         })?;
                 }
+                Crd::TestSuite(test_suite) => {
+                    self.test_suite_client()
+                        .delete(test_suite.name_any())
+                        .await
+                        .context(error::ClientSnafu {
+                            action: "delete test suite",
+                        })?;
+                }
+                Crd::ResourcePool(resource_pool) => {
+                    self.resource_pool_client()
+                        .delete(resource_pool.name_any())
+                        .await
+                        .context(error::ClientSnafu {
+                            action: "delete resource pool",
+                        })?;
+                }
+                Crd::TestSchedule(test_schedule) => {
+                    self.test_schedule_client()
+                        .delete(test_schedule.name_any())
+                        .await
+                        .context(error::ClientSnafu {
+                            action: "delete test schedule",
+                        })?;
+                }
             };
         }
         Ok(())
@@ -312,6 +553,7 @@ impl TestManager {
     {
         let pod = self.test_pod(test_name).await?;
         let log_params = LogParams {
+            container: agent_container(&pod),
             follow,
             pretty: true,
             ..Default::default()
@@ -337,6 +579,7 @@ impl TestManager {
     {
         let pod = self.resource_pod(resource_name, state).await?;
         let log_params = LogParams {
+            container: agent_container(&pod),
             follow,
             pretty: true,
             ..Default::default()
@@ -351,7 +594,7 @@ impl TestManager {
     }
 
     pub async fn controller_logs(&self, follow: bool) -> Result<impl AsyncBufRead> {
-        let pod_api: Api<Pod> = self.namespaced_api();
+        let pod_api: Api<Pod> = self.system_api();
         let pod = self.controller_pod().await?;
         let log_params = LogParams {
             follow,
@@ -366,6 +609,32 @@ impl TestManager {
             })
     }
 
+    /// Retrieve the k8s events that the controller has published for the object of `kind`, e.g.
+    /// `Test`, named `name`, ordered from oldest to newest.
+    pub async fn events(&self, kind: &str, name: &str) -> Result<Vec<Event>> {
+        let event_api: Api<Event> = self.namespaced_api();
+        let mut events = event_api
+            .list(&ListParams {
+                field_selector: Some(format!("regarding.kind={},regarding.name={}", kind, name)),
+                ..Default::default()
+            })
+            .await
+            .context(error::KubeSnafu {
+                action: "list events",
+            })?
+            .items;
+        events.sort_by_key(|event| {
+            event.event_time.as_ref().map(|time| time.0).or_else(|| {
+                event
+                    .metadata
+                    .creation_timestamp
+                    .as_ref()
+                    .map(|time| time.0)
+            })
+        });
+        Ok(events)
+    }
+
     /// Write the results from a testsys `Test` to a given `destination`. The results are in the
     /// form of a tarball containing all files placed in the test agents output directory.
     pub async fn write_test_results(&self, test_name: &str, destination: &Path) -> Result<()> {
@@ -412,22 +681,32 @@ impl TestManager {
     }
 }
 
-/// Takes a path to a yaml manifest of testsys crds (`Test` and `Resource`) and creates a set of
-/// `Crd`s through deserialization. These can be added using `TestManager::create_object`
+/// Takes a path to a yaml manifest of testsys crds (`Test`, `Resource` and `TestSuite`) and creates
+/// a set of `Crd`s through deserialization. Any `TestMatrix` in the manifest is expanded into its
+/// `Test`s and `Resource`s. These can be added using `TestManager::create_object`
 pub fn read_manifest(path: &Path) -> Result<Vec<Crd>> {
     // Create the resource objects from its path.
     let manifest_string = std::fs::read_to_string(path).context(error::FileSnafu { path })?;
     convert_manifest(manifest_string)
 }
 
-/// Takes a `String` containing a yaml manifest of testsys crds (`Test` and `Resource`) and creates
-/// a set of `Crd`s through deserialization. These can be added using `TestManager::create_object`
+/// Takes a `String` containing a yaml manifest of testsys crds (`Test`, `Resource` and `TestSuite`)
+/// and creates a set of `Crd`s through deserialization. Any `TestMatrix` in the manifest is expanded
+/// into its `Test`s and `Resource`s. These can be added using `TestManager::create_object`
 pub fn convert_manifest(manifest_string: String) -> Result<Vec<Crd>> {
     let mut crds = Vec::new();
     for crd_doc in serde_yaml::Deserializer::from_str(&manifest_string) {
         let value = serde_yaml::Value::deserialize(crd_doc).context(error::SerdeYamlSnafu {
             action: "deserialize manifest",
         })?;
+        if value.get("kind").and_then(|kind| kind.as_str()) == Some(TEST_MATRIX_KIND) {
+            let matrix: TestMatrix =
+                serde_yaml::from_value(value).context(error::SerdeYamlSnafu {
+                    action: "deserialize test matrix",
+                })?;
+            crds.extend(matrix.expand().map_err(|e| *e)?);
+            continue;
+        }
         let crd: Crd = serde_yaml::from_value(value).context(error::SerdeYamlSnafu {
             action: "deserialize manifest",
         })?;
@@ -449,6 +728,7 @@ fn filter_test_by_state(test: &Test, state: &Option<CrdState>) -> bool {
                     | TestUserState::Failed
                     | TestUserState::Error
                     | TestUserState::ResourceError
+                    | TestUserState::Cancelled
             ),
             CrdState::Passed => {
                 matches!(test.test_user_state(), TestUserState::Passed)
@@ -469,6 +749,28 @@ fn filter_test_by_state(test: &Test, state: &Option<CrdState>) -> bool {
     }
 }
 
+fn filter_test_suite_by_state(test_suite: &TestSuite, state: &Option<CrdState>) -> bool {
+    let outcome = test_suite
+        .status
+        .as_ref()
+        .map(|status| status.outcome)
+        .unwrap_or_default();
+    if let Some(state) = state {
+        match state {
+            CrdState::Running | CrdState::NotFinished => {
+                matches!(outcome, Outcome::InProgress | Outcome::Unknown)
+            }
+            CrdState::Completed => {
+                matches!(outcome, Outcome::Pass | Outcome::Fail | Outcome::Cancelled)
+            }
+            CrdState::Passed => matches!(outcome, Outcome::Pass),
+            CrdState::Failed => matches!(outcome, Outcome::Fail),
+        }
+    } else {
+        true
+    }
+}
+
 fn filter_resource_by_state(resource: &Resource, state: &Option<CrdState>) -> bool {
     if let Some(state) = state {
         match state {
@@ -497,3 +799,11 @@ fn filter_resource_by_state(resource: &Resource, state: &Option<CrdState>) -> bo
         true
     }
 }
+
+/// The name of the agent container of an agent `pod`, which comes before any sidecars.
+fn agent_container(pod: &Pod) -> Option<String> {
+    pod.spec
+        .as_ref()
+        .and_then(|spec| spec.containers.first())
+        .map(|container| container.name.clone())
+}
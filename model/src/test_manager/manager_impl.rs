@@ -1,7 +1,10 @@
 use super::{error, ResourceState, Result, TestManager};
-use crate::clients::{AllowNotFound, CrdClient};
+use crate::clients::{
+    AllowNotFound, CrdClient, ResourceClient, ResourcePoolClient, TestClient, TestScheduleClient,
+    TestSuiteClient,
+};
 use crate::constants::{LABEL_COMPONENT, NAMESPACE};
-use crate::{Crd, CrdName, Resource, Test};
+use crate::{Crd, CrdExt, CrdName, Resource, ResourcePool, Test, TestSchedule, TestSuite};
 use k8s_openapi::api::core::v1::Pod;
 use k8s_openapi::NamespaceResourceScope;
 use kube::api::{ListParams, Patch, PatchParams, PostParams};
@@ -74,8 +77,17 @@ impl TestManager {
         Api::<T>::all(self.k8s_client.clone())
     }
 
-    /// Creates a namespaced api of type `T`
+    /// Creates an api of type `T` for the namespace that the `TestManager` works in
     pub(super) fn namespaced_api<T>(&self) -> Api<T>
+    where
+        T: KubeResource<Scope = NamespaceResourceScope>,
+        <T as KubeResource>::DynamicType: Default,
+    {
+        Api::<T>::namespaced(self.k8s_client.clone(), &self.namespace)
+    }
+
+    /// Creates an api of type `T` for the `testsys` namespace, which the controller is installed in
+    pub(super) fn system_api<T>(&self) -> Api<T>
     where
         T: KubeResource<Scope = NamespaceResourceScope>,
         <T as KubeResource>::DynamicType: Default,
@@ -93,6 +105,12 @@ impl TestManager {
             let resources = match crd {
                 Crd::Test(test) => test.spec.resources,
                 Crd::Resource(resource) => resource.spec.depends_on.unwrap_or_default(),
+                // The children of a test suite are owned, and deleted, by the test suite itself.
+                Crd::TestSuite(_) => Vec::new(),
+                // The members of a resource pool are owned, and deleted, by the pool itself.
+                Crd::ResourcePool(_) => Vec::new(),
+                // The runs of a test schedule are owned, and deleted, by the schedule itself.
+                Crd::TestSchedule(_) => Vec::new(),
             };
             for resource in resources {
                 if let Some(resource_spec) = self
@@ -150,12 +168,26 @@ impl TestManager {
                 );
                 pods
             }
+            CrdName::TestSuite(_) | CrdName::ResourcePool(_) | CrdName::TestSchedule(_) => {
+                Vec::new()
+            }
         })
     }
 
+    /// The namespace that `object` is created in: the namespace it names, or the namespace of the
+    /// `TestManager` if it does not name one.
+    fn namespace_of<'a, T: CrdExt>(&'a self, object: &'a T) -> &'a str {
+        object
+            .object_meta()
+            .namespace
+            .as_deref()
+            .unwrap_or(&self.namespace)
+    }
+
     /// Add a testsys test to the cluster.
     pub(super) async fn create_test(&self, test: Test) -> Result<()> {
-        let test_client = self.test_client();
+        let test_client =
+            TestClient::new_namespaced(self.k8s_client.clone(), self.namespace_of(&test));
         test_client.create(test).await.context(error::ClientSnafu {
             action: "create new test",
         })?;
@@ -164,7 +196,8 @@ impl TestManager {
 
     /// Add a testsys resource to the cluster.
     pub(super) async fn create_resource(&self, resource: Resource) -> Result<()> {
-        let resource_client = self.resource_client();
+        let resource_client =
+            ResourceClient::new_namespaced(self.k8s_client.clone(), self.namespace_of(&resource));
         resource_client
             .create(resource)
             .await
@@ -174,6 +207,45 @@ impl TestManager {
         Ok(())
     }
 
+    /// Add a testsys test suite to the cluster.
+    pub(super) async fn create_test_suite(&self, test_suite: TestSuite) -> Result<()> {
+        TestSuiteClient::new_namespaced(self.k8s_client.clone(), self.namespace_of(&test_suite))
+            .create(test_suite)
+            .await
+            .context(error::ClientSnafu {
+                action: "create new test suite",
+            })?;
+        Ok(())
+    }
+
+    /// Add a testsys resource pool to the cluster.
+    pub(super) async fn create_resource_pool(&self, resource_pool: ResourcePool) -> Result<()> {
+        ResourcePoolClient::new_namespaced(
+            self.k8s_client.clone(),
+            self.namespace_of(&resource_pool),
+        )
+        .create(resource_pool)
+        .await
+        .context(error::ClientSnafu {
+            action: "create new resource pool",
+        })?;
+        Ok(())
+    }
+
+    /// Add a testsys test schedule to the cluster.
+    pub(super) async fn create_test_schedule(&self, test_schedule: TestSchedule) -> Result<()> {
+        TestScheduleClient::new_namespaced(
+            self.k8s_client.clone(),
+            self.namespace_of(&test_schedule),
+        )
+        .create(test_schedule)
+        .await
+        .context(error::ClientSnafu {
+            action: "create new test schedule",
+        })?;
+        Ok(())
+    }
+
     /// Get a pod for a testsys test.
     pub(super) async fn test_pod<S>(&self, test: S) -> Result<Pod>
     where
@@ -231,7 +303,7 @@ impl TestManager {
 
     /// Get a pod for the testsys controller.
     pub(super) async fn controller_pod(&self) -> Result<Pod> {
-        let pod_api: Api<Pod> = self.namespaced_api();
+        let pod_api: Api<Pod> = self.system_api();
         pod_api
             .list(&ListParams {
                 label_selector: Some(format!("{}={}", LABEL_COMPONENT, "controller")),
@@ -0,0 +1,500 @@
+use super::{error, Error};
+use crate::constants::{LABEL_MATRIX, LABEL_MATRIX_AXIS_PREFIX, NAMESPACE};
+use crate::{Crd, Resource, ResourceSpec, Test, TestSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use snafu::{ensure, ResultExt};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// `Error` is large, so it is boxed while matrices are expanded.
+type Result<T> = std::result::Result<T, Box<Error>>;
+
+/// The `kind` used by a `TestMatrix` document in a manifest.
+pub(super) const TEST_MATRIX_KIND: &str = "TestMatrix";
+
+/// The maximum length of the name of a `Test` or `Resource`, which is also used as a label value.
+const MAX_NAME_LENGTH: usize = 63;
+
+/// Matches a k8s label value, or the name part of a label key.
+const LABEL_PATTERN_REGEX: &str = r"^[A-Za-z0-9]([-A-Za-z0-9_.]*[A-Za-z0-9])?$";
+
+lazy_static::lazy_static! {
+    static ref LABEL_REGEX: Regex = {
+        #[allow(clippy::unwrap_used)]
+        Regex::new(LABEL_PATTERN_REGEX).unwrap()
+    };
+}
+
+/// A `TestMatrix` is a manifest document that expands a single templated `Test`, and the
+/// `Resource`s it uses, into one uniquely named copy for every combination of the values in
+/// `matrix`. It is expanded when the manifest is read and is never created in the cluster.
+///
+/// Any string in `test` or `resources` may refer to the value of an axis with
+/// `${matrix.<axis>}`. Each copy is named `<name>-<values>` and labeled with its coordinates in
+/// the matrix. References to the resources of the matrix (in `resources`, `dependsOn`,
+/// `conflictsWith` and `${<resource>.<field>}` templates) are renamed to the matching copy.
+///
+/// ```yaml
+/// apiVersion: testsys.system/v1
+/// kind: TestMatrix
+/// metadata:
+///   name: sonobuoy
+/// spec:
+///   matrix:
+///     version: ["1.24", "1.25"]
+///     arch: [x86_64, aarch64]
+///   resources:
+///     cluster:
+///       agent: ...
+///   test:
+///     resources: [cluster]
+///     agent: ...
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TestMatrix {
+    pub metadata: ObjectMeta,
+    pub spec: TestMatrixSpec,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestMatrixSpec {
+    /// The axes of the matrix and the values that each axis takes.
+    pub matrix: BTreeMap<String, Vec<String>>,
+    /// The templated `ResourceSpec`s, keyed by name.
+    #[serde(default)]
+    pub resources: BTreeMap<String, Value>,
+    /// The templated `TestSpec`.
+    pub test: Value,
+}
+
+/// The label key used for the value of the matrix `axis`.
+pub fn matrix_axis_label(axis: &str) -> String {
+    format!("{}{}", LABEL_MATRIX_AXIS_PREFIX, axis)
+}
+
+impl TestMatrix {
+    fn name(&self) -> &str {
+        self.metadata.name.as_deref().unwrap_or_default()
+    }
+
+    /// Creates a `Test` and a set of `Resource`s for every combination of values in the matrix.
+    pub fn expand(&self) -> Result<Vec<Crd>> {
+        self.validate()?;
+        let coordinates = self.coordinates();
+        let suffixes = name_suffixes(&coordinates);
+        let mut names = BTreeSet::new();
+        let mut crds = Vec::new();
+        for (coordinates, suffix) in coordinates.iter().zip(suffixes) {
+            let mut renames = BTreeMap::new();
+            for name in self.spec.resources.keys() {
+                renames.insert(name.to_owned(), object_name(name, &suffix, &mut names)?);
+            }
+            let test_name = object_name(self.name(), &suffix, &mut names)?;
+
+            let mut resources = Vec::new();
+            for (name, spec) in &self.spec.resources {
+                let mut spec: ResourceSpec =
+                    serde_yaml::from_value(substitute(spec.clone(), coordinates, &renames))
+                        .context(error::SerdeYamlSnafu {
+                            action: format!("deserialize matrix resource '{}'", name),
+                        })?;
+                spec.depends_on = spec.depends_on.map(|names| rename_all(names, &renames));
+                spec.conflicts_with = spec.conflicts_with.map(|names| rename_all(names, &renames));
+                resources.push(Resource {
+                    metadata: self.metadata(&renames[name], coordinates),
+                    spec,
+                    status: None,
+                });
+            }
+            crds.extend(dependency_order(resources).into_iter().map(Crd::Resource));
+
+            let mut spec: TestSpec =
+                serde_yaml::from_value(substitute(self.spec.test.clone(), coordinates, &renames))
+                    .context(error::SerdeYamlSnafu {
+                    action: "deserialize matrix test",
+                })?;
+            spec.resources = rename_all(spec.resources, &renames);
+            crds.push(Crd::Test(Test {
+                metadata: self.metadata(&test_name, coordinates),
+                spec,
+                status: None,
+            }));
+        }
+        Ok(crds)
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            !self.name().is_empty(),
+            error::InvalidMatrixSnafu {
+                what: "the matrix has no name"
+            }
+        );
+        for (axis, values) in &self.spec.matrix {
+            let label = matrix_axis_label(axis);
+            let label_name = label
+                .split_once('/')
+                .map(|(_, name)| name)
+                .unwrap_or(&label);
+            ensure!(
+                is_label_value(label_name),
+                error::InvalidMatrixSnafu {
+                    what: format!("'{}' is not a valid axis name", axis)
+                }
+            );
+            ensure!(
+                !values.is_empty(),
+                error::InvalidMatrixSnafu {
+                    what: format!("axis '{}' has no values", axis)
+                }
+            );
+            for value in values {
+                ensure!(
+                    is_label_value(value),
+                    error::InvalidMatrixSnafu {
+                        what: format!("'{}' of axis '{}' is not a valid label value", value, axis)
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Every combination of axis values, ordered by axis name and then by the order of the values.
+    fn coordinates(&self) -> Vec<BTreeMap<String, String>> {
+        self.spec
+            .matrix
+            .iter()
+            .fold(vec![BTreeMap::new()], |combinations, (axis, values)| {
+                combinations
+                    .into_iter()
+                    .flat_map(|combination| {
+                        values.iter().map(move |value| {
+                            let mut combination = combination.clone();
+                            combination.insert(axis.to_owned(), value.to_owned());
+                            combination
+                        })
+                    })
+                    .collect()
+            })
+    }
+
+    fn metadata(&self, name: &str, coordinates: &BTreeMap<String, String>) -> ObjectMeta {
+        let mut labels = self.metadata.labels.clone().unwrap_or_default();
+        labels.insert(LABEL_MATRIX.to_string(), self.name().to_string());
+        for (axis, value) in coordinates {
+            labels.insert(matrix_axis_label(axis), value.to_owned());
+        }
+        ObjectMeta {
+            name: Some(name.to_string()),
+            namespace: Some(
+                self.metadata
+                    .namespace
+                    .clone()
+                    .unwrap_or_else(|| NAMESPACE.to_string()),
+            ),
+            labels: Some(labels),
+            ..Default::default()
+        }
+    }
+}
+
+fn is_label_value(value: &str) -> bool {
+    value.len() <= 63 && LABEL_REGEX.is_match(value)
+}
+
+/// The name of the copy of the object called `name` with `suffix`, which is added to `names`. Names
+/// must be unique and short enough to be used as a label value.
+fn object_name(name: &str, suffix: &str, names: &mut BTreeSet<String>) -> Result<String> {
+    let name = format!("{}-{}", name, suffix);
+    ensure!(
+        name.len() <= MAX_NAME_LENGTH,
+        error::InvalidMatrixSnafu {
+            what: format!(
+                "'{}' is longer than the {} characters allowed in a name",
+                name, MAX_NAME_LENGTH
+            )
+        }
+    );
+    ensure!(
+        names.insert(name.clone()),
+        error::InvalidMatrixSnafu {
+            what: format!("more than one object would be named '{}'", name)
+        }
+    );
+    Ok(name)
+}
+
+/// The string that is used in the object names of each of `coordinates`. The values of the
+/// coordinates are joined, e.g. `{"arch": "x86_64", "version": "1.24"}` becomes `x86-64-1-24`.
+/// Coordinates whose values would be joined into the same string, e.g. `1.28` and `1-28`, use a
+/// short hash of their values instead.
+fn name_suffixes(coordinates: &[BTreeMap<String, String>]) -> Vec<String> {
+    let readable: Vec<String> = coordinates.iter().map(readable_suffix).collect();
+    let mut counts = BTreeMap::new();
+    for suffix in &readable {
+        *counts.entry(suffix.as_str()).or_insert(0) += 1;
+    }
+    readable
+        .iter()
+        .zip(coordinates)
+        .map(|(suffix, coordinates)| {
+            if counts.get(suffix.as_str()).copied().unwrap_or_default() > 1 {
+                coordinates_hash(coordinates)
+            } else {
+                suffix.to_owned()
+            }
+        })
+        .collect()
+}
+
+fn readable_suffix(coordinates: &BTreeMap<String, String>) -> String {
+    coordinates
+        .values()
+        .map(|value| {
+            value
+                .to_lowercase()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// A 32-bit FNV-1a hash of `coordinates` in hex. It is stable across releases so that expanding
+/// the same matrix always results in the same names.
+fn coordinates_hash(coordinates: &BTreeMap<String, String>) -> String {
+    let hash = coordinates
+        .iter()
+        .flat_map(|(axis, value)| [axis.as_bytes(), b"=", value.as_bytes(), b"\n"])
+        .flatten()
+        .fold(0x811c9dc5_u32, |hash, byte| {
+            (hash ^ u32::from(*byte)).wrapping_mul(0x01000193)
+        });
+    format!("{:08x}", hash)
+}
+
+fn rename_all(names: Vec<String>, renames: &BTreeMap<String, String>) -> Vec<String> {
+    names
+        .into_iter()
+        .map(|name| renames.get(&name).cloned().unwrap_or(name))
+        .collect()
+}
+
+/// Orders `resources` so that each resource comes after the resources it depends on, allowing them
+/// to be created one at a time. Resources that are part of a dependency cycle keep their order at
+/// the end.
+fn dependency_order(mut resources: Vec<Resource>) -> Vec<Resource> {
+    let names: BTreeSet<String> = resources
+        .iter()
+        .filter_map(|resource| resource.metadata.name.clone())
+        .collect();
+    let mut created = BTreeSet::new();
+    let mut ordered = Vec::new();
+    while !resources.is_empty() {
+        let (ready, waiting): (Vec<_>, Vec<_>) = resources.into_iter().partition(|resource| {
+            resource
+                .spec
+                .depends_on
+                .iter()
+                .flatten()
+                .all(|name| created.contains(name) || !names.contains(name))
+        });
+        if ready.is_empty() {
+            ordered.extend(waiting);
+            break;
+        }
+        created.extend(
+            ready
+                .iter()
+                .filter_map(|resource| resource.metadata.name.clone()),
+        );
+        ordered.extend(ready);
+        resources = waiting;
+    }
+    ordered
+}
+
+/// Recursively replaces `${matrix.<axis>}` with the value of the axis, and `${<resource>.` with
+/// the name of the resource's copy, in every string of `value`.
+fn substitute(
+    value: Value,
+    coordinates: &BTreeMap<String, String>,
+    renames: &BTreeMap<String, String>,
+) -> Value {
+    match value {
+        Value::String(mut s) => {
+            for (axis, axis_value) in coordinates {
+                s = s.replace(&format!("${{matrix.{}}}", axis), axis_value);
+            }
+            for (name, rename) in renames {
+                s = s.replace(&format!("${{{}.", name), &format!("${{{}.", rename));
+            }
+            Value::String(s)
+        }
+        Value::Sequence(sequence) => Value::Sequence(
+            sequence
+                .into_iter()
+                .map(|value| substitute(value, coordinates, renames))
+                .collect(),
+        ),
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .map(|(key, value)| (key, substitute(value, coordinates, renames)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_manager::manager::convert_manifest;
+
+    const MANIFEST: &str = r#"
+apiVersion: testsys.system/v1
+kind: TestMatrix
+metadata:
+  name: sonobuoy
+  labels:
+    team: platform
+spec:
+  matrix:
+    version: ["1.24", "1.25"]
+    arch: [x86_64, aarch64]
+  resources:
+    cluster:
+      agent:
+        name: cluster-agent
+        image: example-resource-agent
+        keepRunning: false
+        configuration:
+          version: ${matrix.version}
+    instances:
+      dependsOn: [cluster]
+      agent:
+        name: ec2-agent
+        image: example-resource-agent
+        keepRunning: false
+        configuration:
+          arch: ${matrix.arch}
+          endpoint: ${cluster.endpoint}
+  test:
+    resources: [cluster, instances]
+    agent:
+      name: sonobuoy-agent
+      image: example-test-agent
+      keepRunning: false
+      configuration:
+        kubeconfig: ${cluster.kubeconfig}
+        description: "k8s ${matrix.version} on ${matrix.arch}"
+"#;
+
+    #[test]
+    fn expand_matrix() {
+        let crds = convert_manifest(MANIFEST.to_string()).unwrap();
+        // 4 combinations of 2 resources and a test.
+        assert_eq!(crds.len(), 12);
+        let tests: Vec<_> = crds
+            .iter()
+            .filter_map(|crd| match crd {
+                Crd::Test(test) => Some(test),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<_> = tests
+            .iter()
+            .map(|test| test.metadata.name.clone().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "sonobuoy-x86-64-1-24",
+                "sonobuoy-x86-64-1-25",
+                "sonobuoy-aarch64-1-24",
+                "sonobuoy-aarch64-1-25",
+            ]
+        );
+
+        let test = tests[0];
+        assert_eq!(
+            test.spec.resources,
+            vec!["cluster-x86-64-1-24", "instances-x86-64-1-24"]
+        );
+        let configuration = test.spec.agent.configuration.as_ref().unwrap();
+        assert_eq!(
+            configuration["kubeconfig"],
+            "${cluster-x86-64-1-24.kubeconfig}"
+        );
+        assert_eq!(configuration["description"], "k8s 1.24 on x86_64");
+        let labels = test.metadata.labels.as_ref().unwrap();
+        assert_eq!(labels[LABEL_MATRIX], "sonobuoy");
+        assert_eq!(labels[&matrix_axis_label("arch")], "x86_64");
+        assert_eq!(labels[&matrix_axis_label("version")], "1.24");
+        assert_eq!(labels["team"], "platform");
+
+        let instances = crds
+            .iter()
+            .find_map(|crd| match crd {
+                Crd::Resource(resource)
+                    if resource.metadata.name.as_deref() == Some("instances-x86-64-1-24") =>
+                {
+                    Some(resource)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            instances.spec.depends_on,
+            Some(vec!["cluster-x86-64-1-24".to_string()])
+        );
+        let configuration = instances.spec.agent.configuration.as_ref().unwrap();
+        assert_eq!(configuration["arch"], "x86_64");
+        assert_eq!(configuration["endpoint"], "${cluster-x86-64-1-24.endpoint}");
+    }
+
+    #[test]
+    fn resources_follow_dependencies() {
+        let manifest = MANIFEST
+            .replace("    cluster:\n", "    z-cluster:\n")
+            .replace("[cluster", "[z-cluster")
+            .replace("${cluster.", "${z-cluster.");
+        let crds = convert_manifest(manifest).unwrap();
+        let names: Vec<_> = crds.iter().take(3).filter_map(Crd::name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "z-cluster-x86-64-1-24",
+                "instances-x86-64-1-24",
+                "sonobuoy-x86-64-1-24"
+            ]
+        );
+    }
+
+    #[test]
+    fn colliding_names() {
+        let manifest = MANIFEST.replace("[\"1.24\", \"1.25\"]", "[\"1.28\", \"1-28\"]");
+        let crds = convert_manifest(manifest).unwrap();
+        let names: BTreeSet<_> = crds.iter().filter_map(Crd::name).collect();
+        assert_eq!(names.len(), crds.len());
+        // Every coordinate collides with another one, so they are all named by hash.
+        assert!(names.iter().all(|name| !name.ends_with("-1-28")));
+        assert!(names.iter().all(|name| name.len() <= MAX_NAME_LENGTH));
+    }
+
+    #[test]
+    fn invalid_matrix() {
+        let manifest = MANIFEST.replace("[x86_64, aarch64]", "[]");
+        assert!(convert_manifest(manifest).is_err());
+        let manifest = MANIFEST.replace("[x86_64, aarch64]", "[\"x86 64\"]");
+        assert!(convert_manifest(manifest).is_err());
+        let manifest = MANIFEST.replace("name: sonobuoy\n", &format!("name: {}\n", "a".repeat(60)));
+        assert!(convert_manifest(manifest).is_err());
+    }
+}
@@ -3,9 +3,10 @@ use base64::Engine;
 pub use delete::DeleteEvent;
 pub use error::{Error, Result};
 pub use manager::{read_manifest, TestManager};
+pub use matrix::{matrix_axis_label, TestMatrix, TestMatrixSpec};
 use serde::{Deserialize, Serialize};
 use serde_plain::derive_fromstr_from_deserialize;
-pub use status::{StatusColumn, StatusSnapshot};
+pub use status::{AxisGroup, AxisSummary, StatusColumn, StatusSnapshot};
 use std::collections::HashMap;
 
 mod delete;
@@ -13,6 +14,7 @@ mod error;
 mod install;
 mod manager;
 mod manager_impl;
+mod matrix;
 mod status;
 
 #[derive(Default, Debug, Clone)]
@@ -34,6 +36,9 @@ pub struct SelectionParams {
 pub enum CrdType {
     Test,
     Resource,
+    TestSuite,
+    ResourcePool,
+    TestSchedule,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -1,12 +1,14 @@
-use crate::{Crd, TaskState};
+use super::matrix_axis_label;
+use crate::{Crd, Outcome, TaskState, TestUserState};
 use kube::{core::object::HasStatus, ResourceExt};
 use serde::Serialize;
 use std::cmp::max;
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use tabled::builder::Builder;
 use tabled::settings::{
     location::ByColumnName,
-    object::Rows,
+    object::{Columns, Rows},
     width::{MinWidth, Width},
     Alignment, Disable, Modify, Style,
 };
@@ -91,6 +93,9 @@ impl StatusSnapshot {
                         passed = false;
                         failed_tests.push(test.name_any());
                     }
+                    TaskState::Completed if test.test_user_state() == TestUserState::Cancelled => {
+                        passed = false
+                    }
                     _ => continue,
                 },
                 Crd::Resource(resource) => {
@@ -110,6 +115,24 @@ impl StatusSnapshot {
                         _ => continue,
                     }
                 }
+                // The tests of a suite are reported individually, so only the aggregate outcome
+                // of the suite is considered here.
+                Crd::TestSuite(test_suite) => {
+                    match test_suite.status.as_ref().map(|status| status.outcome) {
+                        Some(Outcome::Pass) => continue,
+                        Some(Outcome::Fail | Outcome::Timeout | Outcome::Cancelled) => {
+                            passed = false
+                        }
+                        _ => {
+                            passed = false;
+                            finished = false
+                        }
+                    }
+                }
+                // Resource pools are never finished, their members are reported individually.
+                Crd::ResourcePool(_) => continue,
+                // Test schedules are never finished, their runs are reported individually.
+                Crd::TestSchedule(_) => continue,
             }
         }
         Self {
@@ -142,6 +165,93 @@ impl StatusSnapshot {
         self.columns = columns;
         self
     }
+
+    /// Aggregates the results of the `Test`s in this snapshot by their value of the matrix `axis`.
+    /// `Test`s that were not created from a `TestMatrix` with this axis are ignored.
+    pub fn group_by_axis<S>(&self, axis: S) -> AxisSummary
+    where
+        S: Into<String>,
+    {
+        let axis = axis.into();
+        let label = matrix_axis_label(&axis);
+        let mut groups: BTreeMap<String, AxisGroup> = BTreeMap::new();
+        for crd in &self.crds {
+            let test = match crd {
+                Crd::Test(test) => test,
+                _ => continue,
+            };
+            let value = match test.metadata.labels.as_ref().and_then(|l| l.get(&label)) {
+                Some(value) => value,
+                None => continue,
+            };
+            let group = groups.entry(value.to_owned()).or_insert_with(|| AxisGroup {
+                value: value.to_owned(),
+                ..Default::default()
+            });
+            group.tests += 1;
+            match test.test_user_state() {
+                TestUserState::Passed | TestUserState::NoTests => group.passed += 1,
+                TestUserState::Failed
+                | TestUserState::Error
+                | TestUserState::ResourceError
+                | TestUserState::Cancelled => group.failed += 1,
+                TestUserState::Unknown
+                | TestUserState::Waiting
+                | TestUserState::Running
+                | TestUserState::Deleting => group.not_finished += 1,
+            }
+        }
+        AxisSummary {
+            axis,
+            groups: groups.into_values().collect(),
+        }
+    }
+}
+
+/// The aggregated results of the `Test`s that share a value of a matrix axis.
+#[derive(Debug, Default, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AxisGroup {
+    pub value: String,
+    pub tests: u64,
+    pub passed: u64,
+    pub failed: u64,
+    pub not_finished: u64,
+}
+
+/// `AxisSummary` is created by `StatusSnapshot::group_by_axis`. `AxisSummary::to_string()` creates
+/// a table with a row for each value of the axis.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct AxisSummary {
+    pub axis: String,
+    pub groups: Vec<AxisGroup>,
+}
+
+impl Display for AxisSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut builder = Builder::default();
+        builder.push_record([
+            self.axis.to_uppercase(),
+            "TESTS".to_string(),
+            "PASSED".to_string(),
+            "FAILED".to_string(),
+            "NOT FINISHED".to_string(),
+        ]);
+        for group in &self.groups {
+            builder.push_record([
+                group.value.to_owned(),
+                group.tests.to_string(),
+                group.passed.to_string(),
+                group.failed.to_string(),
+                group.not_finished.to_string(),
+            ]);
+        }
+        let mut table = builder.build();
+        table
+            .with(Style::blank())
+            .with(Modify::new(Columns::new(1..)).with(Alignment::right()));
+        write!(f, "{}", table)
+    }
 }
 
 impl From<&StatusSnapshot> for Table {
@@ -291,6 +401,14 @@ impl StatusColumn {
             ..Default::default()
         }
     }
+
+    pub fn failed_test_cases() -> StatusColumn {
+        StatusColumn {
+            header: "FAILED TEST CASES".to_string(),
+            values: crd_failed_test_cases,
+            ..Default::default()
+        }
+    }
 }
 
 /// Determine the time of the last update to the CRD
@@ -303,6 +421,15 @@ fn crd_time(crd: &Crd) -> Vec<String> {
         Crd::Resource(resource) => resource
             .status()
             .and_then(|status| status.last_update.to_owned()),
+        Crd::TestSuite(test_suite) => test_suite
+            .status()
+            .and_then(|status| status.last_update.to_owned()),
+        Crd::ResourcePool(resource_pool) => resource_pool
+            .status()
+            .and_then(|status| status.last_update.to_owned()),
+        Crd::TestSchedule(test_schedule) => test_schedule
+            .status()
+            .and_then(|status| status.last_update.to_owned()),
     }
     .into_iter()
     .collect()
@@ -313,6 +440,9 @@ fn crd_type(crd: &Crd) -> Vec<String> {
     match crd {
         Crd::Test(_) => vec!["Test".to_string()],
         Crd::Resource(_) => vec!["Resource".to_string()],
+        Crd::TestSuite(_) => vec!["TestSuite".to_string()],
+        Crd::ResourcePool(_) => vec!["ResourcePool".to_string()],
+        Crd::TestSchedule(_) => vec!["TestSchedule".to_string()],
     }
 }
 
@@ -333,6 +463,29 @@ fn crd_state(crd: &Crd) -> Vec<String> {
             };
             vec![state.to_string()]
         }
+        Crd::TestSuite(test_suite) => vec![test_suite
+            .status()
+            .map(|status| status.outcome)
+            .unwrap_or_default()
+            .to_string()],
+        Crd::ResourcePool(resource_pool) => vec![format!(
+            "{}/{} available",
+            resource_pool
+                .status()
+                .map(|status| status.num_available)
+                .unwrap_or_default(),
+            resource_pool.spec.size
+        )],
+        Crd::TestSchedule(test_schedule) if test_schedule.spec.suspend => {
+            vec!["suspended".to_string()]
+        }
+        Crd::TestSchedule(test_schedule) => vec![format!(
+            "{} active",
+            test_schedule
+                .status()
+                .map(|status| status.active.len())
+                .unwrap_or_default()
+        )],
     }
 }
 
@@ -345,7 +498,17 @@ enum ResultType {
 /// Collect the
 fn crd_results(crd: &Crd, res_type: ResultType) -> Vec<String> {
     match crd {
-        Crd::Resource(_) => Default::default(),
+        Crd::Resource(_) | Crd::ResourcePool(_) | Crd::TestSchedule(_) => Default::default(),
+        Crd::TestSuite(test_suite) => test_suite
+            .status()
+            .and_then(|status| match res_type {
+                ResultType::Passed => Some(status.num_passed),
+                ResultType::Failed => Some(status.num_failed),
+                ResultType::Skipped => None,
+            })
+            .map(|count| count.to_string())
+            .into_iter()
+            .collect(),
         Crd::Test(test) => {
             let mut results = Vec::new();
             let test_results = &test.agent_status().results;
@@ -368,7 +531,12 @@ fn crd_results(crd: &Crd, res_type: ResultType) -> Vec<String> {
 
 fn crd_progress(crd: &Crd) -> Vec<String> {
     match crd {
-        Crd::Resource(_) => Default::default(),
+        Crd::Resource(_) | Crd::ResourcePool(_) | Crd::TestSchedule(_) => Default::default(),
+        Crd::TestSuite(test_suite) => test_suite
+            .status()
+            .map(|status| status.progress())
+            .into_iter()
+            .collect(),
         Crd::Test(test) => test
             .agent_status()
             .current_test
@@ -379,6 +547,21 @@ fn crd_progress(crd: &Crd) -> Vec<String> {
     }
 }
 
+/// List the names of the failed test cases in the most recent results of a `Test`.
+fn crd_failed_test_cases(crd: &Crd) -> Vec<String> {
+    match crd {
+        Crd::Test(test) => test
+            .agent_status()
+            .results
+            .last()
+            .map(|results| results.failed_test_cases().map(String::from).collect())
+            .unwrap_or_default(),
+        Crd::Resource(_) | Crd::TestSuite(_) | Crd::ResourcePool(_) | Crd::TestSchedule(_) => {
+            Default::default()
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -437,4 +620,52 @@ mod test {
         println!("{}", snapshot_str);
         assert_eq!(snapshot_str, expected);
     }
+
+    #[test]
+    fn group_by_axis() {
+        let labels = |arch: &str| {
+            let mut labels = BTreeMap::new();
+            labels.insert(matrix_axis_label("arch"), arch.to_string());
+            labels
+        };
+        let crds = vec![
+            Crd::Test(create_test_crd(
+                "test-x86-64",
+                "test",
+                Some(&labels("x86_64")),
+                TestSpec::default(),
+            )),
+            Crd::Test(create_test_crd(
+                "test-aarch64",
+                "test",
+                Some(&labels("aarch64")),
+                TestSpec::default(),
+            )),
+            Crd::Test(create_test_crd(
+                "test-aarch64-2",
+                "test",
+                Some(&labels("aarch64")),
+                TestSpec::default(),
+            )),
+            Crd::Test(create_test_crd("other", "test", None, TestSpec::default())),
+        ];
+        let summary = StatusSnapshot::new(crds).group_by_axis("arch");
+        assert_eq!(
+            summary.groups,
+            vec![
+                AxisGroup {
+                    value: "aarch64".to_string(),
+                    tests: 2,
+                    not_finished: 2,
+                    ..Default::default()
+                },
+                AxisGroup {
+                    value: "x86_64".to_string(),
+                    tests: 1,
+                    not_finished: 1,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
 }
@@ -0,0 +1,615 @@
+use crate::agent::timeout_schema;
+use crate::constants::{ANNOTATION_SCHEDULED_TIME, LABEL_TEST_SCHEDULE, NAMESPACE};
+use crate::crd_ext::CrdExt;
+use crate::error::{self, Result};
+use crate::validation::duration_seconds;
+use crate::{Outcome, TestSuite, TestSuiteSpec};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use croner::Cron;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::{CustomResource, Resource as KubeResource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_plain::{derive_display_from_serialize, derive_fromstr_from_deserialize};
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+
+/// The number of finished runs that are kept when `historyLimit` is not set.
+const DEFAULT_HISTORY_LIMIT: u32 = 3;
+
+/// Runs a set of TestSys `Test`s and `Resource`s on a cron schedule. The `CustomResource` derive
+/// also produces a struct named `TestSchedule` which represents a test schedule CRD object in the
+/// k8s API.
+///
+/// Each time the schedule is due, the controller creates a `TestSuite` from the `template`. The
+/// suite, which is called a run, is named `<schedule>-<minutes since the epoch>`, and each of its
+/// `Test`s and `Resource`s is named `<run>-<key>` so that runs do not collide. References to the
+/// template's tests and resources (in `resources`, `dependsOn`, `conflictsWith` and templated
+/// configuration values) are renamed to match. Runs are labeled with the name of the schedule and
+/// owned by it.
+#[derive(Clone, CustomResource, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[kube(
+    derive = "Default",
+    derive = "PartialEq",
+    group = "testsys.system",
+    kind = "TestSchedule",
+    namespaced,
+    plural = "testschedules",
+    singular = "testschedule",
+    status = "TestScheduleStatus",
+    category = "testsys",
+    version = "v1",
+    printcolumn = r#"{"name":"Schedule", "type":"string", "jsonPath":".spec.schedule"}"#,
+    printcolumn = r#"{"name":"Suspend", "type":"boolean", "jsonPath":".spec.suspend"}"#,
+    printcolumn = r#"{"name":"Last Schedule", "type":"string", "jsonPath":".status.lastScheduleTime"}"#
+)]
+#[serde(rename_all = "camelCase")]
+pub struct TestScheduleSpec {
+    /// A standard cron expression with five fields, evaluated in UTC, e.g. `0 2 * * *` for every
+    /// night at 02:00.
+    pub schedule: String,
+    /// What to do when a run is due while an earlier run has not finished (`Forbid` is the
+    /// default).
+    #[serde(default)]
+    pub concurrency_policy: ConcurrencyPolicy,
+    /// The number of finished runs to keep. Older runs are deleted along with their tests and
+    /// resources. Defaults to 3.
+    pub history_limit: Option<u32>,
+    /// How long a run may stay active, e.g. `12h`, counted from the time it was due. A run that has
+    /// not finished by then is recorded as finished with a `timeout` outcome, so that a run that
+    /// cannot make progress, e.g. because a test waits for a failed dependency, does not keep
+    /// later runs from starting. Runs do not have a deadline if this is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "timeout_schema")]
+    pub active_deadline: Option<String>,
+    /// Stops the controller from starting new runs. Runs that have already started are not
+    /// affected. Runs that were due while the schedule was suspended are skipped.
+    #[serde(default)]
+    pub suspend: bool,
+    /// The tests and resources of each run.
+    pub template: TestSuiteSpec,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ConcurrencyPolicy {
+    /// A run is skipped if an earlier run has not finished.
+    #[default]
+    Forbid,
+    /// The runs that have not finished are deleted before the new run is started.
+    Replace,
+    /// Runs may overlap.
+    Allow,
+}
+
+derive_display_from_serialize!(ConcurrencyPolicy);
+derive_fromstr_from_deserialize!(ConcurrencyPolicy);
+
+/// The status field of the TestSys TestSchedule CRD. This is where the controller records the runs
+/// of the schedule.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TestScheduleStatus {
+    /// The most recent time that a run was due, whether it was started or skipped.
+    pub last_schedule_time: Option<String>,
+    /// The names of the runs that have not finished, oldest first.
+    pub active: Vec<String>,
+    /// The runs that have finished, most recent first.
+    pub history: Vec<TestScheduleRun>,
+    /// The time of the last change to this CRD.
+    pub last_update: Option<String>,
+}
+
+/// A finished run of a `TestSchedule`.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TestScheduleRun {
+    /// The name of the run's `TestSuite`.
+    pub name: String,
+    /// The time that the run was due.
+    pub scheduled_time: Option<String>,
+    /// The aggregate outcome of the run's tests.
+    pub outcome: Outcome,
+    /// The number of tests that passed.
+    pub num_passed: u64,
+    /// The number of tests that failed or errored.
+    pub num_failed: u64,
+}
+
+impl TestScheduleSpec {
+    /// Parses the cron expression in `schedule`.
+    pub fn cron(&self) -> Result<Cron> {
+        Ok(Cron::new(&self.schedule)
+            .parse()
+            .context(error::CronScheduleSnafu {
+                schedule: &self.schedule,
+            })?)
+    }
+}
+
+impl TestSchedule {
+    /// The label selector that matches all `TestSuite`s (runs) belonging to this schedule.
+    pub fn run_selector(&self) -> String {
+        format!("{}={}", LABEL_TEST_SCHEDULE, self.object_name())
+    }
+
+    /// The most recent time, at or before `now`, that a run was due and has not been started or
+    /// skipped yet. Only one run is started for the times that were missed, e.g. while the
+    /// controller was not running.
+    pub fn due_time(&self, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let last = self
+            .status
+            .as_ref()
+            .and_then(|status| status.last_schedule_time.as_deref())
+            .and_then(parse_time)
+            .or_else(|| {
+                self.metadata
+                    .creation_timestamp
+                    .as_ref()
+                    .map(|created| created.0)
+            })
+            .unwrap_or(now);
+        Ok(self
+            .spec
+            .cron()?
+            .iter_after(last)
+            .take_while(|time| *time <= now)
+            .last())
+    }
+
+    /// The first time after `now` that a run is due.
+    pub fn next_time(&self, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        Ok(self
+            .spec
+            .cron()?
+            .find_next_occurrence(&now, false)
+            .context(error::CronScheduleSnafu {
+                schedule: &self.spec.schedule,
+            })?)
+    }
+
+    /// The name of the run that is due at `time`.
+    pub fn run_name(&self, time: DateTime<Utc>) -> String {
+        format!("{}-{}", self.object_name(), time.timestamp() / 60)
+    }
+
+    /// Builds the `TestSuite` for the run that is due at `time`. The schedule must have been
+    /// retrieved from the k8s API (i.e. it has a `uid`) for the owner reference to be set.
+    pub fn new_run(&self, time: DateTime<Utc>) -> TestSuite {
+        let name = self.run_name(time);
+        let mut labels = self.metadata.labels.clone().unwrap_or_default();
+        labels.insert(
+            LABEL_TEST_SCHEDULE.to_string(),
+            self.object_name().to_string(),
+        );
+        TestSuite {
+            metadata: ObjectMeta {
+                name: Some(name.clone()),
+                namespace: Some(
+                    self.metadata
+                        .namespace
+                        .clone()
+                        .unwrap_or_else(|| NAMESPACE.to_string()),
+                ),
+                labels: Some(labels),
+                annotations: Some(BTreeMap::from([(
+                    ANNOTATION_SCHEDULED_TIME.to_string(),
+                    format_time(time),
+                )])),
+                owner_references: self.controller_owner_ref(&()).map(|owner| vec![owner]),
+                ..Default::default()
+            },
+            spec: prefixed(&self.spec.template, &name),
+            status: None,
+        }
+    }
+
+    /// Computes the status of the schedule from its `runs` at `now`. Runs that are being deleted
+    /// are left out, and runs that are past the `activeDeadline` count as finished. Fields that are
+    /// not derived from the runs are copied from the current status.
+    pub fn summarize(&self, runs: &[TestSuite], now: DateTime<Utc>) -> TestScheduleStatus {
+        let current = self.status.clone().unwrap_or_default();
+        let mut runs: Vec<&TestSuite> = runs
+            .iter()
+            .filter(|run| !run.is_delete_requested())
+            .collect();
+        runs.sort_by_key(|run| (scheduled_time(run), run.object_name().to_string()));
+        let (finished, active): (Vec<&TestSuite>, Vec<&TestSuite>) = runs
+            .into_iter()
+            .partition(|run| is_finished(run) || self.is_past_deadline(run, now));
+        TestScheduleStatus {
+            last_schedule_time: current.last_schedule_time,
+            active: active
+                .iter()
+                .map(|run| run.object_name().to_string())
+                .collect(),
+            history: finished
+                .iter()
+                .rev()
+                .map(|run| {
+                    let status = run.status.clone().unwrap_or_default();
+                    TestScheduleRun {
+                        name: run.object_name().to_string(),
+                        scheduled_time: scheduled_time(run).map(format_time),
+                        outcome: if is_finished(run) {
+                            status.outcome
+                        } else {
+                            Outcome::Timeout
+                        },
+                        num_passed: status.num_passed,
+                        num_failed: status.num_failed,
+                    }
+                })
+                .collect(),
+            last_update: current.last_update,
+        }
+    }
+
+    /// Whether the `run` has been active for longer than the `activeDeadline` at `now`. An
+    /// `activeDeadline` that cannot be parsed is ignored.
+    fn is_past_deadline(&self, run: &TestSuite, now: DateTime<Utc>) -> bool {
+        let deadline = self
+            .spec
+            .active_deadline
+            .as_deref()
+            .and_then(|deadline| duration_seconds("activeDeadline", deadline).ok())
+            .and_then(|seconds| i64::try_from(seconds).ok())
+            .and_then(Duration::try_seconds);
+        match (deadline, scheduled_time(run)) {
+            (Some(deadline), Some(scheduled)) => now - scheduled > deadline,
+            _ => false,
+        }
+    }
+
+    /// The names of the finished runs that are beyond the `historyLimit` and should be deleted.
+    pub fn expired_runs(&self, status: &TestScheduleStatus) -> Vec<String> {
+        let limit = self.spec.history_limit.unwrap_or(DEFAULT_HISTORY_LIMIT) as usize;
+        status
+            .history
+            .iter()
+            .skip(limit)
+            .map(|run| run.name.clone())
+            .collect()
+    }
+}
+
+impl CrdExt for TestSchedule {
+    fn object_meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+}
+
+impl TestSuite {
+    /// The name of the `TestSchedule` that this suite is a run of, if any.
+    pub fn schedule(&self) -> Option<&str> {
+        self.object_meta()
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get(LABEL_TEST_SCHEDULE))
+            .map(String::as_str)
+    }
+}
+
+/// Whether every test of the `run` has finished.
+fn is_finished(run: &TestSuite) -> bool {
+    matches!(
+        run.status.as_ref().map(|status| status.outcome),
+        Some(Outcome::Pass | Outcome::Fail | Outcome::Timeout | Outcome::Cancelled)
+    )
+}
+
+/// The time that the `run` was due, or the time it was created if it was not created by a
+/// schedule.
+fn scheduled_time(run: &TestSuite) -> Option<DateTime<Utc>> {
+    run.metadata
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(ANNOTATION_SCHEDULED_TIME))
+        .and_then(|time| parse_time(time))
+        .or_else(|| {
+            run.metadata
+                .creation_timestamp
+                .as_ref()
+                .map(|created| created.0)
+        })
+}
+
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Copies `template`, naming each test and resource `<prefix>-<key>` and renaming the references
+/// to them.
+fn prefixed(template: &TestSuiteSpec, prefix: &str) -> TestSuiteSpec {
+    let rename = |names: &mut Vec<String>, keys: &BTreeMap<String, String>| {
+        for name in names.iter_mut() {
+            if let Some(renamed) = keys.get(name) {
+                *name = renamed.clone();
+            }
+        }
+    };
+    let tests: BTreeMap<String, String> = template
+        .tests
+        .keys()
+        .map(|name| (name.clone(), format!("{}-{}", prefix, name)))
+        .collect();
+    let resources: BTreeMap<String, String> = template
+        .resources
+        .keys()
+        .map(|name| (name.clone(), format!("{}-{}", prefix, name)))
+        .collect();
+    TestSuiteSpec {
+        resources: template
+            .resources
+            .iter()
+            .map(|(name, spec)| {
+                let mut spec = spec.clone();
+                spec.depends_on
+                    .iter_mut()
+                    .for_each(|names| rename(names, &resources));
+                spec.conflicts_with
+                    .iter_mut()
+                    .for_each(|names| rename(names, &resources));
+                spec.agent
+                    .configuration
+                    .iter_mut()
+                    .flat_map(|configuration| configuration.values_mut())
+                    .for_each(|value| rename_templates(value, &tests, &resources));
+                (resources[name].clone(), spec)
+            })
+            .collect(),
+        tests: template
+            .tests
+            .iter()
+            .map(|(name, spec)| {
+                let mut spec = spec.clone();
+                rename(&mut spec.resources, &resources);
+                spec.depends_on
+                    .iter_mut()
+                    .for_each(|names| rename(names, &tests));
+                spec.agent
+                    .configuration
+                    .iter_mut()
+                    .flat_map(|configuration| configuration.values_mut())
+                    .for_each(|value| rename_templates(value, &tests, &resources));
+                (tests[name].clone(), spec)
+            })
+            .collect(),
+    }
+}
+
+/// Recursively renames the tests and resources referred to by `${test:<test>.` and `${<resource>.`
+/// in every string of `value`.
+fn rename_templates(
+    value: &mut Value,
+    tests: &BTreeMap<String, String>,
+    resources: &BTreeMap<String, String>,
+) {
+    match value {
+        Value::String(s) => {
+            for (name, renamed) in tests {
+                *s = s.replace(
+                    &format!("${{test:{}.", name),
+                    &format!("${{test:{}.", renamed),
+                );
+            }
+            for (name, renamed) in resources {
+                *s = s.replace(&format!("${{{}.", name), &format!("${{{}.", renamed));
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| rename_templates(value, tests, resources)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|value| rename_templates(value, tests, resources)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Agent, ResourceSpec, TestSpec, TestSuiteStatus};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use maplit::btreemap;
+    use serde_json::json;
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        parse_time(rfc3339).unwrap()
+    }
+
+    fn schedule() -> TestSchedule {
+        let agent = |configuration: Value| Agent {
+            configuration: configuration.as_object().cloned(),
+            ..Default::default()
+        };
+        TestSchedule {
+            metadata: ObjectMeta {
+                name: Some("nightly".to_string()),
+                namespace: Some(NAMESPACE.to_string()),
+                uid: Some("1234".to_string()),
+                creation_timestamp: Some(Time(time("2024-01-01T00:00:00Z"))),
+                ..Default::default()
+            },
+            spec: TestScheduleSpec {
+                schedule: "0 2 * * *".to_string(),
+                template: TestSuiteSpec {
+                    resources: btreemap! {
+                        "cluster".to_string() => ResourceSpec::default(),
+                        "instances".to_string() => ResourceSpec {
+                            depends_on: Some(vec!["cluster".to_string()]),
+                            agent: agent(json!({ "endpoint": "${cluster.endpoint}" })),
+                            ..Default::default()
+                        },
+                    },
+                    tests: btreemap! {
+                        "conformance".to_string() => TestSpec {
+                            resources: vec!["cluster".to_string(), "instances".to_string()],
+                            ..Default::default()
+                        },
+                        "report".to_string() => TestSpec {
+                            depends_on: Some(vec!["conformance".to_string()]),
+                            agent: agent(json!({ "results": ["${test:conformance.url}"] })),
+                            ..Default::default()
+                        },
+                    },
+                },
+                ..Default::default()
+            },
+            status: None,
+        }
+    }
+
+    fn run(name: &str, scheduled: &str, outcome: Outcome) -> TestSuite {
+        TestSuite {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                annotations: Some(btreemap! {
+                    ANNOTATION_SCHEDULED_TIME.to_string() => scheduled.to_string(),
+                }),
+                ..Default::default()
+            },
+            spec: TestSuiteSpec::default(),
+            status: Some(TestSuiteStatus {
+                outcome,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn due_time() {
+        let mut schedule = schedule();
+        assert_eq!(
+            schedule.due_time(time("2024-01-01T01:00:00Z")).unwrap(),
+            None
+        );
+        // Only the most recent of the missed runs is due.
+        assert_eq!(
+            schedule.due_time(time("2024-01-03T03:00:00Z")).unwrap(),
+            Some(time("2024-01-03T02:00:00Z"))
+        );
+        schedule.status = Some(TestScheduleStatus {
+            last_schedule_time: Some("2024-01-03T02:00:00Z".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            schedule.due_time(time("2024-01-03T03:00:00Z")).unwrap(),
+            None
+        );
+        assert_eq!(
+            schedule.next_time(time("2024-01-03T03:00:00Z")).unwrap(),
+            time("2024-01-04T02:00:00Z")
+        );
+        schedule.spec.schedule = "0 2 * *".to_string();
+        assert!(schedule.due_time(time("2024-01-03T03:00:00Z")).is_err());
+    }
+
+    #[test]
+    fn new_run() {
+        let run = schedule().new_run(time("2024-01-03T02:00:00Z"));
+        assert_eq!(run.object_name(), "nightly-28404120");
+        assert_eq!(
+            run.metadata
+                .labels
+                .as_ref()
+                .unwrap()
+                .get(LABEL_TEST_SCHEDULE),
+            Some(&"nightly".to_string())
+        );
+        let owner = &run.metadata.owner_references.as_ref().unwrap()[0];
+        assert_eq!(owner.kind, "TestSchedule");
+        assert_eq!(owner.uid, "1234");
+        assert_eq!(scheduled_time(&run), Some(time("2024-01-03T02:00:00Z")));
+        assert_eq!(run.schedule(), Some("nightly"));
+
+        let instances = &run.spec.resources["nightly-28404120-instances"];
+        assert_eq!(
+            instances.depends_on,
+            Some(vec!["nightly-28404120-cluster".to_string()])
+        );
+        assert_eq!(
+            instances.agent.configuration.as_ref().unwrap()["endpoint"],
+            json!("${nightly-28404120-cluster.endpoint}")
+        );
+        assert_eq!(
+            run.spec.tests["nightly-28404120-conformance"].resources,
+            vec!["nightly-28404120-cluster", "nightly-28404120-instances"]
+        );
+        let report = &run.spec.tests["nightly-28404120-report"];
+        assert_eq!(
+            report.depends_on,
+            Some(vec!["nightly-28404120-conformance".to_string()])
+        );
+        assert_eq!(
+            report.agent.configuration.as_ref().unwrap()["results"],
+            json!(["${test:nightly-28404120-conformance.url}"])
+        );
+    }
+
+    #[test]
+    fn summarize() {
+        let mut schedule = schedule();
+        schedule.spec.history_limit = Some(1);
+        let runs = [
+            run("nightly-3", "2024-01-03T02:00:00Z", Outcome::InProgress),
+            run("nightly-1", "2024-01-01T02:00:00Z", Outcome::Pass),
+            run("nightly-2", "2024-01-02T02:00:00Z", Outcome::Fail),
+        ];
+        let now = time("2024-01-03T12:00:00Z");
+        let status = schedule.summarize(&runs, now);
+        assert_eq!(status.active, vec!["nightly-3".to_string()]);
+        let history: Vec<&str> = status.history.iter().map(|run| run.name.as_str()).collect();
+        assert_eq!(history, vec!["nightly-2", "nightly-1"]);
+        assert_eq!(status.history[0].outcome, Outcome::Fail);
+        assert_eq!(
+            schedule.expired_runs(&status),
+            vec!["nightly-1".to_string()]
+        );
+
+        // A run that is stuck finishes with a timeout once it is past the deadline.
+        schedule.spec.active_deadline = Some("12h".to_string());
+        assert_eq!(schedule.summarize(&runs, now).active, vec!["nightly-3"]);
+        let status = schedule.summarize(&runs, time("2024-01-03T14:00:01Z"));
+        assert!(status.active.is_empty());
+        assert_eq!(status.history[0].name, "nightly-3");
+        assert_eq!(status.history[0].outcome, Outcome::Timeout);
+    }
+
+    #[test]
+    fn deserialize_manifest() {
+        let manifest = r#"
+apiVersion: testsys.system/v1
+kind: TestSchedule
+metadata:
+  name: nightly
+  namespace: testsys
+spec:
+  schedule: "0 2 * * *"
+  concurrencyPolicy: replace
+  template:
+    tests:
+      test-1:
+        agent:
+          name: test-agent
+          image: example-test-agent
+          keepRunning: false
+          configuration: {}
+        resources: []
+"#;
+        let crd: crate::Crd = serde_yaml::from_str(manifest).unwrap();
+        assert!(matches!(crd, crate::Crd::TestSchedule(_)));
+        if let crate::Crd::TestSchedule(schedule) = crd {
+            assert_eq!(schedule.spec.concurrency_policy, ConcurrencyPolicy::Replace);
+            assert!(schedule.spec.template.tests.contains_key("test-1"));
+        }
+    }
+}
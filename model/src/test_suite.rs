@@ -0,0 +1,340 @@
+use crate::constants::{LABEL_TEST_SUITE, NAMESPACE};
+use crate::crd_ext::CrdExt;
+use crate::{Outcome, Resource, ResourceSpec, Test, TestSpec, TestUserState};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::{CustomResource, Resource as KubeResource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A group of TestSys `Test`s and `Resource`s that are created, tracked and deleted as one unit.
+/// The `CustomResource` derive also produces a struct named `TestSuite` which represents a test
+/// suite CRD object in the k8s API.
+///
+/// The controller creates one `Test` for each entry in `tests` and one `Resource` for each entry in
+/// `resources`. The child objects are named by their keys, labeled with the name of the suite and
+/// owned by the suite. Deleting the suite deletes all of its children.
+#[derive(Clone, CustomResource, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[kube(
+    derive = "Default",
+    derive = "PartialEq",
+    group = "testsys.system",
+    kind = "TestSuite",
+    namespaced,
+    plural = "testsuites",
+    singular = "testsuite",
+    status = "TestSuiteStatus",
+    category = "testsys",
+    version = "v1",
+    printcolumn = r#"{"name":"Outcome", "type":"string", "jsonPath":".status.outcome"}"#,
+    printcolumn = r#"{"name":"Passed", "type":"integer", "jsonPath":".status.numPassed"}"#,
+    printcolumn = r#"{"name":"Failed", "type":"integer", "jsonPath":".status.numFailed"}"#,
+    printcolumn = r#"{"name":"Tests", "type":"integer", "jsonPath":".status.numTests"}"#
+)]
+#[serde(rename_all = "camelCase")]
+pub struct TestSuiteSpec {
+    /// The resources that belong to this suite, keyed by the name of the `Resource` to create.
+    #[serde(default)]
+    pub resources: BTreeMap<String, ResourceSpec>,
+    /// The tests that belong to this suite, keyed by the name of the `Test` to create.
+    pub tests: BTreeMap<String, TestSpec>,
+}
+
+/// The status field of the TestSys TestSuite CRD. This is where the controller writes the aggregate
+/// state of the suite's tests.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TestSuiteStatus {
+    /// Whether the controller has created the `Test` and `Resource` objects of this suite. Children
+    /// are only created once, so objects that are deleted individually are not re-created.
+    pub children_created: bool,
+    /// The aggregate outcome of the suite. This is `InProgress` until every test has finished,
+    /// `Pass` if every test passed and `Fail` otherwise.
+    pub outcome: Outcome,
+    /// The number of tests in the suite.
+    pub num_tests: u64,
+    /// The number of tests that have passed.
+    pub num_passed: u64,
+    /// The number of tests that have failed or errored, including tests that were deleted after
+    /// the controller created them.
+    pub num_failed: u64,
+    /// The number of tests whose agent is currently running.
+    pub num_running: u64,
+    /// The names of the tests that have failed, errored or been deleted.
+    pub failed_tests: Vec<String>,
+    /// The time of the last change to this CRD.
+    pub last_update: Option<String>,
+}
+
+impl TestSuiteStatus {
+    /// The number of tests that have finished, whether they passed or failed.
+    pub fn num_finished(&self) -> u64 {
+        self.num_passed + self.num_failed
+    }
+
+    /// A short description of the suite's progress, e.g. `3/10`.
+    pub fn progress(&self) -> String {
+        format!("{}/{}", self.num_finished(), self.num_tests)
+    }
+}
+
+impl TestSuite {
+    /// The label selector that matches all `Test`s and `Resource`s belonging to this suite.
+    pub fn child_selector(&self) -> String {
+        format!("{}={}", LABEL_TEST_SUITE, self.object_name())
+    }
+
+    /// Builds the `Test` objects described by this suite. The suite must have been retrieved from
+    /// the k8s API (i.e. it has a `uid`) for the owner references to be set.
+    pub fn child_tests(&self) -> Vec<Test> {
+        self.spec
+            .tests
+            .iter()
+            .map(|(name, spec)| Test {
+                metadata: self.child_metadata(name),
+                spec: spec.clone(),
+                status: None,
+            })
+            .collect()
+    }
+
+    /// Builds the `Resource` objects described by this suite. The suite must have been retrieved
+    /// from the k8s API (i.e. it has a `uid`) for the owner references to be set.
+    pub fn child_resources(&self) -> Vec<Resource> {
+        self.spec
+            .resources
+            .iter()
+            .map(|(name, spec)| Resource {
+                metadata: self.child_metadata(name),
+                spec: spec.clone(),
+                status: None,
+            })
+            .collect()
+    }
+
+    /// Computes the aggregate status of the suite from its child `tests`. Every test in the spec is
+    /// counted, and a test that is missing once the children have been created has been deleted and
+    /// will never finish, so it is counted as failed. Fields that are not derived from the tests are
+    /// copied from the current status.
+    pub fn summarize(&self, tests: &[Test]) -> TestSuiteStatus {
+        let current = self.status.clone().unwrap_or_default();
+        let mut status = TestSuiteStatus {
+            children_created: current.children_created,
+            num_tests: self.spec.tests.len() as u64,
+            last_update: current.last_update,
+            ..Default::default()
+        };
+        for name in self.spec.tests.keys() {
+            let test = tests.iter().find(|test| test.object_name() == name);
+            match test.map(Test::test_user_state) {
+                Some(TestUserState::Passed | TestUserState::NoTests) => status.num_passed += 1,
+                Some(
+                    TestUserState::Failed
+                    | TestUserState::Error
+                    | TestUserState::ResourceError
+                    | TestUserState::Cancelled,
+                ) => {
+                    status.num_failed += 1;
+                    status.failed_tests.push(name.clone());
+                }
+                None if status.children_created => {
+                    status.num_failed += 1;
+                    status.failed_tests.push(name.clone());
+                }
+                Some(TestUserState::Running) => status.num_running += 1,
+                Some(TestUserState::Unknown | TestUserState::Waiting | TestUserState::Deleting)
+                | None => {}
+            }
+        }
+        status.outcome = if !status.children_created {
+            Outcome::Unknown
+        } else if status.num_finished() < status.num_tests {
+            Outcome::InProgress
+        } else if status.num_failed == 0 {
+            Outcome::Pass
+        } else {
+            Outcome::Fail
+        };
+        status
+    }
+
+    fn child_metadata(&self, name: &str) -> ObjectMeta {
+        let mut labels = self.metadata.labels.clone().unwrap_or_default();
+        labels.insert(LABEL_TEST_SUITE.to_string(), self.object_name().to_string());
+        ObjectMeta {
+            name: Some(name.to_string()),
+            namespace: Some(
+                self.metadata
+                    .namespace
+                    .clone()
+                    .unwrap_or_else(|| NAMESPACE.to_string()),
+            ),
+            labels: Some(labels),
+            owner_references: self.controller_owner_ref(&()).map(|owner| vec![owner]),
+            ..Default::default()
+        }
+    }
+}
+
+impl CrdExt for TestSuite {
+    fn object_meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AgentStatus, TaskState, TestResults, TestStatus};
+    use maplit::btreemap;
+
+    fn suite() -> TestSuite {
+        TestSuite {
+            metadata: ObjectMeta {
+                name: Some("my-suite".to_string()),
+                namespace: Some(NAMESPACE.to_string()),
+                uid: Some("1234".to_string()),
+                labels: Some(btreemap! { "variant".to_string() => "aws-k8s".to_string() }),
+                ..Default::default()
+            },
+            spec: TestSuiteSpec {
+                resources: btreemap! { "cluster".to_string() => ResourceSpec::default() },
+                tests: btreemap! {
+                    "test-1".to_string() => TestSpec::default(),
+                    "test-2".to_string() => TestSpec::default(),
+                },
+            },
+            status: Some(TestSuiteStatus {
+                children_created: true,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn completed_test(name: &str, outcome: Outcome) -> Test {
+        Test {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            spec: TestSpec::default(),
+            status: Some(TestStatus {
+                agent: AgentStatus {
+                    task_state: TaskState::Completed,
+                    results: vec![TestResults {
+                        outcome,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn child_objects() {
+        let suite = suite();
+        let tests = suite.child_tests();
+        let resources = suite.child_resources();
+        assert_eq!(tests.len(), 2);
+        assert_eq!(resources.len(), 1);
+        let labels = resources[0].metadata.labels.as_ref().unwrap();
+        assert_eq!(labels.get(LABEL_TEST_SUITE).unwrap(), "my-suite");
+        assert_eq!(labels.get("variant").unwrap(), "aws-k8s");
+        let owner = &tests[0].metadata.owner_references.as_ref().unwrap()[0];
+        assert_eq!(owner.kind, "TestSuite");
+        assert_eq!(owner.uid, "1234");
+        assert_eq!(owner.controller, Some(true));
+        assert_eq!(suite.child_selector(), "testsys.system/test-suite=my-suite");
+    }
+
+    #[test]
+    fn summarize_in_progress() {
+        let waiting_test = Test {
+            metadata: ObjectMeta {
+                name: Some("test-2".to_string()),
+                ..Default::default()
+            },
+            spec: TestSpec::default(),
+            status: None,
+        };
+        let status = suite().summarize(&[completed_test("test-1", Outcome::Pass), waiting_test]);
+        assert_eq!(status.outcome, Outcome::InProgress);
+        assert_eq!(status.num_passed, 1);
+        assert_eq!(status.progress(), "1/2");
+    }
+
+    #[test]
+    fn summarize_fail() {
+        let status = suite().summarize(&[
+            completed_test("test-1", Outcome::Pass),
+            completed_test("test-2", Outcome::Fail),
+        ]);
+        assert_eq!(status.outcome, Outcome::Fail);
+        assert_eq!(status.failed_tests, vec!["test-2".to_string()]);
+    }
+
+    #[test]
+    fn summarize_pass() {
+        let status = suite().summarize(&[
+            completed_test("test-1", Outcome::Pass),
+            completed_test("test-2", Outcome::Pass),
+        ]);
+        assert_eq!(status.outcome, Outcome::Pass);
+        assert_eq!(status.num_finished(), 2);
+    }
+
+    #[test]
+    fn summarize_missing_test() {
+        // `test-2` was deleted and `other-test` is not part of the suite.
+        let status = suite().summarize(&[
+            completed_test("test-1", Outcome::Pass),
+            completed_test("other-test", Outcome::Pass),
+        ]);
+        assert_eq!(status.outcome, Outcome::Fail);
+        assert_eq!(status.num_passed, 1);
+        assert_eq!(status.failed_tests, vec!["test-2".to_string()]);
+        assert_eq!(status.progress(), "2/2");
+
+        // Tests that have not been created yet are not missing.
+        let mut suite = suite();
+        suite.status = None;
+        let status = suite.summarize(&[]);
+        assert_eq!(status.outcome, Outcome::Unknown);
+        assert_eq!(status.num_failed, 0);
+    }
+
+    #[test]
+    fn deserialize_manifest() {
+        let manifest = r#"
+apiVersion: testsys.system/v1
+kind: TestSuite
+metadata:
+  name: my-suite
+  namespace: testsys
+spec:
+  resources:
+    cluster:
+      agent:
+        name: cluster-agent
+        image: example-resource-agent
+        keepRunning: false
+        configuration: {}
+  tests:
+    test-1:
+      agent:
+        name: test-agent
+        image: example-test-agent
+        keepRunning: false
+        configuration: {}
+      resources: [cluster]
+"#;
+        let crd: crate::Crd = serde_yaml::from_str(manifest).unwrap();
+        assert!(matches!(crd, crate::Crd::TestSuite(_)));
+        if let crate::Crd::TestSuite(suite) = crd {
+            assert_eq!(suite.spec.tests["test-1"].resources, vec!["cluster"]);
+            assert!(suite.spec.resources.contains_key("cluster"));
+        }
+    }
+}
@@ -0,0 +1,474 @@
+use crate::agent::TIMEOUT_PATTERN_REGEX;
+use crate::clients::{config_strings, Source, Template};
+use crate::constants::ENV_PREFIX;
+use crate::{Agent, ResourceSpec, TestScheduleSpec, TestSpec};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+pub use schema::SchemaRegistry;
+
+mod schema;
+
+lazy_static::lazy_static! {
+    static ref TIMEOUT_REGEX: Regex = {
+        #[allow(clippy::unwrap_used)]
+        Regex::new(TIMEOUT_PATTERN_REGEX).unwrap()
+    };
+}
+
+/// The names of the `Test`s and `Resource`s that an object may refer to.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct KnownObjects {
+    pub tests: BTreeSet<String>,
+    pub resources: BTreeSet<String>,
+}
+
+/// Returns a description of each problem found in the `Test` spec, including references to objects
+/// that are not `known`. An empty `Vec` means the spec is valid.
+pub fn validate_test(spec: &TestSpec, known: &KnownObjects) -> Vec<String> {
+    let mut problems = missing_test_references(spec, known);
+    problems.extend(check_test(spec));
+    problems
+}
+
+/// Returns a description of each reference in the `Test` spec to a `Test` or `Resource` that is not
+/// `known`.
+pub fn missing_test_references(spec: &TestSpec, known: &KnownObjects) -> Vec<String> {
+    let mut problems = Vec::new();
+    for resource in &spec.resources {
+        if !known.resources.contains(resource) {
+            problems.push(format!(
+                "resource '{}' in 'resources' does not exist",
+                resource
+            ));
+        }
+    }
+    for test in spec.depends_on.iter().flatten() {
+        if !known.tests.contains(test) {
+            problems.push(format!("test '{}' in 'dependsOn' does not exist", test));
+        }
+    }
+    // Templates may refer to leased resources by the name of the lease.
+    let mut known = known.clone();
+    known.resources.extend(spec.leases.keys().cloned());
+    problems.extend(missing_template_references(&spec.agent, &known));
+    problems
+}
+
+/// Returns a description of each problem found in the `Test` spec that does not depend on which
+/// other objects exist. An empty `Vec` means the spec is valid apart from its references.
+pub fn check_test(spec: &TestSpec) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, selector) in &spec.leases {
+        if selector.trim().is_empty() {
+            problems.push(format!("lease '{}' has an empty selector", name));
+        }
+        if spec.resources.contains(name) {
+            problems.push(format!(
+                "lease '{}' has the same name as a resource in 'resources'",
+                name
+            ));
+        }
+    }
+    if let Some(ttl) = &spec.ttl_after_finished {
+        if let Err(problem) = duration_seconds("ttlAfterFinished", ttl) {
+            problems.push(problem);
+        }
+    }
+    problems.extend(validate_agent(&spec.agent));
+    problems
+}
+
+/// Returns a description of each problem found in the `Resource` spec, including references to
+/// objects that are not `known`. An empty `Vec` means the spec is valid.
+pub fn validate_resource(spec: &ResourceSpec, known: &KnownObjects) -> Vec<String> {
+    let mut problems = missing_resource_references(spec, known);
+    problems.extend(check_resource(spec));
+    problems
+}
+
+/// Returns a description of each reference in the `Resource` spec to a `Test` or `Resource` that is
+/// not `known`. Names in `conflictsWith` are not checked because a conflicting resource is not
+/// expected to exist.
+pub fn missing_resource_references(spec: &ResourceSpec, known: &KnownObjects) -> Vec<String> {
+    let mut problems = Vec::new();
+    for resource in spec.depends_on.iter().flatten() {
+        if !known.resources.contains(resource) {
+            problems.push(format!(
+                "resource '{}' in 'dependsOn' does not exist",
+                resource
+            ));
+        }
+    }
+    problems.extend(missing_template_references(&spec.agent, known));
+    problems
+}
+
+/// Returns a description of each problem found in the `Resource` spec that does not depend on
+/// which other objects exist. An empty `Vec` means the spec is valid apart from its references.
+pub fn check_resource(spec: &ResourceSpec) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(max_lifetime) = &spec.max_lifetime {
+        if let Err(problem) = duration_seconds("maxLifetime", max_lifetime) {
+            problems.push(problem);
+        }
+    }
+    if let Some(retry_policy) = &spec.retry_policy {
+        if retry_policy.max_attempts == Some(0) {
+            problems.push("retryPolicy.maxAttempts must be at least 1".to_string());
+        }
+        for (field, duration) in [
+            ("retryPolicy.backoff", &retry_policy.backoff),
+            ("retryPolicy.maxBackoff", &retry_policy.max_backoff),
+        ] {
+            if let Some(Err(problem)) = duration.as_ref().map(|d| duration_seconds(field, d)) {
+                problems.push(problem);
+            }
+        }
+    }
+    problems.extend(validate_agent(&spec.agent));
+    problems
+}
+
+/// Returns a description of each problem found in the `TestSchedule` spec, apart from its
+/// `template`, whose tests and resources are checked like any others. An empty `Vec` means the spec
+/// is valid.
+pub fn validate_schedule(spec: &TestScheduleSpec) -> Vec<String> {
+    spec.cron()
+        .err()
+        .map(|e| e.to_string())
+        .into_iter()
+        .collect()
+}
+
+fn validate_agent(agent: &Agent) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(timeout) = &agent.timeout {
+        if let Err(problem) = duration_seconds("timeout", timeout) {
+            problems.push(problem);
+        }
+    }
+    if let Some(configuration) = &agent.configuration {
+        validate_templates(configuration, &mut problems);
+    }
+    validate_pod_additions(agent, &mut problems);
+    problems
+}
+
+/// Checks that the agent's own environment variables and volumes do not collide with the ones that
+/// the controller adds to the agent pod, that its extra containers have unique names, and that
+/// every volume mount refers to a volume.
+fn validate_pod_additions(agent: &Agent, problems: &mut Vec<String>) {
+    for env in agent.env.iter().flatten() {
+        if env.name.starts_with(ENV_PREFIX) {
+            problems.push(format!(
+                "env variable '{}' is invalid: the '{}' prefix is reserved",
+                env.name, ENV_PREFIX
+            ));
+        }
+    }
+    let secrets = agent.secret_names();
+    let mut volumes = BTreeSet::new();
+    for volume in agent.volumes.iter().flatten() {
+        if secrets.iter().any(|secret| secret.as_str() == volume.name) {
+            problems.push(format!(
+                "volume '{}' has the same name as a secret in 'secrets'",
+                volume.name
+            ));
+        }
+        if !volumes.insert(volume.name.as_str()) {
+            problems.push(format!(
+                "volume '{}' is defined more than once",
+                volume.name
+            ));
+        }
+    }
+    let mut containers = BTreeSet::new();
+    for container in agent
+        .init_containers
+        .iter()
+        .chain(agent.sidecars.iter())
+        .flatten()
+    {
+        if !containers.insert(container.name.as_str()) {
+            problems.push(format!(
+                "container '{}' is defined more than once",
+                container.name
+            ));
+        }
+    }
+    for mount in agent.volume_mounts.iter().flatten() {
+        let is_secret = secrets.iter().any(|secret| secret.as_str() == mount.name);
+        if !volumes.contains(mount.name.as_str()) && !is_secret {
+            problems.push(format!(
+                "volume mount '{}' refers to volume '{}' which does not exist",
+                mount.mount_path, mount.name
+            ));
+        }
+    }
+}
+
+/// Returns a description of each place where the agent's `configuration` does not match the schema
+/// that is registered for the agent's image. Agents without a registered schema are not checked.
+pub fn validate_configuration(agent: &Agent, registry: &SchemaRegistry) -> Vec<String> {
+    match registry.get(&agent.image) {
+        Some(schema) => {
+            let configuration = Value::Object(agent.configuration.clone().unwrap_or_default());
+            schema::check(schema, &configuration, "configuration")
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Parses a duration like an agent `timeout` into seconds, or describes why the `field` is invalid.
+pub(crate) fn duration_seconds(field: &str, duration: &str) -> Result<u64, String> {
+    let invalid = |reason: &str| format!("{} '{}' is invalid: {}", field, duration, reason);
+    let captures = TIMEOUT_REGEX
+        .captures(duration)
+        .filter(|_| !duration.is_empty())
+        .ok_or_else(|| invalid("expected a number of seconds or a duration like '1d2h3m4s'"))?;
+    if duration.chars().all(|c| c.is_ascii_digit()) {
+        return duration
+            .parse::<u64>()
+            .map_err(|_| invalid("the number is too large"));
+    }
+    // The capture groups of the days, hours, minutes and seconds in `TIMEOUT_PATTERN_REGEX`.
+    let units = [(3, 86400), (5, 3600), (7, 60), (9, 1)];
+    let mut seconds: u64 = 0;
+    for (group, unit_seconds) in units {
+        if let Some(value) = captures.get(group) {
+            seconds = value
+                .as_str()
+                .parse::<u64>()
+                .ok()
+                .and_then(|value| value.checked_mul(unit_seconds))
+                .and_then(|value| value.checked_add(seconds))
+                .ok_or_else(|| invalid("the duration is too large"))?;
+        }
+    }
+    Ok(seconds)
+}
+
+/// Checks the `${<resource>.<path>}` and `${test:<test>.<path>}` templates in every string of
+/// `configuration`, including the strings in nested objects and arrays. The templates are parsed
+/// the same way the resource client resolves them.
+fn validate_templates(configuration: &Map<String, Value>, problems: &mut Vec<String>) {
+    for input in config_strings(configuration) {
+        if Template::parse(input).is_err() {
+            problems.push(format!(
+                "template '{}' is invalid, expected '${{<resource>.<path>}}' or \
+                 '${{test:<test>.<path>}}'",
+                input
+            ));
+        }
+    }
+}
+
+/// Describes each template in the agent's configuration that refers to an object that is not
+/// `known`. Invalid templates are reported by `validate_templates`.
+fn missing_template_references(agent: &Agent, known: &KnownObjects) -> Vec<String> {
+    let mut problems = Vec::new();
+    let configuration = match &agent.configuration {
+        Some(configuration) => configuration,
+        None => return problems,
+    };
+    for input in config_strings(configuration) {
+        let template = match Template::parse(input) {
+            Ok(template) => template,
+            Err(_) => continue,
+        };
+        for reference in template.references() {
+            let (kind, names) = match reference.source {
+                Source::Resource => ("resource", &known.resources),
+                Source::Test => ("test", &known.tests),
+            };
+            let exists = reference
+                .alternatives()
+                .iter()
+                .any(|alternative| names.contains(&alternative.name));
+            if !exists {
+                problems.push(format!(
+                    "template '{}' refers to {} '{}' which does not exist",
+                    reference, kind, reference.name
+                ));
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RetryPolicy;
+    use serde_json::json;
+
+    fn known() -> KnownObjects {
+        KnownObjects {
+            tests: ["first-test".to_string()].into_iter().collect(),
+            resources: ["cluster".to_string(), "my.cluster".to_string()]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn agent(configuration: Value, timeout: Option<&str>) -> Agent {
+        Agent {
+            configuration: configuration.as_object().cloned(),
+            timeout: timeout.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_test() {
+        let spec = TestSpec {
+            resources: vec!["cluster".to_string()],
+            depends_on: Some(vec!["first-test".to_string()]),
+            agent: agent(
+                json!({
+                    "kubeconfig": "${cluster.kubeconfig}",
+                    "nested": {"ids": ["${cluster.status.ids[0]}", "id-${cluster.id}"]},
+                    "endpoint": "${my.cluster.endpoint}",
+                    "version": "${test:first-test.version}",
+                }),
+                Some("1h30m"),
+            ),
+            ..Default::default()
+        };
+        assert!(validate_test(&spec, &known()).is_empty());
+    }
+
+    #[test]
+    fn invalid_test() {
+        let spec = TestSpec {
+            resources: vec!["clutser".to_string()],
+            depends_on: Some(vec!["frist-test".to_string()]),
+            agent: agent(
+                json!({
+                    "a": "${x.}",
+                    "nested": {"b": ["id-${instances.ids}"]},
+                    "c": "echo ${HOME}",
+                    "d": "${test:frist-test.version}",
+                }),
+                Some("1h30"),
+            ),
+            ..Default::default()
+        };
+        let problems = validate_test(&spec, &known());
+        assert_eq!(
+            problems,
+            vec![
+                "resource 'clutser' in 'resources' does not exist",
+                "test 'frist-test' in 'dependsOn' does not exist",
+                "template '${test:frist-test.version}' refers to test 'frist-test' which does not \
+                 exist",
+                "template '${instances.ids}' refers to resource 'instances' which does not exist",
+                "timeout '1h30' is invalid: expected a number of seconds or a duration like \
+                 '1d2h3m4s'",
+                "template '${x.}' is invalid, expected '${<resource>.<path>}' or \
+                 '${test:<test>.<path>}'",
+            ]
+        );
+        // Only the references depend on which objects exist.
+        assert_eq!(check_test(&spec).len(), 2);
+        assert_eq!(missing_test_references(&spec, &known()).len(), 4);
+    }
+
+    #[test]
+    fn leases() {
+        let mut spec = TestSpec {
+            resources: vec!["cluster".to_string()],
+            leases: [("leased".to_string(), "version=1.24".to_string())]
+                .into_iter()
+                .collect(),
+            agent: agent(json!({"endpoint": "${leased.endpoint}"}), None),
+            ..Default::default()
+        };
+        assert!(validate_test(&spec, &known()).is_empty());
+        spec.leases.insert("cluster".to_string(), " ".to_string());
+        assert_eq!(
+            validate_test(&spec, &known()),
+            vec![
+                "lease 'cluster' has an empty selector",
+                "lease 'cluster' has the same name as a resource in 'resources'",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_resource() {
+        let spec = ResourceSpec {
+            depends_on: Some(vec!["cluster".to_string(), "vpc".to_string()]),
+            conflicts_with: Some(vec!["other-cluster".to_string()]),
+            agent: agent(json!({}), Some("")),
+            max_lifetime: Some("2 days".to_string()),
+            retry_policy: Some(RetryPolicy {
+                max_attempts: Some(0),
+                max_backoff: Some("soon".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let problems = validate_resource(&spec, &known());
+        assert_eq!(problems.len(), 5);
+        assert_eq!(problems[0], "resource 'vpc' in 'dependsOn' does not exist");
+        assert_eq!(
+            problems[1],
+            "maxLifetime '2 days' is invalid: expected a number of seconds or a duration like \
+             '1d2h3m4s'"
+        );
+        assert_eq!(problems[2], "retryPolicy.maxAttempts must be at least 1");
+        assert_eq!(
+            problems[3],
+            "retryPolicy.maxBackoff 'soon' is invalid: expected a number of seconds or a duration \
+             like '1d2h3m4s'"
+        );
+    }
+
+    #[test]
+    fn pod_additions() {
+        let agent: Agent = serde_json::from_value(json!({
+            "name": "eks",
+            "image": "eks-resource-agent",
+            "keepRunning": false,
+            "secrets": {"awsCredentials": "aws-creds"},
+            "env": [{"name": "AWS_REGION", "value": "us-west-2"}, {"name": "TESTSYS_TEST_NAME"}],
+            "volumes": [
+                {"name": "eksctl", "configMap": {"name": "eksctl-config"}},
+                {"name": "aws-creds", "emptyDir": {}},
+            ],
+            "volumeMounts": [
+                {"name": "eksctl", "mountPath": "/eksctl"},
+                {"name": "aws-creds", "mountPath": "/root/.aws"},
+                {"name": "scratch", "mountPath": "/scratch"},
+            ],
+            "initContainers": [{"name": "tools", "image": "eksctl"}],
+            "sidecars": [{"name": "tools", "image": "proxy"}],
+        }))
+        .unwrap();
+        let mut problems = Vec::new();
+        validate_pod_additions(&agent, &mut problems);
+        assert_eq!(
+            problems,
+            vec![
+                "env variable 'TESTSYS_TEST_NAME' is invalid: the 'TESTSYS_' prefix is reserved",
+                "volume 'aws-creds' has the same name as a secret in 'secrets'",
+                "container 'tools' is defined more than once",
+                "volume mount '/scratch' refers to volume 'scratch' which does not exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn timeouts() {
+        assert_eq!(duration_seconds("timeout", "90").unwrap(), 90);
+        assert_eq!(duration_seconds("timeout", "1d2h3m4s").unwrap(), 93784);
+        assert_eq!(duration_seconds("timeout", "2m").unwrap(), 120);
+        assert!(duration_seconds("timeout", "").is_err());
+        assert!(duration_seconds("timeout", "5 minutes").is_err());
+        assert!(duration_seconds("timeout", "99999999999999999999").is_err());
+        assert!(duration_seconds("timeout", "999999999999999999d").is_err());
+    }
+}
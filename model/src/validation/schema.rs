@@ -0,0 +1,476 @@
+use crate::agent::image_name;
+use crate::clients::Template;
+use crate::Configuration;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The JSON Schemas of agent configurations, keyed by the name of the agent's image. The name is
+/// the last component of the image's path without its tag or digest, e.g. `sonobuoy-test-agent` for
+/// `public.ecr.aws/bottlerocket-test-system/sonobuoy-test-agent:v0.0.14`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaRegistry {
+    schemas: BTreeMap<String, RootSchema>,
+}
+
+impl SchemaRegistry {
+    /// Registers the schema of the `Configuration` type `C` for agents whose image is named
+    /// `image_name`. Types that do not provide a schema are not registered.
+    pub fn register<C: Configuration>(mut self, image_name: impl Into<String>) -> Self {
+        if let Some(schema) = C::schema() {
+            self.insert(image_name, schema);
+        }
+        self
+    }
+
+    /// Registers `schema` for agents whose image is named `image_name`.
+    pub fn insert(&mut self, image_name: impl Into<String>, schema: RootSchema) {
+        self.schemas.insert(image_name.into(), schema);
+    }
+
+    /// The configuration schema of the agent that runs `image`, if one has been registered.
+    pub fn get(&self, image: &str) -> Option<&RootSchema> {
+        self.schemas.get(image_name(image))
+    }
+
+    /// The names of the images that have a registered schema.
+    pub fn image_names(&self) -> impl Iterator<Item = &str> {
+        self.schemas.keys().map(String::as_str)
+    }
+}
+
+/// Checks `value` against `schema` and describes each place where it does not match. Object fields
+/// that are not in the schema are reported since serde ignores them, which usually means a field
+/// name is misspelled. Strings that contain templates are not checked because their values are not
+/// known until the templates are resolved.
+pub(super) fn check(schema: &RootSchema, value: &Value, path: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    Checker { root: schema }.check_object(&schema.schema, value, path, true, &mut problems);
+    problems
+}
+
+struct Checker<'a> {
+    root: &'a RootSchema,
+}
+
+impl<'a> Checker<'a> {
+    fn check(
+        &self,
+        schema: &Schema,
+        value: &Value,
+        path: &str,
+        unknown_fields: bool,
+        problems: &mut Vec<String>,
+    ) {
+        match schema {
+            Schema::Bool(true) => {}
+            Schema::Bool(false) => problems.push(format!("'{}' is not allowed", path)),
+            Schema::Object(schema) => {
+                self.check_object(schema, value, path, unknown_fields, problems)
+            }
+        }
+    }
+
+    /// Checks `value` against `schema`. Fields that are not in the schema are only reported when
+    /// `unknown_fields` is set, because the fields of a flattened or combined type are spread
+    /// across the schema's subschemas. The fields from all of them are considered at once.
+    fn check_object(
+        &self,
+        schema: &SchemaObject,
+        value: &Value,
+        path: &str,
+        unknown_fields: bool,
+        problems: &mut Vec<String>,
+    ) {
+        if is_template(value) {
+            return;
+        }
+        if let Some(reference) = self.resolve(schema) {
+            self.check(reference, value, path, false, problems);
+        }
+        if let Some(instance_type) = &schema.instance_type {
+            if !matches_type(instance_type, value) {
+                problems.push(format!(
+                    "'{}' must be {}, found {}",
+                    path,
+                    describe_types(instance_type),
+                    describe_value(value)
+                ));
+                return;
+            }
+        }
+        if let Some(allowed) = &schema.enum_values {
+            if !allowed.contains(value) {
+                problems.push(format!(
+                    "'{}' must be one of {}",
+                    path,
+                    describe_values(allowed)
+                ));
+                return;
+            }
+        }
+        if let Some(subschemas) = &schema.subschemas {
+            for all_of in subschemas.all_of.iter().flatten() {
+                self.check(all_of, value, path, false, problems);
+            }
+            for one_of in [&subschemas.any_of, &subschemas.one_of]
+                .into_iter()
+                .flatten()
+            {
+                self.check_one_of(one_of, value, path, problems);
+            }
+        }
+        if let (Some(object), Value::Object(map)) = (&schema.object, value) {
+            for field in &object.required {
+                if !map.contains_key(field) {
+                    problems.push(format!("'{}.{}' is required", path, field));
+                }
+            }
+            for (field, value) in map {
+                let field_path = format!("{}.{}", path, field);
+                if let Some(field_schema) = object.properties.get(field) {
+                    self.check(field_schema, value, &field_path, true, problems);
+                } else if let Some(additional) = &object.additional_properties {
+                    self.check(additional, value, &field_path, true, problems);
+                }
+            }
+        }
+        if let (Some(array), Value::Array(values)) = (&schema.array, value) {
+            for (index, value) in values.iter().enumerate() {
+                let item_schema = match &array.items {
+                    Some(SingleOrVec::Single(schema)) => Some(schema.as_ref()),
+                    Some(SingleOrVec::Vec(schemas)) => schemas.get(index),
+                    None => None,
+                };
+                if let Some(item_schema) = item_schema {
+                    self.check(
+                        item_schema,
+                        value,
+                        &format!("{}[{}]", path, index),
+                        true,
+                        problems,
+                    );
+                }
+            }
+        }
+        if let (true, Value::Object(map)) = (unknown_fields, value) {
+            if let Some(known) = self.known_fields(schema) {
+                for field in map.keys().filter(|field| !known.contains(field.as_str())) {
+                    problems.push(format!("'{}.{}' is not a known field", path, field));
+                }
+            }
+        }
+    }
+
+    /// Checks a value that must match one of `schemas`, i.e. an `anyOf` or a `oneOf`. When exactly
+    /// one of the schemas has the value's type, its problems are reported since that is almost
+    /// certainly the form that was intended.
+    fn check_one_of(
+        &self,
+        schemas: &[Schema],
+        value: &Value,
+        path: &str,
+        problems: &mut Vec<String>,
+    ) {
+        let mut candidates = Vec::new();
+        for schema in schemas
+            .iter()
+            .filter(|schema| self.has_type_of(schema, value))
+        {
+            let mut schema_problems = Vec::new();
+            self.check(schema, value, path, false, &mut schema_problems);
+            if schema_problems.is_empty() {
+                return;
+            }
+            candidates.push(schema_problems);
+        }
+        if candidates.len() == 1 {
+            problems.append(&mut candidates.remove(0));
+        } else if let Some(allowed) = self.allowed_values(schemas) {
+            problems.push(format!(
+                "'{}' must be one of {}",
+                path,
+                describe_values(&allowed)
+            ));
+        } else {
+            problems.push(format!(
+                "'{}' does not match any of its allowed forms",
+                path
+            ));
+        }
+    }
+
+    /// The schema that `schema` refers to with `$ref`, if any.
+    fn resolve(&self, schema: &SchemaObject) -> Option<&'a Schema> {
+        let name = schema.reference.as_ref()?.strip_prefix("#/definitions/")?;
+        self.root.definitions.get(name)
+    }
+
+    /// Whether `schema` allows values of the same JSON type as `value`, ignoring everything else.
+    fn has_type_of(&self, schema: &Schema, value: &Value) -> bool {
+        match schema {
+            Schema::Bool(allowed) => *allowed,
+            Schema::Object(schema) => {
+                schema
+                    .instance_type
+                    .as_ref()
+                    .is_none_or(|instance_type| matches_type(instance_type, value))
+                    && self
+                        .resolve(schema)
+                        .is_none_or(|reference| self.has_type_of(reference, value))
+            }
+        }
+    }
+
+    /// The values allowed by `schemas` if each of them is an enumeration, e.g. the variants of a
+    /// Rust enum whose variants are documented.
+    fn allowed_values(&self, schemas: &[Schema]) -> Option<Vec<Value>> {
+        let mut allowed = Vec::new();
+        for schema in schemas {
+            match schema {
+                Schema::Object(schema) => match (&schema.enum_values, self.resolve(schema)) {
+                    (Some(values), _) => allowed.extend(values.iter().cloned()),
+                    (None, Some(reference)) => {
+                        allowed.extend(self.allowed_values(std::slice::from_ref(reference))?)
+                    }
+                    (None, None) => return None,
+                },
+                Schema::Bool(_) => return None,
+            }
+        }
+        Some(allowed)
+    }
+
+    /// The names of the object fields that `schema` describes, including the fields described by
+    /// its references and subschemas. Returns `None` if the object may have any fields.
+    fn known_fields(&self, schema: &SchemaObject) -> Option<BTreeSet<String>> {
+        let mut known = BTreeSet::new();
+        let mut schemas = vec![schema];
+        let mut visited = BTreeSet::new();
+        while let Some(schema) = schemas.pop() {
+            if let Some(Schema::Object(reference)) = self.resolve(schema) {
+                if visited.insert(&schema.reference) {
+                    schemas.push(reference);
+                }
+            }
+            if let Some(object) = &schema.object {
+                if object.additional_properties.is_some() {
+                    return None;
+                }
+                known.extend(object.properties.keys().cloned());
+            }
+            if let Some(subschemas) = &schema.subschemas {
+                let combined = [&subschemas.all_of, &subschemas.any_of, &subschemas.one_of];
+                for schema in combined.into_iter().flatten().flatten() {
+                    match schema {
+                        Schema::Object(schema) => schemas.push(schema),
+                        Schema::Bool(_) => return None,
+                    }
+                }
+            }
+        }
+        Some(known).filter(|known| !known.is_empty())
+    }
+}
+
+/// Whether `value` is a string with templates that are resolved when the agent runs.
+fn is_template(value: &Value) -> bool {
+    match value {
+        Value::String(value) => Template::parse(value)
+            .map(|template| template.references().next().is_some())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn matches_type(instance_type: &SingleOrVec<InstanceType>, value: &Value) -> bool {
+    let matches = |instance_type: &InstanceType| match instance_type {
+        InstanceType::Null => value.is_null(),
+        InstanceType::Boolean => value.is_boolean(),
+        InstanceType::Object => value.is_object(),
+        InstanceType::Array => value.is_array(),
+        InstanceType::Number => value.is_number(),
+        InstanceType::String => value.is_string(),
+        InstanceType::Integer => value.is_i64() || value.is_u64(),
+    };
+    match instance_type {
+        SingleOrVec::Single(instance_type) => matches(instance_type),
+        SingleOrVec::Vec(instance_types) => instance_types.iter().any(matches),
+    }
+}
+
+fn describe_type(instance_type: &InstanceType) -> &'static str {
+    match instance_type {
+        InstanceType::Null => "null",
+        InstanceType::Boolean => "a boolean",
+        InstanceType::Object => "an object",
+        InstanceType::Array => "an array",
+        InstanceType::Number => "a number",
+        InstanceType::String => "a string",
+        InstanceType::Integer => "an integer",
+    }
+}
+
+fn describe_types(instance_type: &SingleOrVec<InstanceType>) -> String {
+    match instance_type {
+        SingleOrVec::Single(instance_type) => describe_type(instance_type).to_string(),
+        SingleOrVec::Vec(instance_types) => instance_types
+            .iter()
+            .map(describe_type)
+            .collect::<Vec<_>>()
+            .join(" or "),
+    }
+}
+
+fn describe_value(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(number) if number.is_f64() => "a number",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn describe_values(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|value| match value {
+            Value::String(value) => format!("'{}'", value),
+            value => value.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    struct Config {
+        cluster_name: String,
+        instance_count: Option<i32>,
+        /// The mode to run in.
+        mode: Option<Mode>,
+        repo: Option<Repo>,
+        labels: Option<BTreeMap<String, String>>,
+        #[serde(flatten)]
+        source: Source,
+    }
+
+    #[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    struct Repo {
+        metadata_url: String,
+        targets: Vec<String>,
+    }
+
+    #[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    enum Mode {
+        /// Run everything.
+        #[default]
+        All,
+        /// Run a single test.
+        Quick,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+    #[serde(untagged, rename_all = "camelCase")]
+    enum Source {
+        File { path: String },
+        Url { url: String },
+    }
+
+    impl Default for Source {
+        fn default() -> Self {
+            Self::File {
+                path: String::new(),
+            }
+        }
+    }
+
+    fn problems(value: Value) -> Vec<String> {
+        check(&schemars::schema_for!(Config), &value, "configuration")
+    }
+
+    #[test]
+    fn valid() {
+        assert!(problems(json!({
+            "clusterName": "${cluster.name}",
+            "instanceCount": 2,
+            "mode": "quick",
+            "repo": {"metadataUrl": "https://example.com", "targets": ["a", "${repo.target}"]},
+            "labels": {"team": "testsys"},
+            "url": "https://example.com",
+        }))
+        .is_empty());
+        assert!(problems(json!({
+            "clusterName": "x",
+            "instanceCount": "${cluster.count}",
+            "mode": null,
+            "path": "/tmp/file",
+        }))
+        .is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            problems(json!({
+                "clusterNmae": "x",
+                "instanceCount": "two",
+                "mode": "slow",
+                "repo": {"metadataUrl": 5, "targets": ["a", 1], "extra": true},
+                "path": "/tmp/file",
+            })),
+            vec![
+                "'configuration.clusterName' is required",
+                "'configuration.instanceCount' must be an integer or null, found a string",
+                "'configuration.mode' must be one of 'all', 'quick'",
+                "'configuration.repo.metadataUrl' must be a string, found an integer",
+                "'configuration.repo.targets[1]' must be a string, found an integer",
+                "'configuration.repo.extra' is not a known field",
+                "'configuration.clusterNmae' is not a known field",
+            ]
+        );
+        assert_eq!(
+            problems(json!({"clusterName": "x"})),
+            vec!["'configuration' does not match any of its allowed forms"]
+        );
+    }
+
+    impl Configuration for Config {
+        fn schema() -> Option<RootSchema> {
+            Some(schemars::schema_for!(Config))
+        }
+    }
+
+    impl Configuration for Repo {}
+
+    #[test]
+    fn image_names() {
+        let registry = SchemaRegistry::default()
+            .register::<Config>("sonobuoy-test-agent")
+            .register::<Repo>("migration-test-agent");
+        assert_eq!(
+            registry.image_names().collect::<Vec<_>>(),
+            vec!["sonobuoy-test-agent"]
+        );
+        assert!(registry
+            .get("public.ecr.aws/bottlerocket-test-system/sonobuoy-test-agent:v0.0.14")
+            .is_some());
+        assert!(registry
+            .get("localhost:5000/sonobuoy-test-agent@sha256:0123")
+            .is_some());
+        assert!(registry.get("sonobuoy-test-agent").is_some());
+        assert!(registry.get("migration-test-agent").is_none());
+    }
+}